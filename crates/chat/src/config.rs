@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context as AnyhowContext, Error};
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use settings::{RoomConfig, SettingsSnapshot};
+use state::TIMEOUT;
+
+const ROOMS_IDENTIFIER: &str = "coop:rooms";
+const SETTINGS_IDENTIFIER: &str = "coop:settings";
+
+/// Merge the room configs from another device into the local ones.
+///
+/// The most recently changed config wins for each room.
+/// Returns true if the local configs changed.
+pub fn merge_room_configs(
+    local: &mut HashMap<u64, RoomConfig>,
+    remote: HashMap<u64, RoomConfig>,
+) -> bool {
+    let mut changed = false;
+
+    for (id, config) in remote.into_iter() {
+        let newer = local
+            .get(&id)
+            .is_none_or(|current| config.updated_at() > current.updated_at());
+
+        if newer {
+            local.insert(id, config);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// Encrypt and publish the room configs as a NIP-78 app data event to the user's relays.
+pub(crate) async fn publish_room_configs(
+    client: &Client,
+    configs: &HashMap<u64, RoomConfig>,
+) -> Result<(), Error> {
+    publish_app_data(client, ROOMS_IDENTIFIER, configs).await
+}
+
+/// Get the latest room configs published by the user's other devices.
+pub(crate) async fn fetch_room_configs(
+    client: &Client,
+) -> Result<Option<HashMap<u64, RoomConfig>>, Error> {
+    fetch_app_data(client, ROOMS_IDENTIFIER).await
+}
+
+/// Encrypt and publish the synced settings as a NIP-78 app data event to the user's relays.
+pub(crate) async fn publish_settings(
+    client: &Client,
+    snapshot: &SettingsSnapshot,
+) -> Result<(), Error> {
+    publish_app_data(client, SETTINGS_IDENTIFIER, snapshot).await
+}
+
+/// Get the latest settings published by the user's other devices.
+pub(crate) async fn fetch_settings(client: &Client) -> Result<Option<SettingsSnapshot>, Error> {
+    fetch_app_data(client, SETTINGS_IDENTIFIER).await
+}
+
+/// Encrypt a value to the user and publish it as a NIP-78 app data event.
+async fn publish_app_data<T>(client: &Client, identifier: &str, value: &T) -> Result<(), Error>
+where
+    T: Serialize,
+{
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    // Encrypt the value
+    let value = serde_json::to_string(value)?;
+    let content = signer.nip44_encrypt(&public_key, &value).await?;
+
+    // Construct the application data event
+    let builder =
+        EventBuilder::new(Kind::ApplicationSpecificData, content).tag(Tag::identifier(identifier));
+
+    // Sign the event with user's signer
+    let event = client.sign_event_builder(builder).await?;
+
+    // Send the event to write relays
+    client
+        .send_event(&event)
+        .to_nip65()
+        .ack_policy(AckPolicy::none())
+        .await?;
+
+    Ok(())
+}
+
+/// Get and decrypt the latest NIP-78 app data event of the user.
+async fn fetch_app_data<T>(client: &Client, identifier: &str) -> Result<Option<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(identifier)
+        .author(public_key)
+        .limit(1);
+
+    // Stream events from user's write relays
+    let mut stream = client
+        .stream_events(filter)
+        .timeout(Duration::from_secs(TIMEOUT))
+        .await?;
+
+    let mut latest: Option<Event> = None;
+
+    while let Some((_url, res)) = stream.next().await {
+        if let Ok(event) = res
+            && latest
+                .as_ref()
+                .is_none_or(|e| event.created_at > e.created_at)
+        {
+            latest = Some(event);
+        }
+    }
+
+    let Some(event) = latest else {
+        return Ok(None);
+    };
+
+    let content = signer.nip44_decrypt(&public_key, &event.content).await?;
+    let value = serde_json::from_str(&content)?;
+
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_room_configs() {
+        let mut old = RoomConfig::new();
+        old.toggle_pinned();
+
+        let mut new = RoomConfig::new();
+        new.set_archived(true);
+        new.touch();
+
+        let mut local = HashMap::from([(1, new.clone()), (2, old.clone())]);
+        let remote = HashMap::from([(1, old.clone()), (2, new.clone()), (3, old.clone())]);
+
+        assert!(merge_room_configs(&mut local, remote));
+
+        // The newer config is kept on both sides
+        assert_eq!(local.get(&1), Some(&new));
+        assert_eq!(local.get(&2), Some(&new));
+
+        // Unknown rooms are added
+        assert_eq!(local.get(&3), Some(&old));
+
+        // Merging the same configs again changes nothing
+        assert!(!merge_room_configs(&mut local.clone(), local));
+    }
+}
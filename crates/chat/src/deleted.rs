@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use anyhow::{Context as AnyhowContext, Error};
+use nostr_sdk::prelude::*;
+
+const IDENTIFIER_PREFIX: &str = "coop:deleted:";
+
+fn identifier(gift_wrap: EventId) -> String {
+    format!("{IDENTIFIER_PREFIX}{gift_wrap}")
+}
+
+/// Get the gift wrap id of a deletion marker
+fn deleted_gift_wrap(event: &Event) -> Option<EventId> {
+    let id = event.tags.identifier()?.strip_prefix(IDENTIFIER_PREFIX)?;
+    EventId::from_hex(id).ok()
+}
+
+/// Remember the gift wraps of a deleted conversation.
+///
+/// Relays may ignore deletion requests, so synced copies are skipped instead of unwrapped again.
+pub(crate) async fn set_deleted(client: &Client, gift_wraps: &[EventId]) -> Result<(), Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    for id in gift_wraps.iter().copied() {
+        // Construct the application data event
+        let event = EventBuilder::new(Kind::ApplicationSpecificData, "")
+            .tag(Tag::identifier(identifier(id)))
+            .build(public_key)
+            .sign(&Keys::generate())
+            .await?;
+
+        // Save the event to the database
+        client.database().save_event(&event).await?;
+    }
+
+    Ok(())
+}
+
+/// Check if a gift wrap belongs to a deleted conversation.
+pub(crate) async fn is_deleted(client: &Client, gift_wrap: EventId) -> Result<bool, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(identifier(gift_wrap))
+        .author(public_key)
+        .limit(1);
+
+    Ok(!client.database().query(filter).await?.is_empty())
+}
+
+/// Get the gift wraps of all deleted conversations.
+pub(crate) async fn get_deleted(client: &Client) -> Result<HashSet<EventId>, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .author(public_key);
+
+    let ids = client
+        .database()
+        .query(filter)
+        .await?
+        .iter()
+        .filter_map(deleted_gift_wrap)
+        .collect();
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deleted_gift_wrap() {
+        let keys = Keys::generate();
+        let id = EventId::from_byte_array([1; 32]);
+
+        let marker = EventBuilder::new(Kind::ApplicationSpecificData, "")
+            .tag(Tag::identifier(identifier(id)))
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert_eq!(deleted_gift_wrap(&marker), Some(id));
+
+        // Cached rumors are identified by the bare gift wrap id
+        let rumor = EventBuilder::new(Kind::ApplicationSpecificData, "")
+            .tag(Tag::identifier(id))
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert_eq!(deleted_gift_wrap(&rumor), None);
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+
+use anyhow::{Context as AnyhowContext, Error};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use state::ImageMeta;
+
+const IDENTIFIER_PREFIX: &str = "coop:draft:";
+
+/// Unsent content of a chat room's input.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Draft {
+    /// Input text
+    pub content: String,
+
+    /// Uploaded media attachments
+    #[serde(default)]
+    pub attachments: Vec<Url>,
+
+    /// Metadata of the uploaded images, sent in `imeta` tags
+    #[serde(default)]
+    pub media: Vec<ImageMeta>,
+
+    /// Messages being replied to
+    #[serde(default)]
+    pub replies_to: Vec<EventId>,
+}
+
+impl Draft {
+    /// Whether the draft has nothing worth keeping
+    pub fn is_empty(&self) -> bool {
+        self.content.trim().is_empty() && self.attachments.is_empty() && self.replies_to.is_empty()
+    }
+}
+
+fn identifier(room: u64) -> String {
+    format!("{IDENTIFIER_PREFIX}{room}")
+}
+
+/// Encrypt and store a room's draft in the local database.
+///
+/// An empty draft removes the stored one.
+pub(crate) async fn set_draft(client: &Client, room: u64, draft: &Draft) -> Result<(), Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(identifier(room))
+        .author(public_key);
+
+    // Remove the previous draft, replaceable events may share the same timestamp
+    client.database().delete(filter).await?;
+
+    if draft.is_empty() {
+        return Ok(());
+    }
+
+    // Encrypt the value
+    let value = serde_json::to_string(draft)?;
+    let content = signer.nip44_encrypt(&public_key, &value).await?;
+
+    // Construct the application data event
+    let event = EventBuilder::new(Kind::ApplicationSpecificData, content)
+        .tag(Tag::identifier(identifier(room)))
+        .build(public_key)
+        .sign(&Keys::generate())
+        .await?;
+
+    // Save the event to the database
+    client.database().save_event(&event).await?;
+
+    Ok(())
+}
+
+/// Get a room's draft from the local database.
+pub(crate) async fn get_draft(client: &Client, room: u64) -> Result<Option<Draft>, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(identifier(room))
+        .author(public_key);
+
+    if let Some(event) = client.database().query(filter).await?.first() {
+        let content = signer.nip44_decrypt(&public_key, &event.content).await?;
+        let draft = serde_json::from_str(&content)?;
+
+        Ok(Some(draft))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Get the ids of all rooms that have a draft.
+pub(crate) async fn get_draft_rooms(client: &Client) -> Result<HashSet<u64>, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .author(public_key);
+
+    let rooms = client
+        .database()
+        .query(filter)
+        .await?
+        .into_iter()
+        .filter_map(|event| {
+            event
+                .tags
+                .identifier()
+                .and_then(|id| id.strip_prefix(IDENTIFIER_PREFIX))
+                .and_then(|id| id.parse().ok())
+        })
+        .collect();
+
+    Ok(rooms)
+}
@@ -0,0 +1,162 @@
+use std::collections::{HashMap, HashSet};
+
+use nostr_sdk::prelude::*;
+
+/// Kind of NIP-62 requests to vanish
+pub const REQUEST_TO_VANISH: Kind = Kind::Custom(62);
+
+/// Maximum number of gift wraps referenced by a single deletion request
+pub const MAX_DELETION_IDS: usize = 500;
+
+/// Construct a NIP-09 deletion request for gift wraps addressed to the user.
+///
+/// Gift wraps are signed by ephemeral keys, relays honoring this request
+/// accept deletions from the recipient instead of the author.
+pub fn gift_wrap_deletion(ids: &[EventId]) -> EventBuilder {
+    let kind = Tag::custom(
+        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K)),
+        [Kind::GiftWrap.as_u16().to_string()],
+    );
+
+    EventBuilder::new(Kind::EventDeletion, "")
+        .tags(ids.iter().copied().map(Tag::event))
+        .tag(kind)
+}
+
+/// Construct a NIP-62 request to vanish from a single relay.
+///
+/// The relay deletes all events of the user, including gift wraps addressed to them.
+pub fn request_to_vanish(relay: &RelayUrl, reason: &str) -> EventBuilder {
+    EventBuilder::new(REQUEST_TO_VANISH, reason).tag(Tag::relay(relay.clone()))
+}
+
+/// A gift wrap whose rumor is cached locally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedWrap {
+    /// Gift wrap id
+    pub id: EventId,
+
+    /// Id of the rumor inside the gift wrap
+    pub rumor: EventId,
+
+    /// Whether the gift wrap is a backup copy of a message sent by the user
+    pub backup: bool,
+}
+
+/// Get the gift wraps safe to prune, by the relays they were seen on.
+///
+/// A gift wrap is safe to prune when a backup copy of its rumor is kept on a
+/// relay. Backup copies themselves are never pruned.
+pub fn prunable_by_relay(
+    wraps: &[CachedWrap],
+    seens: &HashMap<EventId, HashSet<RelayUrl>>,
+) -> HashMap<RelayUrl, Vec<EventId>> {
+    let on_relays = |id: &EventId| seens.get(id).is_some_and(|relays| !relays.is_empty());
+
+    // Rumors with a backup copy kept on relays
+    let backed_up: HashSet<EventId> = wraps
+        .iter()
+        .filter(|wrap| wrap.backup && on_relays(&wrap.id))
+        .map(|wrap| wrap.rumor)
+        .collect();
+
+    let mut prunable: HashMap<RelayUrl, Vec<EventId>> = HashMap::new();
+
+    for wrap in wraps.iter() {
+        if wrap.backup || !backed_up.contains(&wrap.rumor) {
+            continue;
+        }
+
+        for relay in seens.get(&wrap.id).into_iter().flatten() {
+            prunable.entry(relay.clone()).or_default().push(wrap.id);
+        }
+    }
+
+    prunable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gift_wrap_deletion() {
+        let keys = Keys::generate();
+        let ids = [
+            EventId::from_byte_array([1; 32]),
+            EventId::from_byte_array([2; 32]),
+        ];
+
+        let event = gift_wrap_deletion(&ids).build(keys.public_key());
+
+        assert_eq!(event.kind, Kind::EventDeletion);
+        assert_eq!(event.tags.event_ids().copied().collect::<Vec<_>>(), ids);
+        assert!(event.tags.iter().any(|tag| tag.as_slice() == ["k", "1059"]));
+    }
+
+    #[test]
+    fn test_prunable_by_relay() {
+        let id = |n: u8| EventId::from_byte_array([n; 32]);
+        let relay1 = RelayUrl::parse("wss://relay1.example.com").unwrap();
+        let relay2 = RelayUrl::parse("wss://relay2.example.com").unwrap();
+
+        let wraps = [
+            // Rumor 10 has a backup on relay 1
+            CachedWrap {
+                id: id(1),
+                rumor: id(10),
+                backup: true,
+            },
+            CachedWrap {
+                id: id(2),
+                rumor: id(10),
+                backup: false,
+            },
+            // Rumor 20 has no backup
+            CachedWrap {
+                id: id(3),
+                rumor: id(20),
+                backup: false,
+            },
+            // Rumor 30 has a backup that isn't on any relay
+            CachedWrap {
+                id: id(4),
+                rumor: id(30),
+                backup: true,
+            },
+            CachedWrap {
+                id: id(5),
+                rumor: id(30),
+                backup: false,
+            },
+        ];
+
+        let seens = HashMap::from([
+            (id(1), HashSet::from([relay1.clone()])),
+            (id(2), HashSet::from([relay1.clone(), relay2.clone()])),
+            (id(3), HashSet::from([relay1.clone()])),
+            (id(5), HashSet::from([relay2.clone()])),
+        ]);
+
+        let prunable = prunable_by_relay(&wraps, &seens);
+
+        assert_eq!(prunable.get(&relay1), Some(&vec![id(2)]));
+        assert_eq!(prunable.get(&relay2), Some(&vec![id(2)]));
+    }
+
+    #[test]
+    fn test_request_to_vanish() {
+        let keys = Keys::generate();
+        let relay = RelayUrl::parse("wss://inbox.example.com").unwrap();
+
+        let event = request_to_vanish(&relay, "").build(keys.public_key());
+
+        assert_eq!(event.kind.as_u16(), 62);
+        assert!(
+            event
+                .tags
+                .iter()
+                .any(|tag| tag.as_slice() == ["relay", relay.as_str()])
+        );
+    }
+}
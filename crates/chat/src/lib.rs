@@ -1,12 +1,13 @@
 use std::cmp::Reverse;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
 use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context as AnyhowContext, Error, anyhow};
-use common::EventExt;
+use common::{DebouncedDelay, EventExt};
 use device::{DeviceEvent, DeviceRegistry};
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
@@ -14,16 +15,42 @@ use gpui::{
     App, AppContext, Context, Entity, EventEmitter, Global, SharedString, Subscription, Task,
     WeakEntity, Window,
 };
+use itertools::Itertools;
 use nostr_sdk::prelude::*;
+use settings::{AppSettings, RoomConfig, SettingsSnapshot};
 use smallvec::{SmallVec, smallvec};
 use smol::lock::RwLock;
-use state::{CoopSigner, DEVICE_GIFTWRAP, NostrRegistry, StateEvent, TIMEOUT, USER_GIFTWRAP};
+use state::{
+    CoopSigner, DEVICE_GIFTWRAP, NostrRegistry, StateEvent, TIMEOUT, USER_GIFTWRAP,
+    fetch_server_list, publish_server_list,
+};
 
+mod config;
+mod deleted;
+mod draft;
+mod inbox;
 mod message;
+mod mute;
 mod room;
-
+mod spam;
+mod sync;
+mod thread;
+mod trash;
+
+pub use config::*;
+pub use deleted::*;
+pub use draft::*;
+pub use inbox::*;
 pub use message::*;
+pub use mute::*;
 pub use room::*;
+pub use spam::*;
+pub use sync::*;
+pub use thread::*;
+pub use trash::*;
+
+/// Delay before publishing the changed settings, in seconds
+const SETTINGS_DELAY: u64 = 5;
 
 pub fn init(window: &mut Window, cx: &mut App) {
     ChatRegistry::set_global(cx.new(|cx| ChatRegistry::new(window, cx)), cx);
@@ -47,12 +74,14 @@ pub enum ChatEvent {
 }
 
 /// Channel signal.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Signal {
     /// Message received from relay pool
     Message(NewMessage),
     /// Eose received from relay pool
     Eose,
+    /// Sync progress changed
+    Progress(SyncProgress),
     /// An error occurred
     Error(FailedMessage),
 }
@@ -66,11 +95,11 @@ impl Signal {
         Self::Eose
     }
 
-    pub fn error<T>(event: &Event, reason: T) -> Self
+    pub fn error<T>(event: &Event, kind: FailureKind, reason: T) -> Self
     where
-        T: Into<SharedString>,
+        T: Display,
     {
-        Self::Error(FailedMessage::new(event, reason))
+        Self::Error(FailedMessage::new(event, kind, reason))
     }
 }
 
@@ -86,8 +115,35 @@ pub struct ChatRegistry {
     /// Chat rooms
     rooms: Vec<Entity<Room>>,
 
-    /// Events that failed to unwrap for any reason
-    trashes: Entity<BTreeSet<FailedMessage>>,
+    /// Gift wraps that failed to unwrap, by their event id
+    trashes: Entity<BTreeMap<EventId, FailedMessage>>,
+
+    /// Rooms that have an unsent draft
+    drafts: HashSet<u64>,
+
+    /// Muted and blocked users
+    mute_list: MuteList,
+
+    /// Mute list changes that haven't been published yet
+    pending_mutes: PendingMutes,
+
+    /// Whether the mute list was fetched from relays, it isn't published before
+    mute_list_loaded: bool,
+
+    /// Spam scores of the chat request senders
+    spam_scores: HashMap<PublicKey, SpamScore>,
+
+    /// Chat request senders being scored
+    scoring: HashSet<PublicKey>,
+
+    /// Settings last published or fetched, `None` until fetched
+    synced_settings: Option<SettingsSnapshot>,
+
+    /// Whether the settings are being fetched
+    fetching_settings: bool,
+
+    /// Debouncer for publishing the settings
+    settings_debouncer: DebouncedDelay<Self>,
 
     /// Tracking events seen on which relays in the current session
     seens: Arc<RwLock<HashMap<EventId, HashSet<RelayUrl>>>>,
@@ -98,6 +154,12 @@ pub struct ChatRegistry {
     /// Tracking the status of unwrapping gift wrap events.
     tracking_flag: Arc<AtomicBool>,
 
+    /// Relays still sending stored gift wraps
+    sync_state: Arc<RwLock<SyncState>>,
+
+    /// Progress of the historical message sync, updated by the sync tasks
+    sync_progress: SyncProgress,
+
     /// Channel for sending signals to the UI.
     signal_tx: flume::Sender<Signal>,
 
@@ -108,7 +170,7 @@ pub struct ChatRegistry {
     tasks: SmallVec<[Task<Result<(), Error>>; 2]>,
 
     /// Subscriptions
-    _subscriptions: SmallVec<[Subscription; 2]>,
+    _subscriptions: SmallVec<[Subscription; 3]>,
 }
 
 impl EventEmitter<ChatEvent> for ChatRegistry {}
@@ -128,6 +190,7 @@ impl ChatRegistry {
     fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
         let nostr = NostrRegistry::global(cx);
         let device = DeviceRegistry::global(cx);
+        let settings = AppSettings::global(cx);
 
         let (tx, rx) = flume::unbounded::<Signal>();
         let mut subscriptions = smallvec![];
@@ -139,12 +202,18 @@ impl ChatRegistry {
                     this.reset(cx);
                     this.get_contact_list(cx);
                     this.get_rooms(cx);
+                    this.get_drafts(cx);
+                    this.get_trashes(cx);
+                    this.get_room_configs(cx);
+                    this.get_mute_list(cx);
+                    this.get_server_list(cx);
+                    this.get_settings(window, cx);
 
                     let signer = state.read(cx).signer();
                     cx.spawn_in(window, async move |this, cx| {
                         let user_signer = signer.get().await;
                         this.update(cx, |this, cx| {
-                            this.get_messages(user_signer, cx);
+                            this.get_messages(user_signer, USER_GIFTWRAP, cx);
                         })
                         .ok();
                     })
@@ -155,15 +224,18 @@ impl ChatRegistry {
 
         subscriptions.push(
             // Subscribe to the device event
-            cx.subscribe_in(&device, window, |_this, _s, event, window, cx| {
+            cx.subscribe_in(&device, window, |this, _s, event, window, cx| {
                 if event == &DeviceEvent::Set {
+                    // Gift wraps addressed to the device key can be opened now
+                    this.retry_trashes(None, cx);
+
                     let nostr = NostrRegistry::global(cx);
                     let signer = nostr.read(cx).signer();
 
                     cx.spawn_in(window, async move |this, cx| {
                         if let Some(device_signer) = signer.get_encryption_signer().await {
                             this.update(cx, |this, cx| {
-                                this.get_messages(device_signer, cx);
+                                this.get_messages(device_signer, DEVICE_GIFTWRAP, cx);
                             })
                             .ok();
                         }
@@ -173,9 +245,19 @@ impl ChatRegistry {
             }),
         );
 
+        subscriptions.push(
+            // Observe the settings to apply the stored room configs
+            cx.observe_in(&settings, window, |this, _settings, window, cx| {
+                this.apply_room_configs(cx);
+                this.score_requests(cx);
+                this.sync_settings(window, cx);
+            }),
+        );
+
         // Run at the end of the current cycle
         cx.defer_in(window, |this, _window, cx| {
             this.get_rooms(cx);
+            this.get_drafts(cx);
             this.handle_notifications(cx);
             this.tracking(cx);
         });
@@ -183,10 +265,21 @@ impl ChatRegistry {
         Self {
             initializing: true,
             rooms: vec![],
-            trashes: cx.new(|_| BTreeSet::default()),
+            trashes: cx.new(|_| BTreeMap::default()),
+            drafts: HashSet::default(),
+            mute_list: MuteList::default(),
+            pending_mutes: PendingMutes::default(),
+            mute_list_loaded: false,
+            spam_scores: HashMap::default(),
+            scoring: HashSet::default(),
+            synced_settings: None,
+            fetching_settings: false,
+            settings_debouncer: DebouncedDelay::new(),
             seens: Arc::new(RwLock::new(HashMap::default())),
             event_map: Arc::new(RwLock::new(HashMap::default())),
             tracking_flag: Arc::new(AtomicBool::new(false)),
+            sync_state: Arc::new(RwLock::new(SyncState::default())),
+            sync_progress: SyncProgress::default(),
             signal_rx: rx,
             signal_tx: tx,
             tasks: smallvec![],
@@ -202,6 +295,7 @@ impl ChatRegistry {
         let status = self.tracking_flag.clone();
         let seens = self.seens.clone();
         let event_map = self.event_map.clone();
+        let sync_state = self.sync_state.clone();
         let trashes = self.trashes.downgrade();
 
         let initialized_at = Timestamp::now();
@@ -243,6 +337,16 @@ impl ChatRegistry {
                             continue;
                         }
 
+                        // Skip gift wraps of deleted conversations, relays may keep them
+                        if is_deleted(&client, event.id).await.unwrap_or(false) {
+                            let filter = Filter::new().id(event.id);
+
+                            if let Err(e) = client.database().delete(filter).await {
+                                log::error!("Failed to delete the gift wrap: {e}");
+                            }
+                            continue;
+                        }
+
                         // Extract the rumor from the gift wrap event
                         match extract_rumor(&client, &signer, event.as_ref()).await {
                             Ok(rumor) => {
@@ -255,8 +359,11 @@ impl ChatRegistry {
 
                                 // Check if the rumor has a recipient
                                 if rumor.tags.is_empty() {
-                                    let signal =
-                                        Signal::error(event.as_ref(), "Recipient is missing");
+                                    let signal = Signal::error(
+                                        event.as_ref(),
+                                        FailureKind::MalformedRumor,
+                                        "Recipient is missing",
+                                    );
                                     tx.send_async(signal).await?;
                                 }
 
@@ -270,17 +377,53 @@ impl ChatRegistry {
                                 }
                             }
                             Err(e) => {
-                                let reason = format!("Failed to extract rumor: {e}");
-                                let signal = Signal::error(event.as_ref(), reason);
-                                tx.send_async(signal).await?;
+                                let trash = FailedMessage::new(event.as_ref(), e.kind, e.reason);
+
+                                // Keep the gift wrap for a later retry
+                                if let Err(e) = set_trash(&client, &trash).await {
+                                    log::error!("Failed to save the failed gift wrap: {e}");
+                                }
+
+                                tx.send_async(Signal::Error(trash)).await?;
                             }
                         }
                     }
                     RelayMessage::EndOfStoredEvents(id) => {
                         if id.as_ref() == &sub_id1 || id.as_ref() == &sub_id2 {
+                            let (synced, progress) = {
+                                let mut sync_state = sync_state.write().await;
+                                let synced = sync_state.finish(&id, &relay_url);
+                                (synced, sync_state.progress())
+                            };
+
+                            // Move the relay's cursor to the start of the sync
+                            if let Some((recipient, started_at)) = synced
+                                && let Err(e) =
+                                    set_cursor(&client, &recipient, &relay_url, started_at).await
+                            {
+                                log::error!("Failed to save the sync cursor: {e}");
+                            }
+
+                            tx.send_async(Signal::Progress(progress)).await?;
                             tx.send_async(Signal::eose()).await?;
                         }
                     }
+                    RelayMessage::Closed {
+                        subscription_id, ..
+                    } => {
+                        // The relay won't send the rest of its stored events
+                        if subscription_id.as_ref() == &sub_id1
+                            || subscription_id.as_ref() == &sub_id2
+                        {
+                            let progress = {
+                                let mut sync_state = sync_state.write().await;
+                                sync_state.finish(&subscription_id, &relay_url);
+                                sync_state.progress()
+                            };
+
+                            tx.send_async(Signal::Progress(progress)).await?;
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -301,9 +444,15 @@ impl ChatRegistry {
                             this.get_rooms(cx);
                         })?;
                     }
+                    Signal::Progress(progress) => {
+                        this.update(cx, |this, cx| {
+                            this.sync_progress = progress;
+                            cx.notify();
+                        })?;
+                    }
                     Signal::Error(trash) => {
                         trashes.update(cx, |this, cx| {
-                            this.insert(trash);
+                            this.insert(trash.id(), trash);
                             cx.notify();
                         })?;
                     }
@@ -323,10 +472,11 @@ impl ChatRegistry {
             let loop_duration = Duration::from_secs(15);
 
             loop {
-                if status.load(Ordering::Acquire) {
-                    _ = status.compare_exchange(true, false, Ordering::Release, Ordering::Relaxed);
-                    _ = tx.send_async(Signal::Eose).await;
-                } else {
+                // Only reload rooms when older messages were unwrapped in the meantime
+                if status
+                    .compare_exchange(true, false, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+                {
                     _ = tx.send_async(Signal::Eose).await;
                 }
                 smol::Timer::after(loop_duration).await;
@@ -366,11 +516,11 @@ impl ChatRegistry {
     }
 
     /// Get all messages for the provided signer
-    fn get_messages<T>(&mut self, signer: T, cx: &mut Context<Self>)
+    fn get_messages<T>(&mut self, signer: T, id: &str, cx: &mut Context<Self>)
     where
         T: NostrSigner + 'static,
     {
-        let task = self.subscribe_gift_wrap_events(signer, cx);
+        let task = self.subscribe_gift_wrap_events(signer, SubscriptionId::new(id), cx);
 
         self.tasks.push(cx.spawn(async move |this, cx| {
             match task.await {
@@ -423,39 +573,135 @@ impl ChatRegistry {
         })
     }
 
-    /// Continuously get gift wrap events for the signer
-    fn subscribe_gift_wrap_events<T>(&self, signer: T, cx: &App) -> Task<Result<(), Error>>
+    /// Continuously get gift wrap events for the signer.
+    ///
+    /// Each relay resumes from its sync cursor, relays supporting negentropy
+    /// reconcile the history with the local database instead.
+    fn subscribe_gift_wrap_events<T>(
+        &self,
+        signer: T,
+        id: SubscriptionId,
+        cx: &App,
+    ) -> Task<Result<(), Error>>
     where
         T: NostrSigner + 'static,
     {
         let nostr = NostrRegistry::global(cx);
         let client = nostr.read(cx).client();
         let urls = self.get_messaging_relays(cx);
+        let sync_state = self.sync_state.clone();
+        let tx = self.signal_tx.clone();
 
         cx.background_spawn(async move {
             let urls = urls.await?;
             let public_key = signer.get_public_key().await?;
             let filter = Filter::new().kind(Kind::GiftWrap).pubkey(public_key);
-            let id = SubscriptionId::new(format!("{}-msg", public_key.to_hex()));
+            let started_at = Timestamp::now();
 
             // Ensure relay connections
             for url in urls.iter() {
                 client.add_relay(url).and_connect().await?;
             }
 
-            // Construct target for subscription
-            let target: HashMap<RelayUrl, Filter> = urls
-                .into_iter()
-                .map(|relay| (relay, filter.clone()))
-                .collect();
+            // Mark all relays as syncing
+            let progress = {
+                let mut sync_state = sync_state.write().await;
+
+                for url in urls.iter() {
+                    sync_state.start(id.clone(), url.clone(), public_key, started_at);
+                }
+
+                sync_state.progress()
+            };
+            tx.send_async(Signal::Progress(progress)).await?;
+
+            // Resume each relay from its cursor
+            let mut target: HashMap<RelayUrl, Filter> = HashMap::new();
+
+            for url in urls.into_iter() {
+                let filter = match get_cursor(&client, &public_key, &url).await {
+                    Ok(Some(cursor)) => filter.clone().since(since(cursor)),
+                    _ => filter.clone(),
+                };
+                target.insert(url, filter);
+            }
+
+            // Reconcile the history with negentropy (NIP-77)
+            match client.sync(target.clone()).await {
+                Ok(output) => {
+                    for url in output.success.iter() {
+                        if let Some(filter) = target.get_mut(url) {
+                            *filter = filter.clone().since(since(started_at));
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Negentropy sync failed: {e}");
+                }
+            }
+
+            let output = client.subscribe(target).with_id(id.clone()).await?;
+
+            // Relays that refused the subscription won't send their stored events
+            let progress = {
+                let mut sync_state = sync_state.write().await;
+
+                for url in output.failed.keys() {
+                    sync_state.finish(&id, url);
+                }
 
-            let output = client.subscribe(target).with_id(id).await?;
+                sync_state.progress()
+            };
+            tx.send_async(Signal::Progress(progress)).await?;
 
             log::info!(
                 "Successfully subscribed to gift-wrap messages on: {:?}",
                 output.success
             );
 
+            // Stop waiting for relays that disconnect or don't finish in time
+            let deadline = Instant::now() + SYNC_TIMEOUT;
+
+            loop {
+                smol::Timer::after(SYNC_CHECK_INTERVAL).await;
+
+                let pending = sync_state.read().await.pending_relays(&id, started_at);
+
+                if pending.is_empty() {
+                    break;
+                }
+
+                let timed_out = Instant::now() >= deadline;
+                let mut stalled = vec![];
+
+                for url in pending.into_iter() {
+                    let connected = matches!(
+                        client.relay(&url).await,
+                        Ok(Some(relay)) if relay.is_connected()
+                    );
+
+                    if timed_out || !connected {
+                        stalled.push(url);
+                    }
+                }
+
+                if stalled.is_empty() {
+                    continue;
+                }
+
+                let progress = {
+                    let mut sync_state = sync_state.write().await;
+
+                    for url in stalled.iter() {
+                        log::warn!("Stopped waiting for stored gift wraps from {url}");
+                        sync_state.finish(&id, url);
+                    }
+
+                    sync_state.progress()
+                };
+                tx.send_async(Signal::Progress(progress)).await?;
+            }
+
             Ok(())
         })
     }
@@ -474,10 +720,10 @@ impl ChatRegistry {
             let device_signer = signer.get_encryption_signer().await;
 
             this.update(cx, |this, cx| {
-                this.get_messages(user_signer, cx);
+                this.get_messages(user_signer, USER_GIFTWRAP, cx);
 
                 if let Some(device_signer) = device_signer {
-                    this.get_messages(device_signer, cx);
+                    this.get_messages(device_signer, DEVICE_GIFTWRAP, cx);
                 }
             })
             .ok();
@@ -496,6 +742,11 @@ impl ChatRegistry {
         self.tracking_flag.load(Ordering::Acquire)
     }
 
+    /// Get the progress of the historical message sync
+    pub fn sync_progress(&self) -> SyncProgress {
+        self.sync_progress
+    }
+
     /// Get a weak reference to a room by its ID.
     pub fn room(&self, id: &u64, cx: &App) -> Option<WeakEntity<Room>> {
         self.rooms
@@ -504,191 +755,1083 @@ impl ChatRegistry {
             .map(|this| this.downgrade())
     }
 
-    /// Get all rooms based on the filter.
-    pub fn rooms(&self, filter: &RoomKind, cx: &App) -> Vec<Entity<Room>> {
+    /// Get all rooms based on the filter, rooms of muted users are hidden.
+    pub fn rooms(&self, filter: &RoomFilter, cx: &App) -> Vec<Entity<Room>> {
         self.rooms
             .iter()
-            .filter(|room| &room.read(cx).kind == filter)
+            .filter(|room| self.matches(filter, room, cx))
             .cloned()
             .collect()
     }
 
-    /// Count the number of rooms based on the filter.
-    pub fn count(&self, filter: &RoomKind, cx: &App) -> usize {
+    /// Count the number of rooms based on the filter, rooms of muted users are hidden.
+    pub fn count(&self, filter: &RoomFilter, cx: &App) -> usize {
         self.rooms
             .iter()
-            .filter(|room| &room.read(cx).kind == filter)
+            .filter(|room| self.matches(filter, room, cx))
             .count()
     }
 
-    /// Count the number of messages seen by a given relay.
-    pub fn count_messages(&self, relay_url: &RelayUrl) -> usize {
-        self.seens
-            .read_blocking()
-            .values()
-            .filter(|seen| seen.contains(relay_url))
-            .count()
-    }
+    /// Check if a room matches the filter.
+    ///
+    /// Rooms of muted users are hidden, possible spam is moved out of the requests.
+    fn matches(&self, filter: &RoomFilter, room: &Entity<Room>, cx: &App) -> bool {
+        if !filter.matches(room.read(cx)) || self.is_muted(room, cx) {
+            return false;
+        }
 
-    /// Count the number of trash messages.
-    pub fn count_trash_messages(&self, cx: &App) -> usize {
-        self.trashes.read(cx).len()
+        match filter {
+            RoomFilter::Kind(RoomKind::Request) => !self.is_spam(room.read(cx), cx),
+            RoomFilter::Spam => self.is_spam(room.read(cx), cx),
+            _ => true,
+        }
     }
 
-    /// Get the trash messages entity.
-    pub fn trashes(&self) -> Entity<BTreeSet<FailedMessage>> {
-        self.trashes.clone()
-    }
+    /// Get the spam score of a chat request, from its best scored member.
+    pub fn spam_score(&self, room: &Room, cx: &App) -> Option<SpamScore> {
+        if room.kind != RoomKind::Request || !AppSettings::get_screening(cx) {
+            return None;
+        }
 
-    /// Get the relays that have seen a given rumor id.
-    pub fn rumor_seen_on(&self, id: &EventId) -> Option<HashSet<RelayUrl>> {
-        self.event_map
-            .read_blocking()
-            .get(id)
-            .map(|(id, _dekey)| self.seen_on(id))
-    }
+        let nostr = NostrRegistry::global(cx);
+        let user = nostr.read(cx).signer().public_key();
 
-    /// Get the relays that have seen a given gift wrap id.
-    pub fn seen_on(&self, id: &EventId) -> HashSet<RelayUrl> {
-        self.seens
-            .read_blocking()
-            .get(id)
-            .cloned()
-            .unwrap_or_default()
+        room.members
+            .iter()
+            .filter(|public_key| Some(**public_key) != user)
+            .filter_map(|public_key| self.spam_scores.get(public_key))
+            .max_by_key(|score| score.total())
+            .copied()
     }
 
-    /// Check if a given rumor was encrypted by the dekey.
-    pub fn encrypted_by_dekey(&self, id: &EventId) -> bool {
-        self.event_map
-            .read_blocking()
-            .get(id)
-            .map(|(_, dekey)| *dekey)
-            .unwrap_or(false)
+    /// Check if a chat request is possibly spam.
+    fn is_spam(&self, room: &Room, cx: &App) -> bool {
+        self.spam_score(room, cx)
+            .is_some_and(|score| score.is_spam())
     }
 
-    /// Add a new room to the start of list.
-    pub fn add_room<I>(&mut self, room: I, cx: &mut Context<Self>)
-    where
-        I: Into<Room> + 'static,
-    {
+    /// Score the senders of the chat requests that haven't been scored yet.
+    fn score_requests(&mut self, cx: &mut Context<Self>) {
+        if !AppSettings::get_screening(cx) {
+            return;
+        }
+
         let nostr = NostrRegistry::global(cx);
         let client = nostr.read(cx).client();
+        let user = nostr.read(cx).signer().public_key();
+        let http_client = cx.http_client();
 
-        cx.spawn(async move |this, cx| {
-            let signer = client.signer()?;
-            let public_key = signer.get_public_key().await.ok()?;
-            let room: Room = room.into().organize(&public_key);
+        let public_keys: Vec<PublicKey> = self
+            .rooms
+            .iter()
+            .map(|room| room.read(cx))
+            .filter(|room| room.kind == RoomKind::Request)
+            .flat_map(|room| room.members.iter().copied())
+            .filter(|public_key| {
+                Some(*public_key) != user
+                    && !self.spam_scores.contains_key(public_key)
+                    && !self.scoring.contains(public_key)
+            })
+            .unique()
+            .collect();
+
+        if public_keys.is_empty() {
+            return;
+        }
+
+        self.scoring.extend(public_keys.iter().copied());
+
+        let task: Task<Vec<(PublicKey, SpamScore)>> =
+            cx.background_spawn(
+                async move { score_senders(&client, &http_client, public_keys).await },
+            );
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let scores = task.await;
 
             this.update(cx, |this, cx| {
-                this.rooms.insert(0, cx.new(|_| room));
-                cx.emit(ChatEvent::Ping);
+                for (public_key, score) in scores.into_iter() {
+                    this.scoring.remove(&public_key);
+                    this.spam_scores.insert(public_key, score);
+                }
                 cx.notify();
-            })
-            .ok()
-        })
-        .detach();
+            })?;
+
+            Ok(())
+        }));
     }
 
-    /// Emit an open room event.
-    ///
-    /// If the room is new, add it to the registry.
-    pub fn emit_room(&mut self, room: &Entity<Room>, cx: &mut Context<Self>) {
-        // Get the room's ID.
-        let id = room.read(cx).id;
+    /// Check if a room only has muted or blocked members.
+    fn is_muted(&self, room: &Entity<Room>, cx: &App) -> bool {
+        let nostr = NostrRegistry::global(cx);
+        let user = nostr.read(cx).signer().public_key();
 
-        // If the room is new, add it to the registry.
-        if !self.rooms.iter().any(|r| r.read(cx).id == id) {
-            self.rooms.insert(0, room.to_owned());
-        }
+        self.mute_list
+            .room_kind(&room.read(cx).members, user)
+            .is_some()
+    }
 
-        // Emit the open room event.
-        cx.emit(ChatEvent::OpenRoom(id));
+    /// Get the mute list.
+    pub fn mute_list(&self) -> &MuteList {
+        &self.mute_list
     }
 
-    /// Close a room.
-    pub fn close_room(&mut self, id: u64, cx: &mut Context<Self>) {
-        if self.rooms.iter().any(|r| r.read(cx).id == id) {
-            cx.emit(ChatEvent::CloseRoom(id));
+    /// Mute, block or remove a user from the mute list, then publish the list.
+    ///
+    /// The change is kept locally and published once the list is fetched from relays.
+    pub fn set_mute(
+        &mut self,
+        public_key: PublicKey,
+        kind: Option<MuteKind>,
+        cx: &mut Context<Self>,
+    ) {
+        let was_blocked = self.mute_list.is_blocked(&public_key);
+
+        if !self.mute_list.set(public_key, kind) {
+            return;
         }
-    }
 
-    /// Sort rooms by their created at.
-    pub fn sort(&mut self, cx: &mut Context<Self>) {
-        self.rooms.sort_by_key(|ev| Reverse(ev.read(cx).created_at));
+        if kind == Some(MuteKind::Block) {
+            self.remove_blocked_rooms(cx);
+        } else if was_blocked {
+            // Load the rooms of the unblocked user again
+            self.get_rooms(cx);
+        }
+
+        self.pending_mutes.insert(public_key, kind);
+        self.save_mute_list(cx);
+
         cx.notify();
     }
 
-    /// Finding rooms based on a query.
-    pub fn find(&self, query: &str, cx: &App) -> Vec<Entity<Room>> {
-        let matcher = SkimMatcherV2::default();
+    /// Cache the mute list locally, then publish the pending changes if the list was fetched.
+    fn save_mute_list(&mut self, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let list = self.mute_list.clone();
+        let pending = self.pending_mutes.clone();
+        let publish = self.mute_list_loaded && !pending.is_empty();
 
-        if let Ok(public_key) = PublicKey::parse(query) {
-            self.rooms
-                .iter()
-                .filter(|room| room.read(cx).members.contains(&public_key))
-                .cloned()
-                .collect()
-        } else {
-            self.rooms
-                .iter()
-                .filter(|room| {
-                    matcher
-                        .fuzzy_match(room.read(cx).display_name(cx).as_ref(), query)
-                        .is_some()
-                })
-                .cloned()
-                .collect()
-        }
-    }
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let task = cx.background_spawn(async move {
+                if let Err(e) = set_cached_mute_list(&client, &list, &pending).await {
+                    log::warn!("Failed to cache the mute list: {e}");
+                }
 
-    /// Reset the registry.
-    pub fn reset(&mut self, cx: &mut Context<Self>) {
-        self.initializing = true;
-        self.rooms.clear();
-        self.trashes.update(cx, |this, cx| {
-            this.clear();
-            cx.notify();
-        });
-        cx.notify();
-    }
+                if publish {
+                    publish_mute_list(&client, &list).await?;
+                }
 
-    /// Extend the registry with new rooms.
-    fn extend_rooms(&mut self, rooms: HashSet<Room>, cx: &mut Context<Self>) {
-        let mut room_map: HashMap<u64, usize> = self
-            .rooms
-            .iter()
-            .enumerate()
-            .map(|(idx, room)| (room.read(cx).id, idx))
-            .collect();
+                Ok::<_, Error>(pending)
+            });
 
-        for new_room in rooms.into_iter() {
-            // Check if we already have a room with this ID
-            if let Some(&index) = room_map.get(&new_room.id) {
-                self.rooms[index].update(cx, |this, cx| {
-                    if new_room.created_at > this.created_at {
-                        *this = new_room;
-                        cx.notify();
-                    }
-                });
-            } else {
-                let new_room_id = new_room.id;
-                self.rooms.push(cx.new(|_| new_room));
+            let published = task.await?;
 
-                let new_index = self.rooms.len();
-                room_map.insert(new_room_id, new_index);
+            if publish {
+                this.update(cx, |this, cx| {
+                    // Keep the changes made while publishing, they're published by their own task
+                    this.pending_mutes
+                        .retain(|public_key, kind| published.get(public_key) != Some(kind));
+
+                    if this.pending_mutes.is_empty() {
+                        this.save_mute_list(cx);
+                    }
+                })?;
             }
-        }
+
+            Ok(())
+        }));
     }
 
-    /// Load all rooms from the database.
-    pub fn get_rooms(&mut self, cx: &mut Context<Self>) {
-        let task = self.get_rooms_from_database(cx);
+    /// Drop the rooms that only have blocked members.
+    fn remove_blocked_rooms(&mut self, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let user = nostr.read(cx).signer().public_key();
+        let list = &self.mute_list;
+
+        self.rooms
+            .retain(|room| list.room_kind(&room.read(cx).members, user) != Some(MuteKind::Block));
+    }
+
+    /// Get the mute list from the local cache, then from relays.
+    ///
+    /// Changes made before the list was fetched are applied to the fetched list.
+    fn get_mute_list(&mut self, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task = cx.background_spawn(async move {
+            let cached = get_cached_mute_list(&client).await;
+            let fetched = fetch_mute_list(&client).await;
+
+            (cached, fetched)
+        });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let (cached, fetched) = task.await;
+
+            this.update(cx, |this, cx| {
+                match cached {
+                    Ok(Some((list, pending))) => {
+                        this.mute_list = list;
+                        this.pending_mutes.extend(pending);
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::warn!("Failed to load the cached mute list: {e}"),
+                }
+
+                match fetched {
+                    Ok(list) => {
+                        let mut list = list.unwrap_or_default();
+
+                        for (public_key, kind) in this.pending_mutes.iter() {
+                            list.set(*public_key, *kind);
+                        }
+
+                        this.mute_list = list;
+                        this.mute_list_loaded = true;
+                        this.save_mute_list(cx);
+                    }
+                    Err(e) => {
+                        // Keep the cached list, nothing is published until it's fetched
+                        log::error!("Failed to fetch the mute list: {e}");
+                    }
+                }
+
+                this.remove_blocked_rooms(cx);
+                cx.notify();
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Get the user's media server list from relays.
+    ///
+    /// The published list is merged into the local servers, the merged list is
+    /// published again if it has servers only known by this device.
+    fn get_server_list(&mut self, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task: Task<Result<Option<Vec<Url>>, Error>> = cx.background_spawn(async move {
+            let signer = client.signer().context("Signer not found")?;
+            let public_key = signer.get_public_key().await?;
+
+            fetch_server_list(&client, public_key).await
+        });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let remote = task.await?.unwrap_or_default();
+
+            this.update(cx, |this, cx| {
+                let merged = AppSettings::global(cx)
+                    .update(cx, |this, cx| this.merge_file_servers(remote.clone(), cx));
+
+                // Only update a list the user already published
+                if !remote.is_empty() && merged != remote {
+                    this.publish_server_list(merged, cx);
+                }
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Update the user's media servers, then publish the server list.
+    pub fn set_file_servers(&mut self, servers: Vec<Url>, cx: &mut Context<Self>) {
+        if AppSettings::get_file_servers(cx) == servers {
+            return;
+        }
+
+        AppSettings::update_file_servers(servers.clone(), cx);
+        self.publish_server_list(servers, cx);
+    }
+
+    /// Publish the user's media server list (BUD-03)
+    fn publish_server_list(&mut self, servers: Vec<Url>, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        self.tasks.push(cx.background_spawn(async move {
+            publish_server_list(&client, &servers).await?;
+            Ok(())
+        }));
+    }
+
+    /// Get the names of all folders in use, sorted alphabetically.
+    pub fn folders(&self, cx: &App) -> Vec<SharedString> {
+        self.rooms
+            .iter()
+            .filter_map(|room| room.read(cx).config().folder().map(SharedString::from))
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    /// Count the number of messages seen by a given relay.
+    pub fn count_messages(&self, relay_url: &RelayUrl) -> usize {
+        self.seens
+            .read_blocking()
+            .values()
+            .filter(|seen| seen.contains(relay_url))
+            .count()
+    }
+
+    /// Get the gift wraps safe to prune, by the relays they were seen on.
+    ///
+    /// See [`prunable_by_relay`] for which gift wraps are safe to prune.
+    pub fn prunable_gift_wraps(
+        &self,
+        cx: &App,
+    ) -> Task<Result<HashMap<RelayUrl, Vec<EventId>>, Error>> {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let public_key = nostr.read(cx).signer().public_key();
+        let seens = self.seens.clone();
+
+        cx.background_spawn(async move {
+            let public_key = public_key.context("Signer not found")?;
+            let filter = Filter::new().kind(Kind::ApplicationSpecificData);
+
+            // Cached rumors are identified by their gift wrap id
+            let wraps: Vec<CachedWrap> = client
+                .database()
+                .query(filter)
+                .await?
+                .into_iter()
+                .filter_map(|event| {
+                    let id = EventId::from_hex(event.tags.identifier()?).ok()?;
+                    let rumor = UnsignedEvent::from_json(&event.content).ok()?;
+
+                    Some(CachedWrap {
+                        id,
+                        rumor: rumor.id?,
+                        backup: rumor.pubkey == public_key,
+                    })
+                })
+                .unique_by(|wrap| wrap.id)
+                .collect();
+
+            let seens = seens.read().await;
+
+            Ok(prunable_by_relay(&wraps, &seens))
+        })
+    }
+
+    /// Request a relay to delete gift wraps addressed to the user (NIP-09).
+    ///
+    /// Deletion is best-effort, relays may ignore requests for events signed by
+    /// the gift wraps' ephemeral keys. Returns the number of gift wraps requested.
+    pub fn delete_gift_wraps(
+        &self,
+        relay_url: &RelayUrl,
+        ids: Vec<EventId>,
+        cx: &App,
+    ) -> Task<Result<usize, Error>> {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let relay_url = relay_url.to_owned();
+
+        cx.background_spawn(async move {
+            request_gift_wrap_deletion(&client, &relay_url, &ids).await?;
+            Ok(ids.len())
+        })
+    }
+
+    /// Request a relay to delete all events of the user (NIP-62).
+    pub fn vanish_from_relay(&self, relay_url: &RelayUrl, cx: &App) -> Task<Result<(), Error>> {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let relay_url = relay_url.to_owned();
+
+        cx.background_spawn(async move {
+            let builder = request_to_vanish(&relay_url, "");
+            let event = client.sign_event_builder(builder).await?;
+            let output = client.send_event(&event).to([relay_url.clone()]).await?;
+
+            if let Some(reason) = output.failed.get(&relay_url) {
+                return Err(anyhow!("Relay rejected the request: {reason}"));
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Delete a conversation from the local database.
+    ///
+    /// The messaging relays and the relays the gift wraps were seen on are
+    /// requested to delete the gift wraps too (NIP-09), on a best-effort basis.
+    /// The gift wraps are remembered as deleted, so syncing doesn't restore them.
+    pub fn delete_room(&mut self, id: u64, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let urls = self.get_messaging_relays(cx);
+        let seens = self.seens.clone();
+
+        self.rooms.retain(|room| room.read(cx).id != id);
+        cx.notify();
+
+        let task: Task<Result<(), Error>> = cx.background_spawn(async move {
+            let filter = Filter::new()
+                .kind(Kind::ApplicationSpecificData)
+                .custom_tag(SingleLetterTag::lowercase(Alphabet::C), id.to_string());
+
+            let rows = client.database().query(filter).await?;
+
+            let gift_wraps: Vec<EventId> = rows
+                .iter()
+                .filter_map(|event| EventId::from_hex(event.tags.identifier()?).ok())
+                .collect();
+
+            // Remember the gift wraps before deleting them
+            set_deleted(&client, &gift_wraps).await?;
+
+            // Delete the cached messages and their gift wraps
+            let ids: Vec<EventId> = rows
+                .iter()
+                .map(|event| event.id)
+                .chain(gift_wraps.iter().copied())
+                .collect();
+
+            if !ids.is_empty() {
+                client.database().delete(Filter::new().ids(ids)).await?;
+            }
+
+            if gift_wraps.is_empty() {
+                return Ok(());
+            }
+
+            let mut relays: HashSet<RelayUrl> = match urls.await {
+                Ok(urls) => urls.into_iter().collect(),
+                Err(e) => {
+                    log::warn!("Failed to get messaging relays: {e}");
+                    HashSet::new()
+                }
+            };
+
+            let seens = seens.read().await;
+
+            for id in gift_wraps.iter() {
+                if let Some(seen) = seens.get(id) {
+                    relays.extend(seen.iter().cloned());
+                }
+            }
+
+            drop(seens);
+
+            for url in relays.iter() {
+                if let Err(e) = request_gift_wrap_deletion(&client, url, &gift_wraps).await {
+                    log::warn!("Failed to request deletion from {url}: {e}");
+                }
+            }
+
+            Ok(())
+        });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            if let Err(e) = task.await {
+                this.update(cx, |_this, cx| {
+                    cx.emit(ChatEvent::Error(SharedString::from(e.to_string())));
+                })?;
+            }
+
+            Ok(())
+        }));
+    }
+
+    /// Count the number of trash messages.
+    pub fn count_trash_messages(&self, cx: &App) -> usize {
+        self.trashes.read(cx).len()
+    }
+
+    /// Get the trash messages entity.
+    pub fn trashes(&self) -> Entity<BTreeMap<EventId, FailedMessage>> {
+        self.trashes.clone()
+    }
+
+    /// Load the failed gift wraps of the current user, then retry them.
+    fn get_trashes(&mut self, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task = cx.background_spawn(async move { get_trashes(&client).await });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let trashes = task.await?;
+
+            this.update(cx, |this, cx| {
+                this.trashes.update(cx, |this, cx| {
+                    this.extend(trashes.into_iter().map(|trash| (trash.id(), trash)));
+                    cx.notify();
+                });
+                this.retry_trashes(None, cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Try to unwrap failed gift wraps again, all of them if no ids are provided.
+    ///
+    /// Recovered messages are removed from the trash and pushed to their rooms.
+    pub fn retry_trashes(&mut self, ids: Option<Vec<EventId>>, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let signer = nostr.read(cx).signer();
+
+        let gift_wraps: Vec<Event> = self
+            .trashes
+            .read(cx)
+            .values()
+            .filter(|trash| ids.as_ref().is_none_or(|ids| ids.contains(&trash.id())))
+            .map(|trash| trash.gift_wrap.clone())
+            .collect();
+
+        if gift_wraps.is_empty() {
+            return;
+        }
+
+        let task: Task<Result<_, Error>> = cx.background_spawn(async move {
+            let mut recovered = vec![];
+            let mut failed = vec![];
+
+            for gift_wrap in gift_wraps.iter() {
+                match extract_rumor(&client, &signer, gift_wrap).await {
+                    Ok(rumor) => {
+                        if let Err(e) = remove_trash(&client, gift_wrap.id).await {
+                            log::error!("Failed to remove the recovered gift wrap: {e}");
+                        }
+                        recovered.push(NewMessage::new(gift_wrap.id, rumor));
+                    }
+                    Err(e) => {
+                        let trash = FailedMessage::new(gift_wrap, e.kind, e.reason);
+
+                        if let Err(e) = set_trash(&client, &trash).await {
+                            log::error!("Failed to save the failed gift wrap: {e}");
+                        }
+                        failed.push(trash);
+                    }
+                }
+            }
+
+            Ok((recovered, failed))
+        });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let (recovered, failed) = task.await?;
+
+            this.update(cx, |this, cx| {
+                this.trashes.update(cx, |this, cx| {
+                    for message in recovered.iter() {
+                        this.remove(&message.gift_wrap);
+                    }
+                    for trash in failed.into_iter() {
+                        this.insert(trash.id(), trash);
+                    }
+                    cx.notify();
+                });
+
+                for message in recovered.into_iter() {
+                    this.new_message(message, cx);
+                }
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Re-derive the cached rumors from the gift wraps stored in the local database.
+    ///
+    /// Returns the number of gift wraps unwrapped again.
+    pub fn rebuild_rumor_cache(&mut self, cx: &mut Context<Self>) -> Task<Result<usize, Error>> {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let signer = nostr.read(cx).signer();
+
+        let task: Task<Result<usize, Error>> = cx.background_spawn(async move {
+            let filter = Filter::new().kind(Kind::ApplicationSpecificData);
+            let deleted = get_deleted(&client).await?;
+
+            // Cached rumors are identified by their gift wrap id
+            let mut cached: HashMap<EventId, Vec<EventId>> = HashMap::new();
+
+            for event in client.database().query(filter).await?.into_iter() {
+                if let Some(id) = event
+                    .tags
+                    .identifier()
+                    .and_then(|id| EventId::from_hex(id).ok())
+                {
+                    cached.entry(id).or_default().push(event.id);
+                }
+            }
+
+            let gift_wraps = client
+                .database()
+                .query(Filter::new().kind(Kind::GiftWrap))
+                .await?;
+
+            let mut replaced = vec![];
+            let mut count = 0;
+
+            // Unwrap into new rows first, the old rows are kept if the unwrap fails
+            for gift_wrap in gift_wraps.iter() {
+                // Deleted conversations stay deleted
+                if deleted.contains(&gift_wrap.id) {
+                    continue;
+                }
+
+                let mut rumor = match try_unwrap(&signer, gift_wrap).await {
+                    Ok(unwrapped) => unwrapped.rumor,
+                    Err(e) => {
+                        log::warn!("Failed to unwrap gift wrap {}: {e}", gift_wrap.id);
+                        continue;
+                    }
+                };
+
+                rumor.ensure_id();
+
+                if let Err(e) = set_rumor(&client, gift_wrap.id, &rumor).await {
+                    log::error!("Failed to cache rumor: {e:?}");
+                    continue;
+                }
+
+                if let Some(ids) = cached.remove(&gift_wrap.id) {
+                    replaced.extend(ids);
+                }
+
+                count += 1;
+            }
+
+            // Delete only the rows that were replaced
+            if !replaced.is_empty() {
+                client
+                    .database()
+                    .delete(Filter::new().ids(replaced))
+                    .await?;
+            }
+
+            Ok(count)
+        });
+
+        cx.spawn(async move |this, cx| {
+            let count = task.await?;
+
+            this.update(cx, |this, cx| {
+                this.get_rooms(cx);
+            })?;
+
+            Ok(count)
+        })
+    }
+
+    /// Remove a failed gift wrap from the trash.
+    pub fn discard_trash(&mut self, id: EventId, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        self.trashes.update(cx, |this, cx| {
+            this.remove(&id);
+            cx.notify();
+        });
+
+        self.tasks.push(cx.background_spawn(async move {
+            remove_trash(&client, id).await?;
+            Ok(())
+        }));
+    }
+
+    /// Get the relays that have seen a given rumor id.
+    pub fn rumor_seen_on(&self, id: &EventId) -> Option<HashSet<RelayUrl>> {
+        self.event_map
+            .read_blocking()
+            .get(id)
+            .map(|(id, _dekey)| self.seen_on(id))
+    }
+
+    /// Get the relays that have seen a given gift wrap id.
+    pub fn seen_on(&self, id: &EventId) -> HashSet<RelayUrl> {
+        self.seens
+            .read_blocking()
+            .get(id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Check if a given rumor was encrypted by the dekey.
+    pub fn encrypted_by_dekey(&self, id: &EventId) -> bool {
+        self.event_map
+            .read_blocking()
+            .get(id)
+            .map(|(_, dekey)| *dekey)
+            .unwrap_or(false)
+    }
+
+    /// Add a new room to the start of list.
+    pub fn add_room<I>(&mut self, room: I, cx: &mut Context<Self>)
+    where
+        I: Into<Room> + 'static,
+    {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        cx.spawn(async move |this, cx| {
+            let signer = client.signer()?;
+            let public_key = signer.get_public_key().await.ok()?;
+            let room: Room = room.into().organize(&public_key);
+
+            this.update(cx, |this, cx| {
+                this.rooms.insert(0, cx.new(|_| room));
+                this.apply_room_configs(cx);
+                this.score_requests(cx);
+                cx.emit(ChatEvent::Ping);
+                cx.notify();
+            })
+            .ok()
+        })
+        .detach();
+    }
+
+    /// Emit an open room event.
+    ///
+    /// If the room is new, add it to the registry.
+    pub fn emit_room(&mut self, room: &Entity<Room>, cx: &mut Context<Self>) {
+        // Get the room's ID.
+        let id = room.read(cx).id;
+
+        // If the room is new, add it to the registry.
+        if !self.rooms.iter().any(|r| r.read(cx).id == id) {
+            self.rooms.insert(0, room.to_owned());
+        }
+
+        // Emit the open room event.
+        cx.emit(ChatEvent::OpenRoom(id));
+    }
+
+    /// Close a room.
+    pub fn close_room(&mut self, id: u64, cx: &mut Context<Self>) {
+        if self.rooms.iter().any(|r| r.read(cx).id == id) {
+            cx.emit(ChatEvent::CloseRoom(id));
+        }
+    }
+
+    /// Sort rooms by their created at, pinned rooms first.
+    pub fn sort(&mut self, cx: &mut Context<Self>) {
+        self.rooms.sort_by_key(|ev| {
+            let room = ev.read(cx);
+            (Reverse(room.is_pinned()), Reverse(room.created_at))
+        });
+        cx.notify();
+    }
+
+    /// Update the config of a room, then sync the room configs if enabled.
+    pub fn update_room_config<F>(&mut self, id: u64, f: F, cx: &mut Context<Self>)
+    where
+        F: FnOnce(&mut RoomConfig),
+    {
+        let Some(room) = self.rooms.iter().find(|r| r.read(cx).id == id) else {
+            return;
+        };
+
+        room.update(cx, |this, cx| {
+            this.update_config(f, cx);
+        });
+
+        self.sort(cx);
+        self.publish_room_configs(cx);
+    }
+
+    /// Apply the room configs stored in the settings to the loaded rooms.
+    fn apply_room_configs(&mut self, cx: &mut Context<Self>) {
+        let configs = AppSettings::get_room_configs(cx);
+        let mut changed = false;
+
+        for room in self.rooms.iter() {
+            let id = room.read(cx).id;
+
+            if let Some(config) = configs.get(&id)
+                && &room.read(cx).config != config
+            {
+                room.update(cx, |this, cx| {
+                    this.config = config.clone();
+                    cx.notify();
+                });
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.sort(cx);
+        }
+    }
+
+    /// Publish the room configs for the user's other devices.
+    fn publish_room_configs(&mut self, cx: &mut Context<Self>) {
+        if !AppSettings::get_sync_room_configs(cx) {
+            return;
+        }
+
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let configs = AppSettings::get_room_configs(cx);
+
+        self.tasks.push(cx.background_spawn(async move {
+            publish_room_configs(&client, &configs).await?;
+            Ok(())
+        }));
+    }
+
+    /// Get the room configs from the user's other devices and merge them with the local ones.
+    pub fn get_room_configs(&mut self, cx: &mut Context<Self>) {
+        if !AppSettings::get_sync_room_configs(cx) {
+            return;
+        }
+
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task = cx.background_spawn(async move { fetch_room_configs(&client).await });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let remote = task.await?.unwrap_or_default();
+
+            this.update(cx, |this, cx| {
+                let mut configs = AppSettings::get_room_configs(cx);
+
+                if merge_room_configs(&mut configs, remote.clone()) {
+                    AppSettings::update_room_configs(configs.clone(), cx);
+                }
+
+                // Publish the merged configs if this device has newer changes
+                if configs != remote {
+                    this.publish_room_configs(cx);
+                }
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Sync the settings with the user's other devices, if enabled.
+    fn sync_settings(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if !AppSettings::get_sync_settings(cx) {
+            return;
+        }
+
+        if self.synced_settings.is_some() {
+            let delay = Duration::from_secs(SETTINGS_DELAY);
+
+            self.settings_debouncer
+                .fire_new(delay, window, cx, |this, _window, cx| {
+                    this.publish_settings(cx);
+                    Task::ready(())
+                });
+        } else {
+            self.get_settings(window, cx);
+        }
+    }
+
+    /// Publish the settings for the user's other devices, if they changed since the last sync.
+    fn publish_settings(&mut self, cx: &mut Context<Self>) {
+        // Never publish before merging, newer settings from other devices would be lost
+        let Some(synced) = self.synced_settings.as_ref() else {
+            return;
+        };
+
+        // Keep the settings that are only synced by other devices
+        let mut snapshot = synced.clone();
+        snapshot.extend(AppSettings::global(cx).read(cx).snapshot());
+
+        if &snapshot == synced {
+            return;
+        }
+
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        self.synced_settings = Some(snapshot.clone());
+        self.tasks.push(cx.background_spawn(async move {
+            publish_settings(&client, &snapshot).await?;
+            Ok(())
+        }));
+    }
+
+    /// Get the settings from the user's other devices, then merge them into the local ones.
+    pub fn get_settings(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if !AppSettings::get_sync_settings(cx) || self.fetching_settings {
+            return;
+        }
+
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task = cx.background_spawn(async move { fetch_settings(&client).await });
+        self.fetching_settings = true;
+
+        self.tasks.push(cx.spawn_in(window, async move |this, cx| {
+            let result = task.await;
+
+            this.update_in(cx, |this, window, cx| {
+                this.fetching_settings = false;
+
+                let remote = match result {
+                    Ok(remote) => remote.unwrap_or_default(),
+                    Err(e) => {
+                        log::error!("Failed to fetch settings: {e}");
+                        return;
+                    }
+                };
+
+                AppSettings::global(cx).update(cx, |this, cx| {
+                    this.merge_remote(&remote, window, cx);
+                });
+
+                // Publish the merged settings if this device has newer changes
+                this.synced_settings = Some(remote);
+                this.publish_settings(cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Finding rooms based on a query.
+    pub fn find(&self, query: &str, cx: &App) -> Vec<Entity<Room>> {
+        let matcher = SkimMatcherV2::default();
+
+        if let Ok(public_key) = PublicKey::parse(query) {
+            self.rooms
+                .iter()
+                .filter(|room| room.read(cx).members.contains(&public_key))
+                .cloned()
+                .collect()
+        } else {
+            self.rooms
+                .iter()
+                .filter(|room| {
+                    matcher
+                        .fuzzy_match(room.read(cx).display_name(cx).as_ref(), query)
+                        .is_some()
+                })
+                .cloned()
+                .collect()
+        }
+    }
+
+    /// Reset the registry.
+    pub fn reset(&mut self, cx: &mut Context<Self>) {
+        self.initializing = true;
+        self.rooms.clear();
+        self.drafts.clear();
+        self.mute_list = MuteList::default();
+        self.pending_mutes.clear();
+        self.mute_list_loaded = false;
+        self.spam_scores.clear();
+        self.scoring.clear();
+        self.synced_settings = None;
+        self.sync_state.write_blocking().clear();
+        self.sync_progress = SyncProgress::default();
+        self.trashes.update(cx, |this, cx| {
+            this.clear();
+            cx.notify();
+        });
+        cx.notify();
+    }
+
+    /// Check if a room has an unsent draft.
+    pub fn has_draft(&self, id: &u64) -> bool {
+        self.drafts.contains(id)
+    }
+
+    /// Load the ids of all rooms that have a draft.
+    fn get_drafts(&mut self, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task = cx.background_spawn(async move { get_draft_rooms(&client).await });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let drafts = task.await?;
+
+            this.update(cx, |this, cx| {
+                this.drafts = drafts;
+                cx.notify();
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Get the draft of a room.
+    pub fn load_draft(&self, id: u64, cx: &App) -> Task<Result<Option<Draft>, Error>> {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        cx.background_spawn(async move { get_draft(&client, id).await })
+    }
+
+    /// Save or remove the draft of a room.
+    pub fn save_draft(
+        &mut self,
+        id: u64,
+        draft: Draft,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<(), Error>> {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let changed = if draft.is_empty() {
+            self.drafts.remove(&id)
+        } else {
+            self.drafts.insert(id)
+        };
+
+        if changed {
+            cx.notify();
+        }
+
+        cx.background_spawn(async move { set_draft(&client, id, &draft).await })
+    }
+
+    /// Extend the registry with new rooms.
+    fn extend_rooms(&mut self, rooms: HashSet<Room>, cx: &mut Context<Self>) {
+        let mut room_map: HashMap<u64, usize> = self
+            .rooms
+            .iter()
+            .enumerate()
+            .map(|(idx, room)| (room.read(cx).id, idx))
+            .collect();
+
+        for new_room in rooms.into_iter() {
+            // Check if we already have a room with this ID
+            if let Some(&index) = room_map.get(&new_room.id) {
+                self.rooms[index].update(cx, |this, cx| {
+                    if new_room.created_at > this.created_at {
+                        *this = new_room;
+                        cx.notify();
+                    }
+                });
+            } else {
+                let new_room_id = new_room.id;
+                self.rooms.push(cx.new(|_| new_room));
+
+                let new_index = self.rooms.len();
+                room_map.insert(new_room_id, new_index);
+            }
+        }
+    }
+
+    /// Load all rooms from the database.
+    pub fn get_rooms(&mut self, cx: &mut Context<Self>) {
+        let task = self.get_rooms_from_database(cx);
 
         self.tasks.push(cx.spawn(async move |this, cx| {
             match task.await {
                 Ok(rooms) => {
                     this.update(cx, |this, cx| {
                         this.extend_rooms(rooms, cx);
+                        this.apply_room_configs(cx);
+                        this.score_requests(cx);
                         this.sort(cx);
                     })?;
                 }
@@ -705,6 +1848,7 @@ impl ChatRegistry {
     fn get_rooms_from_database(&self, cx: &App) -> Task<Result<HashSet<Room>, Error>> {
         let nostr = NostrRegistry::global(cx);
         let client = nostr.read(cx).client();
+        let mute_list = self.mute_list.clone();
 
         cx.background_spawn(async move {
             let signer = client.signer().context("Signer not found")?;
@@ -762,6 +1906,11 @@ impl ChatRegistry {
                 // Call `.organize` to ensure the current user is at the end of the list.
                 let mut room = Room::from(latest).organize(&public_key);
 
+                // Skip rooms that only have blocked members
+                if mute_list.room_kind(&room.members, Some(public_key)) == Some(MuteKind::Block) {
+                    continue;
+                }
+
                 // Check if the user has responded to the room
                 let user_sent = messages.iter().any(|m| m.pubkey == public_key);
 
@@ -788,8 +1937,16 @@ impl ChatRegistry {
         let nostr = NostrRegistry::global(cx);
         let signer = nostr.read(cx).signer();
 
+        // Drop messages from blocked users
+        if self.mute_list.is_blocked(&message.rumor.pubkey) {
+            return;
+        }
+
         match self.rooms.iter().find(|e| e.read(cx).id == message.room) {
             Some(room) => {
+                let id = message.room;
+                let archived = room.read(cx).is_archived();
+
                 room.update(cx, |this, cx| {
                     if this.kind == RoomKind::Request
                         && let Some(public_key) = signer.public_key()
@@ -799,7 +1956,13 @@ impl ChatRegistry {
                     }
                     this.push_message(message, cx);
                 });
-                self.sort(cx);
+
+                // Archived rooms are shown again when a new message arrives
+                if archived {
+                    self.update_room_config(id, |config| config.set_archived(false), cx);
+                } else {
+                    self.sort(cx);
+                }
             }
             None => {
                 // Push the new room to the front of the list
@@ -825,7 +1988,7 @@ async fn extract_rumor(
     client: &Client,
     signer: &Arc<CoopSigner>,
     gift_wrap: &Event,
-) -> Result<UnsignedEvent, Error> {
+) -> Result<UnsignedEvent, UnwrapError> {
     // Try to get cached rumor first
     if let Ok(rumor) = get_rumor(client, gift_wrap.id).await {
         return Ok(rumor);
@@ -847,39 +2010,78 @@ async fn extract_rumor(
 }
 
 /// Helper method to try unwrapping with different signers
-async fn try_unwrap(signer: &Arc<CoopSigner>, gift_wrap: &Event) -> Result<UnwrappedGift, Error> {
+async fn try_unwrap(
+    signer: &Arc<CoopSigner>,
+    gift_wrap: &Event,
+) -> Result<UnwrappedGift, UnwrapError> {
+    let recipient = gift_wrap.tags.public_keys().next().copied();
+    let device_signer = signer.get_encryption_signer().await;
+
     // Try with the device signer first
-    if let Some(signer) = signer.get_encryption_signer().await {
+    if let Some(signer) = device_signer.as_ref() {
         log::info!("trying with encryption key");
-        if let Ok(unwrapped) = try_unwrap_with(gift_wrap, &signer).await {
-            return Ok(unwrapped);
+        match try_unwrap_with(gift_wrap, signer).await {
+            Ok(unwrapped) => return Ok(unwrapped),
+            // The gift wrap was opened with the device key, but its content is invalid
+            Err(e) if e.kind != FailureKind::WrongRecipient => return Err(e),
+            Err(_) => {}
         }
     }
 
     // Fallback to the user's signer
     let user_signer = signer.get().await;
-    let unwrapped = try_unwrap_with(gift_wrap, &user_signer).await?;
-
-    Ok(unwrapped)
+    let user_public_key = user_signer.get_public_key().await.ok();
+
+    match try_unwrap_with(gift_wrap, &user_signer).await {
+        Ok(unwrapped) => Ok(unwrapped),
+        // Gift wraps addressed to another key are likely for the device key
+        Err(e)
+            if e.kind == FailureKind::WrongRecipient
+                && device_signer.is_none()
+                && recipient.is_some()
+                && recipient != user_public_key =>
+        {
+            Err(UnwrapError::new(
+                FailureKind::MissingDekey,
+                "The device key is not available yet",
+            ))
+        }
+        Err(e) => Err(e),
+    }
 }
 
 /// Attempts to unwrap a gift wrap event with a given signer.
-async fn try_unwrap_with<T>(gift_wrap: &Event, signer: &T) -> Result<UnwrappedGift, Error>
+async fn try_unwrap_with<T>(gift_wrap: &Event, signer: &T) -> Result<UnwrappedGift, UnwrapError>
 where
     T: NostrSigner + 'static,
 {
     // Get the sealed event
     let seal = signer
         .nip44_decrypt(&gift_wrap.pubkey, &gift_wrap.content)
-        .await?;
+        .await
+        .map_err(|e| UnwrapError::new(FailureKind::WrongRecipient, e))?;
 
     // Verify the sealed event
-    let seal: Event = Event::from_json(seal)?;
-    seal.verify_with_ctx(&SECP256K1)?;
+    let seal: Event =
+        Event::from_json(seal).map_err(|e| UnwrapError::new(FailureKind::BadSeal, e))?;
+    seal.verify_with_ctx(&SECP256K1)
+        .map_err(|e| UnwrapError::new(FailureKind::BadSeal, e))?;
 
     // Get the rumor event
-    let rumor = signer.nip44_decrypt(&seal.pubkey, &seal.content).await?;
-    let rumor = UnsignedEvent::from_json(rumor)?;
+    let rumor = signer
+        .nip44_decrypt(&seal.pubkey, &seal.content)
+        .await
+        .map_err(|e| UnwrapError::new(FailureKind::BadSeal, e))?;
+    let rumor = UnsignedEvent::from_json(rumor)
+        .map_err(|e| UnwrapError::new(FailureKind::MalformedRumor, e))?;
+
+    // The rumor must be authored by the seal's signer
+    if rumor.pubkey != seal.pubkey {
+        return Err(UnwrapError::new(
+            FailureKind::BadSeal,
+            "The seal was not signed by the message author",
+        ));
+    }
 
     Ok(UnwrappedGift {
         sender: seal.pubkey,
@@ -887,6 +2089,24 @@ where
     })
 }
 
+/// Send NIP-09 deletion requests for gift wraps to a single relay
+async fn request_gift_wrap_deletion(
+    client: &Client,
+    relay_url: &RelayUrl,
+    ids: &[EventId],
+) -> Result<(), Error> {
+    for chunk in ids.chunks(MAX_DELETION_IDS) {
+        let event = client.sign_event_builder(gift_wrap_deletion(chunk)).await?;
+        let output = client.send_event(&event).to([relay_url.clone()]).await?;
+
+        if let Some(reason) = output.failed.get(relay_url) {
+            return Err(anyhow!("Relay rejected the deletion: {reason}"));
+        }
+    }
+
+    Ok(())
+}
+
 /// Stores an unwrapped event in local database with reference to original
 async fn set_rumor(client: &Client, id: EventId, rumor: &UnsignedEvent) -> Result<(), Error> {
     let rumor_id = rumor.id.context("Rumor is missing an event id")?;
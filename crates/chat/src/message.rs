@@ -2,8 +2,8 @@ use std::hash::Hash;
 use std::ops::Range;
 
 use common::{EventExt, NostrParser};
-use gpui::SharedString;
 use nostr_sdk::prelude::*;
+use state::ImageMeta;
 
 /// New message.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -25,25 +25,6 @@ impl NewMessage {
     }
 }
 
-/// Trash message.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct FailedMessage {
-    pub raw_event: SharedString,
-    pub reason: SharedString,
-}
-
-impl FailedMessage {
-    pub fn new<T>(event: &Event, reason: T) -> Self
-    where
-        T: Into<SharedString>,
-    {
-        Self {
-            raw_event: SharedString::from(event.as_json()),
-            reason: reason.into(),
-        }
-    }
-}
-
 /// Message.
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Message {
@@ -124,10 +105,131 @@ impl Mention {
     }
 }
 
+/// NIP-21 reference to an event or an addressable event.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    /// The original `nostr:` URI
+    pub uri: String,
+    pub value: Nip21,
+    pub range: Range<usize>,
+}
+
+impl Reference {
+    /// Get the filter to find the referenced event
+    pub fn filter(&self) -> Option<Filter> {
+        match &self.value {
+            Nip21::EventId(id) => Some(Filter::new().id(*id)),
+            Nip21::Event(event) => Some(Filter::new().id(event.event_id)),
+            Nip21::Coordinate(addr) => Some(
+                Filter::new()
+                    .kind(addr.coordinate.kind)
+                    .author(addr.coordinate.public_key)
+                    .identifier(&addr.coordinate.identifier),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Get the relay hints of the reference
+    pub fn relays(&self) -> &[RelayUrl] {
+        match &self.value {
+            Nip21::Event(event) => &event.relays,
+            Nip21::Coordinate(addr) => &addr.relays,
+            _ => &[],
+        }
+    }
+
+    /// Get the bech32 entity, without the `nostr:` scheme
+    pub fn bech32(&self) -> &str {
+        self.uri
+            .split_once(':')
+            .map(|(_, bech32)| bech32)
+            .unwrap_or(&self.uri)
+    }
+}
+
+/// Kind of NIP-17 file messages
+pub const FILE_MESSAGE: Kind = Kind::Custom(15);
+
+/// Metadata of a NIP-17 file message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMessage {
+    /// URL of the (possibly encrypted) file
+    pub url: Option<Url>,
+    /// MIME type of the file before encryption
+    pub mime: Option<String>,
+    /// Size of the file in bytes
+    pub size: Option<u64>,
+    /// Dimensions of an image, e.g. `1920x1080`
+    pub dimensions: Option<String>,
+    /// Whether the file is encrypted
+    pub encrypted: bool,
+    /// Placeholder shown while the file loads
+    pub blurhash: Option<String>,
+    /// URL of a smaller version of the file, encrypted like the file
+    pub thumbnail: Option<Url>,
+}
+
+impl FileMessage {
+    fn new(content: &str, tags: &Tags) -> Self {
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|tag| tag.as_slice().first().is_some_and(|kind| kind == name))
+                .and_then(|tag| tag.content())
+                .map(String::from)
+        };
+
+        Self {
+            url: Url::parse(content.trim()).ok(),
+            mime: tag("file-type"),
+            size: tag("size").and_then(|size| size.parse().ok()),
+            dimensions: tag("dim"),
+            encrypted: tag("encryption-algorithm").is_some(),
+            blurhash: tag("blurhash"),
+            thumbnail: tag("thumb").and_then(|url| Url::parse(&url).ok()),
+        }
+    }
+}
+
+/// Structured payload of a message, based on the rumor kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageBody {
+    /// Kind 14 chat message, the text is the content
+    Text,
+    /// Kind 15 file message
+    File(FileMessage),
+    /// Kind 7 reaction, the emoji is the content
+    Reaction { target: Option<EventId> },
+    /// Kind 5 deletion request
+    Deletion { targets: Vec<EventId> },
+    /// Any other kind, with the raw rumor JSON
+    Unsupported { raw: String },
+}
+
+impl MessageBody {
+    fn new(kind: Kind, content: &str, tags: &Tags, raw: impl FnOnce() -> String) -> Self {
+        match kind {
+            Kind::PrivateDirectMessage => Self::Text,
+            Kind::Reaction => Self::Reaction {
+                target: tags.event_ids().last().copied(),
+            },
+            Kind::EventDeletion => Self::Deletion {
+                targets: tags.event_ids().copied().collect(),
+            },
+            kind if kind == FILE_MESSAGE => Self::File(FileMessage::new(content, tags)),
+            _ => Self::Unsupported { raw: raw() },
+        }
+    }
+}
+
 /// Rendered message.
 #[derive(Debug, Clone)]
 pub struct RenderedMessage {
     pub id: EventId,
+    /// Kind of the rumor
+    pub kind: Kind,
+    /// Structured payload of the message
+    pub body: MessageBody,
     /// Author's public key
     pub author: PublicKey,
     /// The content/text of the message
@@ -136,57 +238,89 @@ pub struct RenderedMessage {
     pub created_at: Timestamp,
     /// List of mentioned public keys in the message
     pub mentions: Vec<Mention>,
+    /// List of referenced events in the message
+    pub references: Vec<Reference>,
     /// List of event of the message this message is a reply to
     pub replies_to: Vec<EventId>,
+    /// NIP-92 metadata of the media linked in the message
+    pub media: Vec<ImageMeta>,
 }
 
-impl From<&Event> for RenderedMessage {
-    fn from(val: &Event) -> Self {
-        let mentions = extract_mentions(&val.content);
-        let replies_to = extract_reply_ids(&val.tags);
+impl RenderedMessage {
+    fn new(
+        id: EventId,
+        kind: Kind,
+        author: PublicKey,
+        content: &str,
+        created_at: Timestamp,
+        tags: &Tags,
+        raw: impl FnOnce() -> String,
+    ) -> Self {
+        let body = MessageBody::new(kind, content, tags, raw);
+
+        // Only text messages can contain mentions, references and media
+        let (mentions, references, media) = if body == MessageBody::Text {
+            (
+                extract_mentions(content),
+                extract_references(content),
+                tags.iter().filter_map(ImageMeta::from_tag).collect(),
+            )
+        } else {
+            (vec![], vec![], vec![])
+        };
 
         Self {
-            id: val.id,
-            author: val.pubkey,
-            content: val.content.clone(),
-            created_at: val.created_at,
+            id,
+            kind,
+            body,
+            author,
+            content: content.to_owned(),
+            created_at,
             mentions,
-            replies_to,
+            references,
+            replies_to: extract_reply_ids(tags),
+            media,
         }
     }
+
+    /// Whether the message is a kind 14 text message
+    pub fn is_text(&self) -> bool {
+        self.body == MessageBody::Text
+    }
+}
+
+impl From<&Event> for RenderedMessage {
+    fn from(val: &Event) -> Self {
+        Self::new(
+            val.id,
+            val.kind,
+            val.pubkey,
+            &val.content,
+            val.created_at,
+            &val.tags,
+            || val.as_json(),
+        )
+    }
 }
 
 impl From<&UnsignedEvent> for RenderedMessage {
     fn from(val: &UnsignedEvent) -> Self {
-        let mentions = extract_mentions(&val.content);
-        let replies_to = extract_reply_ids(&val.tags);
-
-        Self {
+        Self::new(
             // Event ID must be known
-            id: val.id.unwrap(),
-            author: val.pubkey,
-            content: val.content.clone(),
-            created_at: val.created_at,
-            mentions,
-            replies_to,
-        }
+            val.id.unwrap(),
+            val.kind,
+            val.pubkey,
+            &val.content,
+            val.created_at,
+            &val.tags,
+            || val.as_json(),
+        )
     }
 }
 
 impl From<&NewMessage> for RenderedMessage {
     fn from(val: &NewMessage) -> Self {
-        let mentions = extract_mentions(&val.rumor.content);
-        let replies_to = extract_reply_ids(&val.rumor.tags);
-
-        Self {
-            // Event ID must be known
-            id: val.rumor.id.unwrap(),
-            author: val.rumor.pubkey,
-            content: val.rumor.content.clone(),
-            created_at: val.rumor.created_at,
-            mentions,
-            replies_to,
-        }
+        Self::from(&val.rumor)
     }
 }
 
@@ -217,7 +351,7 @@ impl Hash for RenderedMessage {
 }
 
 /// Extracts all mentions (public keys) from a content string.
-fn extract_mentions(content: &str) -> Vec<Mention> {
+pub fn extract_mentions(content: &str) -> Vec<Mention> {
     let parser = NostrParser::new();
     let tokens = parser.parse(content);
 
@@ -230,6 +364,26 @@ fn extract_mentions(content: &str) -> Vec<Mention> {
         .collect()
 }
 
+/// Extracts all event references (note, nevent and naddr) from a content string.
+pub fn extract_references(content: &str) -> Vec<Reference> {
+    let parser = NostrParser::new();
+    let tokens = parser.parse(content);
+
+    tokens
+        .filter(|token| {
+            matches!(
+                token.value,
+                Nip21::EventId(_) | Nip21::Event(_) | Nip21::Coordinate(_)
+            )
+        })
+        .map(|token| Reference {
+            uri: content[token.range.clone()].to_string(),
+            value: token.value,
+            range: token.range,
+        })
+        .collect()
+}
+
 /// Extracts all reply (ids) from the event tags.
 fn extract_reply_ids(inner: &Tags) -> Vec<EventId> {
     let mut replies_to = vec![];
@@ -248,3 +402,79 @@ fn extract_reply_ids(inner: &Tags) -> Vec<EventId> {
 
     replies_to
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_references() {
+        let id = EventId::from_byte_array([1; 32]);
+        let note = id.to_bech32().unwrap();
+        let public_key = Keys::generate().public_key();
+        let content = format!(
+            "look nostr:{note} and nostr:{}",
+            public_key.to_bech32().unwrap()
+        );
+
+        let references = extract_references(&content);
+
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].bech32(), note);
+        assert_eq!(references[0].range, 5..11 + note.len());
+        assert_eq!(references[0].filter(), Some(Filter::new().id(id)));
+    }
+
+    #[test]
+    fn test_message_body() {
+        let keys = Keys::generate();
+        let target = EventId::from_byte_array([2; 32]);
+
+        let rumor = |kind: Kind, content: &str, tags: Vec<Tag>| {
+            let mut rumor = EventBuilder::new(kind, content)
+                .tags(tags)
+                .build(keys.public_key());
+            rumor.ensure_id();
+            RenderedMessage::from(&rumor)
+        };
+
+        let text = rumor(Kind::PrivateDirectMessage, "hello", vec![]);
+        assert!(text.is_text());
+
+        let reaction = rumor(Kind::Reaction, "+", vec![Tag::event(target)]);
+        assert_eq!(
+            reaction.body,
+            MessageBody::Reaction {
+                target: Some(target)
+            }
+        );
+
+        let file = rumor(
+            FILE_MESSAGE,
+            "https://example.com/file",
+            vec![
+                Tag::parse(["file-type", "image/png"]).unwrap(),
+                Tag::parse(["blurhash", "LEHV6nWB2yk8pyo0adR*.7kCMdnj"]).unwrap(),
+            ],
+        );
+        assert!(matches!(
+            file.body,
+            MessageBody::File(FileMessage { ref mime, ref blurhash, encrypted: false, .. })
+                if mime.as_deref() == Some("image/png") && blurhash.is_some()
+        ));
+
+        let image = rumor(
+            Kind::PrivateDirectMessage,
+            "https://example.com/image.jpg",
+            vec![
+                Tag::parse(["imeta", "url https://example.com/image.jpg", "dim 640x480"]).unwrap(),
+            ],
+        );
+        assert_eq!(image.media.len(), 1);
+        assert_eq!(image.media[0].dimensions, Some((640, 480)));
+
+        let unknown = rumor(Kind::Custom(31337), "nostr:npub1...", vec![]);
+        assert!(matches!(unknown.body, MessageBody::Unsupported { .. }));
+        assert!(unknown.mentions.is_empty());
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{Context as AnyhowContext, Error};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use state::TIMEOUT;
+
+/// Marker of the blocked users in the private `p` tags
+const BLOCK_MARKER: &str = "block";
+
+/// Identifier of the mute list cached in the local database
+const CACHE_IDENTIFIER: &str = "coop:mute_list";
+
+/// Changes to the mute list that haven't been published yet
+pub type PendingMutes = BTreeMap<PublicKey, Option<MuteKind>>;
+
+/// How the messages of a user in the mute list are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MuteKind {
+    /// Rooms are hidden, messages are still received
+    Mute,
+    /// Rooms and messages are dropped
+    Block,
+}
+
+/// NIP-51 mute list.
+///
+/// Muted and blocked users are stored in the encrypted content. Blocked users
+/// are marked with `["p", <pubkey>, "", "block"]`, other clients see them as muted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MuteList {
+    users: BTreeMap<PublicKey, MuteKind>,
+    /// Public tags set by other clients (words, hashtags, threads)
+    public_tags: Vec<Tag>,
+    /// Private tags set by other clients
+    private_tags: Vec<Tag>,
+}
+
+impl MuteList {
+    /// Construct the mute list from the public and decrypted private tags
+    pub fn from_tags<P, S>(public_tags: P, private_tags: S) -> Self
+    where
+        P: IntoIterator<Item = Tag>,
+        S: IntoIterator<Item = Tag>,
+    {
+        let mut list = Self::default();
+
+        for tag in public_tags.into_iter() {
+            if let Some((public_key, _)) = parse_user(&tag) {
+                // Public entries are moved to the private tags on the next publish
+                list.users.entry(public_key).or_insert(MuteKind::Mute);
+            } else {
+                list.public_tags.push(tag);
+            }
+        }
+
+        for tag in private_tags.into_iter() {
+            if let Some((public_key, kind)) = parse_user(&tag) {
+                list.users.insert(public_key, kind);
+            } else {
+                list.private_tags.push(tag);
+            }
+        }
+
+        list
+    }
+
+    /// Get the mute kind of a user
+    pub fn get(&self, public_key: &PublicKey) -> Option<MuteKind> {
+        self.users.get(public_key).copied()
+    }
+
+    /// Check if a user is muted or blocked
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.users.contains_key(public_key)
+    }
+
+    /// Check if a user is blocked
+    pub fn is_blocked(&self, public_key: &PublicKey) -> bool {
+        self.get(public_key) == Some(MuteKind::Block)
+    }
+
+    /// Mute, block or remove a user, returns true if the list changed
+    pub fn set(&mut self, public_key: PublicKey, kind: Option<MuteKind>) -> bool {
+        let old = match kind {
+            Some(kind) => self.users.insert(public_key, kind),
+            None => self.users.remove(&public_key),
+        };
+
+        old != kind
+    }
+
+    /// Get the mute kind of a room from its members, except the user.
+    ///
+    /// A room is only muted or blocked when all other members are.
+    pub fn room_kind(&self, members: &[PublicKey], user: Option<PublicKey>) -> Option<MuteKind> {
+        let mut others = members
+            .iter()
+            .filter(|public_key| Some(**public_key) != user);
+        let first = self.get(others.next()?)?;
+
+        others.try_fold(first, |kind, public_key| {
+            Some(kind.min(self.get(public_key)?))
+        })
+    }
+
+    /// Get all muted and blocked users
+    pub fn users(&self) -> impl Iterator<Item = (&PublicKey, &MuteKind)> {
+        self.users.iter()
+    }
+
+    /// Get the public tags
+    pub fn public_tags(&self) -> Vec<Tag> {
+        self.public_tags.clone()
+    }
+
+    /// Get the private tags, to be encrypted in the content
+    pub fn private_tags(&self) -> Vec<Tag> {
+        let users = self.users.iter().map(|(public_key, kind)| match kind {
+            MuteKind::Mute => Tag::public_key(*public_key),
+            MuteKind::Block => Tag::custom(
+                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::P)),
+                [public_key.to_hex(), String::new(), BLOCK_MARKER.to_string()],
+            ),
+        });
+
+        users.chain(self.private_tags.iter().cloned()).collect()
+    }
+}
+
+/// Mute list stored in the local database.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CachedMuteList {
+    public_tags: Vec<Vec<String>>,
+    private_tags: Vec<Vec<String>>,
+    pending: PendingMutes,
+}
+
+/// Get the user and the mute kind from a `p` tag
+fn parse_user(tag: &Tag) -> Option<(PublicKey, MuteKind)> {
+    let values = tag.as_slice();
+
+    if values.first().map(|v| v.as_str()) != Some("p") {
+        return None;
+    }
+
+    let public_key = PublicKey::parse(values.get(1)?).ok()?;
+    let kind = match values.get(3).map(|v| v.as_str()) {
+        Some(BLOCK_MARKER) => MuteKind::Block,
+        _ => MuteKind::Mute,
+    };
+
+    Some((public_key, kind))
+}
+
+/// Encrypt and publish the mute list to the user's relays.
+pub(crate) async fn publish_mute_list(client: &Client, list: &MuteList) -> Result<(), Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    // Encrypt the private tags
+    let tags: Vec<Vec<String>> = list
+        .private_tags()
+        .into_iter()
+        .map(|tag| tag.to_vec())
+        .collect();
+    let value = serde_json::to_string(&tags)?;
+    let content = signer.nip44_encrypt(&public_key, &value).await?;
+
+    // Construct the mute list event
+    let builder = EventBuilder::new(Kind::MuteList, content).tags(list.public_tags());
+
+    // Sign the event with user's signer
+    let event = client.sign_event_builder(builder).await?;
+
+    // Send the event to write relays
+    client
+        .send_event(&event)
+        .to_nip65()
+        .ack_policy(AckPolicy::none())
+        .await?;
+
+    Ok(())
+}
+
+/// Encrypt and store the mute list and its unpublished changes in the local database.
+pub(crate) async fn set_cached_mute_list(
+    client: &Client,
+    list: &MuteList,
+    pending: &PendingMutes,
+) -> Result<(), Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(CACHE_IDENTIFIER)
+        .author(public_key);
+
+    // Remove the previous cache, replaceable events may share the same timestamp
+    client.database().delete(filter).await?;
+
+    let cache = CachedMuteList {
+        public_tags: list.public_tags().into_iter().map(|t| t.to_vec()).collect(),
+        private_tags: list
+            .private_tags()
+            .into_iter()
+            .map(|t| t.to_vec())
+            .collect(),
+        pending: pending.clone(),
+    };
+
+    // Encrypt the value
+    let value = serde_json::to_string(&cache)?;
+    let content = signer.nip44_encrypt(&public_key, &value).await?;
+
+    // Construct the application data event
+    let event = EventBuilder::new(Kind::ApplicationSpecificData, content)
+        .tag(Tag::identifier(CACHE_IDENTIFIER))
+        .build(public_key)
+        .sign(&Keys::generate())
+        .await?;
+
+    // Save the event to the database
+    client.database().save_event(&event).await?;
+
+    Ok(())
+}
+
+/// Get the mute list and its unpublished changes from the local database.
+pub(crate) async fn get_cached_mute_list(
+    client: &Client,
+) -> Result<Option<(MuteList, PendingMutes)>, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(CACHE_IDENTIFIER)
+        .author(public_key);
+
+    let Some(event) = client.database().query(filter).await?.first_owned() else {
+        return Ok(None);
+    };
+
+    let content = signer.nip44_decrypt(&public_key, &event.content).await?;
+    let cache: CachedMuteList = serde_json::from_str(&content)?;
+    let parse = |tags: Vec<Vec<String>>| {
+        tags.into_iter()
+            .filter_map(|tag| Tag::parse(tag).ok())
+            .collect::<Vec<_>>()
+    };
+    let list = MuteList::from_tags(parse(cache.public_tags), parse(cache.private_tags));
+
+    Ok(Some((list, cache.pending)))
+}
+
+/// Get the latest mute list of the user.
+pub(crate) async fn fetch_mute_list(client: &Client) -> Result<Option<MuteList>, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::MuteList)
+        .author(public_key)
+        .limit(1);
+
+    // Stream events from user's write relays
+    let mut stream = client
+        .stream_events(filter)
+        .timeout(Duration::from_secs(TIMEOUT))
+        .await?;
+
+    let mut latest: Option<Event> = None;
+
+    while let Some((_url, res)) = stream.next().await {
+        if let Ok(event) = res
+            && latest
+                .as_ref()
+                .is_none_or(|e| event.created_at > e.created_at)
+        {
+            latest = Some(event);
+        }
+    }
+
+    let Some(event) = latest else {
+        return Ok(None);
+    };
+
+    let private_tags = if event.content.is_empty() {
+        vec![]
+    } else {
+        // Older clients encrypt the private tags with NIP-04
+        let content = match signer.nip44_decrypt(&public_key, &event.content).await {
+            Ok(content) => content,
+            Err(_) => signer.nip04_decrypt(&public_key, &event.content).await?,
+        };
+        let tags: Vec<Vec<String>> = serde_json::from_str(&content)?;
+
+        tags.into_iter()
+            .filter_map(|tag| Tag::parse(tag).ok())
+            .collect()
+    };
+
+    Ok(Some(MuteList::from_tags(event.tags.to_vec(), private_tags)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mute_list_tags() {
+        let muted = Keys::generate().public_key();
+        let blocked = Keys::generate().public_key();
+        let public = Keys::generate().public_key();
+        let word = Tag::parse(["word", "spam"]).unwrap();
+
+        let list = MuteList::from_tags(
+            [Tag::public_key(public), Tag::hashtag("nsfw")],
+            [
+                Tag::public_key(muted),
+                Tag::parse(["p", blocked.to_hex().as_str(), "", "block"]).unwrap(),
+                word.clone(),
+            ],
+        );
+
+        assert_eq!(list.get(&muted), Some(MuteKind::Mute));
+        assert_eq!(list.get(&public), Some(MuteKind::Mute));
+        assert!(list.is_blocked(&blocked));
+
+        // Public users are moved to the private tags, other tags are kept
+        assert_eq!(list.public_tags(), vec![Tag::hashtag("nsfw")]);
+        assert!(list.private_tags().contains(&word));
+        assert!(list.private_tags().contains(&Tag::public_key(public)));
+
+        // Parsing the published tags gives the same list
+        let parsed = MuteList::from_tags(list.public_tags(), list.private_tags());
+        assert_eq!(parsed, list);
+    }
+
+    #[test]
+    fn test_mute_list_set() {
+        let public_key = Keys::generate().public_key();
+        let mut list = MuteList::default();
+
+        assert!(list.set(public_key, Some(MuteKind::Mute)));
+        assert!(!list.set(public_key, Some(MuteKind::Mute)));
+        assert!(list.set(public_key, Some(MuteKind::Block)));
+        assert!(list.is_blocked(&public_key));
+        assert!(list.set(public_key, None));
+        assert!(!list.contains(&public_key));
+    }
+
+    #[test]
+    fn test_mute_list_room_kind() {
+        let user = Keys::generate().public_key();
+        let muted = Keys::generate().public_key();
+        let blocked = Keys::generate().public_key();
+        let other = Keys::generate().public_key();
+
+        let mut list = MuteList::default();
+        list.set(muted, Some(MuteKind::Mute));
+        list.set(blocked, Some(MuteKind::Block));
+
+        assert_eq!(
+            list.room_kind(&[blocked, user], Some(user)),
+            Some(MuteKind::Block)
+        );
+        assert_eq!(
+            list.room_kind(&[muted, blocked, user], Some(user)),
+            Some(MuteKind::Mute)
+        );
+        assert_eq!(list.room_kind(&[muted, other, user], Some(user)), None);
+        assert_eq!(list.room_kind(&[user], Some(user)), None);
+    }
+}
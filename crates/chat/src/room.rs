@@ -4,11 +4,11 @@ use std::time::Duration;
 
 use anyhow::{Error, anyhow};
 use common::EventExt;
-use gpui::{App, AppContext, Context, EventEmitter, SharedString, Task};
+use gpui::{App, AppContext, Context, EventEmitter, ImageSource, SharedString, Task};
 use itertools::Itertools;
 use nostr_sdk::prelude::*;
 use person::{Person, PersonRegistry};
-use settings::{RoomConfig, SignerKind};
+use settings::{AppSettings, RoomConfig, SignerKind};
 use state::{NostrRegistry, TIMEOUT};
 
 use crate::NewMessage;
@@ -107,6 +107,39 @@ pub enum RoomEvent {
     Reload,
 }
 
+/// Sidebar filter for rooms.
+#[derive(Clone, Hash, Debug, PartialEq, Eq)]
+pub enum RoomFilter {
+    /// Rooms of a kind, excluding archived rooms
+    Kind(RoomKind),
+    /// Rooms in a user-defined folder, excluding archived rooms
+    Folder(SharedString),
+    /// Archived rooms
+    Archived,
+    /// Requests with a low spam score, excluding archived rooms
+    Spam,
+}
+
+impl Default for RoomFilter {
+    fn default() -> Self {
+        Self::Kind(RoomKind::Ongoing)
+    }
+}
+
+impl RoomFilter {
+    /// Checks if the room matches the filter
+    pub fn matches(&self, room: &Room) -> bool {
+        match self {
+            Self::Kind(kind) => !room.is_archived() && &room.kind == kind,
+            Self::Folder(name) => {
+                !room.is_archived() && room.config.folder() == Some(name.as_ref())
+            }
+            Self::Archived => room.is_archived(),
+            Self::Spam => !room.is_archived() && room.kind == RoomKind::Request,
+        }
+    }
+}
+
 /// Room kind.
 #[derive(Clone, Copy, Hash, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub enum RoomKind {
@@ -133,7 +166,7 @@ pub struct Room {
     pub kind: RoomKind,
 
     /// Configuration
-    config: RoomConfig,
+    pub(super) config: RoomConfig,
 }
 
 impl Ord for Room {
@@ -251,16 +284,37 @@ impl Room {
         cx.notify();
     }
 
+    /// Updates the config of the room and persists it in the settings
+    pub fn update_config<F>(&mut self, f: F, cx: &mut Context<Self>)
+    where
+        F: FnOnce(&mut RoomConfig),
+    {
+        f(&mut self.config);
+        self.config.touch();
+
+        let id = self.id;
+        let config = self.config.clone();
+
+        AppSettings::global(cx).update(cx, |this, cx| {
+            this.add_room_config(id, config, cx);
+        });
+
+        cx.notify();
+    }
+
     /// Updates the signer kind config for the room
     pub fn set_signer_kind(&mut self, kind: &SignerKind, cx: &mut Context<Self>) {
-        self.config.set_signer_kind(kind);
-        cx.notify();
+        self.update_config(|config| config.set_signer_kind(kind), cx);
     }
 
     /// Updates the backup config for the room
     pub fn set_backup(&mut self, cx: &mut Context<Self>) {
-        self.config.toggle_backup();
-        cx.notify();
+        self.update_config(|config| config.toggle_backup(), cx);
+    }
+
+    /// Updates the link previews config for the room
+    pub fn set_link_previews(&mut self, cx: &mut Context<Self>) {
+        self.update_config(|config| config.toggle_link_previews(), cx);
     }
 
     /// Returns the config of the room
@@ -268,6 +322,16 @@ impl Room {
         &self.config
     }
 
+    /// Checks if the room is pinned to the top of the list
+    pub fn is_pinned(&self) -> bool {
+        self.config.pinned()
+    }
+
+    /// Checks if the room is hidden until a new message arrives
+    pub fn is_archived(&self) -> bool {
+        self.config.archived()
+    }
+
     /// Returns the members of the room
     pub fn members(&self) -> Vec<PublicKey> {
         self.members.clone()
@@ -288,11 +352,11 @@ impl Room {
     }
 
     /// Gets the display image for the room
-    pub fn display_image(&self, cx: &App) -> SharedString {
+    pub fn display_image(&self, cx: &App) -> ImageSource {
         if !self.is_group() {
             self.display_member(cx).avatar()
         } else {
-            SharedString::from("brand/group.png")
+            "brand/group.png".into()
         }
     }
 
@@ -407,11 +471,34 @@ impl Room {
         })
     }
 
+    /// Get the cached messages of the room with the given ids
+    pub fn get_messages_by_ids(
+        &self,
+        ids: Vec<EventId>,
+        cx: &App,
+    ) -> Task<Result<Vec<UnsignedEvent>, Error>> {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let conversation_id = self.id.to_string();
+
+        cx.background_spawn(async move {
+            let filter = Filter::new()
+                .kind(Kind::ApplicationSpecificData)
+                .custom_tag(SingleLetterTag::lowercase(Alphabet::C), &conversation_id)
+                .events(ids.clone());
+
+            let events = client.database().query(filter).await?;
+
+            Ok(cached_messages(events, &conversation_id, &ids))
+        })
+    }
+
     // Construct a rumor event for direct message
-    pub fn rumor<S, I>(&self, content: S, replies: I, cx: &App) -> Option<UnsignedEvent>
+    pub fn rumor<S, I, T>(&self, content: S, replies: I, tags: T, cx: &App) -> Option<UnsignedEvent>
     where
         S: Into<String>,
         I: IntoIterator<Item = EventId>,
+        T: IntoIterator<Item = Tag>,
     {
         let kind = Kind::PrivateDirectMessage;
         let content: String = content.into();
@@ -431,8 +518,8 @@ impl Room {
             .map(|member| persons.read(cx).get(member, cx))
             .collect();
 
-        // Construct event's tags
-        let mut tags = vec![];
+        // Construct event's tags, starting with the extra ones
+        let mut tags: Vec<Tag> = tags.into_iter().collect();
 
         // Add subject tag if present
         if let Some(value) = self.subject.as_ref() {
@@ -587,6 +674,31 @@ impl Room {
     }
 }
 
+/// Get the requested messages from cached rumor rows of a conversation.
+///
+/// Replies reference their parents too, so only the requested ids are kept.
+fn cached_messages<I>(events: I, conversation_id: &str, ids: &[EventId]) -> Vec<UnsignedEvent>
+where
+    I: IntoIterator<Item = Event>,
+{
+    let conversation = TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::C));
+
+    events
+        .into_iter()
+        .filter(|event| {
+            event
+                .tags
+                .find(conversation.clone())
+                .and_then(|tag| tag.content())
+                .is_some_and(|id| id == conversation_id)
+        })
+        .filter_map(|event| UnsignedEvent::from_json(&event.content).ok())
+        .filter(|message| message.id.is_some_and(|id| ids.contains(&id)))
+        .unique_by(|message| message.id)
+        .sorted_by_key(|message| message.created_at)
+        .collect()
+}
+
 // Helper function to send a gift-wrapped event
 async fn send_gift_wrap<T>(
     client: &Client,
@@ -638,3 +750,61 @@ where
 
     Ok(report)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Construct a cached rumor row, the way the chat registry stores it
+    fn row(keys: &Keys, conversation_id: &str, content: &str, replies_to: &[EventId]) -> Event {
+        let mut rumor = EventBuilder::new(Kind::PrivateDirectMessage, content)
+            .tags(replies_to.iter().copied().map(Tag::event))
+            .build(keys.public_key());
+        rumor.ensure_id();
+
+        let conversation = Tag::custom(
+            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::C)),
+            [conversation_id],
+        );
+
+        EventBuilder::new(Kind::ApplicationSpecificData, rumor.as_json())
+            .tags(rumor.tags.clone().to_vec())
+            .tag(conversation)
+            .tag(Tag::event(rumor.id.unwrap()))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    fn rumor_id(event: &Event) -> EventId {
+        UnsignedEvent::from_json(&event.content)
+            .unwrap()
+            .id
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cached_messages_of_two_rooms() {
+        let keys = Keys::generate();
+
+        // A private message in another room, referenced by a reply in this room
+        let private = row(&keys, "2", "private", &[]);
+        let parent = row(&keys, "1", "parent", &[]);
+        let reply = row(
+            &keys,
+            "1",
+            "reply",
+            &[rumor_id(&parent), rumor_id(&private)],
+        );
+
+        let ids = [rumor_id(&parent), rumor_id(&private)];
+        let events = vec![private.clone(), parent.clone(), reply];
+
+        let messages = cached_messages(events.clone(), "1", &ids);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].id, Some(rumor_id(&parent)));
+
+        let messages = cached_messages(events, "2", &ids);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].id, Some(rumor_id(&private)));
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context as AnyhowContext, Error};
+use gpui::http_client::HttpClient;
+use nostr_sdk::prelude::*;
+use state::{BOOTSTRAP_RELAYS, NostrAddress, TIMEOUT};
+
+/// Requests scoring below this are moved to the possible spam bucket
+pub const SPAM_THRESHOLD: i32 = 0;
+
+/// Points given to a sender in the user's contact list
+const FOLLOWED_POINTS: i32 = 100;
+
+/// Points given per mutual contact
+const MUTUAL_POINTS: i32 = 10;
+
+/// Maximum points given for mutual contacts
+const MAX_MUTUAL_POINTS: i32 = 50;
+
+/// Maximum number of senders scored at the same time
+const SCORING_CONCURRENCY: usize = 4;
+
+/// Senders active within this period are considered real users
+const RECENT_ACTIVITY: u64 = 30 * 24 * 60 * 60;
+
+/// Senders inactive for longer than this are considered abandoned
+const STALE_ACTIVITY: u64 = 365 * 24 * 60 * 60;
+
+/// Spam score of a chat request sender.
+///
+/// Combines the signals used by the screening dialog: contact list, mutual
+/// contacts, last activity and NIP-05 verification.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpamScore {
+    /// Whether the sender is in the user's contact list
+    pub followed: bool,
+
+    /// Number of the user's contacts following the sender
+    pub mutual_contacts: usize,
+
+    /// Last time the sender published something, `None` if unknown
+    pub last_active: Option<Timestamp>,
+
+    /// Whether the sender's NIP-05 address is verified, `None` if not set or unknown
+    pub verified: Option<bool>,
+}
+
+impl SpamScore {
+    /// Get the points of each signal.
+    ///
+    /// Unknown signals are neutral, so a relay or network failure doesn't mark a sender as spam.
+    pub fn breakdown(&self) -> Vec<(&'static str, i32)> {
+        let contact = if self.followed { FOLLOWED_POINTS } else { 0 };
+        let mutuals = (self.mutual_contacts as i32 * MUTUAL_POINTS).min(MAX_MUTUAL_POINTS);
+
+        let activity = match self.last_active {
+            Some(timestamp) => {
+                let age = Timestamp::now()
+                    .as_secs()
+                    .saturating_sub(timestamp.as_secs());
+
+                if age <= RECENT_ACTIVITY {
+                    10
+                } else if age > STALE_ACTIVITY {
+                    -10
+                } else {
+                    0
+                }
+            }
+            None => 0,
+        };
+
+        let address = match self.verified {
+            Some(true) => 20,
+            // An address that doesn't match is a common sign of impersonation
+            Some(false) => -20,
+            None => 0,
+        };
+
+        vec![
+            ("Contact", contact),
+            ("Mutual contacts", mutuals),
+            ("Activity", activity),
+            ("Address", address),
+        ]
+    }
+
+    /// Get the total score
+    pub fn total(&self) -> i32 {
+        self.breakdown().iter().map(|(_, points)| points).sum()
+    }
+
+    /// Check if the sender is possibly a spammer
+    pub fn is_spam(&self) -> bool {
+        self.total() < SPAM_THRESHOLD
+    }
+
+    /// Get the breakdown as a readable text
+    pub fn summary(&self) -> String {
+        self.breakdown()
+            .into_iter()
+            .map(|(name, points)| format!("{name}: {points:+}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Check if a person is in the user's contact list
+pub async fn is_followed(client: &Client, public_key: PublicKey) -> Result<bool, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let signer_pubkey = signer.get_public_key().await?;
+
+    // Check if user is in contact list
+    let contacts = client.database().contacts_public_keys(signer_pubkey).await;
+    let followed = contacts.unwrap_or_default().contains(&public_key);
+
+    Ok(followed)
+}
+
+/// Get the user's contacts that follow a person
+pub async fn mutual_contacts(
+    client: &Client,
+    public_key: PublicKey,
+) -> Result<Vec<PublicKey>, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let signer_pubkey = signer.get_public_key().await?;
+
+    // Check mutual contacts
+    let filter = Filter::new().kind(Kind::ContactList).pubkey(public_key);
+    let mut mutual_contacts = vec![];
+
+    if let Ok(events) = client.database().query(filter).await {
+        for event in events.into_iter().filter(|ev| ev.pubkey != signer_pubkey) {
+            mutual_contacts.push(event.pubkey);
+        }
+    }
+
+    Ok(mutual_contacts)
+}
+
+/// Get the last time a person published something on the bootstrap relays
+pub async fn last_activity(client: &Client, public_key: PublicKey) -> Option<Timestamp> {
+    let filter = Filter::new().author(public_key).limit(1);
+    let mut activity: Option<Timestamp> = None;
+
+    // Construct target for subscription
+    let target: HashMap<&str, Vec<Filter>> = BOOTSTRAP_RELAYS
+        .into_iter()
+        .map(|relay| (relay, vec![filter.clone()]))
+        .collect();
+
+    if let Ok(mut stream) = client
+        .stream_events(target)
+        .timeout(Duration::from_secs(TIMEOUT))
+        .await
+    {
+        while let Some((_url, event)) = stream.next().await {
+            if let Ok(event) = event
+                && activity.is_none_or(|t| event.created_at > t)
+            {
+                activity = Some(event.created_at);
+            }
+        }
+    }
+
+    activity
+}
+
+/// Verify the NIP-05 address of a person, from the metadata in the database.
+///
+/// Returns `None` if the person has no address or it couldn't be checked.
+pub async fn verify_address(
+    client: &Client,
+    http_client: &Arc<dyn HttpClient>,
+    public_key: PublicKey,
+) -> Option<bool> {
+    let filter = Filter::new()
+        .kind(Kind::Metadata)
+        .author(public_key)
+        .limit(1);
+
+    let event = client.database().query(filter).await.ok()?.first_owned()?;
+    let metadata = Metadata::from_json(&event.content).ok()?;
+    let address = Nip05Address::parse(&metadata.nip05?).ok()?;
+
+    address.verify(http_client, &public_key).await.ok()
+}
+
+/// Compute the spam score of a chat request sender
+pub async fn score_sender(
+    client: &Client,
+    http_client: &Arc<dyn HttpClient>,
+    public_key: PublicKey,
+) -> SpamScore {
+    let (followed, mutuals, last_active, verified) = futures::join!(
+        is_followed(client, public_key),
+        mutual_contacts(client, public_key),
+        last_activity(client, public_key),
+        verify_address(client, http_client, public_key),
+    );
+
+    SpamScore {
+        followed: followed.unwrap_or(false),
+        mutual_contacts: mutuals.map(|m| m.len()).unwrap_or_default(),
+        last_active,
+        verified,
+    }
+}
+
+/// Compute the spam scores of many senders, a few at a time
+pub async fn score_senders(
+    client: &Client,
+    http_client: &Arc<dyn HttpClient>,
+    public_keys: Vec<PublicKey>,
+) -> Vec<(PublicKey, SpamScore)> {
+    let mut scores = Vec::with_capacity(public_keys.len());
+
+    for chunk in public_keys.chunks(SCORING_CONCURRENCY) {
+        let tasks = chunk.iter().map(|public_key| async move {
+            let score = score_sender(client, http_client, *public_key).await;
+            (*public_key, score)
+        });
+
+        scores.extend(futures::future::join_all(tasks).await);
+    }
+
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spam_score() {
+        let now = Timestamp::now().as_secs();
+
+        // Unknown signals are neutral
+        let unknown = SpamScore::default();
+        assert_eq!(unknown.total(), 0);
+        assert!(!unknown.is_spam());
+
+        // Followed senders are never spam
+        let followed = SpamScore {
+            followed: true,
+            ..Default::default()
+        };
+        assert!(!followed.is_spam());
+
+        // Mutual contacts are capped
+        let popular = SpamScore {
+            mutual_contacts: 100,
+            last_active: Some(Timestamp::from_secs(now - 60)),
+            verified: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(popular.total(), 80);
+
+        // Stale sender with a mismatched address
+        let impostor = SpamScore {
+            mutual_contacts: 1,
+            last_active: Some(Timestamp::from_secs(now - 2 * STALE_ACTIVITY)),
+            verified: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(impostor.total(), -20);
+        assert!(impostor.is_spam());
+    }
+}
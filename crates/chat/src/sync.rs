@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context as AnyhowContext, Error};
+use nostr_sdk::prelude::*;
+
+const IDENTIFIER_PREFIX: &str = "coop:sync:";
+
+/// NIP-59 gift wraps are backdated by up to two days, cursors must look back that far
+pub const CURSOR_LOOKBACK: u64 = 2 * 24 * 60 * 60;
+
+/// Maximum time to wait for a relay to send its stored gift wraps
+pub(crate) const SYNC_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Interval between checks of the relays still syncing
+pub(crate) const SYNC_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Progress of the historical gift wrap sync, counted in relays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl SyncProgress {
+    /// Whether some relays haven't finished sending stored events
+    pub fn is_syncing(&self) -> bool {
+        self.done < self.total
+    }
+}
+
+/// Relays waiting for the end of stored events, by subscription.
+#[derive(Debug, Default)]
+pub(crate) struct SyncState {
+    /// Mapping the pending relays to their recipient and the sync start time
+    pending: HashMap<(SubscriptionId, RelayUrl), (PublicKey, Timestamp)>,
+
+    /// Number of relays in the current sync
+    total: usize,
+}
+
+impl SyncState {
+    /// Mark a relay as syncing
+    pub fn start(
+        &mut self,
+        id: SubscriptionId,
+        relay: RelayUrl,
+        recipient: PublicKey,
+        started_at: Timestamp,
+    ) {
+        if self
+            .pending
+            .insert((id, relay), (recipient, started_at))
+            .is_none()
+        {
+            self.total += 1;
+        }
+    }
+
+    /// Mark a relay as synced, returning its recipient and the sync start time
+    pub fn finish(
+        &mut self,
+        id: &SubscriptionId,
+        relay: &RelayUrl,
+    ) -> Option<(PublicKey, Timestamp)> {
+        let done = self.pending.remove(&(id.clone(), relay.clone()));
+
+        // Start counting from zero for the next sync
+        if self.pending.is_empty() {
+            self.total = 0;
+        }
+
+        done
+    }
+
+    /// Get the relays still syncing from a given start
+    pub fn pending_relays(&self, id: &SubscriptionId, started_at: Timestamp) -> Vec<RelayUrl> {
+        self.pending
+            .iter()
+            .filter(|((sub_id, _), (_, start))| sub_id == id && *start == started_at)
+            .map(|((_, relay), _)| relay.clone())
+            .collect()
+    }
+
+    pub fn progress(&self) -> SyncProgress {
+        SyncProgress {
+            done: self.total - self.pending.len(),
+            total: self.total,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.total = 0;
+    }
+}
+
+/// Get the `since` of a gift wrap filter from a sync cursor
+pub(crate) fn since(cursor: Timestamp) -> Timestamp {
+    Timestamp::from_secs(cursor.as_secs().saturating_sub(CURSOR_LOOKBACK))
+}
+
+fn identifier(recipient: &PublicKey, relay: &RelayUrl) -> String {
+    format!("{IDENTIFIER_PREFIX}{recipient}:{relay}")
+}
+
+/// Store the time a relay was last fully synced for a recipient.
+pub(crate) async fn set_cursor(
+    client: &Client,
+    recipient: &PublicKey,
+    relay: &RelayUrl,
+    cursor: Timestamp,
+) -> Result<(), Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(identifier(recipient, relay))
+        .author(public_key);
+
+    // Remove the previous cursor, replaceable events may share the same timestamp
+    client.database().delete(filter).await?;
+
+    // Construct the application data event
+    let event = EventBuilder::new(Kind::ApplicationSpecificData, cursor.to_string())
+        .tag(Tag::identifier(identifier(recipient, relay)))
+        .build(public_key)
+        .sign(&Keys::generate())
+        .await?;
+
+    // Save the event to the database
+    client.database().save_event(&event).await?;
+
+    Ok(())
+}
+
+/// Get the time a relay was last fully synced for a recipient.
+pub(crate) async fn get_cursor(
+    client: &Client,
+    recipient: &PublicKey,
+    relay: &RelayUrl,
+) -> Result<Option<Timestamp>, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(identifier(recipient, relay))
+        .author(public_key);
+
+    let cursor = client
+        .database()
+        .query(filter)
+        .await?
+        .first()
+        .and_then(|event| event.content.parse::<u64>().ok())
+        .map(Timestamp::from_secs);
+
+    Ok(cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_state() {
+        let keys = Keys::generate();
+        let id = SubscriptionId::new("gift-wraps");
+        let relay1 = RelayUrl::parse("wss://relay1.example.com").unwrap();
+        let relay2 = RelayUrl::parse("wss://relay2.example.com").unwrap();
+        let now = Timestamp::now();
+
+        let mut state = SyncState::default();
+        state.start(id.clone(), relay1.clone(), keys.public_key(), now);
+        state.start(id.clone(), relay2.clone(), keys.public_key(), now);
+        assert_eq!(state.progress(), SyncProgress { done: 0, total: 2 });
+
+        assert!(state.finish(&id, &relay1).is_some());
+        assert!(state.progress().is_syncing());
+        assert_eq!(state.pending_relays(&id, now), vec![relay2.clone()]);
+        assert!(state.pending_relays(&id, now + 1).is_empty());
+
+        // Unknown relays are ignored
+        assert!(state.finish(&id, &relay1).is_none());
+
+        assert!(state.finish(&id, &relay2).is_some());
+        assert!(!state.progress().is_syncing());
+        assert_eq!(state.progress().total, 0);
+    }
+
+    #[test]
+    fn test_since() {
+        let cursor = Timestamp::from_secs(CURSOR_LOOKBACK + 100);
+        assert_eq!(since(cursor), Timestamp::from_secs(100));
+        assert_eq!(since(Timestamp::from_secs(10)), Timestamp::from_secs(0));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+
+use nostr_sdk::prelude::*;
+
+/// Reply relations between the messages of a room, built from their `e` tags.
+#[derive(Debug, Clone, Default)]
+pub struct ReplyGraph {
+    /// Mapping message ids to the messages they reply to
+    parents: HashMap<EventId, Vec<EventId>>,
+
+    /// Mapping message ids to their direct replies
+    children: HashMap<EventId, Vec<EventId>>,
+}
+
+impl ReplyGraph {
+    /// Add a message and the messages it replies to
+    pub fn insert(&mut self, id: EventId, replies_to: &[EventId]) {
+        if self.parents.contains_key(&id) {
+            return;
+        }
+
+        for parent in replies_to.iter() {
+            self.children.entry(*parent).or_default().push(id);
+        }
+
+        self.parents.insert(id, replies_to.to_vec());
+    }
+
+    /// Get the number of direct replies to a message
+    pub fn reply_count(&self, id: &EventId) -> usize {
+        self.children.get(id).map(|c| c.len()).unwrap_or_default()
+    }
+
+    /// Get all messages in the thread of a message.
+    ///
+    /// The thread contains the chain of parents and all descendants.
+    pub fn thread(&self, id: &EventId) -> HashSet<EventId> {
+        let mut ids = HashSet::new();
+
+        // Walk up through the parents
+        let mut stack = vec![*id];
+
+        while let Some(id) = stack.pop() {
+            if ids.insert(id)
+                && let Some(parents) = self.parents.get(&id)
+            {
+                stack.extend(parents);
+            }
+        }
+
+        // Walk down through the replies
+        let mut stack = self.children.get(id).cloned().unwrap_or_default();
+
+        while let Some(id) = stack.pop() {
+            if ids.insert(id)
+                && let Some(children) = self.children.get(&id)
+            {
+                stack.extend(children);
+            }
+        }
+
+        ids
+    }
+
+    /// Get the messages in the thread of a message that haven't been inserted yet
+    pub fn missing(&self, id: &EventId) -> Vec<EventId> {
+        self.thread(id)
+            .into_iter()
+            .filter(|id| !self.parents.contains_key(id))
+            .collect()
+    }
+
+    /// Clear the graph
+    pub fn clear(&mut self) {
+        self.parents.clear();
+        self.children.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u8) -> EventId {
+        EventId::from_byte_array([n; 32])
+    }
+
+    #[test]
+    fn test_thread() {
+        let mut graph = ReplyGraph::default();
+
+        // 1 <- 2 <- 3 <- 4, 1 <- 5, 6 is unrelated
+        graph.insert(id(1), &[]);
+        graph.insert(id(2), &[id(1)]);
+        graph.insert(id(3), &[id(2)]);
+        graph.insert(id(4), &[id(3)]);
+        graph.insert(id(5), &[id(1)]);
+        graph.insert(id(6), &[]);
+
+        assert_eq!(graph.reply_count(&id(1)), 2);
+        assert_eq!(graph.reply_count(&id(4)), 0);
+
+        let thread = graph.thread(&id(3));
+        assert_eq!(thread, HashSet::from([id(1), id(2), id(3), id(4)]));
+
+        let thread = graph.thread(&id(1));
+        assert_eq!(thread.len(), 5);
+        assert!(!thread.contains(&id(6)));
+    }
+
+    #[test]
+    fn test_missing() {
+        let mut graph = ReplyGraph::default();
+
+        // 1 is not loaded, 2 replies to it and 3 replies to 2
+        graph.insert(id(2), &[id(1)]);
+        graph.insert(id(3), &[id(2)]);
+
+        assert_eq!(graph.missing(&id(3)), vec![id(1)]);
+
+        graph.insert(id(1), &[]);
+        assert!(graph.missing(&id(3)).is_empty());
+    }
+
+    #[test]
+    fn test_thread_with_cycle() {
+        let mut graph = ReplyGraph::default();
+
+        graph.insert(id(1), &[id(2)]);
+        graph.insert(id(2), &[id(1)]);
+
+        assert_eq!(graph.thread(&id(1)), HashSet::from([id(1), id(2)]));
+    }
+}
@@ -0,0 +1,176 @@
+use std::fmt::Display;
+
+use anyhow::{Context as AnyhowContext, Error};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const IDENTIFIER_PREFIX: &str = "coop:trash:";
+
+/// Gift wraps that can't be recovered are removed from the trash after this long, in seconds
+const JUNK_EXPIRY: u64 = 30 * 24 * 60 * 60;
+
+/// Why a gift wrap could not be opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureKind {
+    /// The gift wrap is encrypted for a key we don't hold
+    WrongRecipient,
+    /// The seal is invalid or its signature doesn't verify
+    BadSeal,
+    /// The gift wrap is addressed to the device key, which isn't available yet
+    MissingDekey,
+    /// The rumor inside the seal is not a valid event
+    MalformedRumor,
+}
+
+impl FailureKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::WrongRecipient => "Wrong recipient",
+            Self::BadSeal => "Bad seal",
+            Self::MissingDekey => "Missing device key",
+            Self::MalformedRumor => "Malformed message",
+        }
+    }
+
+    /// Whether a retry can't open the gift wrap, unlike a missing device key
+    pub fn is_junk(&self) -> bool {
+        !matches!(self, Self::MissingDekey)
+    }
+}
+
+/// Error returned when unwrapping a gift wrap fails.
+#[derive(Debug)]
+pub struct UnwrapError {
+    pub kind: FailureKind,
+    pub reason: String,
+}
+
+impl UnwrapError {
+    pub fn new<T>(kind: FailureKind, reason: T) -> Self
+    where
+        T: Display,
+    {
+        Self {
+            kind,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl Display for UnwrapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind.label(), self.reason)
+    }
+}
+
+impl std::error::Error for UnwrapError {}
+
+/// Trash message, a gift wrap that failed to unwrap.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedMessage {
+    pub gift_wrap: Event,
+    pub kind: FailureKind,
+    pub reason: String,
+}
+
+impl FailedMessage {
+    pub fn new<T>(gift_wrap: &Event, kind: FailureKind, reason: T) -> Self
+    where
+        T: Display,
+    {
+        Self {
+            gift_wrap: gift_wrap.clone(),
+            kind,
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn id(&self) -> EventId {
+        self.gift_wrap.id
+    }
+}
+
+fn identifier(gift_wrap: EventId) -> String {
+    format!("{IDENTIFIER_PREFIX}{gift_wrap}")
+}
+
+/// Store a failed gift wrap in the local database, replacing the previous failure.
+pub(crate) async fn set_trash(client: &Client, message: &FailedMessage) -> Result<(), Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    // Remove the previous failure, replaceable events may share the same timestamp
+    remove_trash(client, message.id()).await?;
+
+    let content = serde_json::to_string(message)?;
+
+    // Construct the application data event, junk expires instead of piling up
+    let event = EventBuilder::new(Kind::ApplicationSpecificData, content)
+        .tag(Tag::identifier(identifier(message.id())))
+        .tags(
+            message
+                .kind
+                .is_junk()
+                .then(|| Tag::expiration(Timestamp::now() + JUNK_EXPIRY)),
+        )
+        .build(public_key)
+        .sign(&Keys::generate())
+        .await?;
+
+    // Save the event to the database
+    client.database().save_event(&event).await?;
+
+    Ok(())
+}
+
+/// Remove a failed gift wrap from the local database.
+pub(crate) async fn remove_trash(client: &Client, gift_wrap: EventId) -> Result<(), Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .identifier(identifier(gift_wrap))
+        .author(public_key);
+
+    client.database().delete(filter).await?;
+
+    Ok(())
+}
+
+/// Get all failed gift wraps of the current user from the local database.
+///
+/// Expired failures are deleted.
+pub(crate) async fn get_trashes(client: &Client) -> Result<Vec<FailedMessage>, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    let filter = Filter::new()
+        .kind(Kind::ApplicationSpecificData)
+        .author(public_key);
+
+    let (expired, events): (Vec<Event>, Vec<Event>) = client
+        .database()
+        .query(filter)
+        .await?
+        .into_iter()
+        .filter(|event| {
+            event
+                .tags
+                .identifier()
+                .is_some_and(|id| id.starts_with(IDENTIFIER_PREFIX))
+        })
+        .partition(|event| event.is_expired());
+
+    if !expired.is_empty() {
+        let ids = expired.iter().map(|event| event.id);
+        client.database().delete(Filter::new().ids(ids)).await?;
+    }
+
+    let trashes = events
+        .into_iter()
+        .filter_map(|event| serde_json::from_str(&event.content).ok())
+        .collect();
+
+    Ok(trashes)
+}
@@ -1,4 +1,4 @@
-use gpui::Action;
+use gpui::{Action, actions};
 use nostr_sdk::prelude::*;
 use serde::Deserialize;
 use settings::SignerKind;
@@ -10,8 +10,14 @@ pub enum Command {
     ChangeSubject(String),
     ChangeSigner(SignerKind),
     ToggleBackup,
+    ToggleLinkPreviews,
     Copy(PublicKey),
     Relays(PublicKey),
     Njump(PublicKey),
+    Mute(PublicKey),
+    Block(PublicKey),
     Trace(EventId),
+    Thread(EventId),
 }
+
+actions!(chat, [ToggleBold, ToggleItalic, ToggleCode, TogglePreview]);
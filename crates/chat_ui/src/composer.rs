@@ -0,0 +1,176 @@
+use std::ops::Range;
+
+use person::Person;
+
+/// Maximum number of suggestions shown in the mention picker
+pub const MAX_MENTION_CANDIDATES: usize = 6;
+
+/// Markdown styles that can be toggled around the selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    Bold,
+    Italic,
+    Code,
+}
+
+impl Markup {
+    fn marker(&self) -> &'static str {
+        match self {
+            Markup::Bold => "**",
+            Markup::Italic => "_",
+            Markup::Code => "`",
+        }
+    }
+}
+
+/// The composers of the chat panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Composer {
+    /// The composer below the message list
+    Main,
+    /// The reply composer of the opened thread
+    Thread,
+}
+
+/// Autocomplete state for the `@mention` under the cursor.
+#[derive(Debug, Clone)]
+pub struct MentionPicker {
+    /// Composer the mention is typed in
+    pub composer: Composer,
+
+    /// Range of the `@query` in the input
+    pub range: Range<usize>,
+
+    /// Persons matching the query
+    pub candidates: Vec<Person>,
+
+    /// Index of the highlighted candidate
+    pub selected: usize,
+}
+
+impl MentionPicker {
+    pub fn select_prev(&mut self) {
+        if self.selected == 0 {
+            self.selected = self.candidates.len().saturating_sub(1);
+        } else {
+            self.selected -= 1;
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 >= self.candidates.len() {
+            self.selected = 0;
+        } else {
+            self.selected += 1;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Person> {
+        self.candidates.get(self.selected)
+    }
+}
+
+/// Find the `@query` that ends at the cursor.
+///
+/// The `@` must start a word, so email addresses don't trigger the picker.
+pub fn mention_query(text: &str, cursor: usize) -> Option<Range<usize>> {
+    let before = text.get(..cursor)?;
+    let start = before.rfind('@')?;
+    let query = &before[start + 1..];
+
+    if query.chars().any(char::is_whitespace) {
+        return None;
+    }
+
+    if before[..start]
+        .chars()
+        .next_back()
+        .is_some_and(|c| !c.is_whitespace())
+    {
+        return None;
+    }
+
+    Some(start..cursor)
+}
+
+/// Check if a person matches the (lowercase) mention query.
+pub fn mention_matches(person: &Person, query: &str) -> bool {
+    if query.is_empty() {
+        return true;
+    }
+
+    let metadata = person.metadata();
+
+    person.name().to_lowercase().contains(query)
+        || metadata
+            .name
+            .is_some_and(|name| name.to_lowercase().contains(query))
+        || metadata
+            .nip05
+            .is_some_and(|nip05| nip05.to_lowercase().starts_with(query))
+}
+
+/// Toggle a markdown style around the selected range.
+///
+/// Returns the range to replace, its replacement and the new selection.
+pub fn toggle_markup(
+    text: &str,
+    selection: Range<usize>,
+    markup: Markup,
+) -> (Range<usize>, String, Range<usize>) {
+    let marker = markup.marker();
+    let len = marker.len();
+    let inner = &text[selection.clone()];
+
+    let wrapped = selection.start >= len
+        && text.get(selection.start - len..selection.start) == Some(marker)
+        && text.get(selection.end..selection.end + len) == Some(marker);
+
+    if wrapped {
+        let range = selection.start - len..selection.end + len;
+        let new_selection = selection.start - len..selection.end - len;
+
+        (range, inner.to_string(), new_selection)
+    } else {
+        let new_selection = selection.start + len..selection.end + len;
+
+        (selection, format!("{marker}{inner}{marker}"), new_selection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mention_query() {
+        assert_eq!(mention_query("@", 1), Some(0..1));
+        assert_eq!(mention_query("hi @ali", 7), Some(3..7));
+        assert_eq!(mention_query("hi @ali ", 8), None);
+        assert_eq!(mention_query("me@example.com", 14), None);
+        assert_eq!(mention_query("hi @ali and", 5), Some(3..5));
+        assert_eq!(mention_query("no mention", 10), None);
+    }
+
+    #[test]
+    fn test_toggle_markup() {
+        let text = "hello world";
+
+        let (range, replacement, selection) = toggle_markup(text, 6..11, Markup::Bold);
+        assert_eq!(range, 6..11);
+        assert_eq!(replacement, "**world**");
+        assert_eq!(selection, 8..13);
+
+        let text = "hello **world**";
+
+        let (range, replacement, selection) = toggle_markup(text, 8..13, Markup::Bold);
+        assert_eq!(range, 6..15);
+        assert_eq!(replacement, "world");
+        assert_eq!(selection, 6..11);
+
+        let (range, replacement, selection) = toggle_markup("", 0..0, Markup::Code);
+        assert_eq!(range, 0..0);
+        assert_eq!(replacement, "``");
+        assert_eq!(selection, 1..1);
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context as AnyhowContext, Error, anyhow};
+use chat::Reference;
+use nostr_sdk::prelude::*;
+use state::{INDEXER_RELAYS, TIMEOUT};
+
+/// Loading state of an embedded event.
+#[derive(Debug, Clone)]
+pub enum Embed {
+    Loading,
+    Ready(Box<Event>),
+    NotFound,
+}
+
+/// Find a referenced event in the local database, then on the relay hints
+/// and the indexer relays.
+///
+/// Relay hints that aren't in the relay pool are skipped, rendering a message
+/// never connects to relays chosen by its sender.
+pub async fn resolve(client: &Client, reference: &Reference) -> Result<Event, Error> {
+    let filter = reference.filter().context("Not an event reference")?;
+
+    if let Some(event) = client.database().query(filter.clone()).await?.first() {
+        return Ok(event.clone());
+    }
+
+    let mut urls: Vec<RelayUrl> = vec![];
+
+    // Hints come from the message sender, only use the ones already in the relay pool
+    for url in reference.relays().iter() {
+        if let Ok(Some(_)) = client.relay(url).await {
+            urls.push(url.clone());
+        }
+    }
+
+    for url in INDEXER_RELAYS.into_iter() {
+        urls.push(RelayUrl::parse(url)?);
+    }
+
+    // Construct target for subscription
+    let target: HashMap<RelayUrl, Filter> = urls
+        .into_iter()
+        .map(|relay| (relay, filter.clone()))
+        .collect();
+
+    let mut stream = client
+        .stream_events(target)
+        .timeout(Duration::from_secs(TIMEOUT))
+        .await?;
+
+    let mut found: Option<Event> = None;
+
+    // Keep the latest version of addressable events
+    while let Some((_url, res)) = stream.next().await {
+        if let Ok(event) = res
+            && found
+                .as_ref()
+                .is_none_or(|found| event.created_at > found.created_at)
+        {
+            found = Some(event);
+        }
+    }
+
+    found.ok_or_else(|| anyhow!("Event not found"))
+}
+
+/// Get the value of the first tag with the given name
+pub fn tag_value<'a>(event: &'a Event, name: &str) -> Option<&'a str> {
+    event
+        .tags
+        .iter()
+        .find(|tag| tag.as_slice().first().is_some_and(|kind| kind == name))
+        .and_then(|tag| tag.content())
+}
@@ -0,0 +1,171 @@
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use gpui::Hsla;
+use syntect::easy::ScopeRangeIterator;
+use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
+use syntect::util::LinesWithEndings;
+use theme::ThemeColors;
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// Syntax element kinds, each one painted with a theme syntax color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Comment,
+    Keyword,
+    String,
+    Number,
+    Function,
+    Type,
+    Punctuation,
+}
+
+impl SyntaxKind {
+    /// Classify a TextMate scope, e.g. `string.quoted.double.rust`
+    fn from_scope(scope: &str) -> Option<Self> {
+        const KINDS: [(&str, SyntaxKind); 13] = [
+            ("comment", SyntaxKind::Comment),
+            ("string", SyntaxKind::String),
+            ("constant.character", SyntaxKind::String),
+            ("constant", SyntaxKind::Number),
+            ("keyword", SyntaxKind::Keyword),
+            ("storage", SyntaxKind::Keyword),
+            ("entity.name.tag", SyntaxKind::Keyword),
+            ("entity.name.function", SyntaxKind::Function),
+            ("support.function", SyntaxKind::Function),
+            ("entity.name", SyntaxKind::Type),
+            ("support.type", SyntaxKind::Type),
+            ("support.class", SyntaxKind::Type),
+            ("punctuation", SyntaxKind::Punctuation),
+        ];
+
+        // Quotes and comment markers take the color of what they delimit
+        if scope.starts_with("punctuation.definition") {
+            return None;
+        }
+
+        KINDS
+            .iter()
+            .find(|(prefix, _)| {
+                scope == *prefix
+                    || scope
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
+            .map(|(_, kind)| *kind)
+    }
+
+    pub fn color(&self, colors: &ThemeColors) -> Hsla {
+        match self {
+            SyntaxKind::Comment => colors.syntax_comment,
+            SyntaxKind::Keyword => colors.syntax_keyword,
+            SyntaxKind::String => colors.syntax_string,
+            SyntaxKind::Number => colors.syntax_number,
+            SyntaxKind::Function => colors.syntax_function,
+            SyntaxKind::Type => colors.syntax_type,
+            SyntaxKind::Punctuation => colors.syntax_punctuation,
+        }
+    }
+}
+
+/// Highlight a code block written in the given language.
+///
+/// Unknown languages are not highlighted.
+pub fn highlight(code: &str, language: &str) -> Vec<(Range<usize>, SyntaxKind)> {
+    let mut highlights: Vec<(Range<usize>, SyntaxKind)> = vec![];
+
+    let Some(syntax) = SYNTAX_SET.find_syntax_by_token(language) else {
+        return highlights;
+    };
+
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut offset = 0;
+
+    for line in LinesWithEndings::from(code) {
+        let Ok(ops) = state.parse_line(line, &SYNTAX_SET) else {
+            break;
+        };
+
+        for (range, op) in ScopeRangeIterator::new(&ops, line) {
+            if stack.apply(op).is_err() {
+                return highlights;
+            }
+
+            if range.is_empty() {
+                continue;
+            }
+
+            // The innermost classified scope wins
+            let kind = stack
+                .as_slice()
+                .iter()
+                .rev()
+                .find_map(|scope| SyntaxKind::from_scope(&scope.build_string()));
+
+            if let Some(kind) = kind {
+                let range = offset + range.start..offset + range.end;
+
+                // Merge with the previous highlight if they are adjacent
+                match highlights.last_mut() {
+                    Some((last, last_kind)) if last.end == range.start && *last_kind == kind => {
+                        last.end = range.end;
+                    }
+                    _ => highlights.push((range, kind)),
+                }
+            }
+        }
+
+        offset += line.len();
+    }
+
+    highlights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_scope() {
+        let kind = SyntaxKind::from_scope("string.quoted.double.rust");
+        assert_eq!(kind, Some(SyntaxKind::String));
+
+        let kind = SyntaxKind::from_scope("entity.name.function.rust");
+        assert_eq!(kind, Some(SyntaxKind::Function));
+
+        let kind = SyntaxKind::from_scope("constant.numeric.integer");
+        assert_eq!(kind, Some(SyntaxKind::Number));
+
+        assert_eq!(
+            SyntaxKind::from_scope("punctuation.terminator.rust"),
+            Some(SyntaxKind::Punctuation)
+        );
+        assert_eq!(
+            SyntaxKind::from_scope("punctuation.definition.string.begin"),
+            None
+        );
+        assert_eq!(SyntaxKind::from_scope("stringify"), None);
+        assert_eq!(SyntaxKind::from_scope("source.rust"), None);
+    }
+
+    #[test]
+    fn test_highlight() {
+        let code = "let name = \"coop\"; // comment\n";
+        let highlights = highlight(code, "rust");
+
+        let kind_of = |text: &str| {
+            let start = code.find(text).unwrap();
+            highlights
+                .iter()
+                .find(|(range, _)| range.contains(&start))
+                .map(|(_, kind)| *kind)
+        };
+
+        assert_eq!(kind_of("let"), Some(SyntaxKind::Keyword));
+        assert_eq!(kind_of("\"coop\""), Some(SyntaxKind::String));
+        assert_eq!(kind_of("// comment"), Some(SyntaxKind::Comment));
+        assert!(highlight(code, "not-a-language").is_empty());
+    }
+}
@@ -1,50 +1,182 @@
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub use actions::*;
 use anyhow::{Context as AnyhowContext, Error};
-use chat::{ChatRegistry, Message, RenderedMessage, Room, RoomEvent, SendReport, SendStatus};
-use common::TimestampExt;
+use chat::{
+    ChatRegistry, Draft, Message, MessageBody, MuteKind, Reference, RenderedMessage, ReplyGraph,
+    Room, RoomEvent, RoomKind, SendReport, SendStatus, extract_mentions,
+};
+use common::{DebouncedDelay, TimestampExt, format_size};
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    AnyElement, App, AppContext, ClipboardItem, Context, Entity, EventEmitter, FocusHandle,
-    Focusable, InteractiveElement, IntoElement, ListAlignment, ListOffset, ListState, MouseButton,
-    ObjectFit, ParentElement, PathPromptOptions, Render, SharedString, StatefulInteractiveElement,
-    Styled, StyledImage, Subscription, Task, WeakEntity, Window, deferred, div, img, list, px, red,
-    relative, svg, white,
+    AnyElement, App, AppContext, ClipboardEntry, ClipboardItem, Context, Entity, EventEmitter,
+    ExternalPaths, FocusHandle, Focusable, Image, ImageFormat, InteractiveElement, IntoElement,
+    KeyBinding, ListAlignment, ListOffset, ListState, MouseButton, ObjectFit, ParentElement,
+    PathPromptOptions, Render, SharedString, StatefulInteractiveElement, Styled, StyledImage,
+    Subscription, Task, WeakEntity, Window, deferred, div, img, list, px, red, relative, svg,
+    white,
 };
 use itertools::Itertools;
 use nostr_sdk::prelude::*;
-use person::{Person, PersonRegistry};
+use person::{Person, PersonRegistry, shorten_pubkey};
+pub use renderer::{MessageRenderer, MessageRenderers};
 use settings::{AppSettings, SignerKind};
 use smallvec::{SmallVec, smallvec};
 use smol::lock::RwLock;
-use state::{NostrRegistry, upload};
+use state::{ImageMeta, NostrRegistry, UploadProgress, keeps_metadata, upload_media};
 use theme::ActiveTheme;
 use ui::avatar::Avatar;
 use ui::button::{Button, ButtonVariants};
 use ui::dock::{Panel, PanelEvent};
-use ui::input::{InputEvent, InputState, TextInput};
+use ui::input::{
+    Enter, Escape, IndentInline, InputEvent, InputState, MoveDown, MoveUp, Paste, TextInput,
+};
 use ui::menu::DropdownMenu;
 use ui::notification::Notification;
 use ui::scroll::Scrollbar;
 use ui::{
-    Disableable, Icon, IconName, InteractiveElementExt, Sizable, StyledExt, WindowExtension,
-    h_flex, v_flex,
+    Disableable, Icon, IconName, InteractiveElementExt, Selectable, Sizable, StyledExt,
+    WindowExtension, h_flex, v_flex,
 };
 
+use crate::composer::{
+    Composer, MAX_MENTION_CANDIDATES, Markup, MentionPicker, mention_matches, mention_query,
+    toggle_markup,
+};
+use crate::embed::{Embed, tag_value};
+use crate::link_preview::{LinkPreview, LinkPreviewState};
+use crate::media::{Media, MediaKind, is_blob_url};
 use crate::text::RenderedText;
 
 mod actions;
+mod composer;
+mod embed;
+mod highlight;
+mod link_preview;
+mod media;
+mod renderer;
 mod text;
 
+const CONTEXT: &str = "ChatComposer";
+
+/// Delay before saving the draft after the last change, in milliseconds
+const DRAFT_DELAY: u64 = 500;
+
+/// Maximum size of an inline image, in pixels
+const MAX_MEDIA_WIDTH: f32 = 320.;
+const MAX_MEDIA_HEIGHT: f32 = 240.;
+
+/// Delay between two renders of the upload progress, in milliseconds
+const UPLOAD_REFRESH: u64 = 200;
+
 const ANNOUNCEMENT: &str =
     "This conversation is private. Only members can see each other's messages.";
 
+const METADATA_WARN: &str = "can't be re-encoded, it will be uploaded with its metadata, \
+    such as the location where it was taken.";
+
+const BLOCK_MSG: &str = "All messages from this person will be dropped and their chats removed. \
+    You can unblock them from the mute list.";
+
+/// Register the key bindings of the chat composer
+pub fn bind_keys(cx: &mut App) {
+    cx.bind_keys([
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-b", ToggleBold, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-b", ToggleBold, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-i", ToggleItalic, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-i", ToggleItalic, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-e", ToggleCode, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-e", ToggleCode, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-shift-p", TogglePreview, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-shift-p", TogglePreview, Some(CONTEXT)),
+    ]);
+}
+
+/// Remove expired media from the cache
+pub fn prune_media_cache(cx: &App) {
+    cx.background_spawn(async move {
+        if let Err(e) = media::prune_cache() {
+            log::warn!("Failed to prune the media cache: {e}");
+        }
+
+        if let Err(e) = media::prune_pasted() {
+            log::warn!("Failed to remove the pasted images: {e}");
+        }
+    })
+    .detach();
+}
+
 pub fn init(room: WeakEntity<Room>, window: &mut Window, cx: &mut App) -> Entity<ChatPanel> {
     cx.new(|cx| ChatPanel::new(room, window, cx))
 }
 
+/// Create the input of a composer, shared by the main and the thread composer
+fn composer_input(
+    placeholder: impl Into<SharedString>,
+    max_rows: usize,
+    window: &mut Window,
+    cx: &mut Context<InputState>,
+) -> InputState {
+    InputState::new(window, cx)
+        .placeholder(placeholder)
+        .auto_grow(1, max_rows)
+        .prevent_new_line_on_enter()
+        .clean_on_escape()
+}
+
+/// Open a modal to confirm blocking a user
+pub fn confirm_block(public_key: PublicKey, window: &mut Window, cx: &mut App) {
+    let persons = PersonRegistry::global(cx);
+    let name = persons.read(cx).get(&public_key, cx).name();
+
+    window.open_modal(cx, move |this, _window, _cx| {
+        let name = name.clone();
+
+        this.confirm()
+            .show_close(true)
+            .title(SharedString::from(format!("Block {name}?")))
+            .child(div().text_sm().child(SharedString::from(BLOCK_MSG)))
+            .on_ok(move |_ev, window, cx| {
+                ChatRegistry::global(cx).update(cx, |this, cx| {
+                    this.set_mute(public_key, Some(MuteKind::Block), cx);
+                });
+                window.push_notification(format!("{name} has been blocked"), cx);
+
+                // true to close modal
+                true
+            })
+    });
+}
+
+/// A file being uploaded from the composer
+struct PendingUpload {
+    /// File name
+    name: SharedString,
+
+    /// File path, pasted images are deleted once uploaded or cancelled
+    path: PathBuf,
+
+    /// Composer the file is attached to
+    composer: Composer,
+
+    /// Bytes sent, shared with the upload task
+    progress: UploadProgress,
+
+    /// Upload task, dropping it stops the upload
+    _task: Task<Result<(), Error>>,
+}
+
 /// Chat Panel
 pub struct ChatPanel {
     id: SharedString,
@@ -62,6 +194,21 @@ pub struct ChatPanel {
     /// Mapping message ids to their rendered texts
     rendered_texts_by_id: BTreeMap<EventId, RenderedText>,
 
+    /// Reply relations between all messages
+    reply_graph: ReplyGraph,
+
+    /// The message whose thread is opened in the side panel
+    thread: Option<EventId>,
+
+    /// Thread reply input state
+    thread_input: Entity<InputState>,
+
+    /// Media attached to the thread reply
+    thread_attachments: Entity<Vec<Url>>,
+
+    /// Messages of the opened thread loaded from outside of the message list
+    thread_parents: BTreeMap<EventId, RenderedMessage>,
+
     /// Mapping message (rumor event) ids to their reports
     reports_by_id: Entity<BTreeMap<EventId, Vec<SendReport>>>,
 
@@ -83,14 +230,56 @@ pub struct ChatPanel {
     /// Media Attachment
     attachments: Entity<Vec<Url>>,
 
-    /// Upload state
-    uploading: bool,
+    /// Metadata of the attached images, sent in `imeta` tags
+    attachment_meta: HashMap<Url, ImageMeta>,
+
+    /// Files being uploaded, by their upload id
+    uploads: BTreeMap<usize, PendingUpload>,
+
+    /// Id of the next upload
+    next_upload_id: usize,
+
+    /// Task re-rendering the upload progress
+    upload_ticker: Option<Task<()>>,
+
+    /// User's contacts, used for mention suggestions
+    contacts: HashSet<PublicKey>,
+
+    /// Mention autocomplete state
+    mention_picker: Option<MentionPicker>,
+
+    /// Rendered preview of the chat input
+    preview: Option<RenderedText>,
+
+    /// Debouncer for saving the draft
+    draft_debouncer: DebouncedDelay<Self>,
+
+    /// Mapping URLs linked in messages to their media
+    media: HashMap<String, Media>,
+
+    /// Blurhash placeholders of the images being downloaded, with the image dimensions
+    placeholders: HashMap<String, (Arc<Image>, (u32, u32))>,
+
+    /// Posters of the videos and audios, from their `imeta` tags
+    posters: HashMap<String, PathBuf>,
+
+    /// Media not loaded automatically, with their author and `imeta` tag
+    hidden_media: HashMap<String, (PublicKey, Option<ImageMeta>)>,
+
+    /// Media downloads, by URL
+    media_tasks: HashMap<String, Task<Result<(), Error>>>,
+
+    /// Mapping URLs linked in messages to their previews
+    link_previews: HashMap<String, LinkPreviewState>,
+
+    /// Mapping event references in messages to their events
+    embeds: HashMap<String, Embed>,
 
     /// Async operations
     tasks: Vec<Task<Result<(), Error>>>,
 
     /// Event subscriptions
-    subscriptions: SmallVec<[Subscription; 3]>,
+    subscriptions: SmallVec<[Subscription; 6]>,
 }
 
 impl ChatPanel {
@@ -115,13 +304,11 @@ impl ChatPanel {
             .unwrap_or(("Unknown".into(), "Message...".into()));
 
         // Define input state
-        let input = cx.new(|cx| {
-            InputState::new(window, cx)
-                .placeholder(format!("Message {}", name))
-                .auto_grow(1, 20)
-                .prevent_new_line_on_enter()
-                .clean_on_escape()
-        });
+        let input = cx.new(|cx| composer_input(format!("Message {}", name), 20, window, cx));
+
+        // Define thread reply input state
+        let thread_input = cx.new(|cx| composer_input("Reply in thread...", 10, window, cx));
+        let thread_attachments = cx.new(|_| vec![]);
 
         // Define subject input state
         let subject_input = cx.new(|cx| InputState::new(window, cx).placeholder("New subject..."));
@@ -133,8 +320,15 @@ impl ChatPanel {
         subscriptions.push(
             // Subscribe the chat input event
             cx.subscribe_in(&input, window, move |this, _input, event, window, cx| {
-                if let InputEvent::PressEnter { .. } = event {
-                    this.send_text_message(window, cx);
+                match event {
+                    InputEvent::PressEnter { .. } => {
+                        this.send_text_message(window, cx);
+                    }
+                    InputEvent::Change => {
+                        this.on_input_change(cx);
+                        this.schedule_draft(window, cx);
+                    }
+                    _ => {}
                 };
             }),
         );
@@ -152,9 +346,52 @@ impl ChatPanel {
             ),
         );
 
+        subscriptions.push(
+            // Subscribe the thread input event
+            cx.subscribe_in(
+                &thread_input,
+                window,
+                move |this, _input, event, window, cx| {
+                    match event {
+                        InputEvent::PressEnter { .. } => {
+                            this.send_thread_reply(window, cx);
+                        }
+                        InputEvent::Change => {
+                            this.update_mention_picker(Composer::Thread, cx);
+                            cx.notify();
+                        }
+                        _ => {}
+                    };
+                },
+            ),
+        );
+
+        subscriptions.push(
+            // Save the draft when attachments change
+            cx.observe_in(&attachments, window, |this, _, window, cx| {
+                this.schedule_draft(window, cx);
+            }),
+        );
+
+        subscriptions.push(
+            // Save the draft when replies change
+            cx.observe_in(&replies_to, window, |this, _, window, cx| {
+                this.schedule_draft(window, cx);
+            }),
+        );
+
+        subscriptions.push(
+            // Save the pending draft when the panel is closed
+            cx.on_release(|this, cx| {
+                this.save_draft(cx).detach();
+            }),
+        );
+
         // Define all functions that will run after the current cycle
         cx.defer_in(window, |this, window, cx| {
             this.connect(cx);
+            this.load_draft(window, cx);
+            this.load_contacts(cx);
             this.handle_notifications(cx);
             this.subscribe_room_events(window, cx);
             this.get_messages(window, cx);
@@ -171,10 +408,29 @@ impl ChatPanel {
             subject_bar,
             replies_to,
             attachments,
+            attachment_meta: HashMap::new(),
             rendered_texts_by_id: BTreeMap::new(),
+            reply_graph: ReplyGraph::default(),
+            thread: None,
+            thread_input,
+            thread_attachments,
+            thread_parents: BTreeMap::new(),
             reports_by_id,
             sent_ids: Arc::new(RwLock::new(Vec::new())),
-            uploading: false,
+            uploads: BTreeMap::new(),
+            next_upload_id: 0,
+            upload_ticker: None,
+            contacts: HashSet::new(),
+            mention_picker: None,
+            preview: None,
+            draft_debouncer: DebouncedDelay::new(),
+            media: HashMap::new(),
+            placeholders: HashMap::new(),
+            posters: HashMap::new(),
+            hidden_media: HashMap::new(),
+            media_tasks: HashMap::new(),
+            link_previews: HashMap::new(),
+            embeds: HashMap::new(),
             subscriptions,
             tasks: vec![],
         }
@@ -188,6 +444,429 @@ impl ChatPanel {
         }
     }
 
+    /// Load the user's contact list for mention suggestions
+    fn load_contacts(&mut self, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task: Task<Result<HashSet<PublicKey>, Error>> = cx.background_spawn(async move {
+            let signer = client.signer().context("Signer not found")?;
+            let public_key = signer.get_public_key().await?;
+            let contacts = client.database().contacts_public_keys(public_key).await?;
+
+            Ok(contacts)
+        });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let contacts = task.await?;
+
+            this.update(cx, |this, cx| {
+                this.contacts = contacts;
+                cx.notify();
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Check if the media in this room is loaded automatically.
+    ///
+    /// Media in chat requests is never loaded automatically.
+    fn auto_load_media(&self, cx: &App) -> bool {
+        AppSettings::get_auto_load_media(cx)
+            && self
+                .room
+                .read_with(cx, |this, _cx| this.kind != RoomKind::Request)
+                .unwrap_or(false)
+    }
+
+    /// Detect and download the media linked in a message
+    fn load_media(
+        &mut self,
+        urls: &[String],
+        metas: &[ImageMeta],
+        author: PublicKey,
+        cx: &mut Context<Self>,
+    ) {
+        let auto_load = self.auto_load_media(cx);
+
+        for url in urls.iter() {
+            // Load the hidden media once the room allows it, e.g. an accepted request
+            if self.media.get(url) == Some(&Media::Hidden) && auto_load {
+                self.show_media(url, cx);
+            }
+
+            if self.media.contains_key(url) {
+                continue;
+            }
+
+            let meta = metas.iter().find(|meta| meta.url.as_str() == url).cloned();
+
+            let media = match MediaKind::from_url(url) {
+                Some(kind) => Media::from(kind),
+                None if is_blob_url(url) => Media::Loading,
+                None => continue,
+            };
+
+            if auto_load {
+                self.fetch_media(url, media, meta, author, cx);
+            } else if media == Media::Loading {
+                self.media.insert(url.to_owned(), Media::Hidden);
+                self.hidden_media.insert(url.to_owned(), (author, meta));
+            } else {
+                // Videos and audios are only opened externally
+                self.media.insert(url.to_owned(), media);
+            }
+        }
+    }
+
+    /// Replace the thumbnail of a file message by the downloaded one.
+    ///
+    /// The thumbnail is loaded like other media, it's removed until it's downloaded.
+    /// Also returns the thumbnail URL if it wasn't loaded automatically.
+    fn with_local_thumbnail(
+        &mut self,
+        rendered: &RenderedMessage,
+        cx: &mut Context<Self>,
+    ) -> (RenderedMessage, Option<String>) {
+        let mut rendered = rendered.clone();
+
+        let MessageBody::File(file) = &mut rendered.body else {
+            return (rendered, None);
+        };
+
+        let Some(url) = file.thumbnail.take().filter(|_| !file.encrypted) else {
+            return (rendered, None);
+        };
+
+        let url = url.to_string();
+
+        let auto_load = self.auto_load_media(cx);
+
+        match self.media.get(&url) {
+            None | Some(Media::Hidden) if auto_load => {
+                self.fetch_media(&url, Media::Loading, None, rendered.author, cx);
+            }
+            None => {
+                self.media.insert(url.clone(), Media::Hidden);
+                self.hidden_media
+                    .insert(url.clone(), (rendered.author, None));
+            }
+            _ => {}
+        }
+
+        match self.media.get(&url) {
+            Some(Media::Image(path)) => {
+                file.thumbnail = Url::from_file_path(path).ok();
+                (rendered, None)
+            }
+            Some(Media::Hidden) => (rendered, Some(url)),
+            _ => (rendered, None),
+        }
+    }
+
+    /// Load a media that wasn't loaded automatically
+    fn show_media(&mut self, url: &str, cx: &mut Context<Self>) {
+        let Some((author, meta)) = self.hidden_media.remove(url) else {
+            return;
+        };
+
+        self.fetch_media(url, Media::Loading, meta, author, cx);
+        cx.notify();
+    }
+
+    /// Download an image, or the poster of a video or an audio
+    fn fetch_media(
+        &mut self,
+        url: &str,
+        media: Media,
+        meta: Option<ImageMeta>,
+        author: PublicKey,
+        cx: &mut Context<Self>,
+    ) {
+        let loading = media == Media::Loading;
+        self.media.insert(url.to_owned(), media);
+
+        // Show the blurhash until the image is downloaded
+        let blurhash = meta
+            .clone()
+            .filter(|meta| loading && meta.blurhash.is_some());
+
+        // Videos and audios are only opened externally, only their poster is downloaded
+        let poster = meta.and_then(|meta| meta.thumbnail);
+
+        if !loading && poster.is_none() {
+            return;
+        }
+
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+        let http_client = cx.http_client();
+        let url = url.to_owned();
+
+        let task = cx.spawn({
+            let url = url.clone();
+
+            async move |this, cx| {
+                if let Some(meta) = blurhash {
+                    let placeholder = cx
+                        .background_spawn(async move { meta.placeholder().zip(meta.dimensions) })
+                        .await;
+
+                    if let Some((placeholder, dimensions)) = placeholder {
+                        let image = Arc::new(Image::from_bytes(ImageFormat::Png, placeholder));
+
+                        this.update(cx, |this, cx| {
+                            this.placeholders.insert(url.clone(), (image, dimensions));
+                            cx.notify();
+                        })?;
+                    }
+                }
+
+                let result = cx
+                    .background_spawn({
+                        let url = url.clone();
+                        async move {
+                            if let Some(poster) = poster.filter(|_| !loading) {
+                                return media::fetch(poster.as_str(), &http_client).await;
+                            }
+
+                            match media::fetch(&url, &http_client).await {
+                                // Blobs can still be found on the author's other servers
+                                Err(e) if media::blob_hash(&url).is_some() => {
+                                    log::warn!("Failed to load media {url}: {e}");
+                                    media::fetch_from_servers(&url, author, &client, &http_client)
+                                        .await
+                                }
+                                result => result,
+                            }
+                        }
+                    })
+                    .await;
+
+                this.update(cx, |this, cx| {
+                    this.media_tasks.remove(&url);
+
+                    if loading {
+                        let media = result.unwrap_or_else(|e| {
+                            log::warn!("Failed to load media {url}: {e}");
+                            Media::Unsupported
+                        });
+
+                        this.placeholders.remove(&url);
+                        this.media.insert(url, media);
+                    } else if let Ok(Media::Image(path)) = result {
+                        this.posters.insert(url, path);
+                    }
+
+                    cx.notify();
+                })?;
+
+                Ok(())
+            }
+        });
+
+        self.media_tasks.insert(url, task);
+    }
+
+    /// Resolve the events referenced in a message
+    fn load_embeds(&mut self, references: &[Reference], cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+
+        for reference in references.iter() {
+            if self.embeds.contains_key(&reference.uri) {
+                continue;
+            }
+
+            self.embeds.insert(reference.uri.clone(), Embed::Loading);
+
+            let client = nostr.read(cx).client();
+            let reference = reference.clone();
+
+            self.tasks.push(cx.spawn(async move |this, cx| {
+                let embed = cx
+                    .background_spawn({
+                        let reference = reference.clone();
+                        async move { embed::resolve(&client, &reference).await }
+                    })
+                    .await
+                    .map(|event| Embed::Ready(Box::new(event)))
+                    .unwrap_or_else(|e| {
+                        log::warn!("Failed to resolve {}: {e}", reference.uri);
+                        Embed::NotFound
+                    });
+
+                this.update(cx, |this, cx| {
+                    this.embeds.insert(reference.uri, embed);
+                    cx.notify();
+                })?;
+
+                Ok(())
+            }));
+        }
+    }
+
+    /// Check if link previews are loaded automatically in this room
+    fn link_previews_enabled(&self, cx: &App) -> bool {
+        self.room
+            .read_with(cx, |this, _cx| this.config().link_previews())
+            .unwrap_or(false)
+    }
+
+    /// Fetch the OpenGraph or oEmbed preview of a link
+    fn load_link_preview(&mut self, url: &str, cx: &mut Context<Self>) {
+        if self.link_previews.contains_key(url) {
+            return;
+        }
+
+        self.link_previews
+            .insert(url.to_owned(), LinkPreviewState::Loading);
+        cx.notify();
+
+        let http_client = cx.http_client();
+        let url = url.to_owned();
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let state = cx
+                .background_spawn({
+                    let url = url.clone();
+                    async move { link_preview::fetch(&url, &http_client).await }
+                })
+                .await
+                .map(LinkPreviewState::Ready)
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to load preview of {url}: {e}");
+                    LinkPreviewState::Failed
+                });
+
+            this.update(cx, |this, cx| {
+                this.link_previews.insert(url, state);
+                cx.notify();
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Show an image in full size
+    fn zoom_image(
+        &mut self,
+        url: &str,
+        path: PathBuf,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let url = url.to_owned();
+
+        window.open_modal(cx, move |this, window, _cx| {
+            let url = url.clone();
+            let max_height = window.viewport_size().height * 0.75;
+
+            this.width(px(960.)).show_close(true).child(
+                v_flex()
+                    .w_full()
+                    .items_center()
+                    .gap_2()
+                    .child(
+                        img(path.clone())
+                            .max_w_full()
+                            .max_h(max_height)
+                            .object_fit(ObjectFit::Contain),
+                    )
+                    .child(
+                        Button::new("open-externally")
+                            .icon(IconName::Link)
+                            .label("Open externally")
+                            .ghost()
+                            .small()
+                            .on_click(move |_ev, _window, cx| {
+                                cx.open_url(&url);
+                            }),
+                    ),
+            )
+        });
+    }
+
+    /// Restore the unsent draft of this room
+    fn load_draft(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Ok(id) = self.room.read_with(cx, |this, _cx| this.id) else {
+            return;
+        };
+
+        let chat = ChatRegistry::global(cx);
+        let task = chat.read(cx).load_draft(id, cx);
+
+        self.tasks.push(cx.spawn_in(window, async move |this, cx| {
+            if let Some(draft) = task.await? {
+                this.update_in(cx, |this, window, cx| {
+                    this.restore_draft(draft, window, cx);
+                })?;
+            }
+
+            Ok(())
+        }));
+    }
+
+    fn restore_draft(&mut self, draft: Draft, window: &mut Window, cx: &mut Context<Self>) {
+        // Don't overwrite anything typed while the draft was loading
+        if !self.get_input_value(Composer::Main, cx).is_empty() {
+            return;
+        }
+
+        self.input.update(cx, |this, cx| {
+            this.set_value(draft.content, window, cx);
+        });
+        self.attachment_meta = draft
+            .media
+            .into_iter()
+            .map(|meta| (meta.url.clone(), meta))
+            .collect();
+        self.attachments.update(cx, |this, cx| {
+            *this = draft.attachments;
+            cx.notify();
+        });
+        self.replies_to.update(cx, |this, cx| {
+            *this = draft.replies_to.into_iter().collect();
+            cx.notify();
+        });
+    }
+
+    /// Save the draft once the user stops typing
+    fn schedule_draft(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let delay = Duration::from_millis(DRAFT_DELAY);
+
+        self.draft_debouncer
+            .fire_new(delay, window, cx, |this, _window, cx| this.save_draft(cx));
+    }
+
+    fn save_draft(&self, cx: &mut App) -> Task<()> {
+        let Ok(id) = self.room.read_with(cx, |this, _cx| this.id) else {
+            return Task::ready(());
+        };
+
+        let attachments = self.attachments.read(cx).clone();
+        let media = attachments
+            .iter()
+            .filter_map(|url| self.attachment_meta.get(url).cloned())
+            .collect();
+
+        let draft = Draft {
+            content: self.input.read(cx).value().to_string(),
+            attachments,
+            media,
+            replies_to: self.replies_to.read(cx).iter().copied().collect(),
+        };
+
+        let chat = ChatRegistry::global(cx);
+        let task = chat.update(cx, |this, cx| this.save_draft(id, draft, cx));
+
+        cx.background_spawn(async move {
+            if let Err(e) = task.await {
+                log::error!("Failed to save draft: {e}");
+            }
+        })
+    }
+
     /// Handle nostr notifications
     fn handle_notifications(&mut self, cx: &mut Context<Self>) {
         let nostr = NostrRegistry::global(cx);
@@ -291,13 +970,38 @@ impl ChatPanel {
         }));
     }
 
+    /// Get the input of a composer
+    fn input_of(&self, composer: Composer) -> &Entity<InputState> {
+        match composer {
+            Composer::Main => &self.input,
+            Composer::Thread => &self.thread_input,
+        }
+    }
+
+    /// Get the attachments of a composer
+    fn attachments_of(&self, composer: Composer) -> &Entity<Vec<Url>> {
+        match composer {
+            Composer::Main => &self.attachments,
+            Composer::Thread => &self.thread_attachments,
+        }
+    }
+
+    /// Get the composer that has focus, the main one if none has
+    fn focused_composer(&self, window: &Window, cx: &App) -> Composer {
+        if self.thread.is_some() && self.thread_input.focus_handle(cx).is_focused(window) {
+            Composer::Thread
+        } else {
+            Composer::Main
+        }
+    }
+
     /// Get user input content and merged all attachments if available
-    fn get_input_value(&self, cx: &Context<Self>) -> String {
+    fn get_input_value(&self, composer: Composer, cx: &App) -> String {
         // Get input's value
-        let mut content = self.input.read(cx).value().trim().to_string();
+        let mut content = self.input_of(composer).read(cx).value().trim().to_string();
 
         // Get all attaches and merge its with message
-        let attachments = self.attachments.read(cx);
+        let attachments = self.attachments_of(composer).read(cx);
 
         if !attachments.is_empty() {
             let urls = attachments
@@ -328,7 +1032,7 @@ impl ChatPanel {
 
     fn send_text_message(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         // Get the message which includes all attachments
-        let content = self.get_input_value(cx);
+        let content = self.get_input_value(Composer::Main, cx);
 
         // Return if message is empty
         if content.trim().is_empty() {
@@ -346,26 +1050,14 @@ impl ChatPanel {
             return;
         }
 
-        // Get room entity
-        let room = self.room.clone();
-
         // Get content and replies
         let replies: Vec<EventId> = self.replies_to.read(cx).iter().copied().collect();
         let content = value.to_string();
 
         self.tasks.push(cx.spawn_in(window, async move |this, cx| {
-            let room = room.upgrade().context("Room is not available")?;
-
             this.update_in(cx, |this, window, cx| {
-                match room.read(cx).rumor(content, replies, cx) {
-                    Some(rumor) => {
-                        this.insert_message(&rumor, true, cx);
-                        this.send_and_wait(rumor, window, cx);
-                        this.clear(window, cx);
-                    }
-                    None => {
-                        window.push_notification("Failed to create message", cx);
-                    }
+                if this.create_and_send(content, replies, window, cx) {
+                    this.clear(window, cx);
                 }
             })?;
 
@@ -373,6 +1065,62 @@ impl ChatPanel {
         }));
     }
 
+    /// Send a reply to the opened thread
+    fn send_thread_reply(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(id) = self.thread else {
+            return;
+        };
+
+        // Get the reply which includes all attachments
+        let content = self.get_input_value(Composer::Thread, cx);
+
+        if content.is_empty() {
+            window.push_notification("Cannot send an empty message", cx);
+            return;
+        }
+
+        if self.create_and_send(content, vec![id], window, cx) {
+            self.thread_input.update(cx, |this, cx| {
+                this.set_value("", window, cx);
+            });
+            self.clear_attachments(Composer::Thread, cx);
+        }
+    }
+
+    /// Create a rumor and send it to all members of the chat
+    fn create_and_send(
+        &mut self,
+        content: String,
+        replies: Vec<EventId>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(room) = self.room.upgrade() else {
+            window.push_notification("Room is not available", cx);
+            return false;
+        };
+
+        // Describe the attached images, so recipients can show a placeholder while they load
+        let tags: Vec<Tag> = self
+            .attachment_meta
+            .values()
+            .filter(|meta| content.contains(meta.url.as_str()))
+            .map(|meta| meta.to_tag())
+            .collect();
+
+        match room.read(cx).rumor(content, replies, tags, cx) {
+            Some(rumor) => {
+                self.insert_message(&rumor, true, cx);
+                self.send_and_wait(rumor, window, cx);
+                true
+            }
+            None => {
+                window.push_notification("Failed to create message", cx);
+                false
+            }
+        }
+    }
+
     /// Send message in the background and wait for the response
     fn send_and_wait(&mut self, rumor: UnsignedEvent, window: &mut Window, cx: &mut Context<Self>) {
         let sent_ids = self.sent_ids.clone();
@@ -418,16 +1166,25 @@ impl ChatPanel {
         self.input.update(cx, |this, cx| {
             this.set_value("", window, cx);
         });
-        self.attachments.update(cx, |this, cx| {
-            this.clear();
-            cx.notify();
-        });
+        self.clear_attachments(Composer::Main, cx);
         self.replies_to.update(cx, |this, cx| {
             this.clear();
             cx.notify();
         })
     }
 
+    /// Remove all attachments of a composer
+    fn clear_attachments(&mut self, composer: Composer, cx: &mut Context<Self>) {
+        let urls = self.attachments_of(composer).update(cx, |this, cx| {
+            cx.notify();
+            std::mem::take(this)
+        });
+
+        for url in urls.iter() {
+            self.attachment_meta.remove(url);
+        }
+    }
+
     /// Insert reports
     fn insert_reports(&mut self, id: EventId, reports: Vec<SendReport>, cx: &mut Context<Self>) {
         self.reports_by_id.update(cx, |this, cx| {
@@ -442,9 +1199,15 @@ impl ChatPanel {
         E: Into<Message>,
     {
         let old_len = self.messages.len();
+        let message = m.into();
+
+        // Track the reply relations for threads
+        if let Message::User(rendered) = &message {
+            self.reply_graph.insert(rendered.id, &rendered.replies_to);
+        }
 
         // Extend the messages list with the new events
-        if self.messages.insert(m.into()) {
+        if self.messages.insert(message) {
             self.list_state.splice(old_len..old_len, 1);
 
             if scroll {
@@ -536,71 +1299,548 @@ impl ChatPanel {
         self.replies_to.update(cx, |this, cx| {
             this.remove(id);
             cx.notify();
-        });
+        });
+    }
+
+    fn open_thread(&mut self, id: &EventId, window: &mut Window, cx: &mut Context<Self>) {
+        self.thread = Some(*id);
+        self.thread_input.update(cx, |this, cx| {
+            this.focus(window, cx);
+        });
+        self.load_thread_parents(cx);
+        cx.notify();
+    }
+
+    /// Load the messages of the opened thread that aren't in the message list
+    ///
+    /// Runs again for the parents of the loaded messages, until none is found.
+    fn load_thread_parents(&mut self, cx: &mut Context<Self>) {
+        let Some(id) = self.thread else {
+            return;
+        };
+
+        let missing = self.reply_graph.missing(&id);
+
+        if missing.is_empty() {
+            return;
+        }
+
+        let Ok(task) = self
+            .room
+            .read_with(cx, |this, cx| this.get_messages_by_ids(missing, cx))
+        else {
+            return;
+        };
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let events = task.await?;
+
+            if !events.is_empty() {
+                this.update(cx, |this, cx| {
+                    this.insert_thread_parents(&events, cx);
+                    this.load_thread_parents(cx);
+                })?;
+            }
+
+            Ok(())
+        }));
+    }
+
+    /// Insert messages of the opened thread, without adding them to the message list
+    fn insert_thread_parents(&mut self, events: &[UnsignedEvent], cx: &mut Context<Self>) {
+        for event in events.iter() {
+            let rendered = RenderedMessage::from(event);
+
+            self.reply_graph.insert(rendered.id, &rendered.replies_to);
+            self.thread_parents.insert(rendered.id, rendered);
+        }
+
+        cx.notify();
+    }
+
+    fn close_thread(&mut self, cx: &mut Context<Self>) {
+        self.thread = None;
+        cx.notify();
+    }
+
+    /// Get all messages in the opened thread, sorted by time
+    fn thread_messages(&self) -> Vec<RenderedMessage> {
+        let Some(id) = self.thread.as_ref() else {
+            return vec![];
+        };
+
+        let ids = self.reply_graph.thread(id);
+
+        let mut messages: Vec<RenderedMessage> = self
+            .messages
+            .iter()
+            .filter_map(|message| match message {
+                Message::User(rendered) if ids.contains(&rendered.id) => Some(rendered.clone()),
+                _ => None,
+            })
+            .collect();
+
+        // Add the parents loaded from outside of the message list
+        for message in self.thread_parents.values() {
+            if ids.contains(&message.id) && !messages.contains(message) {
+                messages.push(message.clone());
+            }
+        }
+
+        messages.sort_by_key(|message| message.created_at);
+        messages
+    }
+
+    fn upload(&mut self, composer: Composer, window: &mut Window, cx: &mut Context<Self>) {
+        // Ask user for files to upload
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: true,
+            prompt: None,
+        });
+
+        self.tasks.push(cx.spawn_in(window, async move |this, cx| {
+            let paths = paths.await??.context("Not found")?;
+
+            this.update_in(cx, |this, window, cx| {
+                this.upload_paths(paths, composer, window, cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Upload files as attachments, each one in its own task
+    fn upload_paths(
+        &mut self,
+        paths: Vec<PathBuf>,
+        composer: Composer,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        for path in paths.into_iter().filter(|path| path.is_file()) {
+            self.upload_path(path, composer, window, cx);
+        }
+    }
+
+    /// Upload a file, asking first if its metadata can't be removed
+    fn upload_path(
+        &mut self,
+        path: PathBuf,
+        composer: Composer,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if !keeps_metadata(&path) {
+            self.start_upload(path, composer, window, cx);
+            return;
+        }
+
+        let entity = cx.entity().downgrade();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        window.open_modal(cx, move |this, _window, _cx| {
+            let entity = entity.clone();
+            let path = path.clone();
+
+            this.confirm()
+                .show_close(true)
+                .title(SharedString::from("Upload with metadata?"))
+                .child(
+                    div()
+                        .text_sm()
+                        .child(SharedString::from(format!("{name} {METADATA_WARN}"))),
+                )
+                .on_ok({
+                    let path = path.clone();
+                    move |_ev, window, cx| {
+                        entity
+                            .update(cx, |this, cx| {
+                                this.start_upload(path.clone(), composer, window, cx);
+                            })
+                            .ok();
+                        // true to close modal
+                        true
+                    }
+                })
+                .on_cancel(move |_ev, _window, _cx| {
+                    media::remove_pasted(&path);
+                    // true to close modal
+                    true
+                })
+        });
+    }
+
+    fn start_upload(
+        &mut self,
+        path: PathBuf,
+        composer: Composer,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        // Get the user's configured blossom servers
+        let servers = AppSettings::get_file_servers(cx);
+        let max_dimension = AppSettings::get_max_image_dimension(cx);
+        let http_client = cx.http_client();
+        let progress = UploadProgress::default();
+
+        let id = self.next_upload_id;
+        self.next_upload_id += 1;
+
+        let name: SharedString = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+            .into();
+
+        let task = cx.spawn_in(window, {
+            let name = name.clone();
+            let path = path.clone();
+            let progress = progress.clone();
+
+            async move |this, cx| {
+                // Strip the metadata of images and upload via blossom client
+                let result = upload_media(
+                    servers,
+                    path.clone(),
+                    max_dimension,
+                    progress,
+                    http_client,
+                    cx,
+                )
+                .await;
+
+                media::remove_pasted(&path);
+
+                this.update_in(cx, |this, window, cx| {
+                    this.uploads.remove(&id);
+
+                    match result {
+                        Ok((url, meta)) => {
+                            this.add_attachment(url, meta, composer, cx);
+                        }
+                        Err(e) => {
+                            window.push_notification(
+                                Notification::error(format!("{name}: {e}")).autohide(false),
+                                cx,
+                            );
+                        }
+                    }
+
+                    cx.notify();
+                })?;
+
+                Ok(())
+            }
+        });
+
+        self.uploads.insert(
+            id,
+            PendingUpload {
+                name,
+                path,
+                composer,
+                progress,
+                _task: task,
+            },
+        );
+        self.refresh_uploads(cx);
+        cx.notify();
+    }
+
+    /// Cancel an upload, the file is not attached
+    fn cancel_upload(&mut self, id: usize, cx: &mut Context<Self>) {
+        if let Some(upload) = self.uploads.remove(&id) {
+            upload.progress.cancel();
+            media::remove_pasted(&upload.path);
+            cx.notify();
+        }
+    }
+
+    /// Re-render the upload progress until all uploads are done
+    fn refresh_uploads(&mut self, cx: &mut Context<Self>) {
+        if self.upload_ticker.is_some() {
+            return;
+        }
+
+        self.upload_ticker = Some(cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor()
+                    .timer(Duration::from_millis(UPLOAD_REFRESH))
+                    .await;
+
+                let done = this
+                    .update(cx, |this, cx| {
+                        cx.notify();
+                        this.uploads.is_empty()
+                    })
+                    .unwrap_or(true);
+
+                if done {
+                    break;
+                }
+            }
+
+            this.update(cx, |this, _cx| {
+                this.upload_ticker = None;
+            })
+            .ok();
+        }));
+    }
+
+    fn is_uploading(&self) -> bool {
+        !self.uploads.is_empty()
+    }
+
+    /// Upload the images pasted into the composer, other content is pasted as text
+    fn on_paste(&mut self, _ev: &Paste, window: &mut Window, cx: &mut Context<Self>) {
+        let composer = self.focused_composer(window, cx);
+        let images: Vec<Image> = cx
+            .read_from_clipboard()
+            .map(|item| {
+                item.entries()
+                    .iter()
+                    .filter_map(|entry| match entry {
+                        ClipboardEntry::Image(image) => Some(image.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if images.is_empty() {
+            cx.propagate();
+            return;
+        }
+
+        // Save the images to files, so they can be uploaded like the picked ones
+        let task: Task<Result<Vec<PathBuf>, Error>> = cx.background_spawn(async move {
+            let mut paths = vec![];
+
+            for (ix, image) in images.into_iter().enumerate() {
+                let extension = match image.format {
+                    ImageFormat::Jpeg => "jpg",
+                    ImageFormat::Webp => "webp",
+                    ImageFormat::Gif => "gif",
+                    ImageFormat::Bmp => "bmp",
+                    ImageFormat::Tiff => "tiff",
+                    ImageFormat::Svg => "svg",
+                    _ => "png",
+                };
+                let path = media::paste_path(ix, extension);
+
+                smol::fs::write(&path, &image.bytes).await?;
+                paths.push(path);
+            }
+
+            Ok(paths)
+        });
+
+        self.tasks.push(cx.spawn_in(window, async move |this, cx| {
+            let paths = task.await?;
+
+            this.update_in(cx, |this, window, cx| {
+                this.upload_paths(paths, composer, window, cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn add_attachment(
+        &mut self,
+        url: Url,
+        meta: Option<ImageMeta>,
+        composer: Composer,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(meta) = meta {
+            self.attachment_meta.insert(url.clone(), meta);
+        }
+
+        self.attachments_of(composer).update(cx, |this, cx| {
+            this.push(url);
+            cx.notify();
+        });
+    }
+
+    fn remove_attachment(&mut self, url: &Url, composer: Composer, cx: &mut Context<Self>) {
+        self.attachment_meta.remove(url);
+
+        self.attachments_of(composer).update(cx, |this, cx| {
+            if let Some(ix) = this.iter().position(|this| this == url) {
+                this.remove(ix);
+                cx.notify();
+            }
+        });
+    }
+
+    fn on_input_change(&mut self, cx: &mut Context<Self>) {
+        self.update_mention_picker(Composer::Main, cx);
+
+        if self.preview.is_some() {
+            self.preview = Some(self.preview_text(cx));
+        }
+
+        cx.notify();
+    }
+
+    /// Get the persons matching a mention query, room members first
+    fn mention_candidates(&self, query: &str, cx: &App) -> Vec<Person> {
+        let nostr = NostrRegistry::global(cx);
+        let user = nostr.read(cx).signer().public_key();
+        let query = query.to_lowercase();
+
+        let members = self
+            .room
+            .read_with(cx, |this, _cx| this.members())
+            .unwrap_or_default();
+
+        members
+            .into_iter()
+            .chain(self.contacts.iter().copied())
+            .filter(|public_key| Some(*public_key) != user)
+            .unique()
+            .map(|public_key| self.profile(&public_key, cx))
+            .filter(|person| mention_matches(person, &query))
+            .take(MAX_MENTION_CANDIDATES)
+            .collect()
+    }
+
+    fn update_mention_picker(&mut self, composer: Composer, cx: &App) {
+        let input = self.input_of(composer).read(cx);
+        let text = input.value();
+
+        let picker = mention_query(&text, input.cursor()).and_then(|range| {
+            let query = &text[range.start + 1..range.end];
+            let candidates = self.mention_candidates(query, cx);
+
+            (!candidates.is_empty()).then_some(MentionPicker {
+                composer,
+                range,
+                candidates,
+                selected: 0,
+            })
+        });
+
+        self.mention_picker = picker;
+    }
+
+    /// Replace the `@query` with a NIP-21 URI of the selected person
+    fn confirm_mention(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(picker) = self.mention_picker.take() else {
+            return;
+        };
+
+        let Some(person) = picker.selected() else {
+            return;
+        };
+
+        let uri = format!("nostr:{} ", person.public_key().to_bech32().unwrap());
+
+        self.input_of(picker.composer).update(cx, |this, cx| {
+            this.replace_range(picker.range.clone(), uri, window, cx);
+        });
+
+        cx.notify();
+    }
+
+    fn select_mention(&mut self, ix: usize, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(picker) = self.mention_picker.as_mut() {
+            picker.selected = ix;
+            self.confirm_mention(window, cx);
+        }
+    }
+
+    fn on_mention_prev(&mut self, _: &MoveUp, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(picker) = self.mention_picker.as_mut() {
+            picker.select_prev();
+            cx.stop_propagation();
+            cx.notify();
+        }
+    }
+
+    fn on_mention_next(&mut self, _: &MoveDown, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(picker) = self.mention_picker.as_mut() {
+            picker.select_next();
+            cx.stop_propagation();
+            cx.notify();
+        }
+    }
+
+    fn on_mention_enter(&mut self, _: &Enter, window: &mut Window, cx: &mut Context<Self>) {
+        if self.mention_picker.is_some() {
+            self.confirm_mention(window, cx);
+            cx.stop_propagation();
+        }
+    }
+
+    fn on_mention_tab(&mut self, _: &IndentInline, window: &mut Window, cx: &mut Context<Self>) {
+        if self.mention_picker.is_some() {
+            self.confirm_mention(window, cx);
+            cx.stop_propagation();
+        }
+    }
+
+    fn on_mention_escape(&mut self, _: &Escape, _window: &mut Window, cx: &mut Context<Self>) {
+        if self.mention_picker.take().is_some() {
+            cx.stop_propagation();
+            cx.notify();
+        }
     }
 
-    fn upload(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        // Get the user's configured blossom server
-        let server = AppSettings::get_file_server(cx);
+    /// Wrap or unwrap the selected text with a markdown style
+    fn apply_markup(&mut self, markup: Markup, window: &mut Window, cx: &mut Context<Self>) {
+        let input = self.input_of(self.focused_composer(window, cx)).clone();
 
-        // Ask user for file upload
-        let path = cx.prompt_for_paths(PathPromptOptions {
-            files: true,
-            directories: false,
-            multiple: false,
-            prompt: None,
-        });
+        input.update(cx, |this, cx| {
+            let text = this.value();
+            let (range, replacement, selection) =
+                toggle_markup(&text, this.selected_range(), markup);
 
-        self.tasks.push(cx.spawn_in(window, async move |this, cx| {
-            this.update(cx, |this, cx| {
-                this.set_uploading(true, cx);
-            })?;
+            this.replace_range(range, replacement, window, cx);
+            this.set_selected_range(selection, cx);
+        });
+    }
 
-            let mut paths = path.await??.context("Not found")?;
-            let path = paths.pop().context("No path")?;
+    fn on_toggle_bold(&mut self, _: &ToggleBold, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_markup(Markup::Bold, window, cx);
+    }
 
-            // Upload via blossom client
-            match upload(server, path, cx).await {
-                Ok(url) => {
-                    this.update_in(cx, |this, _window, cx| {
-                        this.add_attachment(url, cx);
-                        this.set_uploading(false, cx);
-                    })?;
-                }
-                Err(e) => {
-                    this.update_in(cx, |this, window, cx| {
-                        this.set_uploading(false, cx);
-                        window.push_notification(
-                            Notification::error(e.to_string()).autohide(false),
-                            cx,
-                        );
-                    })?;
-                }
-            }
+    fn on_toggle_italic(&mut self, _: &ToggleItalic, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_markup(Markup::Italic, window, cx);
+    }
 
-            Ok(())
-        }));
+    fn on_toggle_code(&mut self, _: &ToggleCode, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_markup(Markup::Code, window, cx);
     }
 
-    fn set_uploading(&mut self, uploading: bool, cx: &mut Context<Self>) {
-        self.uploading = uploading;
+    fn on_toggle_preview(
+        &mut self,
+        _: &TogglePreview,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.preview = match self.preview {
+            Some(_) => None,
+            None => Some(self.preview_text(cx)),
+        };
         cx.notify();
     }
 
-    fn add_attachment(&mut self, url: Url, cx: &mut Context<Self>) {
-        self.attachments.update(cx, |this, cx| {
-            this.push(url);
-            cx.notify();
-        });
-    }
+    /// Render the chat input the same way as a sent message
+    fn preview_text(&self, cx: &mut Context<Self>) -> RenderedText {
+        let persons = PersonRegistry::global(cx);
+        let content = self.get_input_value(Composer::Main, cx);
+        let mentions = extract_mentions(&content);
+        let text = RenderedText::new(&content, &mentions, &persons, cx);
 
-    fn remove_attachment(&mut self, url: &Url, _window: &mut Window, cx: &mut Context<Self>) {
-        self.attachments.update(cx, |this, cx| {
-            if let Some(ix) = this.iter().position(|this| this == url) {
-                this.remove(ix);
-                cx.notify();
-            }
-        });
+        text.highlight_code(cx);
+        text
     }
 
     fn profile(&self, public_key: &PublicKey, cx: &App) -> Person {
@@ -641,6 +1881,20 @@ impl ChatPanel {
                     );
                 }
             }
+            Command::ToggleLinkPreviews => {
+                if self
+                    .room
+                    .update(cx, |this, cx| {
+                        this.set_link_previews(cx);
+                    })
+                    .is_err()
+                {
+                    window.push_notification(
+                        Notification::error("Failed to toggle link previews").autohide(false),
+                        cx,
+                    );
+                }
+            }
             Command::ToggleBackup => {
                 if self
                     .room
@@ -664,9 +1918,18 @@ impl ChatPanel {
             Command::Njump(public_key) => {
                 self.open_njump(public_key, cx);
             }
+            Command::Mute(public_key) => {
+                self.mute(public_key, window, cx);
+            }
+            Command::Block(public_key) => {
+                confirm_block(*public_key, window, cx);
+            }
             Command::Trace(id) => {
                 self.open_trace(id, window, cx);
             }
+            Command::Thread(id) => {
+                self.open_thread(id, window, cx);
+            }
         }
     }
 
@@ -749,6 +2012,17 @@ impl ChatPanel {
         cx.open_url(&content);
     }
 
+    fn mute(&mut self, public_key: &PublicKey, window: &mut Window, cx: &mut Context<Self>) {
+        let chat = ChatRegistry::global(cx);
+        let name = self.profile(public_key, cx).name();
+
+        chat.update(cx, |this, cx| {
+            this.set_mute(*public_key, Some(MuteKind::Mute), cx);
+        });
+
+        window.push_notification(format!("{name} has been muted"), cx);
+    }
+
     fn render_announcement(&self, ix: usize, cx: &Context<Self>) -> AnyElement {
         v_flex()
             .id(ix)
@@ -813,17 +2087,61 @@ impl ChatPanel {
     ) -> AnyElement {
         if let Some(message) = self.messages.iter().nth(ix) {
             match message {
+                Message::User(rendered) if !rendered.is_text() => {
+                    let (rendered, hidden) = self.with_local_thumbnail(rendered, cx);
+                    let body = match MessageRenderers::get(rendered.kind, cx) {
+                        Some(renderer) => renderer(&rendered, window, cx),
+                        None => self.render_unsupported(&rendered, cx),
+                    };
+                    let body = match hidden {
+                        Some(url) => {
+                            let element_id =
+                                SharedString::from(format!("{}-thumbnail", rendered.id.to_hex()));
+
+                            v_flex()
+                                .child(body)
+                                .child(self.render_hidden_media(element_id, &url, cx))
+                                .into_any_element()
+                        }
+                        None => body,
+                    };
+
+                    self.render_text_message(ix, &rendered, body, cx)
+                }
                 Message::User(rendered) => {
                     let persons = PersonRegistry::global(cx);
-                    let text = self
+                    let rendered_text = self
                         .rendered_texts_by_id
                         .entry(rendered.id)
                         .or_insert_with(|| {
-                            RenderedText::new(&rendered.content, &rendered.mentions, &persons, cx)
-                        })
-                        .element(ix.into(), window, cx);
+                            let text = RenderedText::new(
+                                &rendered.content,
+                                &rendered.mentions,
+                                &persons,
+                                cx,
+                            );
+                            text.highlight_code(cx);
+                            text
+                        });
+                    let text = rendered_text.element(ix.into(), window, cx);
+                    let urls = rendered_text.link_urls.clone();
+                    let references = rendered.references.clone();
+                    let metas = rendered.media.clone();
+                    let author = rendered.author;
+                    let element = self.render_text_message(ix, rendered, text, cx);
+
+                    // Only load the media and embeds of visible messages
+                    self.load_media(&urls, &metas, author, cx);
+                    self.load_embeds(&references, cx);
+
+                    // Link previews leak the user's IP address, they must be enabled
+                    if self.link_previews_enabled(cx) {
+                        for url in urls.iter().filter(|url| is_previewable(url)) {
+                            self.load_link_preview(url, cx);
+                        }
+                    }
 
-                    self.render_text_message(ix, rendered, text, cx)
+                    element
                 }
                 Message::Warning(content, _timestamp) => {
                     self.render_warning(ix, SharedString::from(content), cx)
@@ -839,17 +2157,22 @@ impl ChatPanel {
         &self,
         ix: usize,
         message: &RenderedMessage,
-        rendered_text: AnyElement,
+        body: AnyElement,
         cx: &Context<Self>,
     ) -> AnyElement {
         let id = message.id;
         let author = self.profile(&message.author, cx);
         let pk = author.public_key();
 
+        // Don't allow the user to mute themselves
+        let nostr = NostrRegistry::global(cx);
+        let is_self = nostr.read(cx).signer().public_key() == Some(pk);
+
         let replies = message.replies_to.as_slice();
         let has_replies = !replies.is_empty();
         let has_reports = self.has_reports(&id, cx);
         let encrypted_by_dekey = self.encrypted_by_dekey(&id, cx);
+        let reply_count = self.reply_graph.reply_count(&id);
 
         // Hide avatar setting
         let hide_avatar = AppSettings::get_hide_avatar(cx);
@@ -875,6 +2198,11 @@ impl ChatPanel {
                                         .menu("View Relays", Box::new(Command::Relays(pk)))
                                         .separator()
                                         .menu("View on njump.me", Box::new(Command::Njump(pk)))
+                                        .when(!is_self, |this| {
+                                            this.separator()
+                                                .menu("Mute", Box::new(Command::Mute(pk)))
+                                                .menu("Block", Box::new(Command::Block(pk)))
+                                        })
                                 }),
                         )
                     })
@@ -914,31 +2242,501 @@ impl ChatPanel {
                             .when(has_replies, |this| {
                                 this.children(self.render_message_replies(replies, cx))
                             })
-                            .child(rendered_text),
+                            .child(body)
+                            .children(self.render_embeds(message, cx))
+                            .children(self.render_media(&id, cx))
+                            .children(self.render_link_previews(&id, cx))
+                            .when(reply_count > 0, |this| {
+                                this.child(self.render_reply_count(&id, reply_count, cx))
+                            }),
                     ),
             )
             .child(
-                div()
-                    .group_hover("", |this| this.bg(cx.theme().element_active))
-                    .absolute()
-                    .left_0()
-                    .top_0()
-                    .w(px(2.))
-                    .h_full()
-                    .bg(cx.theme().border_transparent),
-            )
-            .child(self.render_actions(&id, &pk, cx))
-            .on_mouse_down(
-                MouseButton::Middle,
-                cx.listener(move |this, _, _window, cx| {
-                    this.copy_message(&id, cx);
-                }),
+                div()
+                    .group_hover("", |this| this.bg(cx.theme().element_active))
+                    .absolute()
+                    .left_0()
+                    .top_0()
+                    .w(px(2.))
+                    .h_full()
+                    .bg(cx.theme().border_transparent),
+            )
+            .child(self.render_actions(&id, &pk, cx))
+            .on_mouse_down(
+                MouseButton::Middle,
+                cx.listener(move |this, _, _window, cx| {
+                    this.copy_message(&id, cx);
+                }),
+            )
+            .on_double_click(cx.listener(move |this, _, _window, cx| {
+                this.reply_to(&id, cx);
+            }))
+            .hover(|this| this.bg(cx.theme().surface_background))
+            .into_any_element()
+    }
+
+    fn render_unsupported(&self, message: &RenderedMessage, cx: &Context<Self>) -> AnyElement {
+        let raw = match &message.body {
+            MessageBody::Unsupported { raw } => raw.clone(),
+            _ => message.content.clone(),
+        };
+
+        h_flex()
+            .mt_1()
+            .p_2()
+            .gap_3()
+            .max_w(px(MAX_MEDIA_WIDTH))
+            .rounded(cx.theme().radius)
+            .bg(cx.theme().elevated_surface_background)
+            .text_xs()
+            .child(
+                div()
+                    .flex_1()
+                    .text_color(cx.theme().text_muted)
+                    .child(SharedString::from(format!(
+                        "Unsupported message type (kind {})",
+                        message.kind.as_u16()
+                    ))),
+            )
+            .child(
+                Button::new("view-raw")
+                    .label("View raw JSON")
+                    .ghost()
+                    .xsmall()
+                    .on_click(cx.listener(move |this, _ev, window, cx| {
+                        this.view_raw(raw.clone(), window, cx);
+                    })),
+            )
+            .into_any_element()
+    }
+
+    fn view_raw(&mut self, raw: String, window: &mut Window, cx: &mut Context<Self>) {
+        // Pretty print the rumor, fallback to the raw JSON
+        let json = serde_json::from_str::<Value>(&raw)
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .unwrap_or(raw);
+        let json = SharedString::from(json);
+
+        window.open_modal(cx, move |this, _window, cx| {
+            this.title("Raw message").show_close(true).child(
+                div()
+                    .id("raw-json")
+                    .p_2()
+                    .max_h_96()
+                    .overflow_y_scroll()
+                    .rounded(cx.theme().radius)
+                    .bg(cx.theme().elevated_surface_background)
+                    .font_family(cx.theme().mono_font_family.clone())
+                    .text_xs()
+                    .child(json.clone()),
+            )
+        });
+    }
+
+    fn render_media(&self, id: &EventId, cx: &Context<Self>) -> Vec<AnyElement> {
+        let Some(text) = self.rendered_texts_by_id.get(id) else {
+            return vec![];
+        };
+        let mut items = vec![];
+
+        for (ix, url) in text.link_urls.iter().enumerate() {
+            let element_id = SharedString::from(format!("{}-media-{ix}", id.to_hex()));
+
+            match self.media.get(url) {
+                Some(Media::Image(path)) => {
+                    items.push(
+                        div()
+                            .id(element_id)
+                            .mt_1()
+                            .max_w(px(MAX_MEDIA_WIDTH))
+                            .max_h(px(MAX_MEDIA_HEIGHT))
+                            .overflow_hidden()
+                            .rounded(cx.theme().radius)
+                            .cursor_pointer()
+                            .child(
+                                img(path.clone())
+                                    .max_w(px(MAX_MEDIA_WIDTH))
+                                    .max_h(px(MAX_MEDIA_HEIGHT))
+                                    .object_fit(ObjectFit::ScaleDown),
+                            )
+                            .on_click({
+                                let url = url.clone();
+                                let path = path.clone();
+                                cx.listener(move |this, _ev, window, cx| {
+                                    this.zoom_image(&url, path.clone(), window, cx);
+                                })
+                            })
+                            .into_any_element(),
+                    );
+                }
+                Some(media @ (Media::Video | Media::Audio)) => {
+                    let label = if *media == Media::Video {
+                        "Video"
+                    } else {
+                        "Audio"
+                    };
+                    let name = url.rsplit('/').next().unwrap_or(url).to_owned();
+
+                    if let Some(poster) = self.posters.get(url) {
+                        items.push(
+                            div()
+                                .id(SharedString::from(format!("{element_id}-poster")))
+                                .mt_1()
+                                .max_w(px(MAX_MEDIA_WIDTH))
+                                .max_h(px(MAX_MEDIA_HEIGHT))
+                                .overflow_hidden()
+                                .rounded(cx.theme().radius)
+                                .cursor_pointer()
+                                .child(
+                                    img(poster.clone())
+                                        .max_w(px(MAX_MEDIA_WIDTH))
+                                        .max_h(px(MAX_MEDIA_HEIGHT))
+                                        .object_fit(ObjectFit::ScaleDown),
+                                )
+                                .on_click({
+                                    let url = url.clone();
+                                    move |_ev, _window, cx| {
+                                        cx.open_url(&url);
+                                    }
+                                })
+                                .into_any_element(),
+                        );
+                    }
+
+                    items.push(
+                        h_flex()
+                            .id(element_id)
+                            .mt_1()
+                            .p_2()
+                            .gap_3()
+                            .max_w(px(MAX_MEDIA_WIDTH))
+                            .rounded(cx.theme().radius)
+                            .bg(cx.theme().elevated_surface_background)
+                            .child(
+                                v_flex()
+                                    .flex_1()
+                                    .overflow_hidden()
+                                    .text_xs()
+                                    .child(
+                                        div()
+                                            .font_semibold()
+                                            .text_color(cx.theme().text_muted)
+                                            .child(SharedString::from(label)),
+                                    )
+                                    .child(div().truncate().child(name)),
+                            )
+                            .child(
+                                Button::new(format!("open-{ix}"))
+                                    .icon(IconName::Link)
+                                    .tooltip("Open externally")
+                                    .ghost()
+                                    .small()
+                                    .on_click({
+                                        let url = url.clone();
+                                        move |_ev, _window, cx| {
+                                            cx.open_url(&url);
+                                        }
+                                    }),
+                            )
+                            .into_any_element(),
+                    );
+                }
+                Some(Media::Loading) => {
+                    let Some((placeholder, (width, height))) = self.placeholders.get(url) else {
+                        continue;
+                    };
+
+                    // Reserve the space of the image, scaled like the image will be
+                    let scale = (MAX_MEDIA_WIDTH / *width as f32)
+                        .min(MAX_MEDIA_HEIGHT / *height as f32)
+                        .min(1.);
+
+                    items.push(
+                        div()
+                            .id(element_id)
+                            .mt_1()
+                            .w(px(*width as f32 * scale))
+                            .h(px(*height as f32 * scale))
+                            .overflow_hidden()
+                            .rounded(cx.theme().radius)
+                            .child(img(placeholder.clone()).size_full())
+                            .into_any_element(),
+                    );
+                }
+                Some(Media::Hidden) => {
+                    items.push(self.render_hidden_media(element_id, url, cx));
+                }
+                _ => {}
+            }
+        }
+
+        items
+    }
+
+    /// Render a media that wasn't loaded automatically, with a button to load it
+    fn render_hidden_media(
+        &self,
+        element_id: SharedString,
+        url: &str,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let name = url.rsplit('/').next().unwrap_or(url).to_owned();
+
+        h_flex()
+            .id(element_id)
+            .mt_1()
+            .p_2()
+            .gap_3()
+            .max_w(px(MAX_MEDIA_WIDTH))
+            .rounded(cx.theme().radius)
+            .bg(cx.theme().elevated_surface_background)
+            .child(
+                v_flex()
+                    .flex_1()
+                    .overflow_hidden()
+                    .text_xs()
+                    .child(
+                        div()
+                            .font_semibold()
+                            .text_color(cx.theme().text_muted)
+                            .child(SharedString::from("Media not loaded")),
+                    )
+                    .child(div().truncate().child(name)),
+            )
+            .child(
+                Button::new("show")
+                    .icon(IconName::Eye)
+                    .tooltip("Load media")
+                    .ghost()
+                    .small()
+                    .on_click({
+                        let url = url.to_owned();
+                        cx.listener(move |this, _ev, _window, cx| {
+                            this.show_media(&url, cx);
+                        })
+                    }),
+            )
+            .into_any_element()
+    }
+
+    fn render_embeds(&self, message: &RenderedMessage, cx: &Context<Self>) -> Vec<AnyElement> {
+        let mut items = vec![];
+
+        for (ix, reference) in message.references.iter().enumerate() {
+            let element_id = SharedString::from(format!("{}-embed-{ix}", message.id.to_hex()));
+            let url = format!("https://njump.me/{}", reference.bech32());
+
+            let content = match self.embeds.get(&reference.uri) {
+                Some(Embed::Ready(event)) => self.render_embed_event(event, cx).into_any_element(),
+                Some(Embed::Loading) | None => div()
+                    .text_color(cx.theme().text_placeholder)
+                    .child(SharedString::from("Loading event..."))
+                    .into_any_element(),
+                Some(Embed::NotFound) => div()
+                    .text_color(cx.theme().text_placeholder)
+                    .child(SharedString::from("Event not found"))
+                    .into_any_element(),
+            };
+
+            items.push(
+                div()
+                    .id(element_id)
+                    .mt_1()
+                    .p_2()
+                    .max_w(px(MAX_MEDIA_WIDTH * 1.5))
+                    .border_l_2()
+                    .border_color(cx.theme().element_active)
+                    .rounded(cx.theme().radius)
+                    .bg(cx.theme().elevated_surface_background)
+                    .text_xs()
+                    .cursor_pointer()
+                    .child(content)
+                    .on_click(move |_ev, _window, cx| {
+                        cx.open_url(&url);
+                    })
+                    .into_any_element(),
+            );
+        }
+
+        items
+    }
+
+    fn render_embed_event(&self, event: &Event, cx: &Context<Self>) -> impl IntoElement {
+        let author = self.profile(&event.pubkey, cx);
+
+        let (label, title, body) = match event.kind {
+            Kind::LongFormTextNote => (
+                "Article",
+                tag_value(event, "title").map(String::from),
+                tag_value(event, "summary").map(String::from),
+            ),
+            Kind::FileMetadata => {
+                let mime = tag_value(event, "m").unwrap_or("Unknown type");
+                let size = tag_value(event, "size")
+                    .and_then(|size| size.parse::<u64>().ok())
+                    .map(|size| format!(" · {} KB", size / 1024))
+                    .unwrap_or_default();
+
+                (
+                    "File",
+                    tag_value(event, "alt")
+                        .map(String::from)
+                        .or_else(|| Some(event.content.clone()))
+                        .filter(|name| !name.is_empty()),
+                    Some(format!("{mime}{size}")),
+                )
+            }
+            Kind::TextNote => ("Note", None, Some(event.content.clone())),
+            _ => (
+                "Event",
+                None,
+                Some(event.content.clone()).filter(|content| !content.is_empty()),
+            ),
+        };
+
+        v_flex()
+            .gap_1()
+            .child(
+                h_flex()
+                    .gap_1p5()
+                    .text_color(cx.theme().text_muted)
+                    .child(Avatar::new(author.avatar()).xsmall())
+                    .child(
+                        div()
+                            .font_semibold()
+                            .text_color(cx.theme().text)
+                            .child(author.name()),
+                    )
+                    .child(SharedString::from(label))
+                    .child(event.created_at.to_human_time()),
+            )
+            .when_some(title, |this, title| {
+                this.child(
+                    div()
+                        .text_sm()
+                        .font_semibold()
+                        .text_color(cx.theme().text_accent)
+                        .line_clamp(2)
+                        .child(title),
+                )
+            })
+            .when_some(body, |this, body| {
+                this.child(div().text_color(cx.theme().text).line_clamp(4).child(body))
+            })
+    }
+
+    fn render_link_previews(&self, id: &EventId, cx: &Context<Self>) -> Vec<AnyElement> {
+        let Some(text) = self.rendered_texts_by_id.get(id) else {
+            return vec![];
+        };
+        let mut items = vec![];
+
+        for (ix, url) in text.link_urls.iter().enumerate() {
+            if !is_previewable(url) {
+                continue;
+            }
+
+            let element_id = SharedString::from(format!("{}-preview-{ix}", id.to_hex()));
+
+            match self.link_previews.get(url) {
+                Some(LinkPreviewState::Ready(preview)) => {
+                    items.push(
+                        self.render_link_preview(element_id, url, preview, cx)
+                            .into_any_element(),
+                    );
+                }
+                Some(LinkPreviewState::Loading) => {
+                    items.push(
+                        div()
+                            .mt_1()
+                            .text_xs()
+                            .text_color(cx.theme().text_placeholder)
+                            .child(SharedString::from("Loading preview..."))
+                            .into_any_element(),
+                    );
+                }
+                Some(LinkPreviewState::Failed) => {}
+                None => {
+                    items.push(
+                        Button::new(element_id)
+                            .label("Load preview")
+                            .tooltip(url.clone())
+                            .ghost()
+                            .xsmall()
+                            .on_click({
+                                let url = url.clone();
+                                cx.listener(move |this, _ev, _window, cx| {
+                                    this.load_link_preview(&url, cx);
+                                })
+                            })
+                            .into_any_element(),
+                    );
+                }
+            }
+        }
+
+        items
+    }
+
+    fn render_link_preview(
+        &self,
+        id: SharedString,
+        url: &str,
+        preview: &LinkPreview,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let url = url.to_owned();
+
+        h_flex()
+            .id(id)
+            .mt_1()
+            .p_2()
+            .gap_3()
+            .max_w(px(MAX_MEDIA_WIDTH * 1.5))
+            .border_l_2()
+            .border_color(cx.theme().element_active)
+            .rounded(cx.theme().radius)
+            .bg(cx.theme().elevated_surface_background)
+            .text_xs()
+            .child(
+                v_flex()
+                    .flex_1()
+                    .gap_0p5()
+                    .overflow_hidden()
+                    .when_some(preview.site_name.clone(), |this, name| {
+                        this.child(div().text_color(cx.theme().text_muted).child(name))
+                    })
+                    .when_some(preview.title.clone(), |this, title| {
+                        this.child(
+                            div()
+                                .font_semibold()
+                                .text_color(cx.theme().text_accent)
+                                .line_clamp(2)
+                                .child(title),
+                        )
+                    })
+                    .when_some(preview.description.clone(), |this, description| {
+                        this.child(
+                            div()
+                                .text_color(cx.theme().text_muted)
+                                .line_clamp(3)
+                                .child(description),
+                        )
+                    }),
             )
-            .on_double_click(cx.listener(move |this, _, _window, cx| {
-                this.reply_to(&id, cx);
-            }))
-            .hover(|this| this.bg(cx.theme().surface_background))
-            .into_any_element()
+            .when_some(preview.image.clone(), |this, image| {
+                this.child(
+                    img(image)
+                        .flex_shrink_0()
+                        .size_16()
+                        .rounded(cx.theme().radius)
+                        .object_fit(ObjectFit::Cover),
+                )
+            })
+            .cursor_pointer()
+            .on_click(move |_ev, _window, cx| {
+                cx.open_url(&url);
+            })
     }
 
     fn render_message_replies(
@@ -987,6 +2785,35 @@ impl ChatPanel {
         items
     }
 
+    fn render_reply_count(
+        &self,
+        id: &EventId,
+        count: usize,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let label = if count == 1 {
+            SharedString::from("1 reply")
+        } else {
+            SharedString::from(format!("{count} replies"))
+        };
+
+        h_flex()
+            .id(SharedString::from(format!("replies-{}", id.to_hex())))
+            .mt_1()
+            .gap_1()
+            .text_xs()
+            .font_semibold()
+            .text_color(cx.theme().text_accent)
+            .child(Icon::new(IconName::Reply).xsmall())
+            .child(label)
+            .on_click({
+                let id = *id;
+                cx.listener(move |this, _event, window, cx| {
+                    this.open_thread(&id, window, cx);
+                })
+            })
+    }
+
     fn render_sent_reports(&self, id: &EventId, cx: &App) -> impl IntoElement {
         let reports = self.sent_reports(id, cx);
 
@@ -1175,6 +3002,19 @@ impl ChatPanel {
                         })
                     }),
             )
+            .child(
+                Button::new("thread")
+                    .icon(IconName::PanelRightOpen)
+                    .tooltip("Open thread")
+                    .small()
+                    .ghost()
+                    .on_click({
+                        let id = id.to_owned();
+                        cx.listener(move |this, _event, window, cx| {
+                            this.open_thread(&id, window, cx);
+                        })
+                    }),
+            )
             .child(
                 Button::new("copy")
                     .icon(IconName::Copy)
@@ -1206,7 +3046,12 @@ impl ChatPanel {
             .group_hover("", |this| this.visible())
     }
 
-    fn render_attachment(&self, url: &Url, cx: &Context<Self>) -> impl IntoElement {
+    fn render_attachment(
+        &self,
+        url: &Url,
+        composer: Composer,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
         div()
             .id(SharedString::from(url.to_string()))
             .relative()
@@ -1233,21 +3078,81 @@ impl ChatPanel {
             )
             .on_click({
                 let url = url.clone();
-                cx.listener(move |this, _, window, cx| {
-                    this.remove_attachment(&url, window, cx);
+                cx.listener(move |this, _, _window, cx| {
+                    this.remove_attachment(&url, composer, cx);
                 })
             })
     }
 
     fn render_attachment_list(
         &self,
+        composer: Composer,
         _window: &Window,
         cx: &Context<Self>,
     ) -> impl IntoIterator<Item = impl IntoElement> {
         let mut items = vec![];
 
-        for url in self.attachments.read(cx).iter() {
-            items.push(self.render_attachment(url, cx));
+        for url in self.attachments_of(composer).read(cx).iter() {
+            items.push(self.render_attachment(url, composer, cx));
+        }
+
+        items
+    }
+
+    fn render_upload_list(&self, composer: Composer, cx: &Context<Self>) -> Vec<impl IntoElement> {
+        let mut items = vec![];
+
+        for (id, upload) in self.uploads.iter() {
+            if upload.composer != composer {
+                continue;
+            }
+
+            let id = *id;
+            let sent = upload.progress.sent();
+
+            let status = match upload.progress.total() {
+                0 => SharedString::from("Preparing..."),
+                total => {
+                    SharedString::from(format!("{} / {}", format_size(sent), format_size(total)))
+                }
+            };
+
+            items.push(
+                h_flex()
+                    .gap_2()
+                    .text_xs()
+                    .child(div().max_w_40().truncate().child(upload.name.clone()))
+                    .child(
+                        div()
+                            .flex_1()
+                            .h_1()
+                            .rounded_full()
+                            .bg(cx.theme().border)
+                            .child(
+                                div()
+                                    .h_full()
+                                    .w(relative(upload.progress.fraction()))
+                                    .rounded_full()
+                                    .bg(cx.theme().icon_accent),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .flex_shrink_0()
+                            .text_color(cx.theme().text_muted)
+                            .child(status),
+                    )
+                    .child(
+                        Button::new(("cancel-upload", id))
+                            .icon(IconName::Close)
+                            .tooltip("Cancel upload")
+                            .xsmall()
+                            .ghost()
+                            .on_click(cx.listener(move |this, _ev, _window, cx| {
+                                this.cancel_upload(id, cx);
+                            })),
+                    ),
+            );
         }
 
         items
@@ -1323,13 +3228,18 @@ impl ChatPanel {
     }
 
     fn render_config_menu(&self, _window: &mut Window, cx: &Context<Self>) -> impl IntoElement {
-        let (backup, signer_kind) = self
+        let (backup, signer_kind, link_previews) = self
             .room
             .read_with(cx, |this, _cx| {
-                (this.config().backup(), this.config().signer_kind().clone())
+                let config = this.config();
+                (
+                    config.backup(),
+                    config.signer_kind().clone(),
+                    config.link_previews(),
+                )
             })
             .ok()
-            .unwrap_or((true, SignerKind::default()));
+            .unwrap_or((true, SignerKind::default(), false));
 
         Button::new("encryption")
             .icon(IconName::Settings2)
@@ -1363,7 +3273,320 @@ impl ChatPanel {
                     .separator()
                     .label("Backup")
                     .menu_with_check("Backup messages", backup, Box::new(Command::ToggleBackup))
+                    .separator()
+                    .label("Privacy")
+                    .menu_with_check(
+                        "Load link previews",
+                        link_previews,
+                        Box::new(Command::ToggleLinkPreviews),
+                    )
+            })
+    }
+
+    fn render_thread(
+        &mut self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Option<impl IntoElement> {
+        let focus = self.thread?;
+        let persons = PersonRegistry::global(cx);
+        let messages = self.thread_messages();
+        let mut items = Vec::with_capacity(messages.len());
+
+        for message in messages.iter() {
+            let author = self.profile(&message.author, cx);
+            let id = SharedString::from(format!("thread-{}", message.id.to_hex()));
+            let text = self
+                .rendered_texts_by_id
+                .entry(message.id)
+                .or_insert_with(|| {
+                    let text = RenderedText::new(&message.content, &message.mentions, &persons, cx);
+                    text.highlight_code(cx);
+                    text
+                })
+                .element(id.into(), window, cx);
+
+            items.push(
+                v_flex()
+                    .w_full()
+                    .py_1p5()
+                    .px_3()
+                    .gap_1()
+                    .when(message.id == focus, |this| {
+                        this.bg(cx.theme().surface_background)
+                    })
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .text_sm()
+                            .text_color(cx.theme().text_placeholder)
+                            .child(Avatar::new(author.avatar()).xsmall())
+                            .child(
+                                div()
+                                    .font_semibold()
+                                    .text_color(cx.theme().text)
+                                    .child(author.name()),
+                            )
+                            .child(message.created_at.to_human_time()),
+                    )
+                    .child(text),
+            );
+        }
+
+        Some(
+            v_flex()
+                .flex_shrink_0()
+                .w(px(320.))
+                .h_full()
+                .border_l_1()
+                .border_color(cx.theme().border)
+                .child(
+                    h_flex()
+                        .flex_shrink_0()
+                        .h_10()
+                        .px_3()
+                        .justify_between()
+                        .border_b_1()
+                        .border_color(cx.theme().border)
+                        .text_sm()
+                        .font_semibold()
+                        .child(SharedString::from("Thread"))
+                        .child(
+                            Button::new("close-thread")
+                                .icon(IconName::Close)
+                                .tooltip("Close thread")
+                                .xsmall()
+                                .ghost()
+                                .on_click(cx.listener(move |this, _event, _window, cx| {
+                                    this.close_thread(cx);
+                                })),
+                        ),
+                )
+                .child(
+                    v_flex()
+                        .id("thread-messages")
+                        .flex_1()
+                        .py_2()
+                        .overflow_y_scroll()
+                        .children(items),
+                )
+                .child(self.render_thread_composer(window, cx)),
+        )
+    }
+
+    fn render_thread_composer(&self, window: &mut Window, cx: &Context<Self>) -> impl IntoElement {
+        let mentioning = self
+            .mention_picker
+            .as_ref()
+            .is_some_and(|picker| picker.composer == Composer::Thread);
+
+        v_flex()
+            .key_context(CONTEXT)
+            .capture_action(cx.listener(Self::on_paste))
+            .on_action(cx.listener(Self::on_toggle_bold))
+            .on_action(cx.listener(Self::on_toggle_italic))
+            .on_action(cx.listener(Self::on_toggle_code))
+            .when(mentioning, |this| {
+                this.capture_action(cx.listener(Self::on_mention_prev))
+                    .capture_action(cx.listener(Self::on_mention_next))
+                    .capture_action(cx.listener(Self::on_mention_enter))
+                    .capture_action(cx.listener(Self::on_mention_tab))
+                    .capture_action(cx.listener(Self::on_mention_escape))
+            })
+            .flex_shrink_0()
+            .p_2()
+            .gap_1p5()
+            .border_t_1()
+            .border_color(cx.theme().border)
+            .children(self.render_attachment_list(Composer::Thread, window, cx))
+            .children(self.render_upload_list(Composer::Thread, cx))
+            .children(self.render_mention_picker(Composer::Thread, cx))
+            .child(
+                h_flex()
+                    .items_end()
+                    .gap_1()
+                    .child(
+                        Button::new("thread-upload")
+                            .icon(IconName::Plus)
+                            .tooltip("Upload media")
+                            .ghost()
+                            .small()
+                            .on_click(cx.listener(move |this, _ev, window, cx| {
+                                this.upload(Composer::Thread, window, cx);
+                            })),
+                    )
+                    .child(
+                        TextInput::new(&self.thread_input)
+                            .text_sm()
+                            .small()
+                            .flex_1(),
+                    )
+                    .child(
+                        Button::new("thread-send")
+                            .icon(IconName::PaperPlaneFill)
+                            .disabled(self.is_uploading())
+                            .ghost()
+                            .small()
+                            .on_click(cx.listener(move |this, _ev, window, cx| {
+                                this.send_thread_reply(window, cx);
+                            })),
+                    ),
+            )
+    }
+
+    fn render_composer(&self, window: &mut Window, cx: &Context<Self>) -> impl IntoElement {
+        let mentioning = self
+            .mention_picker
+            .as_ref()
+            .is_some_and(|picker| picker.composer == Composer::Main);
+
+        v_flex()
+            .key_context(CONTEXT)
+            .capture_action(cx.listener(Self::on_paste))
+            .on_action(cx.listener(Self::on_toggle_bold))
+            .on_action(cx.listener(Self::on_toggle_italic))
+            .on_action(cx.listener(Self::on_toggle_code))
+            .on_action(cx.listener(Self::on_toggle_preview))
+            .when(mentioning, |this| {
+                this.capture_action(cx.listener(Self::on_mention_prev))
+                    .capture_action(cx.listener(Self::on_mention_next))
+                    .capture_action(cx.listener(Self::on_mention_enter))
+                    .capture_action(cx.listener(Self::on_mention_tab))
+                    .capture_action(cx.listener(Self::on_mention_escape))
             })
+            .flex_shrink_0()
+            .p_2()
+            .w_full()
+            .gap_1p5()
+            .children(self.render_attachment_list(Composer::Main, window, cx))
+            .children(self.render_upload_list(Composer::Main, cx))
+            .children(self.render_reply_list(window, cx))
+            .children(self.render_preview(window, cx))
+            .children(self.render_mention_picker(Composer::Main, cx))
+            .child(
+                h_flex()
+                    .items_end()
+                    .child(
+                        Button::new("upload")
+                            .icon(IconName::Plus)
+                            .tooltip("Upload media")
+                            .ghost()
+                            .large()
+                            .on_click(cx.listener(move |this, _ev, window, cx| {
+                                this.upload(Composer::Main, window, cx);
+                            })),
+                    )
+                    .child(
+                        TextInput::new(&self.input)
+                            .appearance(false)
+                            .text_sm()
+                            .flex_1(),
+                    )
+                    .child(
+                        h_flex()
+                            .pl_1()
+                            .gap_1()
+                            .child(
+                                Button::new("preview")
+                                    .icon(IconName::Eye)
+                                    .tooltip("Toggle preview")
+                                    .selected(self.preview.is_some())
+                                    .ghost()
+                                    .large()
+                                    .on_click(cx.listener(move |this, _ev, window, cx| {
+                                        this.on_toggle_preview(&TogglePreview, window, cx);
+                                    })),
+                            )
+                            .child(self.render_emoji_menu(window, cx))
+                            .child(self.render_config_menu(window, cx))
+                            .child(
+                                Button::new("send")
+                                    .icon(IconName::PaperPlaneFill)
+                                    .disabled(self.is_uploading())
+                                    .ghost()
+                                    .large()
+                                    .on_click(cx.listener(move |this, _ev, window, cx| {
+                                        this.send_text_message(window, cx);
+                                    })),
+                            ),
+                    ),
+            )
+    }
+
+    fn render_mention_picker(
+        &self,
+        composer: Composer,
+        cx: &Context<Self>,
+    ) -> Option<impl IntoElement> {
+        let picker = self
+            .mention_picker
+            .as_ref()
+            .filter(|picker| picker.composer == composer)?;
+        let mut items = Vec::with_capacity(picker.candidates.len());
+
+        for (ix, person) in picker.candidates.iter().enumerate() {
+            items.push(
+                h_flex()
+                    .id(ix)
+                    .h_8()
+                    .px_2()
+                    .gap_2()
+                    .text_sm()
+                    .rounded(cx.theme().radius)
+                    .when(ix == picker.selected, |this| {
+                        this.bg(cx.theme().ghost_element_selected)
+                    })
+                    .hover(|this| this.bg(cx.theme().ghost_element_hover))
+                    .child(Avatar::new(person.avatar()).xsmall())
+                    .child(person.name())
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().text_placeholder)
+                            .child(SharedString::from(shorten_pubkey(person.public_key(), 8))),
+                    )
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.select_mention(ix, window, cx);
+                    })),
+            );
+        }
+
+        Some(
+            v_flex()
+                .p_1()
+                .gap_0p5()
+                .w_full()
+                .rounded(cx.theme().radius)
+                .border_1()
+                .border_color(cx.theme().border)
+                .bg(cx.theme().elevated_surface_background)
+                .when(cx.theme().shadow, |this| this.shadow_sm())
+                .children(items),
+        )
+    }
+
+    fn render_preview(&self, window: &Window, cx: &Context<Self>) -> Option<impl IntoElement> {
+        let preview = self.preview.as_ref()?;
+
+        Some(
+            div()
+                .id("preview")
+                .w_full()
+                .max_h(px(240.))
+                .overflow_y_scroll()
+                .p_2()
+                .text_sm()
+                .rounded(cx.theme().radius)
+                .bg(cx.theme().elevated_surface_background)
+                .map(|this| {
+                    if preview.is_empty() {
+                        this.text_color(cx.theme().text_placeholder)
+                            .child(SharedString::from("Nothing to preview"))
+                    } else {
+                        this.child(preview.element("preview-text".into(), window, cx))
+                    }
+                }),
+        )
     }
 
     fn render_emoji_menu(&self, _window: &Window, _cx: &Context<Self>) -> impl IntoElement {
@@ -1405,6 +3628,13 @@ impl Panel for ChatPanel {
             .unwrap_or(div().child("Unknown").into_any_element())
     }
 
+    fn set_active(&self, active: bool, cx: &mut App) {
+        // Save the pending draft when switching to another room
+        if !active {
+            self.save_draft(cx).detach();
+        }
+    }
+
     fn toolbar_buttons(&self, _window: &Window, _cx: &App) -> Vec<Button> {
         let subject_bar = self.subject_bar.clone();
 
@@ -1436,6 +3666,12 @@ impl Render for ChatPanel {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         v_flex()
             .on_action(cx.listener(Self::on_command))
+            .drag_over::<ExternalPaths>(|this, _paths, _window, cx| {
+                this.bg(cx.theme().drop_target_background)
+            })
+            .on_drop(cx.listener(|this, paths: &ExternalPaths, window, cx| {
+                this.upload_paths(paths.paths().to_vec(), Composer::Main, window, cx);
+            }))
             .size_full()
             .when(*self.subject_bar.read(cx), |this| {
                 this.child(
@@ -1457,7 +3693,7 @@ impl Render for ChatPanel {
                                 .icon(IconName::CheckCircle)
                                 .label("Change")
                                 .secondary()
-                                .disabled(self.uploading)
+                                .disabled(self.is_uploading())
                                 .on_click(cx.listener(move |this, _ev, window, cx| {
                                     this.change_subject(window, cx);
                                 })),
@@ -1465,67 +3701,37 @@ impl Render for ChatPanel {
                 )
             })
             .child(
-                v_flex()
+                h_flex()
                     .flex_1()
-                    .relative()
-                    .child(
-                        list(
-                            self.list_state.clone(),
-                            cx.processor(move |this, ix, window, cx| {
-                                this.render_message(ix, window, cx)
-                            }),
-                        )
-                        .size_full(),
-                    )
-                    .child(Scrollbar::vertical(&self.list_state)),
-            )
-            .child(
-                v_flex()
-                    .flex_shrink_0()
-                    .p_2()
                     .w_full()
-                    .gap_1p5()
-                    .children(self.render_attachment_list(window, cx))
-                    .children(self.render_reply_list(window, cx))
+                    .overflow_hidden()
                     .child(
-                        h_flex()
-                            .items_end()
-                            .child(
-                                Button::new("upload")
-                                    .icon(IconName::Plus)
-                                    .tooltip("Upload media")
-                                    .loading(self.uploading)
-                                    .disabled(self.uploading)
-                                    .ghost()
-                                    .large()
-                                    .on_click(cx.listener(move |this, _ev, window, cx| {
-                                        this.upload(window, cx);
-                                    })),
-                            )
-                            .child(
-                                TextInput::new(&self.input)
-                                    .appearance(false)
-                                    .text_sm()
-                                    .flex_1(),
-                            )
+                        v_flex()
+                            .flex_1()
+                            .h_full()
                             .child(
-                                h_flex()
-                                    .pl_1()
-                                    .gap_1()
-                                    .child(self.render_emoji_menu(window, cx))
-                                    .child(self.render_config_menu(window, cx))
+                                v_flex()
+                                    .flex_1()
+                                    .relative()
                                     .child(
-                                        Button::new("send")
-                                            .icon(IconName::PaperPlaneFill)
-                                            .disabled(self.uploading)
-                                            .ghost()
-                                            .large()
-                                            .on_click(cx.listener(move |this, _ev, window, cx| {
-                                                this.send_text_message(window, cx);
-                                            })),
-                                    ),
-                            ),
-                    ),
+                                        list(
+                                            self.list_state.clone(),
+                                            cx.processor(move |this, ix, window, cx| {
+                                                this.render_message(ix, window, cx)
+                                            }),
+                                        )
+                                        .size_full(),
+                                    )
+                                    .child(Scrollbar::vertical(&self.list_state)),
+                            )
+                            .child(self.render_composer(window, cx)),
+                    )
+                    .children(self.render_thread(window, cx)),
             )
     }
 }
+
+/// Check if a link can have a preview, media are rendered inline instead
+fn is_previewable(url: &str) -> bool {
+    url.starts_with("http") && MediaKind::from_url(url).is_none() && !is_blob_url(url)
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use anyhow::{Context as AnyhowContext, Error, anyhow};
+use common::previews_dir;
+use gpui::http_client::{AsyncBody, HttpClient};
+use nostr_sdk::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use smol::io::AsyncReadExt;
+
+/// Maximum size of a downloaded page or oEmbed document, in bytes
+const MAX_DOCUMENT_SIZE: u64 = 512 * 1024;
+
+/// Maximum time spent fetching a preview
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<(meta|link)\s[^>]*>").unwrap());
+
+static ATTR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)([a-z:_-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+static TITLE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+/// OpenGraph or oEmbed summary of a linked page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkPreview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub image: Option<String>,
+
+    /// The oEmbed endpoint advertised by the page
+    #[serde(skip)]
+    oembed: Option<String>,
+}
+
+impl LinkPreview {
+    /// Whether the preview has nothing to show
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none()
+    }
+}
+
+/// Loading state of a link preview.
+#[derive(Debug, Clone)]
+pub enum LinkPreviewState {
+    Loading,
+    Ready(LinkPreview),
+    Failed,
+}
+
+/// Parse the OpenGraph tags of a HTML page
+pub fn parse_html(html: &str, base: &Url) -> LinkPreview {
+    let mut meta: HashMap<String, String> = HashMap::new();
+    let mut oembed = None;
+
+    for tag in TAG_RE.find_iter(html) {
+        let attrs: HashMap<String, String> = ATTR_RE
+            .captures_iter(tag.as_str())
+            .map(|caps| {
+                let name = caps[1].to_lowercase();
+                let value = caps.get(2).or(caps.get(3)).map_or("", |m| m.as_str());
+
+                (name, decode_entities(value))
+            })
+            .collect();
+
+        if tag.as_str()[1..].to_lowercase().starts_with("link") {
+            let json = attrs
+                .get("type")
+                .is_some_and(|t| t.eq_ignore_ascii_case("application/json+oembed"));
+
+            if json && let Some(href) = attrs.get("href") {
+                oembed = base.join(href).ok().map(|url| url.to_string());
+            }
+        } else if let Some(content) = attrs.get("content") {
+            let key = attrs.get("property").or(attrs.get("name"));
+
+            if let Some(key) = key {
+                meta.entry(key.to_lowercase())
+                    .or_insert_with(|| content.trim().to_string());
+            }
+        }
+    }
+
+    let title = meta.remove("og:title").or_else(|| {
+        TITLE_RE
+            .captures(html)
+            .map(|caps| decode_entities(caps[1].trim()))
+    });
+
+    let description = meta
+        .remove("og:description")
+        .or_else(|| meta.remove("description"));
+
+    let image = meta
+        .remove("og:image")
+        .and_then(|image| base.join(&image).ok())
+        .filter(|url| url.scheme() == "https")
+        .map(|url| url.to_string());
+
+    LinkPreview {
+        title: title.filter(|t| !t.is_empty()),
+        description: description.filter(|d| !d.is_empty()),
+        site_name: meta.remove("og:site_name"),
+        image,
+        oembed,
+    }
+}
+
+/// Fill the missing fields of a preview with an oEmbed document
+fn merge_oembed(preview: &mut LinkPreview, json: &Value) {
+    let field = |name: &str| json.get(name).and_then(|v| v.as_str()).map(String::from);
+
+    if preview.title.is_none() {
+        preview.title = field("title");
+    }
+
+    if preview.description.is_none() {
+        preview.description = field("author_name");
+    }
+
+    if preview.site_name.is_none() {
+        preview.site_name = field("provider_name");
+    }
+
+    if preview.image.is_none() {
+        preview.image = field("thumbnail_url").filter(|url| url.starts_with("https://"));
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Get the path of a cached preview
+fn cache_path(url: &str) -> PathBuf {
+    let hash = Sha256Hash::hash(url.as_bytes());
+    previews_dir().join(format!("{hash}.json"))
+}
+
+/// Download a document, up to the size limit
+async fn download(url: &str, client: &Arc<dyn HttpClient>) -> Result<String, Error> {
+    let mut body = Vec::new();
+    let mut res = client.get(url, AsyncBody::default(), true).await?;
+
+    if !res.status().is_success() {
+        return Err(anyhow!("Server returned error: {}", res.status()));
+    }
+
+    res.body_mut()
+        .take(MAX_DOCUMENT_SIZE)
+        .read_to_end(&mut body)
+        .await?;
+
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+async fn fetch_uncached(url: &str, client: &Arc<dyn HttpClient>) -> Result<LinkPreview, Error> {
+    let base = Url::parse(url)?;
+    let html = download(url, client).await?;
+    let mut preview = parse_html(&html, &base);
+
+    if let Some(endpoint) = preview.oembed.take()
+        && let Ok(json) = download(&endpoint, client).await
+        && let Ok(json) = serde_json::from_str::<Value>(&json)
+    {
+        merge_oembed(&mut preview, &json);
+    }
+
+    Ok(preview)
+}
+
+/// Get the preview of a link, previews are cached on disk.
+pub async fn fetch(url: &str, client: &Arc<dyn HttpClient>) -> Result<LinkPreview, Error> {
+    let path = cache_path(url);
+
+    if let Ok(content) = smol::fs::read(&path).await {
+        return Ok(serde_json::from_slice(&content)?);
+    }
+
+    let preview = smol::future::or(fetch_uncached(url, client), async {
+        smol::Timer::after(FETCH_TIMEOUT).await;
+        Err(anyhow!("Timed out"))
+    })
+    .await?;
+
+    if preview.is_empty() {
+        return Err(anyhow!("No preview found"));
+    }
+
+    smol::fs::create_dir_all(previews_dir()).await?;
+    smol::fs::write(&path, serde_json::to_vec(&preview)?)
+        .await
+        .context("Failed to cache the preview")?;
+
+    Ok(preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_html() {
+        let base = Url::parse("https://example.com/posts/1").unwrap();
+        let html = r#"
+            <html><head>
+            <title>Fallback</title>
+            <meta property="og:title" content="Hello &amp; welcome">
+            <meta content='A short description' name='description' />
+            <meta property="og:image" content="/cover.png">
+            <link rel="alternate" type="application/json+oembed" href="/oembed?id=1">
+            </head></html>
+        "#;
+
+        let preview = parse_html(html, &base);
+
+        assert_eq!(preview.title.as_deref(), Some("Hello & welcome"));
+        assert_eq!(preview.description.as_deref(), Some("A short description"));
+        assert_eq!(
+            preview.image.as_deref(),
+            Some("https://example.com/cover.png")
+        );
+        assert_eq!(
+            preview.oembed.as_deref(),
+            Some("https://example.com/oembed?id=1")
+        );
+    }
+
+    #[test]
+    fn test_parse_html_title_fallback() {
+        let base = Url::parse("http://example.com").unwrap();
+        let html = "<title>\n  Plain page </title><meta property=\"og:image\" content=\"x.png\">";
+
+        let preview = parse_html(html, &base);
+
+        assert_eq!(preview.title.as_deref(), Some("Plain page"));
+        // Only https images are loaded
+        assert_eq!(preview.image, None);
+    }
+}
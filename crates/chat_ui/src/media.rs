@@ -0,0 +1,419 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context as AnyhowContext, Error, anyhow};
+use common::media_dir;
+use gpui::http_client::{AsyncBody, HttpClient};
+use nostr_sdk::prelude::*;
+use smol::io::AsyncReadExt;
+use state::{blob_urls, get_server_list};
+
+/// Maximum size of an image downloaded for inline rendering, in bytes
+pub const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Number of bytes read to detect the type of a media
+const SNIFF_LEN: u64 = 512;
+
+/// Maximum time to detect and download a media
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum size of the media cache, in bytes
+const MAX_CACHE_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Cached media older than this are removed
+const MEDIA_EXPIRY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Prefix of the files saved from pasted images
+const PASTE_PREFIX: &str = "coop-paste-";
+
+/// Files saved from pasted images older than this are left over from a previous run
+const PASTE_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Kind of media a URL points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Video,
+    Audio,
+}
+
+impl MediaKind {
+    /// Guess the media kind from the file extension of a URL
+    pub fn from_url(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let name = url.path_segments()?.next_back()?;
+        let (_, ext) = name.rsplit_once('.')?;
+
+        match ext.to_lowercase().as_str() {
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" => Some(Self::Image),
+            "mp4" | "m4v" | "mov" | "webm" | "mkv" => Some(Self::Video),
+            "mp3" | "m4a" | "ogg" | "oga" | "opus" | "wav" | "flac" | "aac" => Some(Self::Audio),
+            _ => None,
+        }
+    }
+
+    /// Get the media kind from a MIME type
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let (kind, subtype) = mime.split_once('/')?;
+
+        match kind.trim() {
+            // SVG images are documents, they are not rendered inline
+            "image" if !subtype.starts_with("svg") => Some(Self::Image),
+            "video" => Some(Self::Video),
+            "audio" => Some(Self::Audio),
+            _ => None,
+        }
+    }
+
+    /// Detect the media kind from the first bytes of a file
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        let riff = |format: &[u8]| bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(format);
+
+        if bytes.starts_with(&[0x89, b'P', b'N', b'G'])
+            || bytes.starts_with(&[0xFF, 0xD8, 0xFF])
+            || bytes.starts_with(b"GIF87a")
+            || bytes.starts_with(b"GIF89a")
+            || bytes.starts_with(b"BM")
+            || riff(b"WEBP")
+        {
+            return Some(Self::Image);
+        }
+
+        if riff(b"WAVE")
+            || bytes.starts_with(b"OggS")
+            || bytes.starts_with(b"fLaC")
+            || bytes.starts_with(b"ID3")
+            || bytes.starts_with(&[0xFF, 0xFB])
+            || bytes.starts_with(&[0xFF, 0xF3])
+            || bytes.starts_with(&[0xFF, 0xF2])
+        {
+            return Some(Self::Audio);
+        }
+
+        if bytes.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+            return Some(Self::Video);
+        }
+
+        // ISO base media files (mp4, mov, m4a...)
+        if bytes.get(4..8) == Some(&b"ftyp"[..]) {
+            return match bytes.get(8..12) {
+                Some(b"M4A ") | Some(b"M4B ") => Some(Self::Audio),
+                // HEIF and AVIF images can't be decoded
+                Some(b"avif") | Some(b"heic") | Some(b"mif1") => None,
+                Some(_) => Some(Self::Video),
+                None => None,
+            };
+        }
+
+        None
+    }
+}
+
+/// Loading state of a media linked in a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Media {
+    /// The media is being downloaded or detected
+    Loading,
+
+    /// Not loaded automatically, the user has to load it
+    Hidden,
+
+    /// The image is cached on disk
+    Image(PathBuf),
+
+    /// Video, only opened externally
+    Video,
+
+    /// Audio, only opened externally
+    Audio,
+
+    /// Not a media, too large or unreachable
+    Unsupported,
+}
+
+impl From<MediaKind> for Media {
+    fn from(kind: MediaKind) -> Self {
+        match kind {
+            // Images must be downloaded before they can be rendered
+            MediaKind::Image => Self::Loading,
+            MediaKind::Video => Self::Video,
+            MediaKind::Audio => Self::Audio,
+        }
+    }
+}
+
+/// Check if a URL without extension looks like a Blossom blob.
+///
+/// Blossom blobs are addressed by their sha256 hash, so their type can only
+/// be known by sniffing the content.
+pub fn is_blob_url(url: &str) -> bool {
+    blob_hash(url).is_some_and(|(_, extension)| extension.is_none())
+}
+
+/// Get the sha256 hash and the extension of a URL that looks like a Blossom blob
+pub fn blob_hash(url: &str) -> Option<(String, Option<String>)> {
+    let url = Url::parse(url).ok()?;
+    let name = url.path_segments()?.next_back()?;
+
+    let (hash, extension) = match name.split_once('.') {
+        Some((hash, extension)) => (hash, Some(extension.to_string())),
+        None => (name, None),
+    };
+
+    if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some((hash.to_lowercase(), extension))
+    } else {
+        None
+    }
+}
+
+/// Get the path of a cached media
+fn cache_path(url: &str) -> PathBuf {
+    let hash = Sha256Hash::hash(url.as_bytes());
+    media_dir().join(hash.to_string())
+}
+
+/// Detect and download a media, images are cached on disk.
+pub async fn fetch(url: &str, client: &Arc<dyn HttpClient>) -> Result<Media, Error> {
+    let path = cache_path(url);
+
+    if smol::fs::metadata(&path).await.is_ok() {
+        return Ok(Media::Image(path));
+    }
+
+    smol::future::or(fetch_uncached(url, path, client), async {
+        smol::Timer::after(FETCH_TIMEOUT).await;
+        Err(anyhow!("Timed out"))
+    })
+    .await
+}
+
+async fn fetch_uncached(
+    url: &str,
+    path: PathBuf,
+    client: &Arc<dyn HttpClient>,
+) -> Result<Media, Error> {
+    let mut res = client.get(url, AsyncBody::default(), true).await?;
+
+    if !res.status().is_success() {
+        return Err(anyhow!("Server returned error: {}", res.status()));
+    }
+
+    let mime = res
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .and_then(MediaKind::from_mime);
+
+    let length = res
+        .headers()
+        .get("content-length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    // Read the first bytes to detect the media kind
+    let mut body = Vec::new();
+    res.body_mut()
+        .take(SNIFF_LEN)
+        .read_to_end(&mut body)
+        .await?;
+
+    let kind = MediaKind::sniff(&body)
+        .or(mime)
+        .or_else(|| MediaKind::from_url(url));
+
+    match kind {
+        Some(MediaKind::Image) => {
+            if length.is_some_and(|length| length > MAX_IMAGE_SIZE) {
+                return Err(anyhow!("Image is too large"));
+            }
+
+            // Read the rest of the image, up to the size limit
+            res.body_mut()
+                .take(MAX_IMAGE_SIZE + 1 - body.len() as u64)
+                .read_to_end(&mut body)
+                .await?;
+
+            if body.len() as u64 > MAX_IMAGE_SIZE {
+                return Err(anyhow!("Image is too large"));
+            }
+
+            // Write to a temporary file first, so a partial image is never served
+            let tmp_path = path.with_extension("tmp");
+            smol::fs::create_dir_all(media_dir()).await?;
+            smol::fs::write(&tmp_path, body).await?;
+            smol::fs::rename(&tmp_path, &path).await?;
+
+            Ok(Media::Image(path))
+        }
+        Some(MediaKind::Video) => Ok(Media::Video),
+        Some(MediaKind::Audio) => Ok(Media::Audio),
+        None => Ok(Media::Unsupported),
+    }
+}
+
+/// Download a blob from the author's servers, when its URL is unreachable (BUD-03).
+///
+/// Only used for media the user loads, it's gated like the other media downloads.
+pub async fn fetch_from_servers(
+    url: &str,
+    author: PublicKey,
+    client: &Client,
+    http_client: &Arc<dyn HttpClient>,
+) -> Result<Media, Error> {
+    let (hash, extension) = blob_hash(url).context("Not a blob URL")?;
+    let servers = get_server_list(client, author).await?;
+
+    for blob_url in blob_urls(&hash, extension.as_deref(), &servers) {
+        // Skip the server that already failed
+        if blob_url.as_str() == url {
+            continue;
+        }
+
+        match fetch(blob_url.as_str(), http_client).await {
+            Ok(media) => return Ok(media),
+            Err(e) => log::warn!("Failed to load media {blob_url}: {e}"),
+        }
+    }
+
+    Err(anyhow!("Media not found on the author's servers"))
+}
+
+/// Delete expired media, then the oldest ones until the cache fits its size limit
+pub fn prune_cache() -> Result<(), Error> {
+    let Ok(entries) = std::fs::read_dir(media_dir()) else {
+        return Ok(());
+    };
+
+    let mut files: Vec<(PathBuf, SystemTime, u64)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            Some((entry.path(), metadata.modified().ok()?, metadata.len()))
+        })
+        .collect();
+
+    // Newest first
+    files.sort_by_key(|(_, modified, _)| std::cmp::Reverse(*modified));
+
+    let mut total = 0;
+
+    for (path, modified, size) in files.into_iter() {
+        let expired = modified.elapsed().is_ok_and(|age| age >= MEDIA_EXPIRY);
+        total += size;
+
+        if (expired || total > MAX_CACHE_SIZE)
+            && let Err(e) = std::fs::remove_file(&path)
+        {
+            log::warn!("Failed to remove media {}: {e}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the path of a file saved from a pasted image
+pub fn paste_path(ix: usize, extension: &str) -> PathBuf {
+    let name = format!("{PASTE_PREFIX}{}-{ix}.{extension}", Timestamp::now());
+    std::env::temp_dir().join(name)
+}
+
+/// Check if a file was saved from a pasted image
+fn is_pasted(path: &Path) -> bool {
+    path.parent() == Some(std::env::temp_dir().as_path())
+        && path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(PASTE_PREFIX))
+}
+
+/// Delete a file saved from a pasted image, other files are kept
+pub fn remove_pasted(path: &Path) {
+    if is_pasted(path)
+        && let Err(e) = std::fs::remove_file(path)
+    {
+        log::warn!("Failed to remove pasted image {}: {e}", path.display());
+    }
+}
+
+/// Delete the pasted images left over by a previous run
+pub fn prune_pasted() -> Result<(), Error> {
+    for entry in std::fs::read_dir(std::env::temp_dir())?.flatten() {
+        let path = entry.path();
+
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age >= PASTE_EXPIRY));
+
+        if expired {
+            remove_pasted(&path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_kind_from_url() {
+        let image = "https://example.com/a/cat.JPG?size=large";
+        let video = "https://example.com/clip.webm";
+        let audio = "https://example.com/voice.m4a";
+
+        assert_eq!(MediaKind::from_url(image), Some(MediaKind::Image));
+        assert_eq!(MediaKind::from_url(video), Some(MediaKind::Video));
+        assert_eq!(MediaKind::from_url(audio), Some(MediaKind::Audio));
+        assert_eq!(MediaKind::from_url("https://example.com/page.html"), None);
+        assert_eq!(MediaKind::from_url("https://example.com"), None);
+    }
+
+    #[test]
+    fn test_media_kind_sniff() {
+        assert_eq!(MediaKind::sniff(b"GIF89a\x01\x00"), Some(MediaKind::Image));
+        assert_eq!(
+            MediaKind::sniff(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(MediaKind::Image)
+        );
+        assert_eq!(
+            MediaKind::sniff(b"RIFF\0\0\0\0WAVEfmt "),
+            Some(MediaKind::Audio)
+        );
+        assert_eq!(
+            MediaKind::sniff(b"\0\0\0\x18ftypmp42"),
+            Some(MediaKind::Video)
+        );
+        assert_eq!(
+            MediaKind::sniff(b"\0\0\0\x18ftypM4A "),
+            Some(MediaKind::Audio)
+        );
+        assert_eq!(MediaKind::sniff(b"<!DOCTYPE html>"), None);
+    }
+
+    #[test]
+    fn test_is_blob_url() {
+        let hash = "b1674191a88ec5cdd733e4240a81803105dc412d6c6708d53ab94fc248f4f553";
+
+        assert!(is_blob_url(&format!("https://cdn.example.com/{hash}")));
+        assert!(!is_blob_url(&format!("https://cdn.example.com/{hash}.png")));
+        assert!(!is_blob_url("https://example.com/about"));
+    }
+
+    #[test]
+    fn test_blob_hash() {
+        let hash = "B1674191A88EC5CDD733E4240A81803105DC412D6C6708D53AB94FC248F4F553";
+
+        assert_eq!(
+            blob_hash(&format!("https://cdn.example.com/{hash}.png")),
+            Some((hash.to_lowercase(), Some("png".to_string())))
+        );
+        assert_eq!(
+            blob_hash(&format!("https://cdn.example.com/{hash}")),
+            Some((hash.to_lowercase(), None))
+        );
+        assert_eq!(blob_hash("https://example.com/cat.png"), None);
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use chat::{FILE_MESSAGE, MessageBody, RenderedMessage};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AnyElement, App, Global, IntoElement, ObjectFit, ParentElement, SharedString, Styled,
+    StyledImage, Window, div, img,
+};
+use nostr_sdk::prelude::*;
+use theme::ActiveTheme;
+use ui::button::{Button, ButtonVariants};
+use ui::{IconName, Sizable, StyledExt, h_flex, v_flex};
+
+/// Render the body of a message, everything below the author and timestamp.
+pub type MessageRenderer = Rc<dyn Fn(&RenderedMessage, &mut Window, &mut App) -> AnyElement>;
+
+/// Renderers of message bodies, by rumor kind.
+///
+/// Kind 14 text messages are always rendered by the chat panel, kinds without
+/// a renderer are shown as an unsupported message card.
+pub struct MessageRenderers {
+    renderers: HashMap<Kind, MessageRenderer>,
+}
+
+impl Global for MessageRenderers {}
+
+impl Default for MessageRenderers {
+    fn default() -> Self {
+        let mut renderers: HashMap<Kind, MessageRenderer> = HashMap::new();
+
+        renderers.insert(FILE_MESSAGE, Rc::new(render_file));
+        renderers.insert(Kind::Reaction, Rc::new(render_reaction));
+        renderers.insert(Kind::EventDeletion, Rc::new(render_deletion));
+
+        Self { renderers }
+    }
+}
+
+impl MessageRenderers {
+    /// Register a renderer, replacing the existing renderer of the kind
+    pub fn register<F>(kind: Kind, renderer: F, cx: &mut App)
+    where
+        F: Fn(&RenderedMessage, &mut Window, &mut App) -> AnyElement + 'static,
+    {
+        cx.default_global::<Self>()
+            .renderers
+            .insert(kind, Rc::new(renderer));
+    }
+
+    /// Get the renderer of a kind
+    pub fn get(kind: Kind, cx: &mut App) -> Option<MessageRenderer> {
+        cx.default_global::<Self>().renderers.get(&kind).cloned()
+    }
+}
+
+fn render_file(message: &RenderedMessage, _window: &mut Window, cx: &mut App) -> AnyElement {
+    let MessageBody::File(file) = &message.body else {
+        return div().into_any_element();
+    };
+
+    let name = file
+        .url
+        .as_ref()
+        .and_then(|url| url.path_segments()?.next_back().map(String::from))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Unnamed file".into());
+
+    let details = [
+        file.mime.clone(),
+        file.size.map(|size| format!("{} KB", size / 1024)),
+        file.dimensions.clone(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" · ");
+
+    // Only thumbnails downloaded by the chat panel are rendered, it applies the media settings
+    let thumbnail = file
+        .thumbnail
+        .as_ref()
+        .filter(|_| !file.encrypted)
+        .and_then(|url| url.to_file_path().ok());
+
+    h_flex()
+        .mt_1()
+        .p_2()
+        .gap_3()
+        .max_w_80()
+        .rounded(cx.theme().radius)
+        .bg(cx.theme().elevated_surface_background)
+        .when_some(thumbnail, |this, path| {
+            this.child(
+                img(path)
+                    .size_12()
+                    .flex_shrink_0()
+                    .rounded(cx.theme().radius)
+                    .object_fit(ObjectFit::Cover),
+            )
+        })
+        .child(
+            v_flex()
+                .flex_1()
+                .overflow_hidden()
+                .text_xs()
+                .child(
+                    div()
+                        .font_semibold()
+                        .text_color(cx.theme().text_muted)
+                        .child(if file.encrypted {
+                            "Encrypted file"
+                        } else {
+                            "File"
+                        }),
+                )
+                .child(div().truncate().child(name))
+                .when(!details.is_empty(), |this| {
+                    this.child(
+                        div()
+                            .text_color(cx.theme().text_placeholder)
+                            .child(SharedString::from(details)),
+                    )
+                }),
+        )
+        .when_some(file.url.clone(), |this, url| {
+            this.child(
+                Button::new("open-file")
+                    .icon(IconName::Link)
+                    .tooltip("Open externally")
+                    .ghost()
+                    .small()
+                    .on_click(move |_ev, _window, cx| {
+                        cx.open_url(url.as_str());
+                    }),
+            )
+        })
+        .into_any_element()
+}
+
+fn render_reaction(message: &RenderedMessage, _window: &mut Window, cx: &mut App) -> AnyElement {
+    let content = match message.content.as_str() {
+        "" | "+" => "👍",
+        "-" => "👎",
+        emoji => emoji,
+    };
+
+    div()
+        .text_sm()
+        .text_color(cx.theme().text_muted)
+        .child(SharedString::from(format!("Reacted with {content}")))
+        .into_any_element()
+}
+
+fn render_deletion(message: &RenderedMessage, _window: &mut Window, cx: &mut App) -> AnyElement {
+    let count = match &message.body {
+        MessageBody::Deletion { targets } => targets.len(),
+        _ => 0,
+    };
+    let noun = if count == 1 { "message" } else { "messages" };
+
+    div()
+        .text_sm()
+        .italic()
+        .text_color(cx.theme().text_placeholder)
+        .child(SharedString::from(format!(
+            "Requested deletion of {count} {noun}"
+        )))
+        .into_any_element()
+}
@@ -1,14 +1,20 @@
 use std::ops::Range;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use chat::Mention;
 use common::RangeExt;
 use gpui::{
-    AnyElement, App, ElementId, Entity, FontStyle, FontWeight, HighlightStyle, InteractiveText,
-    IntoElement, SharedString, StrikethroughStyle, StyledText, UnderlineStyle, Window,
+    AnyElement, App, AppContext, ClipboardItem, Context, ElementId, Entity, FontStyle, FontWeight,
+    HighlightStyle, InteractiveElement, InteractiveText, IntoElement, ParentElement, SharedString,
+    StatefulInteractiveElement, StrikethroughStyle, Styled, StyledText, UnderlineStyle, Window,
+    div,
 };
 use person::PersonRegistry;
 use theme::ActiveTheme;
+use ui::button::{Button, ButtonVariants};
+use ui::{IconName, Sizable, h_flex, v_flex};
+
+use crate::highlight::{SyntaxKind, highlight};
 
 #[allow(clippy::enum_variant_names)]
 #[allow(dead_code)]
@@ -26,11 +32,42 @@ impl From<HighlightStyle> for Highlight {
     }
 }
 
+/// Fenced code block of a message.
+#[derive(Debug, Clone)]
+pub struct CodeBlock {
+    pub language: Option<SharedString>,
+    pub code: SharedString,
+    /// Syntax highlights, set once computed in the background
+    pub highlights: Arc<OnceLock<Vec<(Range<usize>, SyntaxKind)>>>,
+}
+
+impl CodeBlock {
+    fn new(language: Option<String>, mut code: String) -> Self {
+        code.truncate(code.trim_end().len());
+
+        Self {
+            language: language.map(SharedString::from),
+            code: SharedString::from(code),
+            highlights: Arc::default(),
+        }
+    }
+}
+
+/// A paragraph group or a code block of a message.
+pub enum RenderedBlock {
+    Text {
+        text: SharedString,
+        highlights: Vec<(Range<usize>, Highlight)>,
+        link_ranges: Vec<Range<usize>>,
+        link_urls: Arc<[String]>,
+    },
+    Code(CodeBlock),
+}
+
 #[derive(Default)]
 pub struct RenderedText {
-    pub text: SharedString,
-    pub highlights: Vec<(Range<usize>, Highlight)>,
-    pub link_ranges: Vec<Range<usize>>,
+    pub blocks: Vec<RenderedBlock>,
+    /// All links of the message
     pub link_urls: Arc<[String]>,
 }
 
@@ -45,6 +82,7 @@ impl RenderedText {
         let mut highlights = Vec::new();
         let mut link_ranges = Vec::new();
         let mut link_urls = Vec::new();
+        let mut code_blocks = Vec::new();
 
         render_plain_text_mut(
             content,
@@ -53,76 +91,276 @@ impl RenderedText {
             &mut highlights,
             &mut link_ranges,
             &mut link_urls,
+            &mut code_blocks,
             persons,
             cx,
         );
 
-        text.truncate(text.trim_end().len());
+        // Split the text around the code blocks
+        let mut blocks = Vec::with_capacity(code_blocks.len() * 2 + 1);
+        let mut start = 0;
+
+        for (position, code_block) in code_blocks {
+            blocks.extend(text_block(
+                &text,
+                start..position,
+                &highlights,
+                &link_ranges,
+                &link_urls,
+            ));
+            blocks.push(RenderedBlock::Code(code_block));
+            start = position;
+        }
+
+        blocks.extend(text_block(
+            &text,
+            start..text.len(),
+            &highlights,
+            &link_ranges,
+            &link_urls,
+        ));
 
         RenderedText {
-            text: SharedString::from(text),
+            blocks,
             link_urls: link_urls.into(),
-            link_ranges,
-            highlights,
         }
     }
 
+    /// Whether the message has nothing to render
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Highlight the code blocks in the background, then re-render the view
+    pub fn highlight_code<V: 'static>(&self, cx: &mut Context<V>) {
+        let blocks: Vec<_> = self
+            .blocks
+            .iter()
+            .filter_map(|block| match block {
+                RenderedBlock::Code(CodeBlock {
+                    language: Some(language),
+                    code,
+                    highlights,
+                }) => Some((language.clone(), code.clone(), highlights.clone())),
+                _ => None,
+            })
+            .collect();
+
+        if blocks.is_empty() {
+            return;
+        }
+
+        let task = cx.background_spawn(async move {
+            for (language, code, highlights) in blocks {
+                highlights.set(highlight(&code, &language)).ok();
+            }
+        });
+
+        cx.spawn(async move |this, cx| {
+            task.await;
+            this.update(cx, |_this, cx| cx.notify()).ok();
+        })
+        .detach();
+    }
+
     pub fn element(&self, id: ElementId, window: &Window, cx: &App) -> AnyElement {
-        let code_background = cx.theme().elevated_surface_background;
-
-        InteractiveText::new(
-            id,
-            StyledText::new(self.text.clone()).with_default_highlights(
-                &window.text_style(),
-                self.highlights.iter().map(|(range, highlight)| {
-                    (
-                        range.clone(),
-                        match highlight {
-                            Highlight::Code => HighlightStyle {
-                                background_color: Some(code_background),
-                                ..Default::default()
-                            },
-                            Highlight::InlineCode(link) => {
-                                if *link {
-                                    HighlightStyle {
-                                        background_color: Some(code_background),
-                                        underline: Some(UnderlineStyle {
-                                            thickness: 1.0.into(),
-                                            ..Default::default()
-                                        }),
-                                        ..Default::default()
-                                    }
-                                } else {
-                                    HighlightStyle {
-                                        background_color: Some(code_background),
+        v_flex()
+            .id(id)
+            .w_full()
+            .gap_1()
+            .children(
+                self.blocks
+                    .iter()
+                    .enumerate()
+                    .map(|(ix, block)| match block {
+                        RenderedBlock::Text {
+                            text,
+                            highlights,
+                            link_ranges,
+                            link_urls,
+                        } => render_text_block(
+                            ix,
+                            text,
+                            highlights,
+                            link_ranges,
+                            link_urls,
+                            window,
+                            cx,
+                        ),
+                        RenderedBlock::Code(code_block) => {
+                            render_code_block(ix, code_block, window, cx)
+                        }
+                    }),
+            )
+            .into_any_element()
+    }
+}
+
+/// Slice a range of the rendered text into a block, rebasing its highlights and links
+fn text_block(
+    text: &str,
+    range: Range<usize>,
+    highlights: &[(Range<usize>, Highlight)],
+    link_ranges: &[Range<usize>],
+    link_urls: &[String],
+) -> Option<RenderedBlock> {
+    let slice = &text[range.clone()];
+    let start = range.start + (slice.len() - slice.trim_start_matches('\n').len());
+    let end = range.start + slice.trim_end().len();
+
+    if start >= end {
+        return None;
+    }
+
+    let contains = |r: &Range<usize>| r.start >= start && r.end <= end;
+    let rebase = |r: &Range<usize>| r.start - start..r.end - start;
+
+    let highlights = highlights
+        .iter()
+        .filter(|(r, _)| contains(r))
+        .map(|(r, highlight)| (rebase(r), highlight.clone()))
+        .collect();
+
+    let (link_ranges, link_urls): (Vec<_>, Vec<_>) = link_ranges
+        .iter()
+        .zip(link_urls.iter())
+        .filter(|(r, _)| contains(r))
+        .map(|(r, url)| (rebase(r), url.clone()))
+        .unzip();
+
+    Some(RenderedBlock::Text {
+        text: SharedString::from(text[start..end].to_string()),
+        highlights,
+        link_ranges,
+        link_urls: link_urls.into(),
+    })
+}
+
+fn render_text_block(
+    ix: usize,
+    text: &SharedString,
+    highlights: &[(Range<usize>, Highlight)],
+    link_ranges: &[Range<usize>],
+    link_urls: &Arc<[String]>,
+    window: &Window,
+    cx: &App,
+) -> AnyElement {
+    let code_background = cx.theme().elevated_surface_background;
+
+    InteractiveText::new(
+        ix,
+        StyledText::new(text.clone()).with_default_highlights(
+            &window.text_style(),
+            highlights.iter().map(|(range, highlight)| {
+                (
+                    range.clone(),
+                    match highlight {
+                        Highlight::Code => HighlightStyle {
+                            background_color: Some(code_background),
+                            ..Default::default()
+                        },
+                        Highlight::InlineCode(link) => {
+                            if *link {
+                                HighlightStyle {
+                                    background_color: Some(code_background),
+                                    underline: Some(UnderlineStyle {
+                                        thickness: 1.0.into(),
                                         ..Default::default()
-                                    }
+                                    }),
+                                    ..Default::default()
                                 }
-                            }
-                            Highlight::Mention => HighlightStyle {
-                                underline: Some(UnderlineStyle {
-                                    thickness: 1.0.into(),
+                            } else {
+                                HighlightStyle {
+                                    background_color: Some(code_background),
                                     ..Default::default()
-                                }),
+                                }
+                            }
+                        }
+                        Highlight::Mention => HighlightStyle {
+                            underline: Some(UnderlineStyle {
+                                thickness: 1.0.into(),
                                 ..Default::default()
-                            },
-                            Highlight::Highlight(highlight) => *highlight,
+                            }),
+                            ..Default::default()
                         },
-                    )
-                }),
-            ),
-        )
-        .on_click(self.link_ranges.clone(), {
-            let link_urls = self.link_urls.clone();
-            move |ix, _, cx| {
-                let url = &link_urls[ix];
-                if url.starts_with("http") {
-                    cx.open_url(url);
-                }
+                        Highlight::Highlight(highlight) => *highlight,
+                    },
+                )
+            }),
+        ),
+    )
+    .on_click(link_ranges.to_vec(), {
+        let link_urls = link_urls.clone();
+        move |ix, _, cx| {
+            let url = &link_urls[ix];
+            if url.starts_with("http") {
+                cx.open_url(url);
             }
-        })
+        }
+    })
+    .into_any_element()
+}
+
+fn render_code_block(ix: usize, block: &CodeBlock, window: &Window, cx: &App) -> AnyElement {
+    let mut text_style = window.text_style();
+    text_style.font_family = cx.theme().mono_font_family.clone();
+
+    let highlights = block
+        .highlights
+        .get()
+        .into_iter()
+        .flatten()
+        .map(|(range, kind)| {
+            (
+                range.clone(),
+                HighlightStyle::color(kind.color(&cx.theme().colors)),
+            )
+        });
+
+    v_flex()
+        .id(ix)
+        .w_full()
+        .my_1()
+        .rounded(cx.theme().radius)
+        .bg(cx.theme().elevated_surface_background)
+        .border_1()
+        .border_color(cx.theme().border_variant)
+        .child(
+            h_flex()
+                .px_2()
+                .py_0p5()
+                .justify_between()
+                .text_xs()
+                .text_color(cx.theme().text_muted)
+                .child(block.language.clone().unwrap_or_default())
+                .child(
+                    Button::new("copy")
+                        .icon(IconName::Copy)
+                        .tooltip("Copy code")
+                        .ghost()
+                        .xsmall()
+                        .on_click({
+                            let code = block.code.clone();
+                            move |_ev, _window, cx| {
+                                cx.write_to_clipboard(ClipboardItem::new_string(code.to_string()));
+                            }
+                        }),
+                ),
+        )
+        .child(
+            div()
+                .id("code")
+                .w_full()
+                .px_2()
+                .pb_2()
+                .overflow_x_scroll()
+                .whitespace_nowrap()
+                .child(
+                    StyledText::new(block.code.clone())
+                        .with_default_highlights(&text_style, highlights),
+                ),
+        )
         .into_any_element()
-    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -133,16 +371,18 @@ fn render_plain_text_mut(
     highlights: &mut Vec<(Range<usize>, Highlight)>,
     link_ranges: &mut Vec<Range<usize>>,
     link_urls: &mut Vec<String>,
+    code_blocks: &mut Vec<(usize, CodeBlock)>,
     persons: &Entity<PersonRegistry>,
     cx: &App,
 ) {
-    use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+    use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
 
     let mut bold_depth = 0;
     let mut italic_depth = 0;
     let mut strikethrough_depth = 0;
     let mut link_url = None;
     let mut list_stack = Vec::new();
+    let mut code_block: Option<(Option<String>, String)> = None;
 
     let mut options = Options::all();
     options.remove(pulldown_cmark::Options::ENABLE_DEFINITION_LIST);
@@ -150,6 +390,31 @@ fn render_plain_text_mut(
     for (event, source_range) in Parser::new_ext(block, options).into_offset_iter() {
         let prev_len = text.len();
 
+        // Collect the content of code blocks as is
+        if let Some((_, code)) = code_block.as_mut() {
+            match event {
+                Event::Text(t) => {
+                    code.push_str(t.as_ref());
+
+                    // Mentions are not rendered inside code
+                    while mentions
+                        .first()
+                        .is_some_and(|mention| source_range.contains_inclusive(&mention.range))
+                    {
+                        mentions = &mentions[1..];
+                    }
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    if let Some((language, code)) = code_block.take() {
+                        code_blocks.push((text.len(), CodeBlock::new(language, code)));
+                    }
+                }
+                _ => {}
+            }
+
+            continue;
+        }
+
         match event {
             Event::Text(t) => {
                 // Process text with mention replacements
@@ -230,8 +495,16 @@ fn render_plain_text_mut(
                     new_paragraph(text, &mut list_stack);
                     bold_depth += 1;
                 }
-                Tag::CodeBlock(_kind) => {
-                    new_paragraph(text, &mut list_stack);
+                Tag::CodeBlock(kind) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(info) => info
+                            .split_whitespace()
+                            .next()
+                            .map(|language| language.to_string()),
+                        CodeBlockKind::Indented => None,
+                    };
+
+                    code_block = Some((language, String::new()));
                 }
                 Tag::Emphasis => italic_depth += 1,
                 Tag::Strong => bold_depth += 1,
@@ -62,12 +62,21 @@ impl TimestampExt for Timestamp {
 
 pub trait StringExt {
     fn to_qr(&self) -> Option<Arc<Image>>;
+    fn to_qr_svg(&self) -> Option<String>;
 }
 
 impl<T: AsRef<str>> StringExt for T {
     fn to_qr(&self) -> Option<Arc<Image>> {
-        let s = self.as_ref();
-        let code = QrCode::new(s).unwrap();
+        let svg = self.to_qr_svg()?;
+
+        Some(Arc::new(Image::from_bytes(
+            ImageFormat::Svg,
+            svg.into_bytes(),
+        )))
+    }
+
+    fn to_qr_svg(&self) -> Option<String> {
+        let code = QrCode::new(self.as_ref()).ok()?;
         let svg = code
             .render()
             .min_dimensions(256, 256)
@@ -75,9 +84,15 @@ impl<T: AsRef<str>> StringExt for T {
             .light_color(svg::Color("#FFFFFF"))
             .build();
 
-        Some(Arc::new(Image::from_bytes(
-            ImageFormat::Svg,
-            svg.into_bytes(),
-        )))
+        Some(svg)
+    }
+}
+
+/// Format a size in bytes for display
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.),
     }
 }
@@ -63,3 +63,27 @@ pub fn support_dir() -> &'static PathBuf {
         config_dir().clone()
     })
 }
+
+/// Returns the path to the directory of themes imported by the user.
+pub fn themes_dir() -> &'static PathBuf {
+    static THEMES_DIR: OnceLock<PathBuf> = OnceLock::new();
+    THEMES_DIR.get_or_init(|| config_dir().join("themes"))
+}
+
+/// Returns the path to the directory of cached message media.
+pub fn media_dir() -> &'static PathBuf {
+    static MEDIA_DIR: OnceLock<PathBuf> = OnceLock::new();
+    MEDIA_DIR.get_or_init(|| support_dir().join("media"))
+}
+
+/// Returns the path to the directory of cached link previews.
+pub fn previews_dir() -> &'static PathBuf {
+    static PREVIEWS_DIR: OnceLock<PathBuf> = OnceLock::new();
+    PREVIEWS_DIR.get_or_init(|| support_dir().join("previews"))
+}
+
+/// Returns the path to the directory of cached avatars.
+pub fn avatars_dir() -> &'static PathBuf {
+    static AVATARS_DIR: OnceLock<PathBuf> = OnceLock::new();
+    AVATARS_DIR.get_or_init(|| support_dir().join("avatars"))
+}
@@ -1,7 +1,5 @@
-use std::collections::HashMap;
-use std::time::Duration;
-
-use anyhow::{Context as AnyhowContext, Error};
+use anyhow::Error;
+use chat::{ChatRegistry, MuteKind};
 use common::TimestampExt;
 use gpui::prelude::FluentBuilder;
 use gpui::{
@@ -11,7 +9,7 @@ use gpui::{
 use nostr_sdk::prelude::*;
 use person::{Person, PersonRegistry, shorten_pubkey};
 use smallvec::{SmallVec, smallvec};
-use state::{BOOTSTRAP_RELAYS, NostrAddress, NostrRegistry, TIMEOUT};
+use state::{BOOTSTRAP_RELAYS, NostrAddress, NostrRegistry};
 use theme::ActiveTheme;
 use ui::avatar::Avatar;
 use ui::button::{Button, ButtonVariants};
@@ -78,16 +76,8 @@ impl Screening {
         let client = nostr.read(cx).client();
         let public_key = self.public_key;
 
-        let task: Task<Result<bool, Error>> = cx.background_spawn(async move {
-            let signer = client.signer().context("Signer not found")?;
-            let signer_pubkey = signer.get_public_key().await?;
-
-            // Check if user is in contact list
-            let contacts = client.database().contacts_public_keys(signer_pubkey).await;
-            let followed = contacts.unwrap_or_default().contains(&public_key);
-
-            Ok(followed)
-        });
+        let task: Task<Result<bool, Error>> =
+            cx.background_spawn(async move { chat::is_followed(&client, public_key).await });
 
         self.tasks.push(cx.spawn(async move |this, cx| {
             let result = task.await.unwrap_or(false);
@@ -105,22 +95,8 @@ impl Screening {
         let client = nostr.read(cx).client();
         let public_key = self.public_key;
 
-        let task: Task<Result<Vec<PublicKey>, Error>> = cx.background_spawn(async move {
-            let signer = client.signer().context("Signer not found")?;
-            let signer_pubkey = signer.get_public_key().await?;
-
-            // Check mutual contacts
-            let filter = Filter::new().kind(Kind::ContactList).pubkey(public_key);
-            let mut mutual_contacts = vec![];
-
-            if let Ok(events) = client.database().query(filter).await {
-                for event in events.into_iter().filter(|ev| ev.pubkey != signer_pubkey) {
-                    mutual_contacts.push(event.pubkey);
-                }
-            }
-
-            Ok(mutual_contacts)
-        });
+        let task: Task<Result<Vec<PublicKey>, Error>> =
+            cx.background_spawn(async move { chat::mutual_contacts(&client, public_key).await });
 
         self.tasks.push(cx.spawn(async move |this, cx| {
             match task.await {
@@ -143,30 +119,8 @@ impl Screening {
         let client = nostr.read(cx).client();
         let public_key = self.public_key;
 
-        let task: Task<Option<Timestamp>> = cx.background_spawn(async move {
-            let filter = Filter::new().author(public_key).limit(1);
-            let mut activity: Option<Timestamp> = None;
-
-            // Construct target for subscription
-            let target: HashMap<&str, Vec<Filter>> = BOOTSTRAP_RELAYS
-                .into_iter()
-                .map(|relay| (relay, vec![filter.clone()]))
-                .collect();
-
-            if let Ok(mut stream) = client
-                .stream_events(target)
-                .timeout(Duration::from_secs(TIMEOUT))
-                .await
-            {
-                while let Some((_url, event)) = stream.next().await {
-                    if let Ok(event) = event {
-                        activity = Some(event.created_at);
-                    }
-                }
-            }
-
-            activity
-        });
+        let task: Task<Option<Timestamp>> =
+            cx.background_spawn(async move { chat::last_activity(&client, public_key).await });
 
         self.tasks.push(cx.spawn(async move |this, cx| {
             let result = task.await;
@@ -246,6 +200,24 @@ impl Screening {
         }));
     }
 
+    fn mute(&mut self, kind: MuteKind, window: &mut Window, cx: &mut Context<Self>) {
+        window.close_modal(cx);
+
+        if kind == MuteKind::Block {
+            chat_ui::confirm_block(self.public_key, window, cx);
+            return;
+        }
+
+        let chat = ChatRegistry::global(cx);
+        let name = self.profile(cx).name();
+
+        chat.update(cx, |this, cx| {
+            this.set_mute(self.public_key, Some(kind), cx);
+        });
+
+        window.push_notification(format!("{name} has been muted"), cx);
+    }
+
     fn mutual_contacts(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         let contacts = self.mutual_contacts.clone();
 
@@ -353,6 +325,28 @@ impl Render for Screening {
                                         this.open_njump(window, cx);
                                     })),
                             )
+                            .child(
+                                Button::new("mute")
+                                    .tooltip("Hide conversations with this person")
+                                    .label("Mute")
+                                    .secondary()
+                                    .small()
+                                    .rounded()
+                                    .on_click(cx.listener(move |this, _e, window, cx| {
+                                        this.mute(MuteKind::Mute, window, cx);
+                                    })),
+                            )
+                            .child(
+                                Button::new("block")
+                                    .tooltip("Drop all messages from this person")
+                                    .label("Block")
+                                    .secondary()
+                                    .small()
+                                    .rounded()
+                                    .on_click(cx.listener(move |this, _e, window, cx| {
+                                        this.mute(MuteKind::Block, window, cx);
+                                    })),
+                            )
                             .child(
                                 Button::new("report")
                                     .tooltip("Report as a scam or impostor")
@@ -511,7 +505,7 @@ impl Render for Screening {
     }
 }
 
-fn status_badge(status: Option<bool>, cx: &App) -> Div {
+pub(crate) fn status_badge(status: Option<bool>, cx: &App) -> Div {
     h_flex()
         .size_6()
         .justify_center()
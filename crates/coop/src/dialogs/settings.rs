@@ -1,11 +1,19 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Error;
+use chat::ChatRegistry;
 use gpui::http_client::Url;
+use gpui::prelude::FluentBuilder;
 use gpui::{
     App, AppContext, Context, Entity, IntoElement, ParentElement, Render, SharedString, Styled,
-    Window, div, px,
+    Task, Window, div, px,
 };
-use settings::{AppSettings, AuthMode};
+use settings::{AppSettings, AuthMode, SyncField, display_value};
+use state::check_server;
 use theme::{ActiveTheme, Theme, ThemeMode};
 use ui::button::{Button, ButtonVariants};
+use ui::checkbox::Checkbox;
 use ui::group_box::{GroupBox, GroupBoxVariants};
 use ui::input::{InputState, TextInput};
 use ui::menu::{DropdownMenu, PopupMenuItem};
@@ -17,29 +25,174 @@ pub fn init(window: &mut Window, cx: &mut App) -> Entity<Preferences> {
     cx.new(|cx| Preferences::new(window, cx))
 }
 
+/// Reachability of a media server
+#[derive(Debug, Clone)]
+enum ServerHealth {
+    Checking,
+    Online(Duration),
+    Offline(SharedString),
+}
+
+/// Label of a maximum image dimension
+fn image_size_label(size: Option<u32>) -> SharedString {
+    match size {
+        Some(size) => format!("{size} px").into(),
+        None => "Original".into(),
+    }
+}
+
 pub struct Preferences {
     file_input: Entity<InputState>,
+    proxy_input: Entity<InputState>,
+
+    /// Reachability of each media server
+    health: HashMap<Url, ServerHealth>,
+
+    /// Health check tasks
+    tasks: Vec<Task<()>>,
 }
 
 impl Preferences {
     pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
-        let server = AppSettings::get_file_server(cx);
-        let file_input = cx.new(|cx| {
+        let file_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder("https://myblossom.com"));
+
+        let proxy = AppSettings::get_image_proxy(cx);
+        let proxy_input = cx.new(|cx| {
             InputState::new(window, cx)
-                .default_value(server.to_string())
-                .placeholder("https://myblossom.com")
+                .default_value(proxy.map(|url| url.to_string()).unwrap_or_default())
+                .placeholder("https://proxy.example.com/?url=")
         });
 
-        Self { file_input }
+        let mut this = Self {
+            file_input,
+            proxy_input,
+            health: HashMap::default(),
+            tasks: vec![],
+        };
+
+        this.check_servers(cx);
+        this
     }
 
-    /// Update the file server (blossom) URL
-    fn update_file_server(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    /// Add a file server (blossom) to the user's server list
+    fn add_file_server(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         let value = self.file_input.read(cx).value();
 
-        match Url::parse(&value) {
+        match Url::parse(value.trim()) {
             Ok(url) => {
-                AppSettings::update_file_server(url, cx);
+                let mut servers = AppSettings::get_file_servers(cx);
+
+                if !servers.contains(&url) {
+                    servers.push(url.clone());
+                    Self::set_file_servers(servers, cx);
+                    self.check_server(url, cx);
+                }
+
+                self.file_input.update(cx, |this, cx| {
+                    this.set_value("", window, cx);
+                });
+            }
+            Err(e) => {
+                window.push_notification(Notification::error(e.to_string()).autohide(false), cx);
+            }
+        }
+    }
+
+    /// Remove a file server from the user's server list, asking before removing the last one
+    fn remove_file_server(ix: usize, window: &mut Window, cx: &mut App) {
+        const LAST_SERVER_WARN: &str =
+            "Without a media server, you can't upload files and fetch missing media.";
+
+        let mut servers = AppSettings::get_file_servers(cx);
+
+        if ix >= servers.len() {
+            return;
+        }
+
+        servers.remove(ix);
+
+        if !servers.is_empty() {
+            Self::set_file_servers(servers, cx);
+            return;
+        }
+
+        window.open_modal(cx, move |this, _window, _cx| {
+            this.confirm()
+                .show_close(true)
+                .title(SharedString::from("Remove the last media server"))
+                .child(div().text_sm().child(SharedString::from(LAST_SERVER_WARN)))
+                .on_ok(move |_ev, _window, cx| {
+                    Self::set_file_servers(vec![], cx);
+                    // true to close modal
+                    true
+                })
+        });
+    }
+
+    /// Move a file server to the top of the list, uploads go to it first
+    fn make_primary(ix: usize, cx: &mut App) {
+        let mut servers = AppSettings::get_file_servers(cx);
+
+        if ix < servers.len() {
+            let server = servers.remove(ix);
+            servers.insert(0, server);
+            Self::set_file_servers(servers, cx);
+        }
+    }
+
+    /// Save and publish the user's server list
+    fn set_file_servers(servers: Vec<Url>, cx: &mut App) {
+        ChatRegistry::global(cx).update(cx, |this, cx| {
+            this.set_file_servers(servers, cx);
+        });
+    }
+
+    /// Check if the file servers are reachable
+    fn check_servers(&mut self, cx: &mut Context<Self>) {
+        for server in AppSettings::get_file_servers(cx).into_iter() {
+            self.check_server(server, cx);
+        }
+    }
+
+    /// Check if a file server is reachable
+    fn check_server(&mut self, server: Url, cx: &mut Context<Self>) {
+        let http_client = cx.http_client();
+
+        self.health.insert(server.clone(), ServerHealth::Checking);
+        cx.notify();
+
+        let task: Task<Result<Duration, Error>> = cx.background_spawn({
+            let server = server.clone();
+            async move { check_server(&server, &http_client).await }
+        });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let health = match task.await {
+                Ok(latency) => ServerHealth::Online(latency),
+                Err(e) => ServerHealth::Offline(e.to_string().into()),
+            };
+
+            this.update(cx, |this, cx| {
+                this.health.insert(server, health);
+                cx.notify();
+            })
+            .ok();
+        }));
+    }
+
+    /// Update the proxy for fetching avatars, an empty value fetches them directly
+    fn update_image_proxy(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let value = self.proxy_input.read(cx).value();
+
+        if value.trim().is_empty() {
+            AppSettings::update_image_proxy(None, cx);
+            return;
+        }
+
+        match Url::parse(value.trim()) {
+            Ok(url) => {
+                AppSettings::update_image_proxy(Some(url), cx);
             }
             Err(e) => {
                 window.push_notification(Notification::error(e.to_string()).autohide(false), cx);
@@ -52,6 +205,31 @@ impl Preferences {
         AppSettings::update_theme_mode(mode, cx);
         Theme::change(mode, Some(window), cx);
     }
+
+    /// Add or remove a setting from the synced ones
+    fn toggle_sync_field(field: SyncField, cx: &mut App) {
+        let mut fields = AppSettings::get_sync_fields(cx);
+
+        if !fields.remove(&field) {
+            fields.insert(field);
+        }
+
+        AppSettings::update_sync_fields(fields, cx);
+    }
+
+    /// Resolve a sync conflict, optionally restoring the value dropped by the merge
+    fn resolve_conflict(field: SyncField, restore: bool, window: &mut Window, cx: &mut App) {
+        AppSettings::global(cx).update(cx, |this, cx| {
+            this.resolve_conflict(field, restore, window, cx);
+        });
+    }
+
+    /// Set the UI font scale
+    fn set_font_scale(scale: f32, window: &mut Window, cx: &mut App) {
+        AppSettings::global(cx).update(cx, |this, cx| {
+            this.set_font_scale(scale, window, cx);
+        });
+    }
 }
 
 impl Render for Preferences {
@@ -60,15 +238,35 @@ impl Render for Preferences {
             "When opening a request, a popup will appear to help you identify the sender.";
         const AVATAR: &str =
             "Hide all avatar pictures to improve performance and protect your privacy.";
+        const RESIZER: &str = "Resize avatars with wsrv.nl instead of on this device. The service sees every avatar you load.";
         const MODE: &str =
             "Choose whether to use the selected light or dark theme, or to follow the OS.";
+        const SYNC_ROOMS: &str =
+            "Keep pinned, archived and foldered conversations in sync across your devices.";
         const AUTH: &str = "Choose the authentication behavior for relays.";
         const RESET: &str = "Reset the theme to the default one.";
+        const FONT_SCALE: &str = "Scale the text and controls across the whole interface.";
+        const FONT_SCALES: [f32; 6] = [0.9, 1.0, 1.1, 1.25, 1.5, 1.75];
+        const SYNC_SETTINGS: &str = "Keep the selected preferences in sync across your devices. They are encrypted to your key.";
+        const CONFLICTS: &str = "Changed on another device too, the most recent change was kept.";
+        const SERVERS: &str = "Uploads go to the primary server and are copied to the others. The list is published, so others can still find your media when a server is down.";
+        const IMAGE_SIZE: &str = "Photos are sent without their metadata, such as the location, and downsized to this size. You're asked first when a photo can't be cleaned.";
+        const IMAGE_SIZES: [Option<u32>; 4] = [Some(1280), Some(2048), Some(4096), None];
+        const AUTO_LOAD_MEDIA: &str = "Download images linked in messages, their servers see your IP address. Media in chat requests is only loaded on click.";
 
         let screening = AppSettings::get_screening(cx);
         let hide_avatar = AppSettings::get_hide_avatar(cx);
+        let remote_resizer = AppSettings::get_remote_resizer(cx);
+        let sync_room_configs = AppSettings::get_sync_room_configs(cx);
         let auth_mode = AppSettings::get_auth_mode(cx);
         let theme_mode = AppSettings::get_theme_mode(cx);
+        let font_scale = AppSettings::get_font_scale(cx);
+        let sync_settings = AppSettings::get_sync_settings(cx);
+        let sync_fields = AppSettings::get_sync_fields(cx);
+        let conflicts = AppSettings::global(cx).read(cx).conflicts().to_vec();
+        let file_servers = AppSettings::get_file_servers(cx);
+        let max_image_dimension = AppSettings::get_max_image_dimension(cx);
+        let auto_load_media = AppSettings::get_auto_load_media(cx);
 
         v_flex()
             .gap_4()
@@ -95,6 +293,29 @@ impl Render for Preferences {
                                 AppSettings::update_hide_avatar(!hide_avatar, cx);
                             }),
                     )
+                    .child(
+                        Switch::new("remote-resizer")
+                            .label("Use remote image resizer")
+                            .description(RESIZER)
+                            .checked(remote_resizer)
+                            .on_click(move |_, _window, cx| {
+                                AppSettings::update_remote_resizer(!remote_resizer, cx);
+                            }),
+                    )
+                    .child(
+                        Switch::new("sync-rooms")
+                            .label("Sync conversation organization")
+                            .description(SYNC_ROOMS)
+                            .checked(sync_room_configs)
+                            .on_click(move |_, _window, cx| {
+                                AppSettings::update_sync_room_configs(!sync_room_configs, cx);
+
+                                // Merge with the configs from other devices
+                                ChatRegistry::global(cx).update(cx, |this, cx| {
+                                    this.get_room_configs(cx);
+                                });
+                            }),
+                    )
                     .child(
                         h_flex()
                             .gap_3()
@@ -180,6 +401,38 @@ impl Render for Preferences {
                                     }),
                             ),
                     )
+                    .child(
+                        h_flex()
+                            .gap_3()
+                            .justify_between()
+                            .child(
+                                v_flex()
+                                    .child(div().text_sm().child(SharedString::from("Font size")))
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(cx.theme().text_muted)
+                                            .child(SharedString::from(FONT_SCALE)),
+                                    ),
+                            )
+                            .child(
+                                Button::new("font-scale")
+                                    .label(format!("{:.0}%", font_scale * 100.))
+                                    .ghost_alt()
+                                    .small()
+                                    .dropdown_menu(|this, _window, _cx| {
+                                        FONT_SCALES.into_iter().fold(this, |this, scale| {
+                                            let label = format!("{:.0}%", scale * 100.);
+
+                                            this.item(PopupMenuItem::new(label).on_click(
+                                                move |_, window, cx| {
+                                                    Self::set_font_scale(scale, window, cx);
+                                                },
+                                            ))
+                                        })
+                                    }),
+                            ),
+                    )
                     .child(
                         h_flex()
                             .gap_3()
@@ -210,8 +463,135 @@ impl Render for Preferences {
             .child(
                 GroupBox::new()
                     .id("media")
-                    .title("Media Upload Service")
+                    .title("Media Servers")
                     .fill()
+                    .child(
+                        Switch::new("auto-load-media")
+                            .label("Load media automatically")
+                            .description(AUTO_LOAD_MEDIA)
+                            .checked(auto_load_media)
+                            .on_click(move |_, _window, cx| {
+                                AppSettings::update_auto_load_media(!auto_load_media, cx);
+                            }),
+                    )
+                    .child(
+                        h_flex()
+                            .gap_3()
+                            .justify_between()
+                            .child(
+                                v_flex()
+                                    .child(div().text_sm().child(SharedString::from("Image size")))
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(cx.theme().text_muted)
+                                            .child(SharedString::from(IMAGE_SIZE)),
+                                    ),
+                            )
+                            .child(
+                                Button::new("image-size")
+                                    .label(image_size_label(max_image_dimension))
+                                    .ghost_alt()
+                                    .small()
+                                    .dropdown_menu(|this, _window, _cx| {
+                                        IMAGE_SIZES.into_iter().fold(this, |this, size| {
+                                            this.item(
+                                                PopupMenuItem::new(image_size_label(size))
+                                                    .on_click(move |_, _window, cx| {
+                                                        AppSettings::update_max_image_dimension(
+                                                            size, cx,
+                                                        );
+                                                    }),
+                                            )
+                                        })
+                                    }),
+                            ),
+                    )
+                    .child(
+                        h_flex()
+                            .gap_3()
+                            .justify_between()
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .text_color(cx.theme().text_muted)
+                                    .child(SharedString::from(SERVERS)),
+                            )
+                            .child(
+                                Button::new("check-file-servers")
+                                    .icon(IconName::Refresh)
+                                    .ghost()
+                                    .small()
+                                    .on_click(cx.listener(move |this, _ev, _window, cx| {
+                                        this.check_servers(cx);
+                                    })),
+                            ),
+                    )
+                    .children(file_servers.into_iter().enumerate().map(|(ix, server)| {
+                        let (color, status) = match self.health.get(&server) {
+                            Some(ServerHealth::Online(latency)) => (
+                                cx.theme().icon_accent,
+                                SharedString::from(format!("{} ms", latency.as_millis())),
+                            ),
+                            Some(ServerHealth::Offline(e)) => (cx.theme().text_danger, e.clone()),
+                            Some(ServerHealth::Checking) => {
+                                (cx.theme().text_placeholder, SharedString::from("Checking..."))
+                            }
+                            None => (cx.theme().text_placeholder, SharedString::from("Not checked")),
+                        };
+
+                        h_flex()
+                            .gap_3()
+                            .justify_between()
+                            .child(
+                                h_flex()
+                                    .gap_2()
+                                    .min_w_0()
+                                    .child(div().size_1p5().flex_shrink_0().rounded_full().bg(color))
+                                    .child(
+                                        v_flex()
+                                            .min_w_0()
+                                            .child(
+                                                div()
+                                                    .text_sm()
+                                                    .truncate()
+                                                    .child(SharedString::from(server.to_string())),
+                                            )
+                                            .child(
+                                                div()
+                                                    .text_xs()
+                                                    .truncate()
+                                                    .text_color(cx.theme().text_muted)
+                                                    .when(ix == 0, |this| this.child("Primary · "))
+                                                    .child(status),
+                                            ),
+                                    ),
+                            )
+                            .child(
+                                h_flex()
+                                    .gap_1()
+                                    .when(ix > 0, |this| {
+                                        this.child(
+                                            Button::new(("primary-file-server", ix))
+                                                .label("Make primary")
+                                                .ghost_alt()
+                                                .xsmall()
+                                                .on_click(move |_ev, _window, cx| {
+                                                    Self::make_primary(ix, cx);
+                                                }),
+                                        )
+                                    })
+                                    .child(
+                                        Button::new(("remove-file-server", ix))
+                                            .icon(IconName::Close)
+                                            .ghost()
+                                            .xsmall()
+                                            .on_click(move |_ev, window, cx| {
+                                                Self::remove_file_server(ix, window, cx);
+                                            }),
+                                    ),
+                            )
+                    }))
                     .child(
                         v_flex()
                             .gap_0p5()
@@ -220,12 +600,12 @@ impl Render for Preferences {
                                     .gap_1()
                                     .child(TextInput::new(&self.file_input).text_xs().small())
                                     .child(
-                                        Button::new("update-file-server")
-                                            .icon(IconName::Check)
+                                        Button::new("add-file-server")
+                                            .icon(IconName::Plus)
                                             .ghost()
                                             .size_8()
                                             .on_click(cx.listener(move |this, _ev, window, cx| {
-                                                this.update_file_server(window, cx)
+                                                this.add_file_server(window, cx)
                                             })),
                                     ),
                             )
@@ -238,5 +618,132 @@ impl Render for Preferences {
                             ),
                     ),
             )
+            .child(
+                GroupBox::new()
+                    .id("image-proxy")
+                    .title("Avatar Proxy")
+                    .fill()
+                    .child(
+                        v_flex()
+                            .gap_0p5()
+                            .child(
+                                h_flex()
+                                    .gap_1()
+                                    .child(TextInput::new(&self.proxy_input).text_xs().small())
+                                    .child(
+                                        Button::new("update-image-proxy")
+                                            .icon(IconName::Check)
+                                            .ghost()
+                                            .size_8()
+                                            .on_click(cx.listener(move |this, _ev, window, cx| {
+                                                this.update_image_proxy(window, cx)
+                                            })),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .text_size(px(10.))
+                                    .italic()
+                                    .text_color(cx.theme().text_placeholder)
+                                    .child(SharedString::from(
+                                        "The encoded avatar URL is appended to the proxy URL. Leave empty to fetch avatars directly",
+                                    )),
+                            ),
+                    ),
+            )
+            .child(
+                GroupBox::new()
+                    .id("sync")
+                    .title("Sync")
+                    .fill()
+                    .child(
+                        Switch::new("sync-settings")
+                            .label("Sync preferences")
+                            .description(SYNC_SETTINGS)
+                            .checked(sync_settings)
+                            .on_click(move |_, window, cx| {
+                                AppSettings::update_sync_settings(!sync_settings, cx);
+
+                                // Merge with the settings from other devices
+                                ChatRegistry::global(cx).update(cx, |this, cx| {
+                                    this.get_settings(window, cx);
+                                });
+                            }),
+                    )
+                    .when(sync_settings, |this| {
+                        this.child(h_flex().gap_3().flex_wrap().children(
+                            SyncField::ALL.into_iter().map(|field| {
+                                Checkbox::new(field.name())
+                                    .label(field.label())
+                                    .checked(sync_fields.contains(&field))
+                                    .on_click(move |_, _window, cx| {
+                                        Self::toggle_sync_field(field, cx);
+                                    })
+                            }),
+                        ))
+                    })
+                    .when(!conflicts.is_empty(), |this| {
+                        this.child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().text_muted)
+                                .child(SharedString::from(CONFLICTS)),
+                        )
+                        .children(conflicts.into_iter().map(|conflict| {
+                            let field = conflict.field;
+                            let kept = if conflict.remote_won {
+                                &conflict.remote
+                            } else {
+                                &conflict.local
+                            };
+                            let dropped = display_value(conflict.dropped());
+
+                            h_flex()
+                                .gap_3()
+                                .justify_between()
+                                .child(
+                                    v_flex()
+                                        .child(div().text_sm().child(SharedString::from(field.label())))
+                                        .child(
+                                            div()
+                                                .text_xs()
+                                                .text_color(cx.theme().text_muted)
+                                                .child(SharedString::from(format!(
+                                                    "Kept: {}, dropped: {dropped}",
+                                                    display_value(kept)
+                                                ))),
+                                        ),
+                                )
+                                .child(
+                                    h_flex()
+                                        .gap_1()
+                                        .child(
+                                            Button::new(SharedString::from(format!(
+                                                "restore-{}",
+                                                field.name()
+                                            )))
+                                            .label("Use dropped")
+                                            .ghost_alt()
+                                            .small()
+                                            .on_click(move |_ev, window, cx| {
+                                                Self::resolve_conflict(field, true, window, cx);
+                                            }),
+                                        )
+                                        .child(
+                                            Button::new(SharedString::from(format!(
+                                                "dismiss-{}",
+                                                field.name()
+                                            )))
+                                            .label("Dismiss")
+                                            .ghost()
+                                            .small()
+                                            .on_click(move |_ev, window, cx| {
+                                                Self::resolve_conflict(field, false, window, cx);
+                                            }),
+                                        ),
+                                )
+                        }))
+                    }),
+            )
     }
 }
@@ -77,6 +77,12 @@ fn main() {
                     // Initialize components
                     ui::init(cx);
 
+                    // Register chat composer key bindings
+                    chat_ui::bind_keys(cx);
+
+                    // Remove expired media from the cache
+                    chat_ui::prune_media_cache(cx);
+
                     // Initialize theme registry
                     theme::init(cx);
 
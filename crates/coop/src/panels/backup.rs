@@ -1,21 +1,37 @@
+use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::Error;
+use anyhow::{Context as AnyhowContext, Error, anyhow};
+use common::{StringExt, download_dir};
+use gpui::prelude::FluentBuilder;
 use gpui::{
     AnyElement, App, AppContext, ClipboardItem, Context, Entity, EventEmitter, FocusHandle,
-    Focusable, IntoElement, ParentElement, Render, SharedString, Styled, Task, Window, div,
+    Focusable, Image, IntoElement, ParentElement, Render, SharedString, Styled, Task, Window, div,
+    img, px,
 };
 use nostr_sdk::prelude::*;
-use state::KEYRING;
+use state::NostrRegistry;
 use theme::ActiveTheme;
 use ui::button::{Button, ButtonVariants};
 use ui::dock::{Panel, PanelEvent};
 use ui::input::{InputState, TextInput};
-use ui::{IconName, Sizable, StyledExt, divider, v_flex};
+use ui::notification::Notification;
+use ui::{Disableable, IconName, Sizable, StyledExt, WindowExtension, divider, h_flex, v_flex};
 
 const MSG: &str = "Store your account keys in a safe location. \
                    You can restore your account or move to another client anytime you want.";
 
+const ENCRYPT_MSG: &str = "Your secret key is encrypted with a passphrase (NIP-49). \
+                           You will need both the backup and the passphrase to restore your account.";
+
+const REMOTE_MSG: &str = "Your account uses a remote signer. \
+                          Back up your keys in the signer app.";
+
+const VERIFY_MSG: &str = "Re-enter your backup and passphrase to make sure you can restore it.";
+
+/// Scrypt rounds (as a power of 2) for the encrypted secret key
+const LOG_N: u8 = 16;
+
 pub fn init(window: &mut Window, cx: &mut App) -> Entity<BackupPanel> {
     cx.new(|cx| BackupPanel::new(window, cx))
 }
@@ -25,11 +41,41 @@ pub struct BackupPanel {
     name: SharedString,
     focus_handle: FocusHandle,
 
+    /// Public key of the active account
+    public_key: Option<PublicKey>,
+
+    /// Whether the active account uses a remote signer
+    remote: bool,
+
     /// Public key input
     npub_input: Entity<InputState>,
 
-    /// Secret key input
-    nsec_input: Entity<InputState>,
+    /// Passphrase input
+    password_input: Entity<InputState>,
+
+    /// Passphrase confirmation input
+    confirm_input: Entity<InputState>,
+
+    /// Encrypted secret key (ncryptsec)
+    ncryptsec: Option<SharedString>,
+
+    /// QR code of the encrypted secret key
+    qr_code: Option<Arc<Image>>,
+
+    /// Backup verification input
+    verify_input: Entity<InputState>,
+
+    /// Backup verification passphrase input
+    verify_password_input: Entity<InputState>,
+
+    /// Result of the backup verification
+    verified: Option<Result<(), SharedString>>,
+
+    /// Whether the panel is running a task
+    loading: bool,
+
+    /// Error message
+    error: Option<SharedString>,
 
     /// Copied status
     copied: bool,
@@ -41,7 +87,22 @@ pub struct BackupPanel {
 impl BackupPanel {
     pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
         let npub_input = cx.new(|cx| InputState::new(window, cx).disabled(true));
-        let nsec_input = cx.new(|cx| InputState::new(window, cx).disabled(true).masked(true));
+        let password_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .masked(true)
+                .placeholder("Passphrase")
+        });
+        let confirm_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .masked(true)
+                .placeholder("Confirm passphrase")
+        });
+        let verify_input = cx.new(|cx| InputState::new(window, cx).placeholder("ncryptsec1..."));
+        let verify_password_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .masked(true)
+                .placeholder("Passphrase")
+        });
 
         // Run at the end of current cycle
         cx.defer_in(window, |this, window, cx| {
@@ -51,39 +112,200 @@ impl BackupPanel {
         Self {
             name: "Backup".into(),
             focus_handle: cx.focus_handle(),
+            public_key: None,
+            remote: false,
             npub_input,
-            nsec_input,
+            password_input,
+            confirm_input,
+            ncryptsec: None,
+            qr_code: None,
+            verify_input,
+            verify_password_input,
+            verified: None,
+            loading: false,
+            error: None,
             copied: false,
             tasks: vec![],
         }
     }
 
     fn load(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        let keyring = cx.read_credentials(KEYRING);
+        let nostr = NostrRegistry::global(cx);
+        let signer = nostr.read(cx).signer();
+
+        let Some(public_key) = signer.public_key() else {
+            return;
+        };
 
-        self.tasks.push(cx.spawn_in(window, async move |this, cx| {
-            if let Some((_, secret)) = keyring.await? {
-                let secret = SecretKey::from_slice(&secret)?;
-                let keys = Keys::new(secret);
+        self.public_key = Some(public_key);
+        self.npub_input.update(cx, |this, cx| {
+            this.set_value(public_key.to_bech32().unwrap(), window, cx);
+        });
 
-                this.update_in(cx, |this, window, cx| {
-                    this.npub_input.update(cx, |this, cx| {
-                        this.set_value(keys.public_key().to_bech32().unwrap(), window, cx);
-                    });
+        let task = nostr.read(cx).get_secret_key(public_key, cx);
 
-                    this.nsec_input.update(cx, |this, cx| {
-                        this.set_value(keys.secret_key().to_bech32().unwrap(), window, cx);
-                    });
-                })?;
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let result = task.await;
+
+            this.update(cx, |this, cx| {
+                match result {
+                    Ok(secret) => this.remote = secret.is_none(),
+                    Err(e) => this.error = Some(e.to_string().into()),
+                }
+                cx.notify();
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn set_loading(&mut self, loading: bool, cx: &mut Context<Self>) {
+        self.loading = loading;
+        cx.notify();
+    }
+
+    fn set_error<S>(&mut self, message: S, cx: &mut Context<Self>)
+    where
+        S: Into<SharedString>,
+    {
+        self.error = Some(message.into());
+        self.loading = false;
+        cx.notify();
+    }
+
+    /// Encrypt the secret key of the active account with the passphrase
+    fn export(&mut self, cx: &mut Context<Self>) {
+        let password = self.password_input.read(cx).value().to_string();
+        let confirm = self.confirm_input.read(cx).value().to_string();
+
+        let Some(public_key) = self.public_key else {
+            return;
+        };
+
+        if password.is_empty() {
+            self.set_error("Passphrase is required", cx);
+            return;
+        }
+
+        if password != confirm {
+            self.set_error("Passphrases do not match", cx);
+            return;
+        }
+
+        let nostr = NostrRegistry::global(cx);
+        let secret = nostr.read(cx).get_secret_key(public_key, cx);
+
+        let task: Task<Result<String, Error>> = cx.background_spawn(async move {
+            let secret = secret.await?.context("Secret key not found")?;
+            let encrypted =
+                EncryptedSecretKey::new(&secret, &password, LOG_N, KeySecurity::Unknown)?;
+
+            Ok(encrypted.to_bech32()?)
+        });
+
+        self.error = None;
+        self.set_loading(true, cx);
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let result = task.await;
+
+            this.update(cx, |this, cx| {
+                match result {
+                    Ok(ncryptsec) => {
+                        this.qr_code = ncryptsec.to_qr();
+                        this.ncryptsec = Some(ncryptsec.into());
+                        this.verified = None;
+                        this.set_loading(false, cx);
+                    }
+                    Err(e) => {
+                        this.set_error(e.to_string(), cx);
+                    }
+                };
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Decrypt the re-entered backup and check that it matches the active account
+    fn verify(&mut self, cx: &mut Context<Self>) {
+        let content = self.verify_input.read(cx).value().trim().to_string();
+        let password = self.verify_password_input.read(cx).value().to_string();
+
+        let Some(public_key) = self.public_key else {
+            return;
+        };
+
+        let task: Task<Result<(), Error>> = cx.background_spawn(async move {
+            let encrypted = EncryptedSecretKey::from_bech32(&content)
+                .map_err(|_| anyhow!("Backup is invalid"))?;
+            let secret = encrypted
+                .decrypt(&password)
+                .map_err(|_| anyhow!("Invalid passphrase"))?;
+
+            if Keys::new(secret).public_key() != public_key {
+                return Err(anyhow!("Backup belongs to another account"));
             }
 
+            Ok(())
+        });
+
+        self.set_loading(true, cx);
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let result = task.await;
+
+            this.update(cx, |this, cx| {
+                this.verified = Some(result.map_err(|e| e.to_string().into()));
+                this.set_loading(false, cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    /// Save a printable page with the backup and open it
+    fn print(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(ncryptsec) = self.ncryptsec.clone() else {
+            return;
+        };
+
+        let npub = self.npub_input.read(cx).value().to_string();
+        let save_dialog = cx.prompt_for_new_path(download_dir(), Some("coop-backup.html"));
+
+        self.tasks.push(cx.spawn_in(window, async move |_this, cx| {
+            // Get the output path from the save dialog
+            let output_path = match save_dialog.await {
+                Ok(Ok(Some(path))) => path,
+                Ok(Ok(None)) | Err(_) => return Ok(()),
+                Ok(Err(error)) => {
+                    cx.update(|window, cx| {
+                        let message = format!("Failed to pick save location: {error:#}");
+                        let note = Notification::error(message).autohide(false);
+                        window.push_notification(note, cx);
+                    })?;
+                    return Ok(());
+                }
+            };
+
+            let content = paper_backup(&npub, &ncryptsec);
+            smol::fs::write(&output_path, content).await?;
+
+            // Open the page with the system's default application
+            cx.update(|_window, cx| {
+                cx.open_with_system(output_path.as_path());
+            })?;
+
             Ok(())
         }));
     }
 
-    fn copy_secret(&mut self, cx: &mut Context<Self>) {
-        let value = self.nsec_input.read(cx).value();
-        let item = ClipboardItem::new_string(value.to_string());
+    fn copy_backup(&mut self, cx: &mut Context<Self>) {
+        let Some(ncryptsec) = self.ncryptsec.as_ref() else {
+            return;
+        };
+
+        let item = ClipboardItem::new_string(ncryptsec.to_string());
         cx.write_to_clipboard(item);
 
         // Set the copied status to true
@@ -94,17 +316,175 @@ impl BackupPanel {
         self.copied = status;
         cx.notify();
 
-        self.tasks.push(cx.spawn(async move |this, cx| {
-            cx.background_executor().timer(Duration::from_secs(2)).await;
+        if status {
+            self.tasks.push(cx.spawn(async move |this, cx| {
+                cx.background_executor().timer(Duration::from_secs(2)).await;
 
-            // Clear the error message after a delay
-            this.update(cx, |this, cx| {
-                this.set_copied(false, cx);
-            })?;
+                // Clear the copied status after a delay
+                this.update(cx, |this, cx| {
+                    this.set_copied(false, cx);
+                })?;
 
-            Ok(())
-        }));
+                Ok(())
+            }));
+        }
     }
+
+    fn render_label(&self, label: &'static str, cx: &Context<Self>) -> impl IntoElement {
+        div()
+            .text_xs()
+            .font_semibold()
+            .text_color(cx.theme().text_muted)
+            .child(SharedString::from(label))
+    }
+
+    fn render_export(&self, cx: &Context<Self>) -> impl IntoElement {
+        v_flex()
+            .gap_1p5()
+            .w_full()
+            .child(self.render_label("Encrypted Backup:", cx))
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().text_muted)
+                    .child(SharedString::from(ENCRYPT_MSG)),
+            )
+            .child(TextInput::new(&self.password_input).small())
+            .child(TextInput::new(&self.confirm_input).small())
+            .child(
+                Button::new("export")
+                    .label("Create backup")
+                    .primary()
+                    .small()
+                    .font_semibold()
+                    .loading(self.loading)
+                    .disabled(self.loading)
+                    .on_click(cx.listener(move |this, _ev, _window, cx| {
+                        this.export(cx);
+                    })),
+            )
+            .when_some(self.ncryptsec.as_ref(), |this, ncryptsec| {
+                this.child(
+                    v_flex()
+                        .gap_2()
+                        .items_center()
+                        .when_some(self.qr_code.as_ref(), |this, qr| {
+                            this.child(
+                                img(qr.clone())
+                                    .size(px(192.))
+                                    .rounded(cx.theme().radius_lg)
+                                    .border_1()
+                                    .border_color(cx.theme().border),
+                            )
+                        })
+                        .child(
+                            div()
+                                .w_full()
+                                .p_2()
+                                .rounded(cx.theme().radius)
+                                .bg(cx.theme().elevated_surface_background)
+                                .text_xs()
+                                .font_family(cx.theme().mono_font_family.clone())
+                                .child(ncryptsec.clone()),
+                        )
+                        .child(
+                            h_flex()
+                                .gap_1()
+                                .child(
+                                    Button::new("copy")
+                                        .icon(IconName::Copy)
+                                        .label(if self.copied { "Copied" } else { "Copy" })
+                                        .ghost()
+                                        .small()
+                                        .on_click(cx.listener(move |this, _ev, _window, cx| {
+                                            this.copy_backup(cx);
+                                        })),
+                                )
+                                .child(
+                                    Button::new("print")
+                                        .icon(IconName::Book)
+                                        .label("Print paper backup")
+                                        .ghost()
+                                        .small()
+                                        .on_click(cx.listener(move |this, _ev, window, cx| {
+                                            this.print(window, cx);
+                                        })),
+                                ),
+                        ),
+                )
+            })
+    }
+
+    fn render_verify(&self, cx: &Context<Self>) -> impl IntoElement {
+        v_flex()
+            .gap_1p5()
+            .w_full()
+            .child(self.render_label("Verify Backup:", cx))
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().text_muted)
+                    .child(SharedString::from(VERIFY_MSG)),
+            )
+            .child(TextInput::new(&self.verify_input).small())
+            .child(TextInput::new(&self.verify_password_input).small())
+            .child(
+                Button::new("verify")
+                    .icon(IconName::CheckCircle)
+                    .label("Verify")
+                    .ghost()
+                    .small()
+                    .disabled(self.loading)
+                    .on_click(cx.listener(move |this, _ev, _window, cx| {
+                        this.verify(cx);
+                    })),
+            )
+            .when_some(self.verified.as_ref(), |this, verified| {
+                let (text, color) = match verified {
+                    Ok(_) => (
+                        SharedString::from("Your backup is valid"),
+                        cx.theme().text_accent,
+                    ),
+                    Err(e) => (e.clone(), cx.theme().text_danger),
+                };
+
+                this.child(div().italic().text_xs().text_color(color).child(text))
+            })
+    }
+}
+
+/// Construct a printable page with the encrypted secret key
+fn paper_backup(npub: &str, ncryptsec: &str) -> String {
+    let qr = ncryptsec.to_qr_svg().unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Coop Backup</title>
+<style>
+body {{ font-family: sans-serif; max-width: 640px; margin: 40px auto; color: #000; }}
+code {{ display: block; padding: 12px; border: 1px solid #000; word-break: break-all; }}
+.qr {{ width: 256px; height: 256px; }}
+</style>
+</head>
+<body>
+<h1>Nostr Account Backup</h1>
+<p>Keep this page in a safe place.</p>
+<h2>Public Key</h2>
+<code>{npub}</code>
+<h2>Encrypted Secret Key</h2>
+<div class="qr">{qr}</div>
+<code>{ncryptsec}</code>
+<p>This key is encrypted with your passphrase (NIP-49). To restore your account,
+import it in Coop or any Nostr client supporting ncryptsec, then enter your passphrase.
+The passphrase is not part of this backup.</p>
+<p>Passphrase hint: ____________________________________</p>
+</body>
+</html>
+"#
+    )
 }
 
 impl Panel for BackupPanel {
@@ -140,63 +520,39 @@ impl Render for BackupPanel {
             .child(divider(cx))
             .child(
                 v_flex()
-                    .gap_2()
-                    .flex_1()
+                    .gap_1p5()
                     .w_full()
                     .text_sm()
+                    .child(self.render_label("Public Key:", cx))
                     .child(
-                        v_flex()
-                            .gap_1p5()
-                            .w_full()
-                            .child(
-                                div()
-                                    .text_xs()
-                                    .font_semibold()
-                                    .text_color(cx.theme().text_muted)
-                                    .child(SharedString::from("Public Key:")),
-                            )
-                            .child(
-                                TextInput::new(&self.npub_input)
-                                    .small()
-                                    .bordered(false)
-                                    .disabled(true),
-                            ),
-                    )
-                    .child(
-                        v_flex()
-                            .gap_1p5()
-                            .w_full()
-                            .child(
-                                div()
-                                    .text_xs()
-                                    .font_semibold()
-                                    .text_color(cx.theme().text_muted)
-                                    .child(SharedString::from("Secret Key:")),
-                            )
-                            .child(
-                                TextInput::new(&self.nsec_input)
-                                    .small()
-                                    .bordered(false)
-                                    .disabled(true),
-                            ),
-                    )
-                    .child(
-                        Button::new("copy")
-                            .icon(IconName::Copy)
-                            .label({
-                                if self.copied {
-                                    "Copied"
-                                } else {
-                                    "Copy secret key"
-                                }
-                            })
-                            .primary()
+                        TextInput::new(&self.npub_input)
                             .small()
-                            .font_semibold()
-                            .on_click(cx.listener(move |this, _ev, _window, cx| {
-                                this.copy_secret(cx);
-                            })),
+                            .bordered(false)
+                            .disabled(true),
                     ),
             )
+            .when_some(self.error.as_ref(), |this, error| {
+                this.child(
+                    div()
+                        .italic()
+                        .text_xs()
+                        .text_color(cx.theme().text_danger)
+                        .child(error.clone()),
+                )
+            })
+            .map(|this| {
+                if self.remote {
+                    this.child(
+                        div()
+                            .text_sm()
+                            .text_color(cx.theme().text_muted)
+                            .child(SharedString::from(REMOTE_MSG)),
+                    )
+                } else {
+                    this.child(self.render_export(cx))
+                        .child(divider(cx))
+                        .child(self.render_verify(cx))
+                }
+            })
     }
 }
@@ -0,0 +1,358 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Error;
+use chat::ChatRegistry;
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AnyElement, App, AppContext, Context, Entity, EventEmitter, FocusHandle, Focusable,
+    IntoElement, ParentElement, Render, SharedString, Styled, Task, Window, div,
+};
+use nostr_sdk::prelude::*;
+use person::PersonRegistry;
+use state::NostrRegistry;
+use theme::ActiveTheme;
+use ui::button::{Button, ButtonVariants};
+use ui::dock::{Panel, PanelEvent};
+use ui::{Disableable, Sizable, StyledExt, WindowExtension, divider, h_flex, v_flex};
+
+const MSG: &str = "Messaging relays keep every message sent to you. \
+                   Messages with a backup copy on your relays can be removed from them.";
+
+const PRUNE_MSG: &str =
+    "The relay will be asked to delete the messages that have a backup copy on your relays.";
+
+const PRUNE_WARN: &str = "Relays may ignore deletion requests for messages sent to you. \
+                          Request to vanish to remove all of them.";
+
+const VANISH_MSG: &str = "The relay will delete all your events, \
+                          including every message sent to you.";
+
+const VANISH_WARN: &str = "Messages that are not backed up on this device will be lost.";
+
+pub fn init(window: &mut Window, cx: &mut App) -> Entity<InboxPanel> {
+    cx.new(|cx| InboxPanel::new(window, cx))
+}
+
+#[derive(Debug)]
+pub struct InboxPanel {
+    name: SharedString,
+    focus_handle: FocusHandle,
+
+    /// Gift wraps with a backup copy on relays, by the relays they were seen on
+    prunable: Option<HashMap<RelayUrl, Vec<EventId>>>,
+
+    /// Relays with a pending request
+    pending: HashSet<RelayUrl>,
+
+    /// Result of the last request, by relay
+    results: HashMap<RelayUrl, Result<SharedString, SharedString>>,
+
+    /// Background tasks
+    tasks: Vec<Task<Result<(), Error>>>,
+}
+
+impl InboxPanel {
+    fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        // Run at the end of current cycle
+        cx.defer_in(window, |this, _window, cx| {
+            this.load(cx);
+        });
+
+        Self {
+            name: "Inbox Maintenance".into(),
+            focus_handle: cx.focus_handle(),
+            prunable: None,
+            pending: HashSet::new(),
+            results: HashMap::new(),
+            tasks: vec![],
+        }
+    }
+
+    fn load(&mut self, cx: &mut Context<Self>) {
+        let chat = ChatRegistry::global(cx);
+        let task = chat.read(cx).prunable_gift_wraps(cx);
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let prunable = task.await?;
+
+            this.update(cx, |this, cx| {
+                this.prunable = Some(prunable);
+                cx.notify();
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn relays(&self, cx: &App) -> Vec<RelayUrl> {
+        let nostr = NostrRegistry::global(cx);
+        let signer = nostr.read(cx).signer();
+
+        let Some(public_key) = signer.public_key() else {
+            return vec![];
+        };
+
+        let persons = PersonRegistry::global(cx);
+        let profile = persons.read(cx).get(&public_key, cx);
+
+        profile.messaging_relays().clone()
+    }
+
+    /// Get the gift wraps safe to prune from a relay
+    fn prunable_ids(&self, url: &RelayUrl) -> Vec<EventId> {
+        self.prunable
+            .as_ref()
+            .and_then(|prunable| prunable.get(url).cloned())
+            .unwrap_or_default()
+    }
+
+    fn set_result(
+        &mut self,
+        url: RelayUrl,
+        result: Result<SharedString, SharedString>,
+        cx: &mut Context<Self>,
+    ) {
+        self.pending.remove(&url);
+        self.results.insert(url, result);
+        cx.notify();
+    }
+
+    fn prune(&mut self, url: RelayUrl, cx: &mut Context<Self>) {
+        let chat = ChatRegistry::global(cx);
+        let ids = self.prunable_ids(&url);
+        let task = chat.read(cx).delete_gift_wraps(&url, ids, cx);
+
+        self.pending.insert(url.clone());
+        cx.notify();
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let result = match task.await {
+                Ok(count) => Ok(format!(
+                    "Sent a deletion request for {count} messages, the relay may ignore it"
+                )
+                .into()),
+                Err(e) => Err(e.to_string().into()),
+            };
+
+            this.update(cx, |this, cx| {
+                this.set_result(url, result, cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn vanish(&mut self, url: RelayUrl, cx: &mut Context<Self>) {
+        let chat = ChatRegistry::global(cx);
+        let task = chat.read(cx).vanish_from_relay(&url, cx);
+
+        self.pending.insert(url.clone());
+        cx.notify();
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let result = match task.await {
+                Ok(_) => Ok("Requested to vanish".into()),
+                Err(e) => Err(e.to_string().into()),
+            };
+
+            this.update(cx, |this, cx| {
+                this.set_result(url, result, cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn confirm_prune(&mut self, url: RelayUrl, window: &mut Window, cx: &mut Context<Self>) {
+        let entity = cx.entity().downgrade();
+
+        window.open_modal(cx, move |this, _window, cx| {
+            let entity = entity.clone();
+            let url = url.clone();
+
+            this.confirm()
+                .show_close(true)
+                .title(SharedString::from(format!(
+                    "Delete backed up messages from {url}"
+                )))
+                .child(
+                    v_flex()
+                        .gap_1()
+                        .text_sm()
+                        .child(SharedString::from(PRUNE_MSG))
+                        .child(
+                            div()
+                                .italic()
+                                .text_color(cx.theme().text_muted)
+                                .child(SharedString::from(PRUNE_WARN)),
+                        ),
+                )
+                .on_ok(move |_ev, _window, cx| {
+                    entity
+                        .update(cx, |this, cx| {
+                            this.prune(url.clone(), cx);
+                        })
+                        .ok();
+                    // true to close modal
+                    true
+                })
+        });
+    }
+
+    fn confirm_vanish(&mut self, url: RelayUrl, window: &mut Window, cx: &mut Context<Self>) {
+        let entity = cx.entity().downgrade();
+
+        window.open_modal(cx, move |this, _window, cx| {
+            let entity = entity.clone();
+            let url = url.clone();
+
+            this.confirm()
+                .show_close(true)
+                .title(SharedString::from(format!("Vanish from {url}")))
+                .child(
+                    v_flex()
+                        .gap_1()
+                        .text_sm()
+                        .child(SharedString::from(VANISH_MSG))
+                        .child(
+                            div()
+                                .italic()
+                                .text_color(cx.theme().text_danger)
+                                .child(SharedString::from(VANISH_WARN)),
+                        ),
+                )
+                .on_ok(move |_ev, _window, cx| {
+                    entity
+                        .update(cx, |this, cx| {
+                            this.vanish(url.clone(), cx);
+                        })
+                        .ok();
+                    // true to close modal
+                    true
+                })
+        });
+    }
+
+    fn render_relay(&self, ix: usize, url: &RelayUrl, cx: &Context<Self>) -> impl IntoElement {
+        let chat = ChatRegistry::global(cx);
+        let seen = chat.read(cx).count_messages(url);
+        let prunable = self.prunable_ids(url).len();
+        let pending = self.pending.contains(url);
+
+        v_flex()
+            .p_2()
+            .gap_2()
+            .w_full()
+            .rounded(cx.theme().radius)
+            .bg(cx.theme().elevated_surface_background)
+            .child(
+                v_flex()
+                    .gap_0p5()
+                    .child(
+                        div()
+                            .text_sm()
+                            .font_semibold()
+                            .child(SharedString::from(url.to_string())),
+                    )
+                    .child(div().text_xs().text_color(cx.theme().text_muted).child(
+                        SharedString::from(format!(
+                            "{seen} messages seen this session · {prunable} with a backup copy"
+                        )),
+                    )),
+            )
+            .when_some(self.results.get(url), |this, result| {
+                let (text, color) = match result {
+                    Ok(text) => (text.clone(), cx.theme().text_muted),
+                    Err(text) => (text.clone(), cx.theme().text_danger),
+                };
+
+                this.child(div().italic().text_xs().text_color(color).child(text))
+            })
+            .child(
+                h_flex()
+                    .gap_1()
+                    .child(
+                        Button::new(format!("prune-{ix}"))
+                            .label("Delete backed up messages")
+                            .ghost()
+                            .xsmall()
+                            .loading(pending)
+                            .disabled(pending || prunable == 0)
+                            .on_click({
+                                let url = url.clone();
+                                cx.listener(move |this, _ev, window, cx| {
+                                    this.confirm_prune(url.clone(), window, cx);
+                                })
+                            }),
+                    )
+                    .child(
+                        Button::new(format!("vanish-{ix}"))
+                            .label("Request to vanish")
+                            .danger()
+                            .xsmall()
+                            .disabled(pending)
+                            .on_click({
+                                let url = url.clone();
+                                cx.listener(move |this, _ev, window, cx| {
+                                    this.confirm_vanish(url.clone(), window, cx);
+                                })
+                            }),
+                    ),
+            )
+    }
+}
+
+impl Panel for InboxPanel {
+    fn panel_id(&self) -> SharedString {
+        self.name.clone()
+    }
+
+    fn title(&self, _cx: &App) -> AnyElement {
+        self.name.clone().into_any_element()
+    }
+}
+
+impl EventEmitter<PanelEvent> for InboxPanel {}
+
+impl Focusable for InboxPanel {
+    fn focus_handle(&self, _: &App) -> gpui::FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for InboxPanel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let relays = self.relays(cx);
+
+        v_flex()
+            .p_3()
+            .gap_3()
+            .w_full()
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().text_muted)
+                    .child(SharedString::from(MSG)),
+            )
+            .child(divider(cx))
+            .child(
+                v_flex()
+                    .gap_2()
+                    .w_full()
+                    .when(relays.is_empty(), |this| {
+                        this.child(
+                            div()
+                                .text_sm()
+                                .text_color(cx.theme().text_muted)
+                                .child(SharedString::from("You have no messaging relays.")),
+                        )
+                    })
+                    .children(
+                        relays
+                            .iter()
+                            .enumerate()
+                            .map(|(ix, url)| self.render_relay(ix, url, cx)),
+                    ),
+            )
+    }
+}
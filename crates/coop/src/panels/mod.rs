@@ -1,7 +1,10 @@
 pub mod backup;
 pub mod contact_list;
 pub mod greeter;
+pub mod inbox;
 pub mod messaging_relays;
+pub mod mute_list;
 pub mod profile;
 pub mod relay_list;
+pub mod storage;
 pub mod trash;
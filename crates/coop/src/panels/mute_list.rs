@@ -0,0 +1,314 @@
+use std::time::Duration;
+
+use anyhow::Error;
+use chat::{ChatRegistry, MuteKind};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AnyElement, App, AppContext, Context, Entity, EventEmitter, FocusHandle, Focusable,
+    InteractiveElement, IntoElement, ParentElement, Render, SharedString, Styled, Subscription,
+    Task, TextAlign, Window, div,
+};
+use nostr_sdk::prelude::*;
+use person::PersonRegistry;
+use smallvec::{SmallVec, smallvec};
+use theme::ActiveTheme;
+use ui::avatar::Avatar;
+use ui::button::{Button, ButtonVariants};
+use ui::dock::{Panel, PanelEvent};
+use ui::input::{InputState, TextInput};
+use ui::{Sizable, StyledExt, h_flex, v_flex};
+
+pub fn init(window: &mut Window, cx: &mut App) -> Entity<MuteListPanel> {
+    cx.new(|cx| MuteListPanel::new(window, cx))
+}
+
+#[derive(Debug)]
+pub struct MuteListPanel {
+    name: SharedString,
+    focus_handle: FocusHandle,
+
+    /// Npub input
+    input: Entity<InputState>,
+
+    /// Error message
+    error: Option<SharedString>,
+
+    /// Event subscriptions
+    _subscriptions: SmallVec<[Subscription; 1]>,
+
+    /// Background tasks
+    tasks: Vec<Task<Result<(), Error>>>,
+}
+
+impl MuteListPanel {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let chat = ChatRegistry::global(cx);
+        let input = cx.new(|cx| InputState::new(window, cx).placeholder("npub1..."));
+        let mut subscriptions = smallvec![];
+
+        subscriptions.push(
+            // Re-render when the mute list changes
+            cx.observe(&chat, |_this, _chat, cx| {
+                cx.notify();
+            }),
+        );
+
+        Self {
+            name: "Muted Users".into(),
+            focus_handle: cx.focus_handle(),
+            input,
+            error: None,
+            _subscriptions: subscriptions,
+            tasks: vec![],
+        }
+    }
+
+    fn add(&mut self, kind: MuteKind, window: &mut Window, cx: &mut Context<Self>) {
+        let value = self.input.read(cx).value().to_string();
+
+        let Ok(public_key) = PublicKey::parse(&value) else {
+            self.set_error("Public Key is invalid", window, cx);
+            return;
+        };
+
+        self.set_mute(public_key, Some(kind), window, cx);
+        self.input.update(cx, |this, cx| {
+            this.set_value("", window, cx);
+        });
+    }
+
+    fn set_mute(
+        &mut self,
+        public_key: PublicKey,
+        kind: Option<MuteKind>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if kind == Some(MuteKind::Block) {
+            chat_ui::confirm_block(public_key, window, cx);
+            return;
+        }
+
+        let chat = ChatRegistry::global(cx);
+
+        chat.update(cx, |this, cx| {
+            this.set_mute(public_key, kind, cx);
+        });
+    }
+
+    fn set_error<E>(&mut self, error: E, window: &mut Window, cx: &mut Context<Self>)
+    where
+        E: Into<SharedString>,
+    {
+        self.error = Some(error.into());
+        cx.notify();
+
+        self.tasks.push(cx.spawn_in(window, async move |this, cx| {
+            cx.background_executor().timer(Duration::from_secs(2)).await;
+
+            // Clear the error message after a delay
+            this.update(cx, |this, cx| {
+                this.error = None;
+                cx.notify();
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn render_list_items(&mut self, cx: &mut Context<Self>) -> Vec<impl IntoElement> {
+        let chat = ChatRegistry::global(cx);
+        let persons = PersonRegistry::global(cx);
+        let mut items = Vec::new();
+
+        let users: Vec<(PublicKey, MuteKind)> = chat
+            .read(cx)
+            .mute_list()
+            .users()
+            .map(|(public_key, kind)| (*public_key, *kind))
+            .collect();
+
+        for (ix, (public_key, kind)) in users.into_iter().enumerate() {
+            let profile = persons.read(cx).get(&public_key, cx);
+
+            let (label, toggle_label, toggle_kind) = match kind {
+                MuteKind::Mute => ("Muted", "Block", MuteKind::Block),
+                MuteKind::Block => ("Blocked", "Mute", MuteKind::Mute),
+            };
+
+            items.push(
+                h_flex()
+                    .id(ix)
+                    .flex_1()
+                    .w_full()
+                    .h_8()
+                    .px_2()
+                    .justify_between()
+                    .rounded(cx.theme().radius)
+                    .bg(cx.theme().secondary_background)
+                    .text_color(cx.theme().secondary_foreground)
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .text_sm()
+                            .child(Avatar::new(profile.avatar()).small())
+                            .child(profile.name())
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .text_color(cx.theme().text_muted)
+                                    .child(SharedString::from(label)),
+                            ),
+                    )
+                    .child(
+                        h_flex()
+                            .gap_1()
+                            .child(
+                                Button::new(("toggle", ix))
+                                    .label(toggle_label)
+                                    .xsmall()
+                                    .ghost()
+                                    .on_click(cx.listener(move |this, _ev, window, cx| {
+                                        this.set_mute(public_key, Some(toggle_kind), window, cx);
+                                    })),
+                            )
+                            .child(
+                                Button::new(("remove", ix))
+                                    .label(match kind {
+                                        MuteKind::Mute => "Unmute",
+                                        MuteKind::Block => "Unblock",
+                                    })
+                                    .xsmall()
+                                    .ghost()
+                                    .on_click(cx.listener(move |this, _ev, window, cx| {
+                                        this.set_mute(public_key, None, window, cx);
+                                    })),
+                            ),
+                    ),
+            )
+        }
+
+        items
+    }
+
+    fn render_empty(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        h_flex()
+            .h_20()
+            .justify_center()
+            .border_2()
+            .border_dashed()
+            .border_color(cx.theme().border)
+            .rounded(cx.theme().radius_lg)
+            .text_sm()
+            .text_align(TextAlign::Center)
+            .child(SharedString::from("You haven't muted anyone."))
+    }
+}
+
+impl Panel for MuteListPanel {
+    fn panel_id(&self) -> SharedString {
+        self.name.clone()
+    }
+
+    fn title(&self, _cx: &App) -> AnyElement {
+        self.name.clone().into_any_element()
+    }
+}
+
+impl EventEmitter<PanelEvent> for MuteListPanel {}
+
+impl Focusable for MuteListPanel {
+    fn focus_handle(&self, _: &App) -> gpui::FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for MuteListPanel {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let is_empty = ChatRegistry::global(cx)
+            .read(cx)
+            .mute_list()
+            .users()
+            .next()
+            .is_none();
+
+        v_flex().p_3().gap_3().w_full().child(
+            v_flex()
+                .gap_2()
+                .flex_1()
+                .w_full()
+                .text_sm()
+                .child(
+                    div()
+                        .text_xs()
+                        .text_color(cx.theme().text_muted)
+                        .child(SharedString::from(
+                            "Conversations with muted users are hidden. \
+                            Messages from blocked users are dropped.",
+                        )),
+                )
+                .child(
+                    div()
+                        .text_xs()
+                        .font_semibold()
+                        .text_color(cx.theme().text_muted)
+                        .child(SharedString::from("Add user:")),
+                )
+                .child(
+                    v_flex()
+                        .gap_1()
+                        .child(
+                            h_flex()
+                                .gap_1()
+                                .w_full()
+                                .child(
+                                    TextInput::new(&self.input)
+                                        .small()
+                                        .bordered(false)
+                                        .cleanable(),
+                                )
+                                .child(
+                                    Button::new("mute")
+                                        .label("Mute")
+                                        .secondary()
+                                        .small()
+                                        .on_click(cx.listener(move |this, _, window, cx| {
+                                            this.add(MuteKind::Mute, window, cx);
+                                        })),
+                                )
+                                .child(
+                                    Button::new("block")
+                                        .label("Block")
+                                        .warning()
+                                        .small()
+                                        .on_click(cx.listener(move |this, _, window, cx| {
+                                            this.add(MuteKind::Block, window, cx);
+                                        })),
+                                ),
+                        )
+                        .when_some(self.error.as_ref(), |this, error| {
+                            this.child(
+                                div()
+                                    .italic()
+                                    .text_xs()
+                                    .text_color(cx.theme().text_danger)
+                                    .child(error.clone()),
+                            )
+                        }),
+                )
+                .map(|this| {
+                    if is_empty {
+                        this.child(self.render_empty(window, cx))
+                    } else {
+                        this.child(
+                            v_flex()
+                                .gap_1()
+                                .flex_1()
+                                .w_full()
+                                .children(self.render_list_items(cx)),
+                        )
+                    }
+                }),
+        )
+    }
+}
@@ -1,29 +1,96 @@
-use std::str::FromStr;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::time::Duration;
 
-use anyhow::{Context as AnyhowContext, Error};
+use anyhow::{Context as AnyhowContext, Error, anyhow};
+use common::DebouncedDelay;
+use gpui::prelude::FluentBuilder;
 use gpui::{
     AnyElement, App, AppContext, ClipboardItem, Context, Entity, EventEmitter, FocusHandle,
-    Focusable, IntoElement, ParentElement, PathPromptOptions, Render, SharedString, Styled, Task,
-    Window, div,
+    Focusable, IntoElement, ObjectFit, ParentElement, PathPromptOptions, Render, SharedString,
+    Styled, StyledImage, Subscription, Task, Window, div, img, relative,
 };
 use nostr_sdk::prelude::*;
-use person::{Person, PersonRegistry, shorten_pubkey};
+use person::{Person, PersonRegistry, crop_picture, shorten_pubkey};
 use settings::AppSettings;
-use state::{NostrRegistry, upload};
+use smallvec::{SmallVec, smallvec};
+use state::{NostrAddress, NostrRegistry, UploadProgress, upload};
 use theme::ActiveTheme;
 use ui::avatar::Avatar;
 use ui::button::{Button, ButtonVariants};
 use ui::dock::{Panel, PanelEvent};
-use ui::input::{InputState, TextInput};
+use ui::input::{InputEvent, InputState, TextInput};
 use ui::notification::Notification;
+use ui::switch::Switch;
 use ui::{Disableable, IconName, Sizable, StyledExt, WindowExtension, h_flex, v_flex};
 
+use crate::dialogs::screening::status_badge;
+use crate::sidebar::entry::RoomEntry;
+
+/// Delay before verifying the NIP-05 address, in milliseconds
+const NIP05_DELAY: u64 = 500;
+
 pub fn init(public_key: PublicKey, window: &mut Window, cx: &mut App) -> Entity<ProfilePanel> {
     cx.new(|cx| ProfilePanel::new(public_key, window, cx))
 }
 
-#[derive(Debug)]
+/// Image of the profile uploaded to the file server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ProfileImage {
+    Avatar,
+    Banner,
+}
+
+impl ProfileImage {
+    /// Size the image is cropped to before uploading
+    fn size(&self) -> (u32, u32) {
+        match self {
+            Self::Avatar => (400, 400),
+            Self::Banner => (1500, 500),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Avatar => "avatar",
+            Self::Banner => "banner",
+        }
+    }
+}
+
+/// Cropped image saved before uploading it.
+///
+/// The file is deleted once the upload is done, or cancelled by dropping the task.
+struct CroppedImage(PathBuf);
+
+impl CroppedImage {
+    /// Get a unique path in the temporary directory
+    fn new(image: ProfileImage, extension: &str) -> Self {
+        let name = format!("coop-{}-{}.{extension}", image.name(), Timestamp::now());
+        Self(std::env::temp_dir().join(name))
+    }
+}
+
+impl Drop for CroppedImage {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            log::warn!("Failed to remove cropped image {}: {e}", self.0.display());
+        }
+    }
+}
+
+/// Verification state of the NIP-05 address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Nip05Status {
+    Empty,
+    Verifying,
+    Verified,
+    Mismatch,
+    Invalid,
+}
+
 pub struct ProfilePanel {
     name: SharedString,
     focus_handle: FocusHandle,
@@ -31,20 +98,41 @@ pub struct ProfilePanel {
     /// User's public key
     public_key: PublicKey,
 
-    /// User's name text input
-    name_input: Entity<InputState>,
+    /// User's display name text input
+    display_name_input: Entity<InputState>,
+
+    /// User's username text input
+    username_input: Entity<InputState>,
 
     /// User's avatar url text input
     avatar_input: Entity<InputState>,
 
+    /// User's banner url text input
+    banner_input: Entity<InputState>,
+
     /// User's bio multi line input
     bio_input: Entity<InputState>,
 
     /// User's website url text input
     website_input: Entity<InputState>,
 
-    /// Uploading state
-    uploading: bool,
+    /// User's NIP-05 address text input
+    nip05_input: Entity<InputState>,
+
+    /// User's lightning address text input
+    lud16_input: Entity<InputState>,
+
+    /// Whether the account is a bot
+    bot: bool,
+
+    /// NIP-05 address verification state
+    nip05_status: Nip05Status,
+
+    /// Debouncer for the NIP-05 address verification
+    nip05_debouncer: DebouncedDelay<Self>,
+
+    /// Images being uploaded
+    uploading: HashSet<ProfileImage>,
 
     /// Copied states
     copied: bool,
@@ -54,13 +142,20 @@ pub struct ProfilePanel {
 
     /// Tasks
     tasks: Vec<Task<Result<(), Error>>>,
+
+    /// Event subscriptions
+    _subscriptions: SmallVec<[Subscription; 1]>,
 }
 
 impl ProfilePanel {
     fn new(public_key: PublicKey, window: &mut Window, cx: &mut Context<Self>) -> Self {
-        let name_input = cx.new(|cx| InputState::new(window, cx).placeholder("Alice"));
+        let display_name_input = cx.new(|cx| InputState::new(window, cx).placeholder("Alice"));
+        let username_input = cx.new(|cx| InputState::new(window, cx).placeholder("alice"));
         let website_input = cx.new(|cx| InputState::new(window, cx).placeholder("alice.me"));
         let avatar_input = cx.new(|cx| InputState::new(window, cx).placeholder("alice.me/a.jpg"));
+        let banner_input = cx.new(|cx| InputState::new(window, cx).placeholder("alice.me/b.jpg"));
+        let nip05_input = cx.new(|cx| InputState::new(window, cx).placeholder("alice@alice.me"));
+        let lud16_input = cx.new(|cx| InputState::new(window, cx).placeholder("alice@getalby.com"));
 
         // Use multi-line input for bio
         let bio_input = cx.new(|cx| {
@@ -70,6 +165,20 @@ impl ProfilePanel {
                 .placeholder("A short introduce about you.")
         });
 
+        let mut subscriptions = smallvec![];
+
+        subscriptions.push(
+            // Verify the NIP-05 address once the user stops typing
+            cx.subscribe_in(&nip05_input, window, |this, _state, event, window, cx| {
+                if let InputEvent::Change = event {
+                    let delay = Duration::from_millis(NIP05_DELAY);
+
+                    this.nip05_debouncer
+                        .fire_new(delay, window, cx, |this, _window, cx| this.verify_nip05(cx));
+                }
+            }),
+        );
+
         // Get user's profile and update inputs
         cx.defer_in(window, move |this, window, cx| {
             this.set_profile(window, cx);
@@ -79,14 +188,22 @@ impl ProfilePanel {
             name: "Update Profile".into(),
             focus_handle: cx.focus_handle(),
             public_key,
-            name_input,
+            display_name_input,
+            username_input,
             avatar_input,
+            banner_input,
             bio_input,
             website_input,
-            uploading: false,
+            nip05_input,
+            lud16_input,
+            bot: false,
+            nip05_status: Nip05Status::Empty,
+            nip05_debouncer: DebouncedDelay::new(),
+            uploading: HashSet::new(),
             copied: false,
             updating: false,
             tasks: vec![],
+            _subscriptions: subscriptions,
         }
     }
 
@@ -95,29 +212,71 @@ impl ProfilePanel {
         let profile = persons.read(cx).get(&self.public_key, cx);
         let metadata = profile.metadata();
 
-        self.avatar_input.update(cx, |this, cx| {
-            if let Some(avatar) = metadata.picture.as_ref() {
-                this.set_value(avatar, window, cx);
-            }
-        });
+        let fields = [
+            (&self.display_name_input, metadata.display_name.as_ref()),
+            (&self.username_input, metadata.name.as_ref()),
+            (&self.avatar_input, metadata.picture.as_ref()),
+            (&self.banner_input, metadata.banner.as_ref()),
+            (&self.bio_input, metadata.about.as_ref()),
+            (&self.website_input, metadata.website.as_ref()),
+            (&self.nip05_input, metadata.nip05.as_ref()),
+            (&self.lud16_input, metadata.lud16.as_ref()),
+        ];
+
+        for (input, value) in fields.into_iter() {
+            input.update(cx, |this, cx| {
+                if let Some(value) = value {
+                    this.set_value(value, window, cx);
+                }
+            });
+        }
 
-        self.bio_input.update(cx, |this, cx| {
-            if let Some(bio) = metadata.about.as_ref() {
-                this.set_value(bio, window, cx);
-            }
-        });
+        self.bot = metadata
+            .custom
+            .get("bot")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
 
-        self.name_input.update(cx, |this, cx| {
-            if let Some(display_name) = metadata.display_name.as_ref() {
-                this.set_value(display_name, window, cx);
-            }
-        });
+        cx.notify();
+    }
 
-        self.website_input.update(cx, |this, cx| {
-            if let Some(website) = metadata.website.as_ref() {
-                this.set_value(website, window, cx);
-            }
-        });
+    fn verify_nip05(&mut self, cx: &mut Context<Self>) -> Task<()> {
+        let http_client = cx.http_client();
+        let public_key = self.public_key;
+        let value = self.nip05_input.read(cx).value();
+
+        if value.trim().is_empty() {
+            self.set_nip05_status(Nip05Status::Empty, cx);
+            return Task::ready(());
+        }
+
+        let Ok(address) = Nip05Address::parse(value.trim()) else {
+            self.set_nip05_status(Nip05Status::Invalid, cx);
+            return Task::ready(());
+        };
+
+        let task: Task<Result<bool, Error>> =
+            cx.background_spawn(async move { address.verify(&http_client, &public_key).await });
+
+        self.set_nip05_status(Nip05Status::Verifying, cx);
+
+        cx.spawn(async move |this, cx| {
+            let status = match task.await {
+                Ok(true) => Nip05Status::Verified,
+                Ok(false) => Nip05Status::Mismatch,
+                Err(_) => Nip05Status::Invalid,
+            };
+
+            this.update(cx, |this, cx| {
+                this.set_nip05_status(status, cx);
+            })
+            .ok();
+        })
+    }
+
+    fn set_nip05_status(&mut self, status: Nip05Status, cx: &mut Context<Self>) {
+        self.nip05_status = status;
+        cx.notify();
     }
 
     fn copy(&mut self, value: String, window: &mut Window, cx: &mut Context<Self>) {
@@ -148,14 +307,26 @@ impl ProfilePanel {
         }
     }
 
-    fn set_uploading(&mut self, status: bool, cx: &mut Context<Self>) {
-        self.uploading = status;
+    fn set_uploading(&mut self, image: ProfileImage, status: bool, cx: &mut Context<Self>) {
+        if status {
+            self.uploading.insert(image);
+        } else {
+            self.uploading.remove(&image);
+        }
         cx.notify();
     }
 
-    fn upload(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        // Get the user's configured blossom server
-        let server = AppSettings::get_file_server(cx);
+    fn image_input(&self, image: ProfileImage) -> &Entity<InputState> {
+        match image {
+            ProfileImage::Avatar => &self.avatar_input,
+            ProfileImage::Banner => &self.banner_input,
+        }
+    }
+
+    fn upload(&mut self, image: ProfileImage, window: &mut Window, cx: &mut Context<Self>) {
+        // Get the user's configured blossom servers
+        let servers = AppSettings::get_file_servers(cx);
+        let http_client = cx.http_client();
 
         // Ask user for file upload
         let path = cx.prompt_for_paths(PathPromptOptions {
@@ -166,26 +337,50 @@ impl ProfilePanel {
         });
 
         self.tasks.push(cx.spawn_in(window, async move |this, cx| {
+            let mut paths = path.await??.context("Not found")?;
+            let path = paths.pop().context("No path")?;
+
             this.update(cx, |this, cx| {
-                this.set_uploading(true, cx);
+                this.set_uploading(image, true, cx);
             })?;
 
-            let mut paths = path.await??.context("Not found")?;
-            let path = paths.pop().context("No path")?;
+            // Crop the image before uploading it
+            let cropped: Result<_, Error> = cx
+                .background_executor()
+                .spawn(async move {
+                    let bytes = smol::fs::read(&path).await?;
+                    let (width, height) = image.size();
+                    let (data, format) = crop_picture(&bytes, width, height)?;
+                    let extension = format.extensions_str().first().context("Unknown format")?;
 
-            // Upload via blossom client
-            match upload(server, path, cx).await {
+                    let cropped = CroppedImage::new(image, extension);
+                    smol::fs::write(&cropped.0, data).await?;
+
+                    Ok(cropped)
+                })
+                .await;
+
+            // Upload via blossom client, the cropped image is deleted afterwards
+            let result = match cropped {
+                Ok(cropped) => {
+                    let path = cropped.0.clone();
+                    upload(servers, path, UploadProgress::default(), http_client, cx).await
+                }
+                Err(e) => Err(anyhow!("Failed to crop the image: {e}")),
+            };
+
+            match result {
                 Ok(url) => {
                     this.update_in(cx, |this, window, cx| {
-                        this.avatar_input.update(cx, |this, cx| {
+                        this.image_input(image).update(cx, |this, cx| {
                             this.set_value(url.to_string(), window, cx);
                         });
-                        this.set_uploading(false, cx);
+                        this.set_uploading(image, false, cx);
                     })?;
                 }
                 Err(e) => {
                     this.update_in(cx, |this, window, cx| {
-                        this.set_uploading(false, cx);
+                        this.set_uploading(image, false, cx);
                         window.push_notification(
                             Notification::error(e.to_string()).autohide(false),
                             cx,
@@ -203,6 +398,31 @@ impl ProfilePanel {
         cx.notify();
     }
 
+    /// Construct the metadata from the inputs
+    fn metadata(&self, cx: &App) -> Metadata {
+        let persons = PersonRegistry::global(cx);
+        let old = persons.read(cx).get(&self.public_key, cx).metadata();
+        let value = |input: &Entity<InputState>| input.read(cx).value().trim().to_string();
+
+        let mut metadata = old.clone();
+        metadata.display_name = text_value(value(&self.display_name_input));
+        metadata.name = text_value(value(&self.username_input));
+        metadata.about = text_value(value(&self.bio_input));
+        metadata.nip05 = text_value(value(&self.nip05_input));
+        metadata.lud16 = text_value(value(&self.lud16_input));
+        metadata.picture = url_value(value(&self.avatar_input), old.picture);
+        metadata.banner = url_value(value(&self.banner_input), old.banner);
+        metadata.website = url_value(value(&self.website_input), old.website);
+
+        if self.bot {
+            metadata.custom.insert("bot".into(), Value::Bool(true));
+        } else {
+            metadata.custom.remove("bot");
+        }
+
+        metadata
+    }
+
     /// Set the metadata for the current user
     fn publish(&self, metadata: &Metadata, cx: &App) -> Task<Result<(), Error>> {
         let nostr = NostrRegistry::global(cx);
@@ -224,29 +444,9 @@ impl ProfilePanel {
     fn update(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         let persons = PersonRegistry::global(cx);
         let public_key = self.public_key;
-        let old_metadata = persons.read(cx).get(&public_key, cx).metadata();
-
-        // Extract all new metadata fields
-        let avatar = self.avatar_input.read(cx).value();
-        let name = self.name_input.read(cx).value();
-        let bio = self.bio_input.read(cx).value();
-        let website = self.website_input.read(cx).value();
 
         // Construct the new metadata
-        let mut new_metadata = old_metadata
-            .display_name(name.as_ref())
-            .name(name.as_ref())
-            .about(bio.as_ref());
-
-        // Verify the avatar URL before adding it
-        if let Ok(url) = Url::from_str(&avatar) {
-            new_metadata = new_metadata.picture(url);
-        }
-
-        // Verify the website URL before adding it
-        if let Ok(url) = Url::from_str(&website) {
-            new_metadata = new_metadata.website(url);
-        }
+        let new_metadata = self.metadata(cx);
 
         // Set the metadata
         let task = self.publish(&new_metadata, cx);
@@ -271,7 +471,8 @@ impl ProfilePanel {
                     })?;
                 }
                 Err(e) => {
-                    cx.update(|window, cx| {
+                    this.update_in(cx, |this, window, cx| {
+                        this.set_updating(false, cx);
                         window.push_notification(
                             Notification::error(e.to_string()).autohide(false),
                             cx,
@@ -283,6 +484,148 @@ impl ProfilePanel {
             Ok(())
         }));
     }
+
+    fn render_preview(&self, cx: &Context<Self>) -> impl IntoElement {
+        const NIP05_MATCH: &str = "The address matches your public key.";
+        const NIP05_NOT_MATCH: &str = "The address does not match your public key.";
+        const NIP05_INVALID: &str = "The address could not be verified.";
+        const NIP05_VERIFYING: &str = "Verifying the address...";
+        const NO_NIP05: &str = "You have not set up a friendly address.";
+
+        let metadata = self.metadata(cx);
+        let profile = Person::new(self.public_key, metadata.clone());
+        let name = profile.name();
+
+        // Show the picture as entered, it's resized once the profile is published
+        let avatar = metadata
+            .picture
+            .clone()
+            .map(SharedString::from)
+            .unwrap_or_else(|| "brand/avatar.png".into());
+
+        let (status, message) = match self.nip05_status {
+            Nip05Status::Empty => (Some(false), NO_NIP05),
+            Nip05Status::Verifying => (None, NIP05_VERIFYING),
+            Nip05Status::Verified => (Some(true), NIP05_MATCH),
+            Nip05Status::Mismatch => (Some(false), NIP05_NOT_MATCH),
+            Nip05Status::Invalid => (Some(false), NIP05_INVALID),
+        };
+
+        v_flex()
+            .gap_3()
+            .pb_3()
+            .rounded(cx.theme().radius)
+            .overflow_hidden()
+            .bg(cx.theme().elevated_surface_background)
+            .child(
+                div()
+                    .h_24()
+                    .w_full()
+                    .bg(cx.theme().surface_background)
+                    .when_some(metadata.banner.clone(), |this, banner| {
+                        this.child(img(banner).size_full().object_fit(ObjectFit::Cover))
+                    }),
+            )
+            .child(
+                v_flex()
+                    .gap_3()
+                    .items_center()
+                    .justify_center()
+                    .text_center()
+                    .child(Avatar::new(avatar.clone()).large())
+                    .child(
+                        div()
+                            .font_semibold()
+                            .line_height(relative(1.25))
+                            .child(name.clone()),
+                    ),
+            )
+            .child(
+                v_flex()
+                    .px_3()
+                    .gap_2()
+                    .child(
+                        h_flex()
+                            .items_start()
+                            .gap_2()
+                            .text_sm()
+                            .child(status_badge(status, cx))
+                            .child(
+                                v_flex()
+                                    .text_sm()
+                                    .child(SharedString::from("Friendly Address (NIP-05)"))
+                                    .child(
+                                        div()
+                                            .line_clamp(1)
+                                            .text_color(cx.theme().text_muted)
+                                            .child(SharedString::from(message)),
+                                    ),
+                            ),
+                    )
+                    .when_some(metadata.lud16.clone(), |this, lud16| {
+                        this.child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().text_muted)
+                                .child(SharedString::from(format!("Lightning: {lud16}"))),
+                        )
+                    })
+                    .child(
+                        RoomEntry::new(0)
+                            .public_key(self.public_key)
+                            .name(name)
+                            .avatar(avatar),
+                    ),
+            )
+    }
+
+    fn render_field(
+        &self,
+        label: &'static str,
+        input: &Entity<InputState>,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        v_flex()
+            .gap_1p5()
+            .child(
+                div()
+                    .text_sm()
+                    .text_color(cx.theme().text_muted)
+                    .child(SharedString::from(label)),
+            )
+            .child(TextInput::new(input).bordered(false).small())
+    }
+
+    fn render_upload(&self, image: ProfileImage, cx: &Context<Self>) -> impl IntoElement {
+        let uploading = self.uploading.contains(&image);
+
+        Button::new(image.name())
+            .icon(IconName::PlusCircle)
+            .label(format!("Upload {}", image.name()))
+            .xsmall()
+            .ghost()
+            .rounded()
+            .disabled(uploading)
+            .loading(uploading)
+            .on_click(cx.listener(move |this, _, window, cx| {
+                this.upload(image, window, cx);
+            }))
+    }
+}
+
+/// Get the value of a text input, an empty input clears the field
+fn text_value(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// Get the value of a URL input, an empty input clears the field
+fn url_value(value: String, old: Option<String>) -> Option<String> {
+    if value.is_empty() {
+        return None;
+    }
+
+    // Keep the old value if the new one is not a valid URL
+    Url::parse(&value).map(|url| url.to_string()).ok().or(old)
 }
 
 impl Panel for ProfilePanel {
@@ -305,15 +648,6 @@ impl Focusable for ProfilePanel {
 
 impl Render for ProfilePanel {
     fn render(&mut self, _window: &mut gpui::Window, cx: &mut Context<Self>) -> impl IntoElement {
-        let avatar_input = self.avatar_input.read(cx).value();
-
-        // Get the avatar
-        let avatar = if avatar_input.is_empty() {
-            "brand/avatar.png"
-        } else {
-            avatar_input.as_str()
-        };
-
         // Get the public key as short string
         let shorten_pkey = SharedString::from(shorten_pubkey(self.public_key, 8));
 
@@ -321,60 +655,31 @@ impl Render for ProfilePanel {
             .p_3()
             .gap_3()
             .w_full()
+            .child(self.render_preview(cx))
             .child(
-                v_flex()
-                    .h_40()
-                    .w_full()
-                    .items_center()
+                h_flex()
+                    .gap_2()
                     .justify_center()
-                    .gap_4()
-                    .child(Avatar::new(avatar).large())
-                    .child(
-                        Button::new("upload")
-                            .icon(IconName::PlusCircle)
-                            .label("Add an avatar")
-                            .xsmall()
-                            .ghost()
-                            .rounded()
-                            .disabled(self.uploading)
-                            .loading(self.uploading)
-                            .on_click(cx.listener(move |this, _, window, cx| {
-                                this.upload(window, cx);
-                            })),
-                    ),
+                    .child(self.render_upload(ProfileImage::Avatar, cx))
+                    .child(self.render_upload(ProfileImage::Banner, cx)),
             )
+            .child(self.render_field("What should people call you?", &self.display_name_input, cx))
+            .child(self.render_field("Username:", &self.username_input, cx))
+            .child(self.render_field("A short introduction about you:", &self.bio_input, cx))
+            .child(self.render_field("Avatar URL:", &self.avatar_input, cx))
+            .child(self.render_field("Banner URL:", &self.banner_input, cx))
+            .child(self.render_field("Website:", &self.website_input, cx))
+            .child(self.render_field("Friendly address (NIP-05):", &self.nip05_input, cx))
+            .child(self.render_field("Lightning address:", &self.lud16_input, cx))
             .child(
-                v_flex()
-                    .gap_1p5()
-                    .child(
-                        div()
-                            .text_sm()
-                            .text_color(cx.theme().text_muted)
-                            .child(SharedString::from("What should people call you?")),
-                    )
-                    .child(TextInput::new(&self.name_input).bordered(false).small()),
-            )
-            .child(
-                v_flex()
-                    .gap_1p5()
-                    .child(
-                        div()
-                            .text_sm()
-                            .text_color(cx.theme().text_muted)
-                            .child(SharedString::from("A short introduction about you:")),
-                    )
-                    .child(TextInput::new(&self.bio_input).bordered(false).small()),
-            )
-            .child(
-                v_flex()
-                    .gap_1p5()
-                    .child(
-                        div()
-                            .text_sm()
-                            .text_color(cx.theme().text_muted)
-                            .child(SharedString::from("Website:")),
-                    )
-                    .child(TextInput::new(&self.website_input).bordered(false).small()),
+                Switch::new("bot")
+                    .label("Bot account")
+                    .description("Let others know this account is automated.")
+                    .checked(self.bot)
+                    .on_click(cx.listener(move |this, _checked, _window, cx| {
+                        this.bot = !this.bot;
+                        cx.notify();
+                    })),
             )
             .child(
                 v_flex()
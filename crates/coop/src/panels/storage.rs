@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+
+use anyhow::Error;
+use chat::ChatRegistry;
+use common::format_size;
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AnyElement, App, AppContext, Context, Entity, EventEmitter, FocusHandle, Focusable,
+    IntoElement, ParentElement, Render, SharedString, Styled, Task, Window, div,
+};
+use nostr_sdk::prelude::*;
+use state::{NostrRegistry, PrunePolicy, StorageUsage, prune, request_compaction, storage_usage};
+use theme::ActiveTheme;
+use ui::button::{Button, ButtonVariants};
+use ui::dock::{Panel, PanelEvent};
+use ui::{Disableable, IconName, Sizable, StyledExt, divider, h_flex, v_flex};
+
+const MSG: &str = "Coop keeps profiles, relay lists and decrypted messages in a local database. \
+                   Pruned events are fetched again when needed.";
+
+pub fn init(window: &mut Window, cx: &mut App) -> Entity<StoragePanel> {
+    cx.new(|cx| StoragePanel::new(window, cx))
+}
+
+#[derive(Debug)]
+pub struct StoragePanel {
+    name: SharedString,
+    focus_handle: FocusHandle,
+
+    /// Database usage
+    usage: Option<StorageUsage>,
+
+    /// Whether the panel is running a task
+    running: bool,
+
+    /// Result of the last task
+    message: Option<SharedString>,
+
+    /// Number of events deleted by each policy
+    pruned: HashMap<&'static str, usize>,
+
+    /// Background tasks
+    tasks: Vec<Task<Result<(), Error>>>,
+}
+
+impl StoragePanel {
+    fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        // Run at the end of current cycle
+        cx.defer_in(window, |this, _window, cx| {
+            this.load(cx);
+        });
+
+        Self {
+            name: "Storage".into(),
+            focus_handle: cx.focus_handle(),
+            usage: None,
+            running: false,
+            message: None,
+            pruned: HashMap::new(),
+            tasks: vec![],
+        }
+    }
+
+    fn load(&mut self, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task = cx.background_spawn(async move { storage_usage(&client).await });
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let usage = task.await?;
+
+            this.update(cx, |this, cx| {
+                this.usage = Some(usage);
+                cx.notify();
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn set_running(&mut self, running: bool, cx: &mut Context<Self>) {
+        self.running = running;
+        cx.notify();
+    }
+
+    fn prune(&mut self, policy: PrunePolicy, cx: &mut Context<Self>) {
+        let nostr = NostrRegistry::global(cx);
+        let client = nostr.read(cx).client();
+
+        let task = cx.background_spawn(async move { prune(&client, policy).await });
+
+        self.set_running(true, cx);
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let result = task.await;
+
+            this.update(cx, |this, cx| {
+                match result {
+                    Ok(count) => {
+                        this.pruned.insert(policy.label(), count);
+                        this.message = None;
+                    }
+                    Err(e) => {
+                        this.message = Some(e.to_string().into());
+                    }
+                }
+                this.set_running(false, cx);
+                this.load(cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn rebuild(&mut self, cx: &mut Context<Self>) {
+        let chat = ChatRegistry::global(cx);
+        let task = chat.update(cx, |this, cx| this.rebuild_rumor_cache(cx));
+
+        self.set_running(true, cx);
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let result = task.await;
+
+            this.update(cx, |this, cx| {
+                this.message = Some(match result {
+                    Ok(count) => format!("Rebuilt the cache for {count} messages").into(),
+                    Err(e) => e.to_string().into(),
+                });
+                this.set_running(false, cx);
+                this.load(cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn compact(&mut self, cx: &mut Context<Self>) {
+        let task = cx.background_spawn(async move { request_compaction() });
+
+        self.set_running(true, cx);
+
+        self.tasks.push(cx.spawn(async move |this, cx| {
+            let result = task.await;
+
+            this.update(cx, |this, cx| {
+                this.message = Some(match result {
+                    Ok(_) => "The database will be compacted the next time Coop starts".into(),
+                    Err(e) => e.to_string().into(),
+                });
+                this.set_running(false, cx);
+            })?;
+
+            Ok(())
+        }));
+    }
+
+    fn render_usage(&self, usage: &StorageUsage, cx: &Context<Self>) -> impl IntoElement {
+        v_flex()
+            .gap_1()
+            .child(
+                h_flex()
+                    .justify_between()
+                    .text_sm()
+                    .font_semibold()
+                    .child(SharedString::from("Database size"))
+                    .child(SharedString::from(format_size(usage.disk_size))),
+            )
+            .children(usage.kinds.iter().map(|usage| {
+                h_flex()
+                    .h_7()
+                    .px_2()
+                    .gap_2()
+                    .justify_between()
+                    .rounded(cx.theme().radius)
+                    .bg(cx.theme().elevated_surface_background)
+                    .text_xs()
+                    .child(div().flex_1().child(kind_name(usage.kind)))
+                    .child(
+                        div()
+                            .text_color(cx.theme().text_muted)
+                            .child(SharedString::from(format!("{} events", usage.count))),
+                    )
+                    .child(
+                        div()
+                            .w_16()
+                            .text_right()
+                            .child(SharedString::from(format_size(usage.size))),
+                    )
+            }))
+            .when(usage.others > 0, |this| {
+                this.child(
+                    h_flex()
+                        .h_7()
+                        .px_2()
+                        .gap_2()
+                        .justify_between()
+                        .rounded(cx.theme().radius)
+                        .bg(cx.theme().elevated_surface_background)
+                        .text_xs()
+                        .child(div().flex_1().child(SharedString::from("Other events")))
+                        .child(
+                            div()
+                                .text_color(cx.theme().text_muted)
+                                .child(SharedString::from(format!("{} events", usage.others))),
+                        )
+                        .child(div().w_16()),
+                )
+            })
+    }
+
+    fn render_policies(&self, cx: &Context<Self>) -> impl IntoElement {
+        v_flex().gap_1().children(
+            PrunePolicy::ALL
+                .into_iter()
+                .enumerate()
+                .map(|(ix, policy)| {
+                    h_flex()
+                        .gap_2()
+                        .justify_between()
+                        .text_xs()
+                        .child(
+                            v_flex()
+                                .child(SharedString::from(policy.label()))
+                                .when_some(self.pruned.get(policy.label()), |this, count| {
+                                    this.child(div().text_color(cx.theme().text_muted).child(
+                                        SharedString::from(format!("Deleted {count} events")),
+                                    ))
+                                }),
+                        )
+                        .child(
+                            Button::new(format!("prune-{ix}"))
+                                .label("Prune")
+                                .ghost()
+                                .xsmall()
+                                .disabled(self.running)
+                                .on_click(cx.listener(move |this, _ev, _window, cx| {
+                                    this.prune(policy, cx);
+                                })),
+                        )
+                }),
+        )
+    }
+}
+
+/// Get a readable name for the kinds stored by Coop
+fn kind_name(kind: Kind) -> SharedString {
+    let name = match kind {
+        Kind::Metadata => "Profiles",
+        Kind::ContactList => "Contact lists",
+        Kind::RelayList => "Relay lists",
+        Kind::InboxRelays => "Messaging relay lists",
+        Kind::GiftWrap => "Gift wrapped messages",
+        Kind::ApplicationSpecificData => "Decrypted messages and app data",
+        Kind::Custom(5315) | Kind::Custom(6315) | Kind::Custom(7000) => "Web of Trust search",
+        kind => return SharedString::from(format!("Kind {}", kind.as_u16())),
+    };
+
+    SharedString::from(name)
+}
+
+impl Panel for StoragePanel {
+    fn panel_id(&self) -> SharedString {
+        self.name.clone()
+    }
+
+    fn title(&self, _cx: &App) -> AnyElement {
+        self.name.clone().into_any_element()
+    }
+}
+
+impl EventEmitter<PanelEvent> for StoragePanel {}
+
+impl Focusable for StoragePanel {
+    fn focus_handle(&self, _: &App) -> gpui::FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for StoragePanel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .p_3()
+            .gap_3()
+            .w_full()
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().text_muted)
+                    .child(SharedString::from(MSG)),
+            )
+            .child(divider(cx))
+            .map(|this| match self.usage.as_ref() {
+                Some(usage) => this.child(self.render_usage(usage, cx)),
+                None => this.child(
+                    div()
+                        .text_sm()
+                        .text_color(cx.theme().text_muted)
+                        .child(SharedString::from("Calculating...")),
+                ),
+            })
+            .child(divider(cx))
+            .child(
+                div()
+                    .text_xs()
+                    .font_semibold()
+                    .text_color(cx.theme().text_muted)
+                    .child(SharedString::from("Cleanup:")),
+            )
+            .child(self.render_policies(cx))
+            .child(
+                Button::new("rebuild")
+                    .icon(IconName::Refresh)
+                    .label("Rebuild message cache")
+                    .small()
+                    .loading(self.running)
+                    .disabled(self.running)
+                    .on_click(cx.listener(move |this, _ev, _window, cx| {
+                        this.rebuild(cx);
+                    })),
+            )
+            .child(
+                Button::new("compact")
+                    .icon(IconName::Archive)
+                    .label("Compact database")
+                    .small()
+                    .loading(self.running)
+                    .disabled(self.running)
+                    .on_click(cx.listener(move |this, _ev, _window, cx| {
+                        this.compact(cx);
+                    })),
+            )
+            .when_some(self.message.as_ref(), |this, message| {
+                this.child(
+                    div()
+                        .italic()
+                        .text_xs()
+                        .text_color(cx.theme().text_muted)
+                        .child(message.clone()),
+                )
+            })
+    }
+}
@@ -1,14 +1,15 @@
-use chat::ChatRegistry;
+use chat::{ChatRegistry, FailedMessage};
 use gpui::{
     AnyElement, App, AppContext, ClipboardItem, Context, Entity, EventEmitter, FocusHandle,
     Focusable, InteractiveElement, IntoElement, ListAlignment, ListState, ParentElement, Render,
-    SharedString, Styled, Window, div, list, px, relative,
+    SharedString, Styled, Subscription, Window, div, list, px, relative,
 };
+use nostr_sdk::prelude::*;
 use theme::ActiveTheme;
 use ui::button::{Button, ButtonVariants};
 use ui::dock::{Panel, PanelEvent};
 use ui::scroll::Scrollbar;
-use ui::{Icon, IconName, Sizable, h_flex, v_flex};
+use ui::{Icon, IconName, Sizable, StyledExt, h_flex, v_flex};
 
 pub fn init(window: &mut Window, cx: &mut App) -> Entity<TrashPanel> {
     cx.new(|cx| TrashPanel::new(window, cx))
@@ -20,41 +21,73 @@ pub struct TrashPanel {
 
     /// List state for messages
     list_state: ListState,
+
+    /// Subscriptions
+    _subscriptions: Vec<Subscription>,
 }
 
 impl TrashPanel {
-    fn new(_window: &mut Window, cx: &mut App) -> Self {
+    fn new(_window: &mut Window, cx: &mut Context<Self>) -> Self {
         let chat = ChatRegistry::global(cx);
-        let count = chat.read(cx).count_trash_messages(cx);
+        let trashes = chat.read(cx).trashes();
+        let count = trashes.read(cx).len();
         let list_state = ListState::new(count, ListAlignment::Bottom, px(1024.));
 
+        let subscriptions = vec![
+            // Update the list when trashes are added, recovered or discarded
+            cx.observe(&trashes, |this, trashes, cx| {
+                this.list_state.reset(trashes.read(cx).len());
+                cx.notify();
+            }),
+        ];
+
         Self {
             name: "Trash".into(),
             focus_handle: cx.focus_handle(),
             list_state,
+            _subscriptions: subscriptions,
         }
     }
 
-    fn copy(&self, ix: usize, cx: &App) {
+    fn message(&self, ix: usize, cx: &App) -> Option<FailedMessage> {
         let chat = ChatRegistry::global(cx);
         let trashes = chat.read(cx).trashes();
 
-        if let Some(message) = trashes.read(cx).iter().nth(ix) {
-            let item = ClipboardItem::new_string(message.raw_event.to_string());
+        trashes.read(cx).values().nth(ix).cloned()
+    }
+
+    fn copy(&self, ix: usize, cx: &App) {
+        if let Some(message) = self.message(ix, cx) {
+            let item = ClipboardItem::new_string(message.gift_wrap.as_json());
             cx.write_to_clipboard(item);
         }
     }
 
+    fn retry(&self, id: EventId, cx: &mut App) {
+        let chat = ChatRegistry::global(cx);
+
+        chat.update(cx, |this, cx| {
+            this.retry_trashes(Some(vec![id]), cx);
+        });
+    }
+
+    fn discard(&self, id: EventId, cx: &mut App) {
+        let chat = ChatRegistry::global(cx);
+
+        chat.update(cx, |this, cx| {
+            this.discard_trash(id, cx);
+        });
+    }
+
     fn render_list_item(
         &mut self,
         ix: usize,
         _window: &mut Window,
         cx: &mut Context<Self>,
     ) -> AnyElement {
-        let chat = ChatRegistry::global(cx);
-        let trashes = chat.read(cx).trashes();
+        if let Some(message) = self.message(ix, cx) {
+            let id = message.id();
 
-        if let Some(message) = trashes.read(cx).iter().nth(ix) {
             v_flex()
                 .id(ix)
                 .p_2()
@@ -67,10 +100,40 @@ impl TrashPanel {
                         .rounded(cx.theme().radius_lg)
                         .bg(cx.theme().surface_background)
                         .text_sm()
+                        .child(
+                            h_flex()
+                                .gap_2()
+                                .child(
+                                    div()
+                                        .flex_1()
+                                        .font_semibold()
+                                        .text_color(cx.theme().text_danger)
+                                        .child(message.kind.label()),
+                                )
+                                .child(
+                                    Button::new(format!("retry-{ix}"))
+                                        .label("Retry now")
+                                        .ghost()
+                                        .xsmall()
+                                        .on_click(cx.listener(move |this, _ev, _window, cx| {
+                                            this.retry(id, cx);
+                                        })),
+                                )
+                                .child(
+                                    Button::new(format!("discard-{ix}"))
+                                        .label("Discard")
+                                        .ghost()
+                                        .xsmall()
+                                        .on_click(cx.listener(move |this, _ev, _window, cx| {
+                                            this.discard(id, cx);
+                                        })),
+                                ),
+                        )
                         .child(
                             div()
-                                .text_color(cx.theme().text_danger)
-                                .child(message.reason.clone()),
+                                .text_xs()
+                                .text_color(cx.theme().text_muted)
+                                .child(SharedString::from(message.reason.clone())),
                         )
                         .child(
                             h_flex()
@@ -88,7 +151,7 @@ impl TrashPanel {
                                         .text_ellipsis()
                                         .text_xs()
                                         .line_height(relative(1.))
-                                        .child(message.raw_event.clone()),
+                                        .child(SharedString::from(message.gift_wrap.as_json())),
                                 )
                                 .child(
                                     Button::new(format!("copy-{ix}"))
@@ -1,29 +1,41 @@
 use std::rc::Rc;
 
-use chat::RoomKind;
+use chat::{ChatRegistry, RoomKind, SpamScore};
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    App, ClickEvent, InteractiveElement, IntoElement, ParentElement as _, RenderOnce, SharedString,
-    StatefulInteractiveElement, Styled, Window, div,
+    AnyElement, App, AppContext, ClickEvent, ImageSource, InteractiveElement, IntoElement,
+    ParentElement as _, RenderOnce, SharedString, StatefulInteractiveElement, Styled, Window, div,
 };
 use nostr_sdk::prelude::*;
-use settings::AppSettings;
+use settings::{AppSettings, RoomConfig};
 use theme::ActiveTheme;
 use ui::avatar::Avatar;
 use ui::dock::ClosePanel;
+use ui::input::{InputState, TextInput};
+use ui::menu::{ContextMenuExt, PopupMenu, PopupMenuItem};
 use ui::modal::ModalButtonProps;
-use ui::{Icon, IconName, Selectable, Sizable, StyledExt, WindowExtension, h_flex};
+use ui::tooltip::Tooltip;
+use ui::{Icon, IconName, Selectable, Sizable, StyledExt, WindowExtension, h_flex, v_flex};
 
 use crate::dialogs::screening;
 
+const DELETE_MSG: &str = "Messages of this conversation will be deleted from this device.";
+
+const DELETE_WARN: &str = "Your messaging relays will be asked to delete them too, \
+                           but relays may ignore the request.";
+
 #[derive(IntoElement)]
 pub struct RoomEntry {
     ix: usize,
     public_key: Option<PublicKey>,
     name: Option<SharedString>,
-    avatar: Option<SharedString>,
+    avatar: Option<ImageSource>,
     created_at: Option<SharedString>,
     kind: Option<RoomKind>,
+    room: Option<(u64, RoomConfig)>,
+    folders: Vec<SharedString>,
+    draft: bool,
+    spam_score: Option<SpamScore>,
     selected: bool,
     #[allow(clippy::type_complexity)]
     handler: Option<Rc<dyn Fn(&ClickEvent, &mut Window, &mut App)>>,
@@ -38,6 +50,10 @@ impl RoomEntry {
             avatar: None,
             created_at: None,
             kind: None,
+            room: None,
+            folders: vec![],
+            draft: false,
+            spam_score: None,
             handler: None,
             selected: false,
         }
@@ -53,7 +69,7 @@ impl RoomEntry {
         self
     }
 
-    pub fn avatar(mut self, avatar: impl Into<SharedString>) -> Self {
+    pub fn avatar(mut self, avatar: impl Into<ImageSource>) -> Self {
         self.avatar = Some(avatar.into());
         self
     }
@@ -68,6 +84,24 @@ impl RoomEntry {
         self
     }
 
+    /// Set the room's config, enables the context menu for organizing the room.
+    pub fn config(mut self, id: u64, config: &RoomConfig, folders: Vec<SharedString>) -> Self {
+        self.room = Some((id, config.clone()));
+        self.folders = folders;
+        self
+    }
+
+    pub fn draft(mut self, draft: bool) -> Self {
+        self.draft = draft;
+        self
+    }
+
+    /// Set the spam score of a chat request, shown with its breakdown on hover.
+    pub fn spam_score(mut self, score: Option<SpamScore>) -> Self {
+        self.spam_score = score;
+        self
+    }
+
     pub fn on_click(
         mut self,
         handler: impl Fn(&ClickEvent, &mut Window, &mut App) + 'static,
@@ -77,6 +111,132 @@ impl RoomEntry {
     }
 }
 
+/// Update a room's config through the chat registry
+fn update_config<F>(id: u64, f: F, cx: &mut App)
+where
+    F: FnOnce(&mut RoomConfig),
+{
+    ChatRegistry::global(cx).update(cx, |this, cx| {
+        this.update_room_config(id, f, cx);
+    });
+}
+
+/// Open a modal to move a room to a new folder
+fn new_folder(id: u64, window: &mut Window, cx: &mut App) {
+    let input = cx.new(|cx| InputState::new(window, cx).placeholder("Folder name"));
+
+    window.open_modal(cx, move |this, _window, _cx| {
+        let input = input.clone();
+
+        this.confirm()
+            .show_close(true)
+            .title(SharedString::from("New folder"))
+            .child(TextInput::new(&input).small())
+            .on_ok(move |_ev, _window, cx| {
+                let name = input.read(cx).value().trim().to_string();
+
+                if !name.is_empty() {
+                    update_config(id, |config| config.set_folder(Some(name)), cx);
+                }
+
+                // true to close modal
+                true
+            })
+    });
+}
+
+/// Open a modal to confirm deleting a room
+fn delete_room(id: u64, window: &mut Window, cx: &mut App) {
+    window.open_modal(cx, move |this, _window, cx| {
+        this.confirm()
+            .show_close(true)
+            .title(SharedString::from("Delete conversation"))
+            .child(
+                v_flex()
+                    .gap_1()
+                    .text_sm()
+                    .child(SharedString::from(DELETE_MSG))
+                    .child(
+                        div()
+                            .italic()
+                            .text_color(cx.theme().text_muted)
+                            .child(SharedString::from(DELETE_WARN)),
+                    ),
+            )
+            .on_ok(move |_ev, _window, cx| {
+                ChatRegistry::global(cx).update(cx, |this, cx| {
+                    this.delete_room(id, cx);
+                });
+                // true to close modal
+                true
+            })
+    });
+}
+
+/// Build the context menu for organizing a room
+fn room_menu(
+    id: u64,
+    config: &RoomConfig,
+    folders: &[SharedString],
+    mut menu: PopupMenu,
+) -> PopupMenu {
+    let pinned = config.pinned();
+    let archived = config.archived();
+    let current = config.folder().map(SharedString::from);
+
+    menu = menu
+        .item(
+            PopupMenuItem::new(if pinned { "Unpin" } else { "Pin" })
+                .icon(IconName::Pin)
+                .on_click(move |_ev, _window, cx| {
+                    update_config(id, |config| config.toggle_pinned(), cx);
+                }),
+        )
+        .item(
+            PopupMenuItem::new(if archived { "Unarchive" } else { "Archive" })
+                .icon(IconName::Archive)
+                .on_click(move |_ev, _window, cx| {
+                    update_config(id, |config| config.set_archived(!archived), cx);
+                }),
+        )
+        .separator();
+
+    for name in folders.iter().cloned() {
+        let checked = current.as_ref() == Some(&name);
+
+        menu = menu.item(PopupMenuItem::new(name.clone()).checked(checked).on_click(
+            move |_ev, _window, cx| {
+                let folder = (!checked).then(|| name.to_string());
+                update_config(id, |config| config.set_folder(folder), cx);
+            },
+        ));
+    }
+
+    menu = menu.item(
+        PopupMenuItem::new("New folder...")
+            .icon(IconName::Folder)
+            .on_click(move |_ev, window, cx| {
+                new_folder(id, window, cx);
+            }),
+    );
+
+    if current.is_some() {
+        menu = menu.item(PopupMenuItem::new("Remove from folder").on_click(
+            move |_ev, _window, cx| {
+                update_config(id, |config| config.set_folder(None), cx);
+            },
+        ));
+    }
+
+    menu.separator().item(
+        PopupMenuItem::new("Delete conversation")
+            .icon(IconName::CloseCircle)
+            .on_click(move |_ev, window, cx| {
+                delete_room(id, window, cx);
+            }),
+    )
+}
+
 impl Selectable for RoomEntry {
     fn selected(mut self, selected: bool) -> Self {
         self.selected = selected;
@@ -95,8 +255,12 @@ impl RenderOnce for RoomEntry {
 
         let public_key = self.public_key;
         let is_selected = self.is_selected();
+        let pinned = self
+            .room
+            .as_ref()
+            .is_some_and(|(_, config)| config.pinned());
 
-        h_flex()
+        let entry = h_flex()
             .id(self.ix)
             .h_9()
             .w_full()
@@ -140,6 +304,28 @@ impl RenderOnce for RoomEntry {
                             .flex_shrink_0()
                             .text_xs()
                             .text_color(cx.theme().text_placeholder)
+                            .when(pinned, |this| this.child(Icon::new(IconName::Pin).xsmall()))
+                            .when(self.draft, |this| {
+                                this.child(
+                                    div()
+                                        .italic()
+                                        .text_color(cx.theme().text_accent)
+                                        .child(SharedString::from("Draft")),
+                                )
+                            })
+                            .when_some(self.spam_score, |this, score| {
+                                this.child(
+                                    div()
+                                        .id("spam-score")
+                                        .when(score.is_spam(), |this| {
+                                            this.text_color(cx.theme().text_danger)
+                                        })
+                                        .child(SharedString::from(format!("{:+}", score.total())))
+                                        .tooltip(move |window, cx| {
+                                            Tooltip::new(score.summary(), window, cx).into()
+                                        }),
+                                )
+                            })
                             .when_some(self.created_at, |this, created_at| this.child(created_at)),
                     ),
             )
@@ -171,6 +357,17 @@ impl RenderOnce for RoomEntry {
                         });
                     }
                 })
-            })
+            });
+
+        match self.room {
+            Some((id, config)) => {
+                let folders = self.folders;
+
+                entry
+                    .context_menu(move |this, _window, _cx| room_menu(id, &config, &folders, this))
+                    .into_any_element()
+            }
+            None => entry.into_any_element(),
+        }
     }
 }
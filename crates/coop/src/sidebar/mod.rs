@@ -3,14 +3,14 @@ use std::ops::Range;
 use std::time::Duration;
 
 use anyhow::{Context as AnyhowContext, Error};
-use chat::{ChatEvent, ChatRegistry, Room, RoomKind};
+use chat::{ChatEvent, ChatRegistry, Room, RoomFilter, RoomKind};
 use common::{DebouncedDelay, TimestampExt};
 use entry::RoomEntry;
 use gpui::prelude::FluentBuilder;
 use gpui::{
     App, AppContext, Context, Entity, EventEmitter, FocusHandle, Focusable, IntoElement,
     ParentElement, Render, RetainAllImageCache, SharedString, Styled, Subscription, Task,
-    UniformListScrollHandle, Window, div, uniform_list,
+    UniformListScrollHandle, Window, div, px, relative, uniform_list,
 };
 use nostr_sdk::prelude::*;
 use person::PersonRegistry;
@@ -25,7 +25,7 @@ use ui::notification::Notification;
 use ui::scroll::Scrollbar;
 use ui::{Icon, IconName, Selectable, Sizable, StyledExt, WindowExtension, h_flex, v_flex};
 
-mod entry;
+pub mod entry;
 
 const INPUT_PLACEHOLDER: &str = "Find or start a conversation";
 
@@ -70,7 +70,7 @@ pub struct Sidebar {
     selected_pkeys: Entity<HashSet<PublicKey>>,
 
     /// Chatroom filter
-    filter: Entity<RoomKind>,
+    filter: Entity<RoomFilter>,
 
     /// User's contacts
     contact_list: Entity<Option<Vec<PublicKey>>>,
@@ -85,7 +85,7 @@ pub struct Sidebar {
 impl Sidebar {
     fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
         let chat = ChatRegistry::global(cx);
-        let filter = cx.new(|_| RoomKind::Ongoing);
+        let filter = cx.new(|_| RoomFilter::default());
         let contact_list = cx.new(|_| None);
         let selected_pkeys = cx.new(|_| HashSet::new());
         let find_results = cx.new(|_| None);
@@ -352,15 +352,15 @@ impl Sidebar {
     }
 
     /// Get the active filter.
-    fn current_filter(&self, kind: &RoomKind, cx: &Context<Self>) -> bool {
-        self.filter.read(cx) == kind
+    fn current_filter(&self, filter: &RoomFilter, cx: &Context<Self>) -> bool {
+        self.filter.read(cx) == filter
     }
 
     /// Set the active filter for the sidebar.
-    fn set_filter(&mut self, kind: RoomKind, window: &mut Window, cx: &mut Context<Self>) {
+    fn set_filter(&mut self, filter: RoomFilter, window: &mut Window, cx: &mut Context<Self>) {
         self.set_input_focus(false, window, cx);
         self.filter.update(cx, |this, cx| {
-            *this = kind;
+            *this = filter;
             cx.notify();
         });
         self.new_requests = false;
@@ -373,6 +373,7 @@ impl Sidebar {
     ) -> Vec<impl IntoElement + use<>> {
         let chat = ChatRegistry::global(cx);
         let rooms = chat.read(cx).rooms(self.filter.read(cx), cx);
+        let folders = chat.read(cx).folders(cx);
 
         rooms
             .get(range.clone())
@@ -394,6 +395,9 @@ impl Sidebar {
                     .avatar(room.display_image(cx))
                     .public_key(public_key)
                     .kind(room.kind)
+                    .config(room.id, room.config(), folders.clone())
+                    .draft(chat.read(cx).has_draft(&room.id))
+                    .spam_score(chat.read(cx).spam_score(room, cx))
                     .created_at(room.created_at.to_ago())
                     .on_click(handler)
                     .into_any_element()
@@ -494,7 +498,14 @@ impl Render for Sidebar {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let chat = ChatRegistry::global(cx);
         let loading = chat.read(cx).loading();
+        let progress = chat.read(cx).sync_progress();
+        let syncing = progress.is_syncing();
         let total_rooms = chat.read(cx).count(self.filter.read(cx), cx);
+        let total_archived = chat.read(cx).count(&RoomFilter::Archived, cx);
+        let total_spam = chat.read(cx).count(&RoomFilter::Spam, cx);
+        let folders = chat.read(cx).folders(cx);
+        let inbox = RoomFilter::Kind(RoomKind::Ongoing);
+        let requests = RoomFilter::Kind(RoomKind::Request);
 
         // Whether the find panel should be shown
         let show_find_panel = self.has_search || self.find_focused;
@@ -553,7 +564,7 @@ impl Render for Sidebar {
                     .child(
                         Button::new("all")
                             .map(|this| {
-                                if self.current_filter(&RoomKind::Ongoing, cx) {
+                                if self.current_filter(&inbox, cx) {
                                     this.icon(IconName::InboxFill)
                                 } else {
                                     this.icon(IconName::Inbox)
@@ -565,17 +576,15 @@ impl Render for Sidebar {
                             .ghost_alt()
                             .font_semibold()
                             .flex_1()
-                            .selected(
-                                !show_find_panel && self.current_filter(&RoomKind::Ongoing, cx),
-                            )
+                            .selected(!show_find_panel && self.current_filter(&inbox, cx))
                             .on_click(cx.listener(|this, _ev, window, cx| {
-                                this.set_filter(RoomKind::Ongoing, window, cx);
+                                this.set_filter(RoomFilter::Kind(RoomKind::Ongoing), window, cx);
                             })),
                     )
                     .child(
                         Button::new("requests")
                             .map(|this| {
-                                if self.current_filter(&RoomKind::Request, cx) {
+                                if self.current_filter(&requests, cx) {
                                     this.icon(IconName::FistbumpFill)
                                 } else {
                                     this.icon(IconName::Fistbump)
@@ -587,45 +596,97 @@ impl Render for Sidebar {
                             .ghost_alt()
                             .font_semibold()
                             .flex_1()
-                            .selected(
-                                !show_find_panel && !self.current_filter(&RoomKind::Ongoing, cx),
-                            )
+                            .selected(!show_find_panel && self.current_filter(&requests, cx))
                             .when(self.new_requests, |this| {
                                 this.child(div().size_1().rounded_full().bg(cx.theme().cursor))
                             })
                             .on_click(cx.listener(|this, _ev, window, cx| {
-                                this.set_filter(RoomKind::default(), window, cx);
+                                this.set_filter(RoomFilter::Kind(RoomKind::Request), window, cx);
                             })),
                     ),
             )
-            .when(!show_find_panel && !loading && total_rooms == 0, |this| {
-                this.child(
-                    div().w(SIDEBAR_WIDTH).px_2().child(
-                        v_flex()
-                            .p_3()
-                            .h_24()
-                            .w_full()
-                            .border_2()
-                            .border_dashed()
-                            .border_color(cx.theme().border_variant)
-                            .rounded(cx.theme().radius_lg)
-                            .items_center()
-                            .justify_center()
-                            .text_center()
-                            .child(
-                                div()
-                                    .text_sm()
-                                    .font_semibold()
-                                    .child(SharedString::from("No conversations")),
-                            )
-                            .child(div().text_xs().text_color(cx.theme().text_muted).child(
-                                SharedString::from(
-                                    "Start a conversation with someone to get started.",
-                                ),
-                            )),
-                    ),
-                )
-            })
+            .when(
+                !show_find_panel && (!folders.is_empty() || total_archived > 0 || total_spam > 0),
+                |this| {
+                    this.child(
+                        h_flex()
+                            .px_2()
+                            .gap_1()
+                            .flex_wrap()
+                            .children(folders.into_iter().enumerate().map(|(ix, name)| {
+                                let filter = RoomFilter::Folder(name.clone());
+
+                                Button::new(format!("folder-{ix}"))
+                                    .icon(IconName::Folder)
+                                    .label(name)
+                                    .xsmall()
+                                    .ghost_alt()
+                                    .selected(self.current_filter(&filter, cx))
+                                    .on_click(cx.listener(move |this, _ev, window, cx| {
+                                        this.set_filter(filter.clone(), window, cx);
+                                    }))
+                            }))
+                            .when(total_spam > 0, |this| {
+                                this.child(
+                                    Button::new("spam")
+                                        .icon(IconName::Warning)
+                                        .label(format!("Possible spam ({total_spam})"))
+                                        .tooltip("Requests from unknown senders with a low score")
+                                        .xsmall()
+                                        .ghost_alt()
+                                        .selected(self.current_filter(&RoomFilter::Spam, cx))
+                                        .on_click(cx.listener(|this, _ev, window, cx| {
+                                            this.set_filter(RoomFilter::Spam, window, cx);
+                                        })),
+                                )
+                            })
+                            .when(total_archived > 0, |this| {
+                                this.child(
+                                    Button::new("archived")
+                                        .icon(IconName::Archive)
+                                        .label(format!("Archived ({total_archived})"))
+                                        .xsmall()
+                                        .ghost_alt()
+                                        .selected(self.current_filter(&RoomFilter::Archived, cx))
+                                        .on_click(cx.listener(|this, _ev, window, cx| {
+                                            this.set_filter(RoomFilter::Archived, window, cx);
+                                        })),
+                                )
+                            }),
+                    )
+                },
+            )
+            .when(
+                !show_find_panel && !loading && !syncing && total_rooms == 0,
+                |this| {
+                    this.child(
+                        div().w(SIDEBAR_WIDTH).px_2().child(
+                            v_flex()
+                                .p_3()
+                                .h_24()
+                                .w_full()
+                                .border_2()
+                                .border_dashed()
+                                .border_color(cx.theme().border_variant)
+                                .rounded(cx.theme().radius_lg)
+                                .items_center()
+                                .justify_center()
+                                .text_center()
+                                .child(
+                                    div()
+                                        .text_sm()
+                                        .font_semibold()
+                                        .child(SharedString::from("No conversations")),
+                                )
+                                .child(div().text_xs().text_color(cx.theme().text_muted).child(
+                                    SharedString::from(
+                                        "Start a conversation with someone to get started.",
+                                    ),
+                                )),
+                        ),
+                    )
+                },
+            )
             .child(
                 v_flex()
                     .size_full()
@@ -728,7 +789,16 @@ impl Render for Sidebar {
                         ),
                 )
             })
-            .when(loading, |this| {
+            .when(loading || syncing, |this| {
+                let label = if syncing {
+                    format!(
+                        "Syncing messages ({}/{} relays)",
+                        progress.done, progress.total
+                    )
+                } else {
+                    "Getting messages...".to_string()
+                };
+
                 this.child(
                     div()
                         .absolute()
@@ -739,10 +809,12 @@ impl Render for Sidebar {
                         .px_8()
                         .child(
                             h_flex()
+                                .relative()
                                 .gap_2()
                                 .w_full()
                                 .h_9()
                                 .justify_center()
+                                .overflow_hidden()
                                 .bg(cx.theme().background.opacity(0.85))
                                 .border_color(cx.theme().border_disabled)
                                 .border_1()
@@ -751,8 +823,21 @@ impl Render for Sidebar {
                                 .text_xs()
                                 .font_semibold()
                                 .text_color(cx.theme().text_muted)
+                                .when(syncing, |this| {
+                                    let ratio = progress.done as f32 / progress.total as f32;
+
+                                    this.child(
+                                        div()
+                                            .absolute()
+                                            .left_0()
+                                            .bottom_0()
+                                            .h(px(2.))
+                                            .w(relative(ratio))
+                                            .bg(cx.theme().icon_accent),
+                                    )
+                                })
                                 .child(Indicator::new().small().color(cx.theme().icon_accent))
-                                .child(SharedString::from("Getting messages...")),
+                                .child(SharedString::from(label)),
                         ),
                 )
             })
@@ -2,20 +2,20 @@ use std::sync::Arc;
 
 use ::settings::AppSettings;
 use chat::{ChatEvent, ChatRegistry};
-use common::download_dir;
+use common::{download_dir, themes_dir};
 use device::{DeviceEvent, DeviceRegistry};
 use gpui::prelude::FluentBuilder;
 use gpui::{
     Action, App, AppContext, Axis, Context, Entity, InteractiveElement, IntoElement, ParentElement,
-    Render, SharedString, StatefulInteractiveElement, Styled, Subscription, Window, div, px,
-    relative,
+    PathPromptOptions, Render, SharedString, StatefulInteractiveElement, Styled, Subscription,
+    Task, Window, div, px, relative,
 };
 use nostr_sdk::prelude::*;
 use person::{PersonRegistry, shorten_pubkey};
 use serde::Deserialize;
 use smallvec::{SmallVec, smallvec};
 use state::{NostrRegistry, StateEvent};
-use theme::{ActiveTheme, SIDEBAR_WIDTH, Theme, ThemeRegistry};
+use theme::{ActiveTheme, SIDEBAR_WIDTH, Theme, ThemeFamily, ThemeRegistry, user_theme_key};
 use title_bar::TitleBar;
 use ui::avatar::Avatar;
 use ui::button::{Button, ButtonVariants};
@@ -26,7 +26,10 @@ use ui::{Icon, IconName, Root, Sizable, WindowExtension, h_flex, v_flex};
 
 use crate::dialogs::restore::RestoreEncryption;
 use crate::dialogs::{accounts, settings};
-use crate::panels::{backup, contact_list, greeter, messaging_relays, profile, relay_list, trash};
+use crate::panels::{
+    backup, contact_list, greeter, inbox, messaging_relays, mute_list, profile, relay_list,
+    storage, trash,
+};
 use crate::sidebar;
 
 const PREPARE_MSG: &str = "Coop is preparing a new identity for you. This may take a moment...";
@@ -34,6 +37,8 @@ const ENC_MSG: &str = "Encryption Key is a special key that used to encrypt and
                        Your identity is completely decoupled from all encryption processes to protect your privacy.";
 const ENC_WARN: &str = "By resetting your encryption key, you will lose access to \
                         all your encrypted messages before. This action cannot be undone.";
+const THEME_EXISTS_MSG: &str =
+    "has already been imported. Importing it again will replace the saved copy.";
 
 pub fn init(window: &mut Window, cx: &mut App) -> Entity<Workspace> {
     cx.new(|cx| Workspace::new(window, cx))
@@ -48,6 +53,7 @@ struct RelayNotifcation;
 enum Command {
     ToggleTheme,
     ToggleAccount,
+    ImportTheme,
 
     RefreshMessagingRelays,
     BackupEncryption,
@@ -57,10 +63,13 @@ enum Command {
 
     ShowRelayList,
     ShowMessaging,
+    ShowInbox,
     ShowProfile,
     ShowSettings,
     ShowBackup,
+    ShowStorage,
     ShowContactList,
+    ShowMuteList,
 }
 
 pub struct Workspace {
@@ -334,6 +343,26 @@ impl Workspace {
                     );
                 });
             }
+            Command::ShowStorage => {
+                self.dock.update(cx, |this, cx| {
+                    this.add_panel(
+                        Arc::new(storage::init(window, cx)),
+                        DockPlacement::Right,
+                        window,
+                        cx,
+                    );
+                });
+            }
+            Command::ShowMuteList => {
+                self.dock.update(cx, |this, cx| {
+                    this.add_panel(
+                        Arc::new(mute_list::init(window, cx)),
+                        DockPlacement::Right,
+                        window,
+                        cx,
+                    );
+                });
+            }
             Command::ShowMessaging => {
                 self.dock.update(cx, |this, cx| {
                     this.add_panel(
@@ -344,6 +373,16 @@ impl Workspace {
                     );
                 });
             }
+            Command::ShowInbox => {
+                self.dock.update(cx, |this, cx| {
+                    this.add_panel(
+                        Arc::new(inbox::init(window, cx)),
+                        DockPlacement::Right,
+                        window,
+                        cx,
+                    );
+                });
+            }
             Command::RefreshMessagingRelays => {
                 let chat = ChatRegistry::global(cx);
                 // Trigger a refresh of the chat registry
@@ -373,6 +412,9 @@ impl Workspace {
             Command::ToggleTheme => {
                 self.theme_selector(window, cx);
             }
+            Command::ImportTheme => {
+                self.import_theme(window, cx);
+            }
             Command::ToggleAccount => {
                 self.account_selector(window, cx);
             }
@@ -550,9 +592,156 @@ impl Workspace {
 
                     items
                 }))
+                .footer(|_ok, _cancel, _window, _cx| {
+                    vec![
+                        Button::new("import-theme")
+                            .icon(IconName::Upload)
+                            .label("Import theme…")
+                            .ghost()
+                            .small()
+                            .on_click(|_ev, window, cx| {
+                                window.dispatch_action(Box::new(Command::ImportTheme), cx);
+                            }),
+                    ]
+                })
         });
     }
 
+    fn import_theme(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        // Ask user for the theme file
+        let path = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Import".into()),
+        });
+
+        cx.spawn_in(window, async move |this, cx| {
+            let mut paths = match path.await {
+                Ok(Ok(Some(paths))) => paths,
+                Ok(Ok(None)) | Err(_) => return Ok(()),
+                Ok(Err(error)) => {
+                    cx.update(|window, cx| {
+                        let message = format!("Failed to pick theme file: {error:#}");
+                        let note = Notification::error(message).autohide(false);
+                        window.push_notification(note, cx);
+                    })?;
+                    return Ok(());
+                }
+            };
+
+            let Some(path) = paths.pop() else {
+                return Ok(());
+            };
+
+            // Convert the theme and check if a theme with the same id was imported before
+            let task: Task<Result<(ThemeFamily, bool), anyhow::Error>> =
+                cx.background_spawn(async move {
+                    let theme = ThemeFamily::import(&path)?;
+                    let exists = themes_dir().join(format!("{}.json", theme.id)).exists();
+
+                    Ok((theme, exists))
+                });
+
+            match task.await {
+                Ok((theme, exists)) => {
+                    this.update_in(cx, |this, window, cx| {
+                        if exists {
+                            this.confirm_replace_theme(theme, window, cx);
+                        } else {
+                            this.save_theme(theme, window, cx);
+                        }
+                    })?;
+                }
+                Err(e) => {
+                    cx.update(|window, cx| {
+                        let message = format!("Failed to import theme: {e}");
+                        let note = Notification::error(message).autohide(false);
+                        window.push_notification(note, cx);
+                    })?;
+                }
+            }
+
+            Ok::<_, anyhow::Error>(())
+        })
+        .detach();
+    }
+
+    /// Ask before replacing an imported theme with the same id
+    fn confirm_replace_theme(
+        &mut self,
+        theme: ThemeFamily,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let entity = cx.entity().downgrade();
+
+        window.open_modal(cx, move |this, _window, _cx| {
+            let entity = entity.clone();
+            let theme = theme.clone();
+            let message = format!("{} {THEME_EXISTS_MSG}", theme.name);
+
+            this.confirm()
+                .show_close(true)
+                .title(SharedString::from("Replace theme?"))
+                .child(div().text_sm().child(SharedString::from(message)))
+                .on_ok(move |_ev, window, cx| {
+                    entity
+                        .update(cx, |this, cx| {
+                            this.save_theme(theme.clone(), window, cx);
+                        })
+                        .ok();
+                    // true to close modal
+                    true
+                })
+        });
+    }
+
+    /// Keep a copy of an imported theme in the user's themes directory, then apply it
+    fn save_theme(&mut self, theme: ThemeFamily, window: &mut Window, cx: &mut Context<Self>) {
+        let registry = ThemeRegistry::global(cx);
+
+        cx.spawn_in(window, async move |_this, cx| {
+            let task: Task<Result<(String, ThemeFamily), anyhow::Error>> =
+                cx.background_spawn(async move {
+                    let output = themes_dir().join(format!("{}.json", theme.id));
+
+                    smol::fs::create_dir_all(themes_dir()).await?;
+                    smol::fs::write(&output, serde_json::to_vec_pretty(&theme)?).await?;
+
+                    Ok((user_theme_key(&theme.id), theme))
+                });
+
+            match task.await {
+                Ok((key, theme)) => {
+                    cx.update(|window, cx| {
+                        let message = format!("Imported theme: {}", theme.name);
+
+                        registry.update(cx, |this, cx| {
+                            this.insert(key.clone(), theme, cx);
+                        });
+
+                        AppSettings::global(cx).update(cx, |this, cx| {
+                            this.set_theme(key, window, cx);
+                        });
+
+                        window.push_notification(Notification::success(message), cx);
+                    })?;
+                }
+                Err(e) => {
+                    cx.update(|window, cx| {
+                        let message = format!("Failed to import theme: {e}");
+                        let note = Notification::error(message).autohide(false);
+                        window.push_notification(note, cx);
+                    })?;
+                }
+            }
+
+            Ok::<_, anyhow::Error>(())
+        })
+        .detach();
+    }
+
     fn titlebar_left(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
         let nostr = NostrRegistry::global(cx);
         let signer = nostr.read(cx).signer();
@@ -606,11 +795,21 @@ impl Workspace {
                                     IconName::Book,
                                     Box::new(Command::ShowContactList),
                                 )
+                                .menu_with_icon(
+                                    "Muted Users",
+                                    IconName::Shield,
+                                    Box::new(Command::ShowMuteList),
+                                )
                                 .menu_with_icon(
                                     "Backup",
                                     IconName::UserKey,
                                     Box::new(Command::ShowBackup),
                                 )
+                                .menu_with_icon(
+                                    "Storage",
+                                    IconName::Usb,
+                                    Box::new(Command::ShowStorage),
+                                )
                                 .menu_with_icon(
                                     "Themes",
                                     IconName::Sun,
@@ -823,6 +1022,11 @@ impl Workspace {
                                 IconName::Settings,
                                 Box::new(Command::ShowMessaging),
                             )
+                            .menu_with_icon(
+                                "Inbox maintenance",
+                                IconName::Inbox,
+                                Box::new(Command::ShowInbox),
+                            )
                     }),
             )
     }
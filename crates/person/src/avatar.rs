@@ -0,0 +1,325 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Error, anyhow};
+use common::avatars_dir;
+use gpui::http_client::{AsyncBody, HttpClient};
+use gpui::{ImageSource, SharedString};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits, RgbaImage};
+use nostr_sdk::prelude::*;
+use smol::io::AsyncReadExt;
+
+/// Remote service used when the user opts out of local processing
+const IMAGE_RESIZER: &str = "https://wsrv.nl";
+
+/// Width and height of a processed avatar, in pixels
+const AVATAR_SIZE: u32 = 128;
+
+/// Maximum time to download a picture
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum size of a downloaded picture, in bytes
+const MAX_PICTURE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Maximum width or height of a decoded picture, in pixels
+const MAX_PICTURE_DIMENSION: u32 = 4096;
+
+/// Maximum size of the avatar cache, in bytes
+const MAX_CACHE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Cached avatars older than this are fetched again
+const AVATAR_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Delay before fetching a failed avatar again, doubled on each failure
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Maximum delay before fetching a failed avatar again
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Source of a profile avatar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AvatarSource {
+    /// Avatar processed locally and cached on disk
+    Local(PathBuf),
+
+    /// Avatar resized by a remote service
+    Remote(SharedString),
+}
+
+impl From<AvatarSource> for ImageSource {
+    fn from(source: AvatarSource) -> Self {
+        match source {
+            AvatarSource::Local(path) => path.into(),
+            AvatarSource::Remote(url) => url.into(),
+        }
+    }
+}
+
+/// Get the URL of a picture resized by the remote service
+pub fn remote_avatar(picture: &str) -> SharedString {
+    let encoded_picture = urlencoding::encode(picture);
+    let url =
+        format!("{IMAGE_RESIZER}/?url={encoded_picture}&w=100&h=100&fit=cover&mask=circle&n=-1");
+    url.into()
+}
+
+/// Get the path of a cached avatar
+fn cache_path(picture: &str) -> PathBuf {
+    let hash = Sha256Hash::hash(picture.as_bytes());
+    avatars_dir().join(format!("{hash}.png"))
+}
+
+/// Check if a cached avatar exists and hasn't expired
+async fn is_fresh(path: &Path) -> bool {
+    smol::fs::metadata(path)
+        .await
+        .ok()
+        .and_then(|metadata| metadata.modified().ok())
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < AVATAR_EXPIRY)
+}
+
+/// Get the delay before fetching an avatar again after the given number of failures.
+pub fn retry_delay(failures: u32) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    RETRY_DELAY.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
+
+/// Fetch, resize and circle-mask a profile picture, the result is cached on disk.
+///
+/// The picture is fetched directly, or through the proxy if one is set.
+pub async fn fetch_avatar(
+    picture: &str,
+    proxy: Option<&Url>,
+    client: &Arc<dyn HttpClient>,
+) -> Result<PathBuf, Error> {
+    let path = cache_path(picture);
+
+    if is_fresh(&path).await {
+        return Ok(path);
+    }
+
+    let url = match proxy {
+        Some(proxy) => format!("{proxy}{}", urlencoding::encode(picture)),
+        None => picture.to_string(),
+    };
+
+    let body = smol::future::or(download_picture(&url, client), async {
+        smol::Timer::after(FETCH_TIMEOUT).await;
+        Err(anyhow!("Timed out"))
+    })
+    .await?;
+
+    let avatar = process_avatar(&body)?;
+
+    // Write to a temporary file first, so a partial avatar is never served
+    let tmp_path = path.with_extension("tmp");
+    smol::fs::create_dir_all(avatars_dir()).await?;
+    smol::fs::write(&tmp_path, avatar).await?;
+    smol::fs::rename(&tmp_path, &path).await?;
+
+    Ok(path)
+}
+
+/// Download a picture, up to the size limit
+async fn download_picture(url: &str, client: &Arc<dyn HttpClient>) -> Result<Vec<u8>, Error> {
+    let mut res = client.get(url, AsyncBody::default(), true).await?;
+
+    if !res.status().is_success() {
+        return Err(anyhow!("Server returned error: {}", res.status()));
+    }
+
+    let mut body = Vec::new();
+    res.body_mut()
+        .take(MAX_PICTURE_SIZE + 1)
+        .read_to_end(&mut body)
+        .await?;
+
+    if body.len() as u64 > MAX_PICTURE_SIZE {
+        return Err(anyhow!("Picture is too large"));
+    }
+
+    Ok(body)
+}
+
+/// Decode a picture, refusing pictures larger than the dimension limit
+fn decode_picture(bytes: &[u8]) -> Result<DynamicImage, Error> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_PICTURE_DIMENSION);
+    limits.max_image_height = Some(MAX_PICTURE_DIMENSION);
+
+    let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    reader.limits(limits);
+
+    Ok(reader.decode()?)
+}
+
+/// Decode a picture and turn it into a round PNG avatar
+fn process_avatar(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut image = decode_picture(bytes)?
+        .resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3)
+        .to_rgba8();
+
+    circle_mask(&mut image);
+
+    let mut output = Vec::new();
+    DynamicImage::ImageRgba8(image).write_to(&mut Cursor::new(&mut output), ImageFormat::Png)?;
+
+    Ok(output)
+}
+
+/// Crop the center of a picture to the aspect ratio of the given size.
+///
+/// Larger pictures are scaled down to the given size. Pictures with transparency
+/// are encoded as PNG, others as JPEG.
+pub fn crop_picture(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<(Vec<u8>, ImageFormat), Error> {
+    let image = decode_picture(bytes)?;
+
+    // Get the largest centered area with the target aspect ratio
+    let scale = (image.width() as f32 / width as f32).min(image.height() as f32 / height as f32);
+    let crop_width = ((width as f32 * scale).round() as u32).clamp(1, image.width());
+    let crop_height = ((height as f32 * scale).round() as u32).clamp(1, image.height());
+    let x = (image.width() - crop_width) / 2;
+    let y = (image.height() - crop_height) / 2;
+
+    let mut image = image.crop_imm(x, y, crop_width, crop_height);
+
+    if crop_width > width {
+        image = image.resize_exact(width, height, FilterType::Lanczos3);
+    }
+
+    let mut output = Vec::new();
+
+    let format = if image.color().has_alpha() {
+        image.write_to(&mut Cursor::new(&mut output), ImageFormat::Png)?;
+        ImageFormat::Png
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_to(&mut Cursor::new(&mut output), ImageFormat::Jpeg)?;
+        ImageFormat::Jpeg
+    };
+
+    Ok((output, format))
+}
+
+/// Make the pixels outside the inscribed circle transparent, with a smooth edge
+fn circle_mask(image: &mut RgbaImage) {
+    let center_x = image.width() as f32 / 2.;
+    let center_y = image.height() as f32 / 2.;
+    let radius = center_x.min(center_y);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - center_x;
+        let dy = y as f32 + 0.5 - center_y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let coverage = (radius - distance + 0.5).clamp(0., 1.);
+
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+}
+
+/// Delete expired avatars, then the oldest ones until the cache fits its size limit
+pub fn prune_avatars() -> Result<(), Error> {
+    let Ok(entries) = std::fs::read_dir(avatars_dir()) else {
+        return Ok(());
+    };
+
+    let mut files: Vec<(PathBuf, SystemTime, u64)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            Some((entry.path(), metadata.modified().ok()?, metadata.len()))
+        })
+        .collect();
+
+    // Newest first
+    files.sort_by_key(|(_, modified, _)| std::cmp::Reverse(*modified));
+
+    let mut total = 0;
+
+    for (path, modified, size) in files.into_iter() {
+        let expired = modified.elapsed().is_ok_and(|age| age >= AVATAR_EXPIRY);
+        total += size;
+
+        if (expired || total > MAX_CACHE_SIZE)
+            && let Err(e) = std::fs::remove_file(&path)
+        {
+            log::warn!("Failed to remove avatar {}: {e}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn test_circle_mask() {
+        let mut image = RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255]));
+        circle_mask(&mut image);
+
+        // Corners are transparent
+        assert_eq!(image.get_pixel(0, 0)[3], 0);
+        assert_eq!(image.get_pixel(63, 63)[3], 0);
+
+        // Center and the inside of the edges are opaque
+        assert_eq!(image.get_pixel(32, 32)[3], 255);
+        assert_eq!(image.get_pixel(2, 32)[3], 255);
+        assert_eq!(image.get_pixel(32, 2)[3], 255);
+    }
+
+    #[test]
+    fn test_process_avatar() {
+        let mut input = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::new(300, 200))
+            .write_to(&mut Cursor::new(&mut input), ImageFormat::Png)
+            .unwrap();
+
+        let output = process_avatar(&input).unwrap();
+        let avatar = image::load_from_memory(&output).unwrap();
+
+        assert_eq!(avatar.width(), AVATAR_SIZE);
+        assert_eq!(avatar.height(), AVATAR_SIZE);
+        assert!(process_avatar(b"<!DOCTYPE html>").is_err());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), RETRY_DELAY);
+        assert_eq!(retry_delay(2), RETRY_DELAY * 2);
+        assert_eq!(retry_delay(3), RETRY_DELAY * 4);
+        assert_eq!(retry_delay(40), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_crop_picture() {
+        let mut input = Vec::new();
+        DynamicImage::ImageRgb8(image::RgbImage::new(300, 100))
+            .write_to(&mut Cursor::new(&mut input), ImageFormat::Png)
+            .unwrap();
+
+        // Small pictures are cropped but not scaled up
+        let (output, format) = crop_picture(&input, 400, 400).unwrap();
+        let square = image::load_from_memory(&output).unwrap();
+
+        assert_eq!(format, ImageFormat::Jpeg);
+        assert_eq!((square.width(), square.height()), (100, 100));
+
+        // Large pictures are scaled down to the given size
+        let (output, _) = crop_picture(&input, 30, 5).unwrap();
+        let banner = image::load_from_memory(&output).unwrap();
+
+        assert_eq!((banner.width(), banner.height()), (30, 5));
+    }
+}
@@ -1,19 +1,32 @@
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{Error, anyhow};
 use common::EventExt;
-use gpui::{App, AppContext, Context, Entity, Global, Task, Window};
+use gpui::http_client::HttpClient;
+use gpui::{App, AppContext, Context, Entity, Global, Subscription, Task, Window};
 use nostr_sdk::prelude::*;
+use settings::AppSettings;
 use smallvec::{SmallVec, smallvec};
-use state::{Announcement, BOOTSTRAP_RELAYS, NostrRegistry, TIMEOUT};
+use state::{
+    Announcement, BOOTSTRAP_RELAYS, FetchTimes, NostrRegistry, TIMEOUT, record_fetch_times,
+};
 
+mod avatar;
 mod person;
 
+pub use avatar::*;
 pub use person::*;
 
+/// Number of avatars fetched at the same time
+const AVATAR_WORKERS: usize = 4;
+
+/// Delay before saving the times profiles were received from relays
+const FETCH_TIMES_DELAY: Duration = Duration::from_secs(30);
+
 pub fn init(window: &mut Window, cx: &mut App) {
     PersonRegistry::set_global(cx.new(|cx| PersonRegistry::new(window, cx)), cx);
 }
@@ -27,6 +40,18 @@ enum Dispatch {
     Person(Box<Person>),
     Announcement(Box<Event>),
     Relays(Box<Event>),
+    Avatar(PublicKey, String, AvatarSource),
+    AvatarFailed(PublicKey, String),
+}
+
+/// Request for an avatar
+#[derive(Debug, Clone)]
+struct AvatarRequest {
+    public_key: PublicKey,
+    picture: String,
+    proxy: Option<Url>,
+    /// Use the remote resizer instead of processing the picture locally
+    remote: bool,
 }
 
 /// Person Registry
@@ -41,8 +66,29 @@ pub struct PersonRegistry {
     /// Sender for requesting metadata
     sender: flume::Sender<PublicKey>,
 
+    /// Sender for requesting avatars
+    avatar_sender: flume::Sender<AvatarRequest>,
+
+    /// Persons whose avatar was requested, avatars are requested when first read
+    avatar_requests: Rc<RefCell<HashSet<PublicKey>>>,
+
+    /// Persons whose avatar failed to load, with the number of failures and the next attempt
+    avatar_failures: HashMap<PublicKey, (u32, Instant)>,
+
+    /// Avatar settings the current avatars were resolved with
+    avatar_options: (bool, Option<Url>, bool),
+
+    /// Profiles received from relays since the fetch times were last saved
+    fetch_times: FetchTimes,
+
+    /// Task saving the fetch times
+    fetch_times_task: Option<Task<()>>,
+
     /// Tasks for asynchronous operations
-    tasks: SmallVec<[Task<()>; 4]>,
+    tasks: SmallVec<[Task<()>; 8]>,
+
+    /// Event subscriptions
+    _subscriptions: SmallVec<[Subscription; 1]>,
 }
 
 impl PersonRegistry {
@@ -60,12 +106,56 @@ impl PersonRegistry {
     fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
         let nostr = NostrRegistry::global(cx);
         let client = nostr.read(cx).client();
+        let http_client = cx.http_client();
+        let settings = AppSettings::global(cx);
 
         // Channel for communication between nostr and gpui
         let (tx, rx) = flume::bounded::<Dispatch>(100);
         let (mta_tx, mta_rx) = flume::unbounded::<PublicKey>();
+        let (avatar_tx, avatar_rx) = flume::unbounded::<AvatarRequest>();
 
         let mut tasks = smallvec![];
+        let mut subscriptions = smallvec![];
+
+        subscriptions.push(
+            // Request all avatars again when the avatar settings change
+            cx.observe(&settings, |this, _settings, cx| {
+                let options = (
+                    AppSettings::get_remote_resizer(cx),
+                    AppSettings::get_image_proxy(cx),
+                    AppSettings::get_hide_avatar(cx),
+                );
+
+                if this.avatar_options != options {
+                    this.avatar_options = options;
+                    this.reset_avatars(cx);
+                }
+            }),
+        );
+
+        tasks.push(
+            // Remove expired avatars from the cache
+            cx.background_spawn(async move {
+                if let Err(e) = prune_avatars() {
+                    log::warn!("Failed to prune the avatar cache: {e}");
+                }
+            }),
+        );
+
+        for _ in 0..AVATAR_WORKERS {
+            tasks.push(
+                // Handle avatar requests
+                cx.background_spawn({
+                    let http_client = http_client.clone();
+                    let avatar_rx = avatar_rx.clone();
+                    let tx = tx.clone();
+
+                    async move {
+                        Self::handle_avatars(&http_client, &avatar_rx, &tx).await;
+                    }
+                }),
+            );
+        }
 
         tasks.push(
             // Handle nostr notifications
@@ -96,6 +186,7 @@ impl PersonRegistry {
                     this.update(cx, |this, cx| {
                         match event {
                             Dispatch::Person(person) => {
+                                this.record_fetch(person.public_key(), cx);
                                 this.insert(*person, cx);
                             }
                             Dispatch::Announcement(event) => {
@@ -104,6 +195,12 @@ impl PersonRegistry {
                             Dispatch::Relays(event) => {
                                 this.set_messaging_relays(&event, cx);
                             }
+                            Dispatch::Avatar(public_key, picture, source) => {
+                                this.set_avatar(&public_key, &picture, source, cx);
+                            }
+                            Dispatch::AvatarFailed(public_key, picture) => {
+                                this.avatar_failed(&public_key, &picture, cx);
+                            }
                         };
                     })
                     .ok();
@@ -120,7 +217,18 @@ impl PersonRegistry {
             persons: HashMap::new(),
             seens: Rc::new(RefCell::new(HashSet::new())),
             sender: mta_tx,
+            avatar_sender: avatar_tx,
+            avatar_requests: Rc::new(RefCell::new(HashSet::new())),
+            avatar_failures: HashMap::new(),
+            avatar_options: (
+                AppSettings::get_remote_resizer(cx),
+                AppSettings::get_image_proxy(cx),
+                AppSettings::get_hide_avatar(cx),
+            ),
+            fetch_times: FetchTimes::new(),
+            fetch_times_task: None,
             tasks,
+            _subscriptions: subscriptions,
         }
     }
 
@@ -195,6 +303,38 @@ impl PersonRegistry {
         }
     }
 
+    /// Handle avatar requests
+    async fn handle_avatars(
+        http_client: &Arc<dyn HttpClient>,
+        rx: &flume::Receiver<AvatarRequest>,
+        tx: &flume::Sender<Dispatch>,
+    ) {
+        while let Ok(request) = rx.recv_async().await {
+            if request.remote {
+                let source = AvatarSource::Remote(remote_avatar(&request.picture));
+                let dispatch = Dispatch::Avatar(request.public_key, request.picture, source);
+                // Send
+                tx.send_async(dispatch).await.ok();
+                continue;
+            }
+
+            match fetch_avatar(&request.picture, request.proxy.as_ref(), http_client).await {
+                Ok(path) => {
+                    let source = AvatarSource::Local(path);
+                    let dispatch = Dispatch::Avatar(request.public_key, request.picture, source);
+                    // Send
+                    tx.send_async(dispatch).await.ok();
+                }
+                Err(e) => {
+                    log::debug!("Failed to fetch avatar {}: {e}", request.picture);
+                    let dispatch = Dispatch::AvatarFailed(request.public_key, request.picture);
+                    // Send
+                    tx.send_async(dispatch).await.ok();
+                }
+            }
+        }
+    }
+
     /// Load all user profiles from the database
     fn load(&mut self, cx: &mut Context<Self>) {
         let nostr = NostrRegistry::global(cx);
@@ -224,6 +364,37 @@ impl PersonRegistry {
         }));
     }
 
+    /// Record when a profile was received from relays, the times are saved in batches
+    fn record_fetch(&mut self, public_key: PublicKey, cx: &mut Context<Self>) {
+        self.fetch_times
+            .insert(public_key, Timestamp::now().as_secs());
+
+        if self.fetch_times_task.is_some() {
+            return;
+        }
+
+        self.fetch_times_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(FETCH_TIMES_DELAY).await;
+
+            let Ok(times) = this.update(cx, |this, _cx| std::mem::take(&mut this.fetch_times))
+            else {
+                return;
+            };
+
+            cx.background_spawn(async move {
+                if let Err(e) = record_fetch_times(&times) {
+                    log::warn!("Failed to save the profile fetch times: {e}");
+                }
+            })
+            .await;
+
+            this.update(cx, |this, _cx| {
+                this.fetch_times_task = None;
+            })
+            .ok();
+        }));
+    }
+
     /// Set profile encryption keys announcement
     fn set_announcement(&mut self, event: &Event, cx: &mut App) {
         let announcement = Announcement::from(event);
@@ -255,13 +426,117 @@ impl PersonRegistry {
         }
     }
 
+    /// Set the avatar for a person
+    fn set_avatar(
+        &mut self,
+        public_key: &PublicKey,
+        picture: &str,
+        source: AvatarSource,
+        cx: &mut App,
+    ) {
+        // Skip if the avatar settings changed in the meantime
+        let remote = matches!(source, AvatarSource::Remote(_));
+
+        if AppSettings::get_hide_avatar(cx) || AppSettings::get_remote_resizer(cx) != remote {
+            return;
+        }
+
+        self.avatar_failures.remove(public_key);
+
+        if let Some(person) = self.persons.get(public_key) {
+            person.update(cx, |person, cx| {
+                // Skip if the picture has changed since the request
+                if person.picture().as_deref() == Some(picture) {
+                    person.set_avatar(Some(source));
+                    cx.notify();
+                }
+            });
+        }
+    }
+
+    /// Allow a failed avatar to be requested again, after a delay growing with each failure
+    fn avatar_failed(&mut self, public_key: &PublicKey, picture: &str, cx: &App) {
+        // Skip if the picture has changed since the request
+        let Some(person) = self.persons.get(public_key) else {
+            return;
+        };
+
+        if person.read(cx).picture().as_deref() != Some(picture) {
+            return;
+        }
+
+        let failures = self
+            .avatar_failures
+            .get(public_key)
+            .map_or(0, |(failures, _)| *failures)
+            + 1;
+        let retry_at = Instant::now() + retry_delay(failures);
+
+        self.avatar_failures
+            .insert(*public_key, (failures, retry_at));
+        self.avatar_requests.borrow_mut().remove(public_key);
+    }
+
+    /// Request the avatar of a person, once, based on the avatar settings.
+    ///
+    /// Nothing is fetched while avatars are hidden, failed avatars are retried with a backoff.
+    fn request_avatar(&self, person: &Person, cx: &App) {
+        if AppSettings::get_hide_avatar(cx) {
+            return;
+        }
+
+        let Some(picture) = person.picture() else {
+            return;
+        };
+
+        let backoff = self
+            .avatar_failures
+            .get(&person.public_key())
+            .is_some_and(|(_, retry_at)| Instant::now() < *retry_at);
+
+        if backoff
+            || !self
+                .avatar_requests
+                .borrow_mut()
+                .insert(person.public_key())
+        {
+            return;
+        }
+
+        let request = AvatarRequest {
+            public_key: person.public_key(),
+            picture,
+            proxy: AppSettings::get_image_proxy(cx),
+            remote: AppSettings::get_remote_resizer(cx),
+        };
+
+        if let Err(e) = self.avatar_sender.send(request) {
+            log::warn!("Failed to send avatar request: {e}");
+        }
+    }
+
+    /// Clear all avatars, they're requested again when read
+    fn reset_avatars(&mut self, cx: &mut Context<Self>) {
+        self.avatar_requests.borrow_mut().clear();
+        self.avatar_failures.clear();
+
+        for person in self.persons.values() {
+            person.update(cx, |person, cx| {
+                person.set_avatar(None);
+                cx.notify();
+            });
+        }
+        cx.notify();
+    }
+
     /// Insert batch of persons
     fn bulk_inserts(&mut self, persons: Vec<Person>, cx: &mut Context<Self>) {
         for person in persons.into_iter() {
             let public_key = person.public_key();
-            self.persons
-                .entry(public_key)
-                .or_insert_with(|| cx.new(|_| person));
+
+            if !self.persons.contains_key(&public_key) {
+                self.persons.insert(public_key, cx.new(|_| person));
+            }
         }
         cx.notify();
     }
@@ -272,10 +547,21 @@ impl PersonRegistry {
 
         match self.persons.get(&public_key) {
             Some(this) => {
+                let picture_changed = this.read(cx).picture() != person.picture();
+
                 this.update(cx, |this, cx| {
                     this.set_metadata(person.metadata());
+
+                    if picture_changed {
+                        this.set_avatar(None);
+                    }
                     cx.notify();
                 });
+
+                if picture_changed {
+                    self.avatar_requests.borrow_mut().remove(&public_key);
+                    self.avatar_failures.remove(&public_key);
+                }
             }
             None => {
                 self.persons.insert(public_key, cx.new(|_| person));
@@ -286,7 +572,10 @@ impl PersonRegistry {
     /// Get single person by public key
     pub fn get(&self, public_key: &PublicKey, cx: &App) -> Person {
         if let Some(person) = self.persons.get(public_key) {
-            return person.read(cx).clone();
+            let person = person.read(cx).clone();
+            self.request_avatar(&person, cx);
+
+            return person;
         }
 
         let public_key = *public_key;
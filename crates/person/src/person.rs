@@ -1,11 +1,11 @@
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 
-use gpui::SharedString;
+use gpui::{ImageSource, SharedString};
 use nostr_sdk::prelude::*;
 use state::Announcement;
 
-const IMAGE_RESIZER: &str = "https://wsrv.nl";
+use crate::AvatarSource;
 
 /// Person
 #[derive(Debug, Clone)]
@@ -21,6 +21,9 @@ pub struct Person {
 
     /// Messaging relays
     messaging_relays: Vec<RelayUrl>,
+
+    /// Resized avatar
+    avatar: Option<AvatarSource>,
 }
 
 impl PartialEq for Person {
@@ -62,6 +65,7 @@ impl Person {
             metadata,
             announcement: None,
             messaging_relays: vec![],
+            avatar: None,
         }
     }
 
@@ -105,20 +109,21 @@ impl Person {
         self.messaging_relays.first().cloned()
     }
 
-    /// Get profile avatar
-    pub fn avatar(&self) -> SharedString {
-        self.metadata()
+    /// Get profile picture URL
+    pub fn picture(&self) -> Option<String> {
+        self.metadata
             .picture
             .as_ref()
             .filter(|picture| !picture.is_empty())
-            .map(|picture| {
-                let encoded_picture = urlencoding::encode(picture);
-                let url = format!(
-                    "{IMAGE_RESIZER}/?url={encoded_picture}&w=100&h=100&fit=cover&mask=circle&n=-1"
-                );
-                url.into()
-            })
-            .unwrap_or_else(|| "brand/avatar.png".into())
+            .cloned()
+    }
+
+    /// Get profile avatar
+    pub fn avatar(&self) -> ImageSource {
+        match self.avatar.clone() {
+            Some(source) => source.into(),
+            None => "brand/avatar.png".into(),
+        }
     }
 
     /// Get profile name
@@ -143,6 +148,11 @@ impl Person {
         self.metadata = metadata;
     }
 
+    /// Set profile resized avatar
+    pub fn set_avatar(&mut self, avatar: Option<AvatarSource>) {
+        self.avatar = avatar;
+    }
+
     /// Set profile encryption keys announcement
     pub fn set_announcement(&mut self, announcement: Announcement) {
         self.announcement = Some(announcement);
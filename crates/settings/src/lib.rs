@@ -1,14 +1,25 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::Display;
+use std::io::ErrorKind;
+use std::path::PathBuf;
 use std::rc::Rc;
 
-use anyhow::{Error, anyhow};
-use common::config_dir;
+use anyhow::Error;
+use common::{config_dir, themes_dir};
 use gpui::{App, AppContext, Context, Entity, Global, Subscription, Task, Window};
 use nostr_sdk::prelude::*;
 use serde::{Deserialize, Serialize};
 use smallvec::{SmallVec, smallvec};
-use theme::{Theme, ThemeFamily, ThemeMode};
+use smol::io::AsyncWriteExt;
+use theme::{Theme, ThemeFamily, ThemeMode, ThemeRegistry};
+use ui::WindowExtension;
+use ui::notification::Notification;
+
+mod migration;
+mod sync;
+
+pub use migration::*;
+pub use sync::*;
 
 pub fn init(window: &mut Window, cx: &mut App) {
     AppSettings::set_global(cx.new(|cx| AppSettings::new(window, cx)), cx)
@@ -26,6 +37,12 @@ macro_rules! setting_accessors {
                     pub fn [<update_ $field>](value: $type, cx: &mut App) {
                         Self::global(cx).update(cx, |this, cx| {
                             this.values.$field = value;
+
+                            // Mark the setting as changed for syncing
+                            if let Some(field) = SyncField::from_name(stringify!($field)) {
+                                this.values.touch(field, Timestamp::now().as_secs());
+                            }
+
                             cx.notify();
                         });
                     }
@@ -38,12 +55,20 @@ macro_rules! setting_accessors {
 setting_accessors! {
     pub theme: Option<String>,
     pub theme_mode: ThemeMode,
+    pub font_scale: f32,
     pub hide_avatar: bool,
+    pub remote_resizer: bool,
+    pub image_proxy: Option<Url>,
     pub screening: bool,
     pub auth_mode: AuthMode,
     pub trusted_relays: HashSet<RelayUrl>,
     pub room_configs: HashMap<u64, RoomConfig>,
-    pub file_server: Url,
+    pub sync_room_configs: bool,
+    pub sync_settings: bool,
+    pub sync_fields: BTreeSet<SyncField>,
+    pub file_servers: Vec<Url>,
+    pub max_image_dimension: Option<u32>,
+    pub auto_load_media: bool,
 }
 
 /// Authentication mode
@@ -91,6 +116,17 @@ impl SignerKind {
 pub struct RoomConfig {
     backup: bool,
     signer_kind: SignerKind,
+    #[serde(default)]
+    link_previews: bool,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    folder: Option<String>,
+    /// Last time the config was changed, used to merge configs from other devices
+    #[serde(default)]
+    updated_at: u64,
 }
 
 impl RoomConfig {
@@ -98,6 +134,11 @@ impl RoomConfig {
         Self {
             backup: true,
             signer_kind: SignerKind::Auto,
+            link_previews: false,
+            pinned: false,
+            archived: false,
+            folder: None,
+            updated_at: 0,
         }
     }
 
@@ -120,20 +161,86 @@ impl RoomConfig {
     pub fn set_signer_kind(&mut self, kind: &SignerKind) {
         self.signer_kind = kind.to_owned();
     }
+
+    /// Get link previews config
+    pub fn link_previews(&self) -> bool {
+        self.link_previews
+    }
+
+    /// Set link previews config
+    pub fn toggle_link_previews(&mut self) {
+        self.link_previews = !self.link_previews;
+    }
+
+    /// Get pinned config
+    pub fn pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// Set pinned config
+    pub fn toggle_pinned(&mut self) {
+        self.pinned = !self.pinned;
+    }
+
+    /// Get archived config
+    pub fn archived(&self) -> bool {
+        self.archived
+    }
+
+    /// Set archived config
+    pub fn set_archived(&mut self, archived: bool) {
+        self.archived = archived;
+    }
+
+    /// Get folder config
+    pub fn folder(&self) -> Option<&str> {
+        self.folder.as_deref()
+    }
+
+    /// Set folder config
+    pub fn set_folder(&mut self, folder: Option<String>) {
+        self.folder = folder;
+    }
+
+    /// Get the last time the config was changed
+    pub fn updated_at(&self) -> u64 {
+        self.updated_at
+    }
+
+    /// Mark the config as changed now
+    pub fn touch(&mut self) {
+        self.updated_at = Timestamp::now().as_secs();
+    }
 }
 
 /// Settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
+    /// Version of the settings format
+    pub version: u32,
+
     /// Theme
     pub theme: Option<String>,
 
     /// Theme mode
     pub theme_mode: ThemeMode,
 
+    /// UI font scale
+    #[serde(default = "default_font_scale")]
+    pub font_scale: f32,
+
     /// Hide user avatars
     pub hide_avatar: bool,
 
+    /// Resize avatars with a remote service instead of processing them locally
+    #[serde(default)]
+    pub remote_resizer: bool,
+
+    /// Proxy for fetching avatars, the encoded picture URL is appended to it
+    #[serde(default)]
+    pub image_proxy: Option<Url>,
+
     /// Enable screening for unknown chat requests
     pub screening: bool,
 
@@ -146,21 +253,122 @@ pub struct Settings {
     /// Configuration for each chat room
     pub room_configs: HashMap<u64, RoomConfig>,
 
-    /// Server for blossom media attachments
-    pub file_server: Url,
+    /// Sync the organization of chat rooms across devices
+    #[serde(default)]
+    pub sync_room_configs: bool,
+
+    /// Sync the settings across devices
+    #[serde(default)]
+    pub sync_settings: bool,
+
+    /// Settings synced across devices
+    #[serde(default = "default_sync_fields")]
+    pub sync_fields: BTreeSet<SyncField>,
+
+    /// Last time each synced setting was changed, used to merge settings from other devices
+    #[serde(default)]
+    pub updated_at: BTreeMap<SyncField, u64>,
+
+    /// Last time the settings were merged with the ones from other devices
+    #[serde(default)]
+    pub synced_at: u64,
+
+    /// Blossom servers for media attachments, the first one is the primary server
+    pub file_servers: Vec<Url>,
+
+    /// Images larger than this are downsized before upload, `None` keeps the original size
+    #[serde(default = "default_max_image_dimension")]
+    pub max_image_dimension: Option<u32>,
+
+    /// Download the media linked in messages, media in chat requests is never loaded automatically
+    #[serde(default = "default_auto_load_media")]
+    pub auto_load_media: bool,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
+            version: SETTINGS_VERSION,
             theme: None,
             theme_mode: ThemeMode::default(),
+            font_scale: default_font_scale(),
             hide_avatar: false,
+            remote_resizer: false,
+            image_proxy: None,
             screening: true,
             auth_mode: AuthMode::default(),
             trusted_relays: HashSet::default(),
             room_configs: HashMap::default(),
-            file_server: Url::parse("https://blossom.band/").unwrap(),
+            sync_room_configs: false,
+            sync_settings: false,
+            sync_fields: default_sync_fields(),
+            updated_at: BTreeMap::default(),
+            synced_at: 0,
+            file_servers: vec![Url::parse("https://blossom.band/").unwrap()],
+            max_image_dimension: default_max_image_dimension(),
+            auto_load_media: default_auto_load_media(),
+        }
+    }
+}
+
+fn default_font_scale() -> f32 {
+    1.0
+}
+
+fn default_max_image_dimension() -> Option<u32> {
+    Some(2048)
+}
+
+fn default_auto_load_media() -> bool {
+    true
+}
+
+/// Get the path of the settings file
+fn settings_path() -> PathBuf {
+    config_dir().join(".settings")
+}
+
+/// Write the settings to a temporary file, then replace the settings file with it.
+///
+/// The settings file is never left partially written.
+async fn write_settings(content: String) -> Result<(), Error> {
+    let path = settings_path();
+    let tmp_path = path.with_extension("tmp");
+
+    let mut file = smol::fs::File::create(&tmp_path).await?;
+    file.write_all(content.as_bytes()).await?;
+    file.sync_all().await?;
+
+    smol::fs::rename(&tmp_path, &path).await?;
+
+    Ok(())
+}
+
+/// Read the settings file.
+///
+/// An unparseable file is moved to a backup, the returned message tells where.
+async fn read_settings() -> Result<(Settings, Option<String>), Error> {
+    let path = settings_path();
+
+    let content = match smol::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((Settings::default(), None)),
+        Err(e) => return Err(e.into()),
+    };
+
+    match parse_settings(&content) {
+        Ok(settings) => Ok((settings, None)),
+        Err(e) => {
+            // Keep the file, it would be overwritten by the next save
+            let backup = config_dir().join(format!(".settings.{}.bak", Timestamp::now()));
+            smol::fs::rename(&path, &backup).await?;
+
+            let message = format!(
+                "Your settings couldn't be loaded ({e}) and were reset. A backup was saved to {}",
+                backup.display()
+            );
+
+            Ok((Settings::default(), Some(message)))
         }
     }
 }
@@ -180,6 +388,18 @@ pub struct AppSettings {
     /// Settings
     values: Settings,
 
+    /// Settings changed on both this device and another one during the last sync
+    conflicts: Vec<SyncConflict>,
+
+    /// Whether the settings file was loaded, saving before would overwrite it
+    loaded: bool,
+
+    /// Content of the last save
+    saved: String,
+
+    /// Task writing the settings file
+    save_task: Option<Task<()>>,
+
     /// Event subscriptions
     _subscriptions: SmallVec<[Subscription; 2]>,
 }
@@ -212,6 +432,10 @@ impl AppSettings {
 
         Self {
             values: Settings::default(),
+            conflicts: vec![],
+            loaded: false,
+            saved: String::new(),
+            save_task: None,
             _subscriptions: subscriptions,
         }
     }
@@ -224,23 +448,47 @@ impl AppSettings {
 
     /// Load settings
     fn load(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        let task: Task<Result<Settings, Error>> = cx.background_spawn(async move {
-            let path = config_dir().join(".settings");
+        let task: Task<Result<(Settings, Option<String>), Error>> =
+            cx.background_spawn(async move { read_settings().await });
 
-            if let Ok(content) = smol::fs::read_to_string(&path).await {
-                Ok(serde_json::from_str(&content)?)
-            } else {
-                Err(anyhow!("Not found"))
-            }
-        });
+        // Read the user's imported themes off the main thread
+        let themes = cx.background_spawn(async move { ThemeRegistry::read_dir(themes_dir()) });
 
         cx.spawn_in(window, async move |this, cx| {
-            let settings = task.await.unwrap_or(Settings::default());
+            let result = task.await;
+            let themes = themes.await;
 
             // Update settings
             this.update_in(cx, |this, window, cx| {
-                this.set_settings(settings, cx);
+                // Register the user's imported themes before applying the theme
+                ThemeRegistry::global(cx).update(cx, |this, cx| {
+                    this.extend(themes, cx);
+                });
+
+                match result {
+                    Ok((settings, warning)) => {
+                        if let Some(message) = warning {
+                            window.push_notification(
+                                Notification::warning(message).autohide(false),
+                                cx,
+                            );
+                        }
+
+                        this.loaded = true;
+                        this.set_settings(settings, cx);
+                    }
+                    Err(e) => {
+                        // Changes are not saved, the settings file may still be readable later
+                        window.push_notification(
+                            Notification::error(format!("Failed to read settings: {e}"))
+                                .autohide(false),
+                            cx,
+                        );
+                    }
+                }
+
                 this.apply_theme(window, cx);
+                this.apply_font_scale(window, cx);
             })
             .ok();
         })
@@ -249,19 +497,38 @@ impl AppSettings {
 
     /// Save settings
     pub fn save(&mut self, cx: &mut Context<Self>) {
-        let settings = self.values.clone();
+        // Never replace the settings file before it's loaded
+        if !self.loaded {
+            return;
+        }
 
-        let task: Task<Result<(), Error>> = cx.background_spawn(async move {
-            let path = config_dir().join(".settings");
-            let content = serde_json::to_string(&settings)?;
+        let content = match serde_json::to_string(&self.values) {
+            Ok(content) => content,
+            Err(e) => {
+                log::error!("Failed to serialize settings: {e}");
+                return;
+            }
+        };
 
-            // Write settings to file
-            smol::fs::write(&path, content).await?;
+        // Skip if nothing changed since the last save
+        if content == self.saved {
+            return;
+        }
 
-            Ok(())
-        });
+        self.saved = content.clone();
+
+        let previous = self.save_task.take();
 
-        task.detach();
+        self.save_task = Some(cx.background_spawn(async move {
+            // Wait for the previous save, so it can't replace a newer one
+            if let Some(previous) = previous {
+                previous.await;
+            }
+
+            if let Err(e) = write_settings(content).await {
+                log::error!("Failed to save settings: {e}");
+            }
+        }));
     }
 
     /// Set theme
@@ -271,6 +538,8 @@ impl AppSettings {
     {
         // Update settings
         self.values.theme = Some(theme.into());
+        self.values
+            .touch(SyncField::Theme, Timestamp::now().as_secs());
         cx.notify();
 
         // Apply the new theme
@@ -280,6 +549,8 @@ impl AppSettings {
     /// Reset theme
     pub fn reset_theme(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         self.values.theme = None;
+        self.values
+            .touch(SyncField::Theme, Timestamp::now().as_secs());
         cx.notify();
 
         self.apply_theme(window, cx);
@@ -289,9 +560,18 @@ impl AppSettings {
     pub fn apply_theme(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         if let Some(name) = self.values.theme.as_ref() {
             let mode = self.values.theme_mode;
-
-            if let Ok(new_theme) = ThemeFamily::from_assets(name) {
-                Theme::apply_theme(Rc::new(new_theme), Some(window), cx);
+            let registry = ThemeRegistry::global(cx);
+
+            // Prefer the registered theme, which also covers imported themes
+            let new_theme = registry
+                .read(cx)
+                .themes()
+                .get(name.as_str())
+                .cloned()
+                .or_else(|| ThemeFamily::from_assets(name).ok().map(Rc::new));
+
+            if let Some(new_theme) = new_theme {
+                Theme::apply_theme(new_theme, Some(window), cx);
                 Theme::change(mode, Some(window), cx);
             } else {
                 log::info!("Failed to load theme: {name}");
@@ -301,6 +581,36 @@ impl AppSettings {
         }
     }
 
+    /// Add the servers of the published server list missing from the local list.
+    ///
+    /// The local order is kept and the setting isn't marked as changed, so the
+    /// published list never overrides the synced settings. Returns the merged list.
+    pub fn merge_file_servers(&mut self, servers: Vec<Url>, cx: &mut Context<Self>) -> Vec<Url> {
+        for server in servers.into_iter() {
+            if !self.values.file_servers.contains(&server) {
+                self.values.file_servers.push(server);
+            }
+        }
+        cx.notify();
+
+        self.values.file_servers.clone()
+    }
+
+    /// Set the UI font scale
+    pub fn set_font_scale(&mut self, scale: f32, window: &mut Window, cx: &mut Context<Self>) {
+        self.values.font_scale = scale;
+        self.values
+            .touch(SyncField::FontScale, Timestamp::now().as_secs());
+        cx.notify();
+
+        self.apply_font_scale(window, cx);
+    }
+
+    /// Apply the UI font scale
+    pub fn apply_font_scale(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        Theme::set_font_scale(self.values.font_scale, Some(window), cx);
+    }
+
     /// Check if the given relay is already authenticated
     pub fn trusted_relay(&self, url: &RelayUrl, _cx: &App) -> bool {
         self.values.trusted_relays.iter().any(|relay| {
@@ -311,16 +621,80 @@ impl AppSettings {
     /// Add a relay to the trusted list
     pub fn add_trusted_relay(&mut self, url: &RelayUrl, cx: &mut Context<Self>) {
         self.values.trusted_relays.insert(url.clone());
+        self.values
+            .touch(SyncField::TrustedRelays, Timestamp::now().as_secs());
         cx.notify();
     }
 
     /// Add a room configuration
     pub fn add_room_config(&mut self, id: u64, config: RoomConfig, cx: &mut Context<Self>) {
-        self.values
-            .room_configs
-            .entry(id)
-            .and_modify(|this| *this = config)
-            .or_default();
+        self.values.room_configs.insert(id, config);
+        cx.notify();
+    }
+
+    /// Get the synced settings, to be published for the user's other devices
+    pub fn snapshot(&self) -> SettingsSnapshot {
+        self.values.snapshot()
+    }
+
+    /// Get the conflicts of the last sync
+    pub fn conflicts(&self) -> &[SyncConflict] {
+        &self.conflicts
+    }
+
+    /// Merge the settings published by the user's other devices
+    pub fn merge_remote(
+        &mut self,
+        remote: &SettingsSnapshot,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let (changed, conflicts) = self.values.merge(remote);
+
+        self.values.synced_at = Timestamp::now().as_secs();
+        self.conflicts = conflicts;
+
+        if changed {
+            self.apply_synced(window, cx);
+        }
+
+        cx.notify();
+    }
+
+    /// Resolve a sync conflict, optionally restoring the value dropped by the merge
+    pub fn resolve_conflict(
+        &mut self,
+        field: SyncField,
+        restore: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(ix) = self.conflicts.iter().position(|c| c.field == field) else {
+            return;
+        };
+
+        let conflict = self.conflicts.remove(ix);
+
+        if restore {
+            let value = conflict.dropped().clone();
+
+            if let Err(e) = self
+                .values
+                .set_synced(field, value, Timestamp::now().as_secs())
+            {
+                log::error!("Failed to restore setting: {e}");
+            }
+
+            self.apply_synced(window, cx);
+        }
+
         cx.notify();
     }
+
+    /// Apply the settings that may have changed from another device
+    fn apply_synced(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_theme(window, cx);
+        Theme::change(self.values.theme_mode, Some(window), cx);
+        self.apply_font_scale(window, cx);
+    }
 }
@@ -0,0 +1,227 @@
+use std::path::Path;
+
+use anyhow::{Error, anyhow};
+use serde_json::Value;
+use theme::user_theme_key;
+
+use crate::Settings;
+
+/// Current version of the settings format
+pub const SETTINGS_VERSION: u32 = 3;
+
+/// Migrations of the settings format, in order.
+///
+/// The migration at index `n` upgrades version `n` to `n + 1`. Append a new
+/// migration and bump [`SETTINGS_VERSION`] when renaming, removing or changing
+/// the type of a field. Added fields only need a default value.
+const MIGRATIONS: [fn(&mut Value) -> Result<(), Error>; SETTINGS_VERSION as usize] =
+    [migrate_v0, migrate_v1, migrate_v2];
+
+/// Settings written before versioning.
+///
+/// Unversioned settings have the same field names as version 1, the fields added
+/// over time are filled with their default values. A `null` left by a hand edit
+/// is dropped so the field falls back to its default instead of failing to parse.
+fn migrate_v0(value: &mut Value) -> Result<(), Error> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Settings are not an object"))?;
+
+    object.retain(|key, value| key == "theme" || key == "image_proxy" || !value.is_null());
+
+    Ok(())
+}
+
+/// Settings with a single media server.
+///
+/// The server becomes the primary server of the list.
+fn migrate_v1(value: &mut Value) -> Result<(), Error> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Settings are not an object"))?;
+
+    if let Some(server) = object.remove("file_server") {
+        object.insert("file_servers".into(), Value::Array(vec![server]));
+    }
+
+    Ok(())
+}
+
+/// Settings with an imported theme referenced by its file path.
+///
+/// The path only exists on this device, the theme is referenced by its id instead.
+fn migrate_v2(value: &mut Value) -> Result<(), Error> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Settings are not an object"))?;
+
+    if let Some(Value::String(theme)) = object.get_mut("theme") {
+        *theme = portable_theme(theme);
+    }
+
+    Ok(())
+}
+
+/// Get the key of an imported theme referenced by its file path
+pub(crate) fn portable_theme(theme: &str) -> String {
+    let path = Path::new(theme);
+
+    match path.file_stem() {
+        Some(id) if path.is_absolute() => user_theme_key(&id.to_string_lossy()),
+        _ => theme.to_string(),
+    }
+}
+
+/// Parse the content of the settings file, upgrading older formats.
+pub fn parse_settings(content: &str) -> Result<Settings, Error> {
+    let mut value: Value = serde_json::from_str(content)?;
+
+    let version = match value.get("version") {
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| anyhow!("Invalid settings version"))? as u32,
+        None => 0,
+    };
+
+    if version > SETTINGS_VERSION {
+        return Err(anyhow!(
+            "Settings version {version} was written by a newer version of Coop"
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(&mut value)?;
+    }
+
+    let mut settings: Settings = serde_json::from_value(value)?;
+    settings.version = SETTINGS_VERSION;
+
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use nostr_sdk::prelude::*;
+    use theme::ThemeMode;
+
+    use super::*;
+    use crate::{AuthMode, SignerKind, SyncField};
+
+    #[test]
+    fn test_parse_unversioned_settings() {
+        let settings = parse_settings(include_str!("../fixtures/settings_v0.json")).unwrap();
+
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.theme.as_deref(), Some("Catppuccin"));
+        assert_eq!(settings.theme_mode, ThemeMode::Dark);
+        assert!(settings.hide_avatar);
+        assert!(!settings.screening);
+        assert_eq!(settings.auth_mode, AuthMode::Manual);
+        assert_eq!(settings.trusted_relays.len(), 1);
+        assert_eq!(settings.file_servers.len(), 1);
+        assert_eq!(settings.file_servers[0].as_str(), "https://nostr.download/");
+
+        // Room configs from before pinning and folders keep their values
+        let config = settings.room_configs.get(&42).unwrap();
+        assert!(!config.backup());
+        assert_eq!(config.signer_kind(), &SignerKind::User);
+        assert!(!config.pinned());
+
+        // Fields added later get their defaults
+        assert_eq!(settings.font_scale, 1.0);
+        assert!(!settings.sync_room_configs);
+        assert_eq!(settings.sync_fields.len(), SyncField::ALL.len());
+    }
+
+    #[test]
+    fn test_parse_unversioned_settings_with_later_fields() {
+        let settings =
+            parse_settings(include_str!("../fixtures/settings_v0_room_configs.json")).unwrap();
+
+        assert_eq!(settings.font_scale, 1.25);
+        assert!(settings.sync_room_configs);
+        assert!(settings.remote_resizer);
+        assert!(settings.image_proxy.is_none());
+
+        let config = settings.room_configs.get(&7).unwrap();
+        assert!(config.pinned());
+        assert_eq!(config.folder(), Some("Work"));
+        assert_eq!(config.updated_at(), 1735689600);
+
+        // Null values fall back to the defaults
+        assert_eq!(settings.hide_avatar, Settings::default().hide_avatar);
+    }
+
+    #[test]
+    fn test_parse_single_file_server() {
+        let content = r#"{
+            "version": 1,
+            "file_server": "https://cdn.example.com/",
+            "sync_fields": ["theme", "file_server"],
+            "updated_at": { "file_server": 1735689600 }
+        }"#;
+        let settings = parse_settings(content).unwrap();
+
+        assert_eq!(settings.file_servers.len(), 1);
+        assert_eq!(
+            settings.file_servers[0].as_str(),
+            "https://cdn.example.com/"
+        );
+
+        // Synced fields keep tracking the servers
+        assert!(settings.sync_fields.contains(&SyncField::FileServers));
+        assert_eq!(
+            settings.updated_at.get(&SyncField::FileServers),
+            Some(&1735689600)
+        );
+    }
+
+    #[test]
+    fn test_parse_imported_theme_path() {
+        let content = r#"{
+            "version": 2,
+            "theme": "/home/user/.config/coop/themes/solarized.json"
+        }"#;
+        let settings = parse_settings(content).unwrap();
+
+        assert_eq!(settings.theme.as_deref(), Some("user:solarized"));
+
+        // Bundled themes are kept as is
+        let content = r#"{ "version": 2, "theme": "themes/catppuccin.json" }"#;
+        let settings = parse_settings(content).unwrap();
+
+        assert_eq!(settings.theme.as_deref(), Some("themes/catppuccin.json"));
+    }
+
+    #[test]
+    fn test_parse_current_settings() {
+        let mut settings = Settings::default();
+        settings.theme_mode = ThemeMode::Dark;
+        settings.sync_fields = BTreeSet::from([SyncField::Theme]);
+        settings.file_servers = vec![
+            Url::parse("https://cdn.example.com/").unwrap(),
+            Url::parse("https://blossom.band/").unwrap(),
+        ];
+
+        let content = serde_json::to_string(&settings).unwrap();
+        let parsed = parse_settings(&content).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(parsed).unwrap(),
+            serde_json::to_value(settings).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_settings() {
+        assert!(parse_settings("{\"theme\": ").is_err());
+        assert!(parse_settings("[]").is_err());
+        assert!(parse_settings("{\"screening\": \"yes\"}").is_err());
+
+        // Settings from a newer version are not silently downgraded
+        let newer = format!("{{\"version\": {}}}", SETTINGS_VERSION + 1);
+        assert!(parse_settings(&newer).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Settings, portable_theme};
+
+/// Settings that can be synced across devices.
+///
+/// Room configs are left out, they are synced per room with `sync_room_configs`.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncField {
+    Theme,
+    ThemeMode,
+    FontScale,
+    HideAvatar,
+    RemoteResizer,
+    ImageProxy,
+    Screening,
+    AuthMode,
+    TrustedRelays,
+    /// Synced as a single server before the server list
+    #[serde(alias = "file_server")]
+    FileServers,
+}
+
+impl SyncField {
+    pub const ALL: [SyncField; 10] = [
+        SyncField::Theme,
+        SyncField::ThemeMode,
+        SyncField::FontScale,
+        SyncField::HideAvatar,
+        SyncField::RemoteResizer,
+        SyncField::ImageProxy,
+        SyncField::Screening,
+        SyncField::AuthMode,
+        SyncField::TrustedRelays,
+        SyncField::FileServers,
+    ];
+
+    /// Get the field from the name of a settings field
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.name() == name)
+    }
+
+    /// Get the name of the settings field
+    pub fn name(&self) -> &'static str {
+        match self {
+            SyncField::Theme => "theme",
+            SyncField::ThemeMode => "theme_mode",
+            SyncField::FontScale => "font_scale",
+            SyncField::HideAvatar => "hide_avatar",
+            SyncField::RemoteResizer => "remote_resizer",
+            SyncField::ImageProxy => "image_proxy",
+            SyncField::Screening => "screening",
+            SyncField::AuthMode => "auth_mode",
+            SyncField::TrustedRelays => "trusted_relays",
+            SyncField::FileServers => "file_servers",
+        }
+    }
+
+    /// Get the label shown in the preferences
+    pub fn label(&self) -> &'static str {
+        match self {
+            SyncField::Theme => "Theme",
+            SyncField::ThemeMode => "Theme mode",
+            SyncField::FontScale => "Font size",
+            SyncField::HideAvatar => "Hide user avatar",
+            SyncField::RemoteResizer => "Remote image resizer",
+            SyncField::ImageProxy => "Avatar proxy",
+            SyncField::Screening => "Screening",
+            SyncField::AuthMode => "Relay authentication",
+            SyncField::TrustedRelays => "Trusted relays",
+            SyncField::FileServers => "Media servers",
+        }
+    }
+
+    /// Get the value of the field
+    fn get(&self, settings: &Settings) -> Value {
+        let value = match self {
+            SyncField::Theme => serde_json::to_value(&settings.theme),
+            SyncField::ThemeMode => serde_json::to_value(settings.theme_mode),
+            SyncField::FontScale => serde_json::to_value(settings.font_scale),
+            SyncField::HideAvatar => serde_json::to_value(settings.hide_avatar),
+            SyncField::RemoteResizer => serde_json::to_value(settings.remote_resizer),
+            SyncField::ImageProxy => serde_json::to_value(&settings.image_proxy),
+            SyncField::Screening => serde_json::to_value(settings.screening),
+            SyncField::AuthMode => serde_json::to_value(&settings.auth_mode),
+            SyncField::TrustedRelays => serde_json::to_value(&settings.trusted_relays),
+            SyncField::FileServers => serde_json::to_value(&settings.file_servers),
+        };
+
+        value.unwrap_or_default()
+    }
+
+    /// Set the value of the field
+    fn set(&self, settings: &mut Settings, value: Value) -> Result<(), Error> {
+        match self {
+            SyncField::Theme => {
+                // Older versions synced the path of imported themes
+                let theme: Option<String> = serde_json::from_value(value)?;
+                settings.theme = theme.as_deref().map(portable_theme);
+            }
+            SyncField::ThemeMode => settings.theme_mode = serde_json::from_value(value)?,
+            SyncField::FontScale => settings.font_scale = serde_json::from_value(value)?,
+            SyncField::HideAvatar => settings.hide_avatar = serde_json::from_value(value)?,
+            SyncField::RemoteResizer => settings.remote_resizer = serde_json::from_value(value)?,
+            SyncField::ImageProxy => settings.image_proxy = serde_json::from_value(value)?,
+            SyncField::Screening => settings.screening = serde_json::from_value(value)?,
+            SyncField::AuthMode => settings.auth_mode = serde_json::from_value(value)?,
+            SyncField::TrustedRelays => settings.trusted_relays = serde_json::from_value(value)?,
+            SyncField::FileServers => settings.file_servers = serde_json::from_value(value)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Sync all settings by default
+pub(crate) fn default_sync_fields() -> BTreeSet<SyncField> {
+    SyncField::ALL.into_iter().collect()
+}
+
+/// A synced setting with the last time it was changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncedValue {
+    pub value: Value,
+    pub updated_at: u64,
+}
+
+/// Settings published for the user's other devices
+pub type SettingsSnapshot = BTreeMap<SyncField, SyncedValue>;
+
+/// A setting changed on both this device and another one since the last sync
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncConflict {
+    pub field: SyncField,
+
+    /// Value on this device before the merge
+    pub local: Value,
+
+    /// Value from the other device
+    pub remote: Value,
+
+    /// Whether the value from the other device was kept
+    pub remote_won: bool,
+}
+
+impl SyncConflict {
+    /// Get the value that was dropped by the merge
+    pub fn dropped(&self) -> &Value {
+        if self.remote_won {
+            &self.local
+        } else {
+            &self.remote
+        }
+    }
+}
+
+/// Format a synced value for display
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "None".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(display_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+impl Settings {
+    /// Get the synced settings with the last time they were changed
+    pub fn snapshot(&self) -> SettingsSnapshot {
+        self.sync_fields
+            .iter()
+            .map(|field| {
+                let value = SyncedValue {
+                    value: field.get(self),
+                    updated_at: self.updated_at.get(field).copied().unwrap_or_default(),
+                };
+                (*field, value)
+            })
+            .collect()
+    }
+
+    /// Mark a setting as changed at the given time
+    pub(crate) fn touch(&mut self, field: SyncField, now: u64) {
+        self.updated_at.insert(field, now);
+    }
+
+    /// Set a synced setting to a new value
+    pub(crate) fn set_synced(
+        &mut self,
+        field: SyncField,
+        value: Value,
+        now: u64,
+    ) -> Result<(), Error> {
+        field.set(self, value)?;
+        self.touch(field, now);
+
+        Ok(())
+    }
+
+    /// Merge the settings from another device.
+    ///
+    /// The most recently changed value wins for each setting. Settings changed on
+    /// both devices since the last sync are returned as conflicts.
+    /// Returns true if the local settings changed.
+    pub fn merge(&mut self, remote: &SettingsSnapshot) -> (bool, Vec<SyncConflict>) {
+        let mut changed = false;
+        let mut conflicts = vec![];
+
+        for (field, remote) in remote.iter() {
+            if !self.sync_fields.contains(field) {
+                continue;
+            }
+
+            let local_value = field.get(self);
+            let local_at = self.updated_at.get(field).copied().unwrap_or_default();
+
+            if local_value == remote.value {
+                continue;
+            }
+
+            let remote_won = remote.updated_at > local_at;
+            let local_changed = local_at > self.synced_at;
+
+            if local_changed && remote.updated_at > self.synced_at {
+                conflicts.push(SyncConflict {
+                    field: *field,
+                    local: local_value,
+                    remote: remote.value.clone(),
+                    remote_won,
+                });
+            }
+
+            if remote_won && field.set(self, remote.value.clone()).is_ok() {
+                self.updated_at.insert(*field, remote.updated_at);
+                changed = true;
+            }
+        }
+
+        (changed, conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use theme::ThemeMode;
+
+    use super::*;
+
+    fn synced(value: impl Serialize, updated_at: u64) -> SyncedValue {
+        SyncedValue {
+            value: serde_json::to_value(value).unwrap(),
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn test_merge_settings() {
+        let mut local = Settings::default();
+        local.synced_at = 100;
+        local.touch(SyncField::HideAvatar, 50);
+        local
+            .set_synced(SyncField::Screening, false.into(), 150)
+            .unwrap();
+        local
+            .set_synced(SyncField::FontScale, 1.25.into(), 200)
+            .unwrap();
+
+        let remote = SettingsSnapshot::from([
+            (SyncField::ThemeMode, synced(ThemeMode::Dark, 10)),
+            (SyncField::HideAvatar, synced(true, 120)),
+            (SyncField::Screening, synced(true, 180)),
+            (SyncField::FontScale, synced(1.5, 160)),
+        ]);
+
+        let (changed, conflicts) = local.merge(&remote);
+        assert!(changed);
+
+        // Untouched and older local settings are replaced
+        assert_eq!(local.theme_mode, ThemeMode::Dark);
+        assert!(local.hide_avatar);
+
+        // The most recent change wins
+        assert!(local.screening);
+        assert_eq!(local.font_scale, 1.25);
+
+        // Settings changed on both sides since the last sync are conflicts
+        let fields: Vec<_> = conflicts.iter().map(|c| (c.field, c.remote_won)).collect();
+        assert_eq!(
+            fields,
+            vec![(SyncField::FontScale, false), (SyncField::Screening, true)]
+        );
+
+        // Merging the merged settings changes nothing
+        let snapshot = local.snapshot();
+        assert_eq!(local.merge(&snapshot), (false, vec![]));
+    }
+
+    #[test]
+    fn test_merge_skips_unsynced_fields() {
+        let mut local = Settings::default();
+        local.sync_fields.remove(&SyncField::Screening);
+
+        let remote = SettingsSnapshot::from([(SyncField::Screening, synced(false, 10))]);
+
+        assert_eq!(local.merge(&remote), (false, vec![]));
+        assert!(local.screening);
+        assert!(!local.snapshot().contains_key(&SyncField::Screening));
+    }
+}
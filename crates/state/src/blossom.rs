@@ -1,27 +1,524 @@
+use std::io;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::task::Poll;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Error};
+use anyhow::{Context as AnyhowContext, Error, anyhow};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use gpui::AsyncApp;
-use gpui_tokio::Tokio;
+use gpui::http_client::{AsyncBody, HttpClient, Method, Request, Response, StatusCode};
 use mime_guess::from_path;
 use nostr_blossom::prelude::*;
+use nostr_sdk::hashes::HashEngine;
 use nostr_sdk::prelude::*;
+use smol::io::{AsyncRead, AsyncReadExt};
 
-pub async fn upload(server: Url, path: PathBuf, cx: &AsyncApp) -> Result<Url, Error> {
-    let content_type = from_path(&path).first_or_octet_stream().to_string();
-    let data = smol::fs::read(path).await?;
+use crate::TIMEOUT;
+
+/// User's Blossom server list (BUD-03)
+pub const BLOSSOM_SERVER_LIST: Kind = Kind::Custom(10063);
+
+/// Blossom authorization event (BUD-01)
+const BLOSSOM_AUTH: Kind = Kind::Custom(24242);
+
+/// Lifetime of an authorization event, in seconds
+const AUTH_EXPIRATION: u64 = 5 * 60;
+
+/// Size of the chunks read when hashing a file
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum time to wait for a server health check
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Progress of an upload, shared between the upload task and the UI.
+#[derive(Debug, Clone, Default)]
+pub struct UploadProgress {
+    sent: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl UploadProgress {
+    /// Get the number of bytes sent
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Get the size of the file, 0 until the file is opened
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Get the sent fraction, between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        match self.total() {
+            0 => 0.,
+            total => (self.sent() as f64 / total as f64).min(1.) as f32,
+        }
+    }
+
+    /// Stop the upload, the request body fails on the next read
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Check if the upload was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Restart the progress, when retrying on another server
+    fn restart(&self, total: u64) {
+        self.sent.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+}
+
+/// Reader counting the bytes sent in the request body
+struct ProgressReader<R> {
+    inner: R,
+    progress: UploadProgress,
+}
+
+impl<R> AsyncRead for ProgressReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.progress.is_cancelled() {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "Upload cancelled",
+            )));
+        }
+
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(read)) = &poll {
+            self.progress
+                .sent
+                .fetch_add(*read as u64, Ordering::Relaxed);
+        }
+
+        poll
+    }
+}
+
+/// A file to upload, hashed without loading it in memory.
+#[derive(Debug, Clone)]
+pub struct UploadFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub content_type: String,
+    pub hash: Sha256Hash,
+}
+
+impl UploadFile {
+    /// Open and hash a file
+    pub async fn open(path: PathBuf) -> Result<Self, Error> {
+        let content_type = from_path(&path).first_or_octet_stream().to_string();
+        let mut file = smol::fs::File::open(&path).await?;
+        let size = file.metadata().await?.len();
+
+        let mut engine = Sha256Hash::engine();
+        let mut buf = vec![0; HASH_CHUNK_SIZE];
+
+        loop {
+            let read = file.read(&mut buf).await?;
+
+            if read == 0 {
+                break;
+            }
+
+            engine.input(&buf[..read]);
+        }
+
+        Ok(Self {
+            path,
+            size,
+            content_type,
+            hash: Sha256Hash::from_engine(engine),
+        })
+    }
+}
+
+/// Upload a file to the first available server, then mirror it to the others.
+///
+/// Servers are tried in order, the returned URL points to the server that
+/// accepted the file. Mirroring runs in the background.
+pub async fn upload(
+    servers: Vec<Url>,
+    path: PathBuf,
+    progress: UploadProgress,
+    http_client: Arc<dyn HttpClient>,
+    cx: &AsyncApp,
+) -> Result<Url, Error> {
+    let file = UploadFile::open(path).await?;
+
+    upload_file(servers, &file, progress, http_client, cx).await
+}
+
+/// Upload an opened file to the first available server, then mirror it to the others.
+pub async fn upload_file(
+    servers: Vec<Url>,
+    file: &UploadFile,
+    progress: UploadProgress,
+    http_client: Arc<dyn HttpClient>,
+    cx: &AsyncApp,
+) -> Result<Url, Error> {
     let keys = Keys::generate();
 
-    // Construct the blossom client
-    let client = BlossomClient::new(server);
+    let mut error = anyhow!("No media server configured");
+
+    for server in servers.iter() {
+        // Ask the server if it accepts the file before sending it
+        if let Err(e) = check_upload(server, file, &keys, &http_client).await {
+            log::warn!("{server} rejected the upload: {e}");
+            error = anyhow!("{server} rejected the upload: {e}");
+            continue;
+        }
+
+        match upload_blob(server, file, &keys, &progress, &http_client).await {
+            Ok(url) => {
+                let mirrors: Vec<Url> = servers.iter().filter(|s| *s != server).cloned().collect();
+                let hash = file.hash;
+
+                cx.background_executor()
+                    .spawn({
+                        let url = url.clone();
+                        async move {
+                            for mirror in mirrors.iter() {
+                                if let Err(e) =
+                                    mirror_blob(mirror, &url, hash, &keys, &http_client).await
+                                {
+                                    log::warn!("Failed to mirror {url} to {mirror}: {e}");
+                                }
+                            }
+                        }
+                    })
+                    .detach();
+
+                return Ok(url);
+            }
+            Err(_) if progress.is_cancelled() => {
+                return Err(anyhow!("Upload cancelled"));
+            }
+            Err(e) => {
+                log::warn!("Failed to upload to {server}: {e}");
+                error = e;
+            }
+        }
+    }
+
+    Err(error)
+}
+
+/// Get the URL of an endpoint of a server.
+///
+/// Servers may be saved without a trailing slash, which `join` would replace by the endpoint.
+fn endpoint(server: &Url, path: &str) -> Result<Url, Error> {
+    let mut base = server.clone();
+
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+
+    Ok(base.join(path)?)
+}
+
+/// Check if a server accepts a file, from its size and type (BUD-06)
+pub async fn check_upload(
+    server: &Url,
+    file: &UploadFile,
+    keys: &Keys,
+    http_client: &Arc<dyn HttpClient>,
+) -> Result<(), Error> {
+    let request = Request::builder()
+        .method(Method::HEAD)
+        .uri(endpoint(server, "upload")?.as_str())
+        .header(
+            "Authorization",
+            authorization("Upload blob", file.hash, keys)?,
+        )
+        .header("X-SHA-256", file.hash.to_string())
+        .header("X-Content-Length", file.size)
+        .header("X-Content-Type", file.content_type.as_str())
+        .body(AsyncBody::default())?;
+
+    let res = http_client.send(request).await?;
+
+    match res.status() {
+        status if status.is_success() => Ok(()),
+        // The server doesn't support upload requirements
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => {
+            Ok(())
+        }
+        StatusCode::PAYLOAD_TOO_LARGE => Err(anyhow!(
+            "File is too large ({})",
+            reason(&res).unwrap_or_else(|| format!("{} bytes", file.size))
+        )),
+        status => Err(anyhow!(
+            "{}",
+            reason(&res).unwrap_or_else(|| status.to_string())
+        )),
+    }
+}
+
+/// Upload a blob to a server, streaming the file (BUD-02)
+async fn upload_blob(
+    server: &Url,
+    file: &UploadFile,
+    keys: &Keys,
+    progress: &UploadProgress,
+    http_client: &Arc<dyn HttpClient>,
+) -> Result<Url, Error> {
+    let reader = ProgressReader {
+        inner: smol::fs::File::open(&file.path).await?,
+        progress: progress.clone(),
+    };
+
+    progress.restart(file.size);
+
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri(endpoint(server, "upload")?.as_str())
+        .header(
+            "Authorization",
+            authorization("Upload blob", file.hash, keys)?,
+        )
+        .header("Content-Type", file.content_type.as_str())
+        .header("Content-Length", file.size)
+        .body(AsyncBody::from_reader(reader))?;
+
+    let mut res = http_client.send(request).await?;
+
+    if !res.status().is_success() {
+        return Err(anyhow!(
+            "Upload error: {}",
+            reason(&res).unwrap_or_else(|| res.status().to_string())
+        ));
+    }
+
+    let mut body = Vec::new();
+    res.body_mut().read_to_end(&mut body).await?;
+
+    let blob: BlobDescriptor = serde_json::from_slice(&body)?;
+
+    Ok(blob.url)
+}
+
+/// Get the reason of a rejected request
+fn reason(res: &Response<AsyncBody>) -> Option<String> {
+    res.headers()
+        .get("X-Reason")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+/// Construct the authorization header for a blob (BUD-01)
+fn authorization(content: &str, hash: Sha256Hash, keys: &Keys) -> Result<String, Error> {
+    let expiration = Timestamp::from(Timestamp::now().as_secs() + AUTH_EXPIRATION);
+
+    let event = EventBuilder::new(BLOSSOM_AUTH, content)
+        .tags([
+            Tag::hashtag("upload"),
+            Tag::custom(
+                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::X)),
+                [hash.to_string()],
+            ),
+            Tag::expiration(expiration),
+        ])
+        .sign_with_keys(keys)?;
+
+    Ok(format!("Nostr {}", BASE64_STANDARD.encode(event.as_json())))
+}
+
+/// Ask a server to copy a blob from another server (BUD-04)
+pub async fn mirror_blob(
+    server: &Url,
+    url: &Url,
+    hash: Sha256Hash,
+    keys: &Keys,
+    http_client: &Arc<dyn HttpClient>,
+) -> Result<(), Error> {
+    let body = serde_json::json!({ "url": url }).to_string();
+
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri(endpoint(server, "mirror")?.as_str())
+        .header("Authorization", authorization("Mirror blob", hash, keys)?)
+        .header("Content-Type", "application/json")
+        .body(AsyncBody::from(body))?;
+
+    let res = http_client.send(request).await?;
+
+    if !res.status().is_success() {
+        return Err(anyhow!("Server returned error: {}", res.status()));
+    }
+
+    Ok(())
+}
+
+/// Check if a server is reachable, returns the response time
+pub async fn check_server(
+    server: &Url,
+    http_client: &Arc<dyn HttpClient>,
+) -> Result<Duration, Error> {
+    let started = Instant::now();
+    let res = smol::future::or(
+        async {
+            let res = http_client
+                .get(server.as_str(), AsyncBody::default(), true)
+                .await?;
+            Ok(res)
+        },
+        async {
+            smol::Timer::after(CHECK_TIMEOUT).await;
+            Err(anyhow!("Timed out"))
+        },
+    )
+    .await?;
+
+    // Blossom doesn't define what the root serves, only server errors mean it's down
+    if res.status().is_server_error() {
+        return Err(anyhow!("Server returned error: {}", res.status()));
+    }
+
+    Ok(started.elapsed())
+}
+
+/// Get the URLs of a blob on other servers, by its sha256 hash (BUD-01)
+pub fn blob_urls(hash: &str, extension: Option<&str>, servers: &[Url]) -> Vec<Url> {
+    let name = match extension {
+        Some(ext) => format!("{hash}.{ext}"),
+        None => hash.to_string(),
+    };
+
+    servers
+        .iter()
+        .filter_map(|server| endpoint(server, &name).ok())
+        .collect()
+}
+
+/// Get the servers of a BUD-03 server list, in order of preference
+pub fn parse_server_list(event: &Event) -> Vec<Url> {
+    let mut servers: Vec<Url> = vec![];
+
+    for tag in event.tags.iter() {
+        let values = tag.as_slice();
+
+        if values.first().map(|v| v.as_str()) != Some("server") {
+            continue;
+        }
+
+        if let Some(url) = values.get(1).and_then(|v| Url::parse(v).ok())
+            && !servers.contains(&url)
+        {
+            servers.push(url);
+        }
+    }
+
+    servers
+}
+
+/// Publish the user's server list to the user's relays.
+pub async fn publish_server_list(client: &Client, servers: &[Url]) -> Result<(), Error> {
+    let tags = servers
+        .iter()
+        .map(|server| Tag::custom(TagKind::custom("server"), [server.to_string()]));
+
+    // Construct the server list event
+    let builder = EventBuilder::new(BLOSSOM_SERVER_LIST, "").tags(tags);
+
+    // Sign the event with user's signer
+    let event = client.sign_event_builder(builder).await?;
+
+    // Send the event to write relays
+    client
+        .send_event(&event)
+        .to_nip65()
+        .ack_policy(AckPolicy::none())
+        .await?;
+
+    Ok(())
+}
+
+/// Get the latest server list of a person from relays.
+pub async fn fetch_server_list(
+    client: &Client,
+    public_key: PublicKey,
+) -> Result<Option<Vec<Url>>, Error> {
+    let filter = Filter::new()
+        .kind(BLOSSOM_SERVER_LIST)
+        .author(public_key)
+        .limit(1);
+
+    // Stream events from the person's write relays
+    let mut stream = client
+        .stream_events(filter)
+        .timeout(Duration::from_secs(TIMEOUT))
+        .await?;
+
+    let mut latest: Option<Event> = None;
+
+    while let Some((_url, res)) = stream.next().await {
+        if let Ok(event) = res
+            && latest
+                .as_ref()
+                .is_none_or(|e| event.created_at > e.created_at)
+        {
+            latest = Some(event);
+        }
+    }
+
+    Ok(latest.map(|event| parse_server_list(&event)))
+}
+
+/// Get the server list of a person, from the database or relays.
+pub async fn get_server_list(client: &Client, public_key: PublicKey) -> Result<Vec<Url>, Error> {
+    let filter = Filter::new()
+        .kind(BLOSSOM_SERVER_LIST)
+        .author(public_key)
+        .limit(1);
+
+    if let Some(event) = client.database().query(filter).await?.first_owned() {
+        return Ok(parse_server_list(&event));
+    }
+
+    fetch_server_list(client, public_key)
+        .await?
+        .context("Server list not found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Tokio::spawn(cx, async move {
-        let blob = client
-            .upload_blob(data, Some(content_type), None, Some(&keys))
-            .await?;
+    #[test]
+    fn test_endpoint() {
+        let server = Url::parse("https://cdn.example.com").unwrap();
+        let nested = Url::parse("https://example.com/blossom").unwrap();
+        let slash = Url::parse("https://example.com/blossom/").unwrap();
 
-        Ok(blob.url)
-    })
-    .await
-    .map_err(|e| anyhow!("Upload error: {e}"))?
+        assert_eq!(
+            endpoint(&server, "upload").unwrap().as_str(),
+            "https://cdn.example.com/upload"
+        );
+        assert_eq!(
+            endpoint(&nested, "upload").unwrap().as_str(),
+            "https://example.com/blossom/upload"
+        );
+        assert_eq!(
+            endpoint(&slash, "mirror").unwrap().as_str(),
+            "https://example.com/blossom/mirror"
+        );
+    }
 }
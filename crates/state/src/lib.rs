@@ -14,14 +14,18 @@ use nostr_sdk::prelude::*;
 mod blossom;
 mod constants;
 mod device;
+mod media;
 mod nip05;
 mod signer;
+mod storage;
 
 pub use blossom::*;
 pub use constants::*;
 pub use device::*;
+pub use media::*;
 pub use nip05::*;
 pub use signer::*;
+pub use storage::*;
 
 pub fn init(window: &mut Window, cx: &mut App) {
     // rustls uses the `aws_lc_rs` provider by default
@@ -123,7 +127,14 @@ impl NostrRegistry {
 
         // Construct the nostr lmdb instance
         let lmdb = cx.foreground_executor().block_on(async move {
-            NostrLmdb::open(config_dir().join("nostr"))
+            // Compact the database before it's opened, if requested
+            match compact_database().await {
+                Ok(Some(count)) => log::info!("Compacted the database, {count} events copied"),
+                Ok(None) => {}
+                Err(e) => log::error!("Failed to compact the database: {e}"),
+            }
+
+            NostrLmdb::open(database_dir())
                 .await
                 .expect("Failed to initialize database")
         });
@@ -289,6 +300,44 @@ impl NostrRegistry {
         }
     }
 
+    /// Get the secret key for a given npub.
+    ///
+    /// Returns `None` if the npub uses a remote signer (NIP-46).
+    pub fn get_secret_key(
+        &self,
+        public_key: PublicKey,
+        cx: &App,
+    ) -> Task<Result<Option<SecretKey>, Error>> {
+        let npub = public_key.to_bech32().unwrap();
+        let key_path = self.key_dir.join(format!("{}.npub", npub));
+        let app_keys = self.app_keys.clone();
+
+        match std::fs::read_to_string(key_path) {
+            Ok(payload) if !payload.is_empty() => cx.background_spawn(async move {
+                if payload.starts_with("bunker://") {
+                    return Ok(None);
+                }
+
+                let decrypted = app_keys.nip44_decrypt(&public_key, &payload).await?;
+                let secret = SecretKey::parse(&decrypted)?;
+
+                Ok(Some(secret))
+            }),
+            _ => {
+                let read = cx.read_credentials(&npub);
+
+                cx.background_spawn(async move {
+                    let (_, secret) = read
+                        .await?
+                        .ok_or_else(|| anyhow!("Secret key not found for {npub}"))?;
+
+                    // NIP-46 URIs are stored as strings
+                    Ok(SecretKey::from_slice(&secret).ok())
+                })
+            }
+        }
+    }
+
     /// Get the secret for a given npub in the OS credentials store.
     #[deprecated = "Use get_secret instead"]
     fn get_secret_keyring(
@@ -0,0 +1,619 @@
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as AnyhowContext, Error, anyhow};
+use gpui::AsyncApp;
+use gpui::http_client::HttpClient;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits, RgbaImage};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{UploadFile, UploadProgress, upload, upload_file};
+
+/// Maximum width or height of an image decoded before upload, in pixels
+const MAX_DECODE_DIMENSION: u32 = 16384;
+
+/// Maximum width or height of a thumbnail, in pixels
+const THUMBNAIL_SIZE: u32 = 320;
+
+/// Maximum width or height of the image sampled for the blurhash, in pixels
+const BLURHASH_SAMPLE_SIZE: u32 = 64;
+
+/// Width of a decoded blurhash placeholder, in pixels
+const PLACEHOLDER_WIDTH: u32 = 32;
+
+/// Quality of re-encoded JPEG images
+const JPEG_QUALITY: u8 = 85;
+
+/// Characters of the base 83 encoding used by blurhash
+const BASE83: &[u8; 83] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// Media metadata sent in a NIP-92 `imeta` tag.
+///
+/// Lets recipients reserve space and show a placeholder before the media loads.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageMeta {
+    pub url: Url,
+    /// MIME type
+    pub mime: Option<String>,
+    /// SHA-256 hash of the file, in hex
+    pub hash: Option<String>,
+    /// Size of the file in bytes
+    pub size: Option<u64>,
+    /// Width and height, in pixels
+    pub dimensions: Option<(u32, u32)>,
+    pub blurhash: Option<String>,
+    /// URL of a smaller version of the image, or of the poster of a video
+    pub thumbnail: Option<Url>,
+}
+
+impl ImageMeta {
+    /// Parse an `imeta` tag, returns `None` for other tags or a missing URL
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        let values = tag.as_slice();
+
+        if values.first().map(|v| v.as_str()) != Some("imeta") {
+            return None;
+        }
+
+        let mut url = None;
+        let mut mime = None;
+        let mut hash = None;
+        let mut size = None;
+        let mut dimensions = None;
+        let mut blurhash = None;
+        let mut thumbnail = None;
+        let mut poster = None;
+
+        for (key, value) in values.iter().skip(1).filter_map(|v| v.split_once(' ')) {
+            match key {
+                "url" => url = Url::parse(value).ok(),
+                "m" => mime = Some(value.to_string()),
+                "x" => hash = Some(value.to_string()),
+                "size" => size = value.parse().ok(),
+                "dim" => dimensions = parse_dimensions(value),
+                "blurhash" => blurhash = Some(value.to_string()),
+                "thumb" => thumbnail = Url::parse(value).ok(),
+                "image" => poster = Url::parse(value).ok(),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            url: url?,
+            mime,
+            hash,
+            size,
+            dimensions,
+            blurhash,
+            thumbnail: thumbnail.or(poster),
+        })
+    }
+
+    /// Get the `imeta` tag
+    pub fn to_tag(&self) -> Tag {
+        let mut values = vec![format!("url {}", self.url)];
+
+        if let Some(mime) = self.mime.as_ref() {
+            values.push(format!("m {mime}"));
+        }
+
+        if let Some(hash) = self.hash.as_ref() {
+            values.push(format!("x {hash}"));
+        }
+
+        if let Some(size) = self.size {
+            values.push(format!("size {size}"));
+        }
+
+        if let Some((width, height)) = self.dimensions {
+            values.push(format!("dim {width}x{height}"));
+        }
+
+        if let Some(blurhash) = self.blurhash.as_ref() {
+            values.push(format!("blurhash {blurhash}"));
+        }
+
+        if let Some(thumbnail) = self.thumbnail.as_ref() {
+            values.push(format!("thumb {thumbnail}"));
+        }
+
+        Tag::custom(TagKind::custom("imeta"), values)
+    }
+
+    /// Render the blurhash as a PNG placeholder with the aspect ratio of the image
+    pub fn placeholder(&self) -> Option<Vec<u8>> {
+        let (width, height) = self.dimensions?;
+        let blurhash = self.blurhash.as_ref()?;
+
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        // Computed in u64, the dimensions come from other users
+        let placeholder_height = (PLACEHOLDER_WIDTH as u64 * height as u64 / width as u64)
+            .clamp(1, PLACEHOLDER_WIDTH as u64 * 4) as u32;
+        let pixels = decode_blurhash(blurhash, PLACEHOLDER_WIDTH, placeholder_height)?;
+        let image = RgbaImage::from_raw(PLACEHOLDER_WIDTH, placeholder_height, pixels)?;
+
+        let mut output = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+            .ok()?;
+
+        Some(output)
+    }
+}
+
+/// Parse dimensions written as `<width>x<height>`
+pub fn parse_dimensions(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+/// An image re-encoded for upload.
+#[derive(Debug, Clone)]
+pub struct ProcessedImage {
+    /// Re-encoded image, in the temporary directory
+    pub path: PathBuf,
+    /// Smaller version of the image, `None` if the image is already small
+    pub thumbnail: Option<PathBuf>,
+    /// Width and height after resizing, in pixels
+    pub dimensions: (u32, u32),
+    pub blurhash: String,
+}
+
+/// Check if a file is an image processed before upload.
+///
+/// Animated and vector images are uploaded as they are.
+pub fn is_processable(path: &Path) -> bool {
+    match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Tiff) => true,
+        Ok(ImageFormat::WebP) => !is_animated_webp(path).unwrap_or(true),
+        _ => false,
+    }
+}
+
+/// Check if a file is uploaded with its metadata, such as the location where it was taken.
+///
+/// HEIF photos and WebP images that are animated or unreadable can't be re-encoded.
+pub fn keeps_metadata(path: &Path) -> bool {
+    if is_processable(path) {
+        return false;
+    }
+
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    matches!(extension.as_str(), "heic" | "heif" | "avif" | "webp")
+}
+
+/// Check if a WebP image is animated
+fn is_animated_webp(path: &Path) -> Result<bool, Error> {
+    let reader = BufReader::new(File::open(path)?);
+
+    Ok(WebPDecoder::new(reader)?.has_animation())
+}
+
+/// Decode an image and re-encode it without its metadata.
+///
+/// The EXIF orientation is applied to the pixels, since the tag is dropped with
+/// the rest of the metadata (camera, location...). Images larger than the maximum
+/// dimension are downsized, keeping their aspect ratio.
+pub fn process_image(path: &Path, max_dimension: Option<u32>) -> Result<ProcessedImage, Error> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODE_DIMENSION);
+
+    let mut reader = ImageReader::open(path)?.with_guessed_format()?;
+    reader.limits(limits);
+
+    // Keep PNG images lossless
+    let png = reader.format() == Some(ImageFormat::Png);
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    if let Some(max) = max_dimension
+        && image.width().max(image.height()) > max
+    {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+
+    let name = temp_name(path);
+    let blurhash = image_blurhash(&image)?;
+
+    let thumbnail = if image.width().max(image.height()) > THUMBNAIL_SIZE {
+        let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        Some(write_image(&thumbnail, &format!("{name}-thumb"), png)?)
+    } else {
+        None
+    };
+
+    Ok(ProcessedImage {
+        path: write_image(&image, &name, png)?,
+        thumbnail,
+        dimensions: (image.width(), image.height()),
+        blurhash,
+    })
+}
+
+/// Get a unique name for the files written while processing an image
+fn temp_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    format!("coop-upload-{nanos}-{stem}")
+}
+
+/// Write an image to the temporary directory, as PNG if it has transparency or
+/// was a PNG, JPEG otherwise.
+///
+/// The encoders don't write any metadata.
+fn write_image(image: &DynamicImage, name: &str, png: bool) -> Result<PathBuf, Error> {
+    let png = png || image.color().has_alpha();
+    let extension = if png { "png" } else { "jpg" };
+    let path = std::env::temp_dir().join(format!("{name}.{extension}"));
+    let mut writer = BufWriter::new(File::create(&path)?);
+
+    if png {
+        image.write_to(&mut writer, ImageFormat::Png)?;
+    } else {
+        let encoder = JpegEncoder::new_with_quality(&mut writer, JPEG_QUALITY);
+        DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)?;
+    }
+
+    writer.flush()?;
+
+    Ok(path)
+}
+
+/// Compute the blurhash of an image, from a small sample
+fn image_blurhash(image: &DynamicImage) -> Result<String, Error> {
+    let sample = image
+        .thumbnail(BLURHASH_SAMPLE_SIZE, BLURHASH_SAMPLE_SIZE)
+        .to_rgba8();
+
+    // Use more components along the longest side
+    let (components_x, components_y) = if sample.width() >= sample.height() {
+        (4, 3)
+    } else {
+        (3, 4)
+    };
+
+    encode_blurhash(
+        components_x,
+        components_y,
+        sample.width(),
+        sample.height(),
+        sample.as_raw(),
+    )
+}
+
+/// Process an image and upload it with its thumbnail, other files are uploaded as they are.
+///
+/// Returns the URL of the file and, for images, the metadata for the `imeta` tag.
+/// The thumbnail is optional, failing to upload it doesn't fail the upload.
+pub async fn upload_media(
+    servers: Vec<Url>,
+    path: PathBuf,
+    max_dimension: Option<u32>,
+    progress: UploadProgress,
+    http_client: Arc<dyn HttpClient>,
+    cx: &AsyncApp,
+) -> Result<(Url, Option<ImageMeta>), Error> {
+    if !is_processable(&path) {
+        let url = upload(servers, path, progress, http_client, cx).await?;
+        return Ok((url, None));
+    }
+
+    // Never fall back to the original, it may contain the location of the user
+    let image = cx
+        .background_executor()
+        .spawn(async move { process_image(&path, max_dimension) })
+        .await
+        .context("Failed to process image")?;
+
+    let file = UploadFile::open(image.path.clone()).await?;
+    let result = upload_file(
+        servers.clone(),
+        &file,
+        progress.clone(),
+        http_client.clone(),
+        cx,
+    )
+    .await;
+
+    let mut thumbnail = None;
+
+    if let Some(path) = image.thumbnail.as_ref() {
+        if result.is_ok() && !progress.is_cancelled() {
+            match upload(
+                servers,
+                path.clone(),
+                UploadProgress::default(),
+                http_client,
+                cx,
+            )
+            .await
+            {
+                Ok(url) => thumbnail = Some(url),
+                Err(e) => log::warn!("Failed to upload thumbnail: {e}"),
+            }
+        }
+
+        smol::fs::remove_file(path).await.ok();
+    }
+
+    smol::fs::remove_file(&image.path).await.ok();
+
+    let url = result?;
+    let meta = ImageMeta {
+        url: url.clone(),
+        mime: Some(file.content_type),
+        hash: Some(file.hash.to_string()),
+        size: Some(file.size),
+        dimensions: Some(image.dimensions),
+        blurhash: Some(image.blurhash),
+        thumbnail,
+    };
+
+    Ok((url, Some(meta)))
+}
+
+/// Encode RGBA pixels as a blurhash.
+///
+/// See <https://github.com/woltapp/blurhash/blob/master/Algorithm.md>
+pub fn encode_blurhash(
+    components_x: u32,
+    components_y: u32,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> Result<String, Error> {
+    if !(1..=9).contains(&components_x) || !(1..=9).contains(&components_y) {
+        return Err(anyhow!("Blurhash components must be between 1 and 9"));
+    }
+
+    if width == 0 || height == 0 || rgba.len() != (width * height * 4) as usize {
+        return Err(anyhow!("Invalid image size"));
+    }
+
+    let mut factors = Vec::with_capacity((components_x * components_y) as usize);
+
+    for j in 0..components_y {
+        for i in 0..components_x {
+            let normalisation = if i == 0 && j == 0 { 1. } else { 2. };
+            let mut factor = [0.; 3];
+
+            for y in 0..height {
+                for x in 0..width {
+                    let basis = (PI * i as f32 * x as f32 / width as f32).cos()
+                        * (PI * j as f32 * y as f32 / height as f32).cos();
+                    let offset = ((y * width + x) * 4) as usize;
+
+                    for (c, value) in factor.iter_mut().enumerate() {
+                        *value += basis * srgb_to_linear(rgba[offset + c]);
+                    }
+                }
+            }
+
+            let scale = normalisation / (width * height) as f32;
+            factors.push(factor.map(|value| value * scale));
+        }
+    }
+
+    let mut hash = String::new();
+    let (dc, ac) = factors.split_first().unwrap();
+
+    encode83((components_x - 1) + (components_y - 1) * 9, 1, &mut hash);
+
+    let max_value = if ac.is_empty() {
+        encode83(0, 1, &mut hash);
+        1.
+    } else {
+        let actual_max = ac.iter().flatten().fold(0f32, |max, v| max.max(v.abs()));
+        let quantised = (actual_max * 166. - 0.5).floor().clamp(0., 82.) as u32;
+
+        encode83(quantised, 1, &mut hash);
+        (quantised + 1) as f32 / 166.
+    };
+
+    let [r, g, b] = dc.map(linear_to_srgb);
+    encode83(
+        ((r as u32) << 16) + ((g as u32) << 8) + b as u32,
+        4,
+        &mut hash,
+    );
+
+    for factor in ac.iter() {
+        let [r, g, b] = factor.map(|value| {
+            (sign_pow(value / max_value, 0.5) * 9. + 9.5)
+                .floor()
+                .clamp(0., 18.) as u32
+        });
+
+        encode83(r * 19 * 19 + g * 19 + b, 2, &mut hash);
+    }
+
+    Ok(hash)
+}
+
+/// Decode a blurhash to RGBA pixels, returns `None` for an invalid blurhash
+pub fn decode_blurhash(hash: &str, width: u32, height: u32) -> Option<Vec<u8>> {
+    if hash.len() < 6 || !hash.is_ascii() || width == 0 || height == 0 {
+        return None;
+    }
+
+    let size_flag = decode83(&hash[0..1])?;
+    let components_x = size_flag % 9 + 1;
+    let components_y = size_flag / 9 + 1;
+
+    if hash.len() != (4 + 2 * components_x * components_y) as usize {
+        return None;
+    }
+
+    let max_value = (decode83(&hash[1..2])? + 1) as f32 / 166.;
+    let dc = decode83(&hash[2..6])?;
+
+    let mut colors = vec![[
+        srgb_to_linear((dc >> 16) as u8),
+        srgb_to_linear((dc >> 8) as u8),
+        srgb_to_linear(dc as u8),
+    ]];
+
+    for ix in 1..(components_x * components_y) as usize {
+        let value = decode83(&hash[4 + ix * 2..6 + ix * 2])?;
+        let quantised = [value / (19 * 19), (value / 19) % 19, value % 19];
+
+        colors.push(quantised.map(|q| sign_pow((q as f32 - 9.) / 9., 2.) * max_value));
+    }
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut pixel = [0.; 3];
+
+            for j in 0..components_y {
+                for i in 0..components_x {
+                    let basis = (PI * x as f32 * i as f32 / width as f32).cos()
+                        * (PI * y as f32 * j as f32 / height as f32).cos();
+                    let color = colors[(i + j * components_x) as usize];
+
+                    for (c, value) in pixel.iter_mut().enumerate() {
+                        *value += color[c] * basis;
+                    }
+                }
+            }
+
+            pixels.extend(pixel.map(linear_to_srgb));
+            pixels.push(255);
+        }
+    }
+
+    Some(pixels)
+}
+
+fn encode83(value: u32, length: u32, hash: &mut String) {
+    for i in 1..=length {
+        let digit = (value / 83u32.pow(length - i)) % 83;
+        hash.push(BASE83[digit as usize] as char);
+    }
+}
+
+fn decode83(value: &str) -> Option<u32> {
+    value.bytes().try_fold(0u32, |acc, c| {
+        let digit = BASE83.iter().position(|b| *b == c)?;
+        Some(acc * 83 + digit as u32)
+    })
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let v = value as f32 / 255.;
+
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let v = value.clamp(0., 1.);
+
+    if v <= 0.0031308 {
+        (v * 12.92 * 255. + 0.5) as u8
+    } else {
+        ((1.055 * v.powf(1. / 2.4) - 0.055) * 255. + 0.5) as u8
+    }
+}
+
+fn sign_pow(value: f32, exp: f32) -> f32 {
+    value.abs().powf(exp).copysign(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blurhash_roundtrip() {
+        let (width, height) = (16, 12);
+        let pixels: Vec<u8> = (0..width * height)
+            .flat_map(|_| [200, 80, 40, 255])
+            .collect();
+
+        let hash = encode_blurhash(4, 3, width, height, &pixels).unwrap();
+        assert_eq!(hash.len(), 4 + 2 * 4 * 3);
+
+        // A plain color decodes to the same color
+        let decoded = decode_blurhash(&hash, 4, 4).unwrap();
+        assert_eq!(decoded.len(), 4 * 4 * 4);
+        for pixel in decoded.chunks(4) {
+            assert!(pixel[0].abs_diff(200) <= 2);
+            assert!(pixel[1].abs_diff(80) <= 2);
+            assert!(pixel[2].abs_diff(40) <= 2);
+        }
+
+        assert!(decode_blurhash("LEHV6nWB2yk8pyo0adR*.7kCMdnj", 32, 32).is_some());
+        assert!(decode_blurhash("LEHV6nWB2yk8", 32, 32).is_none());
+        assert!(encode_blurhash(10, 3, width, height, &pixels).is_err());
+    }
+
+    #[test]
+    fn test_imeta_tag() {
+        let meta = ImageMeta {
+            url: Url::parse("https://cdn.example.com/abc.jpg").unwrap(),
+            mime: Some("image/jpeg".into()),
+            hash: Some("abc".into()),
+            size: Some(1024),
+            dimensions: Some((1920, 1080)),
+            blurhash: Some("LEHV6nWB2yk8pyo0adR*.7kCMdnj".into()),
+            thumbnail: Some(Url::parse("https://cdn.example.com/def.jpg").unwrap()),
+        };
+
+        let tag = meta.to_tag();
+        assert_eq!(tag.as_slice()[5], "dim 1920x1080");
+        assert_eq!(ImageMeta::from_tag(&tag), Some(meta));
+
+        // A tag without a URL is ignored
+        let tag = Tag::parse(["imeta", "m image/png", "dim 10x10"]).unwrap();
+        assert_eq!(ImageMeta::from_tag(&tag), None);
+    }
+
+    #[test]
+    fn test_placeholder_dimensions() {
+        let tag = Tag::parse([
+            "imeta",
+            "url https://cdn.example.com/abc.jpg",
+            "dim 1x4294967295",
+            "blurhash LEHV6nWB2yk8pyo0adR*.7kCMdnj",
+        ])
+        .unwrap();
+        let meta = ImageMeta::from_tag(&tag).unwrap();
+
+        // Extreme dimensions are clamped instead of overflowing
+        assert!(meta.placeholder().is_some());
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as AnyhowContext, Error};
+use common::config_dir;
+use nostr_lmdb::prelude::*;
+use nostr_sdk::prelude::*;
+
+/// Profiles of non-contacts not fetched for this long are considered stale
+const STALE_PROFILE_AGE: u64 = 90 * 24 * 60 * 60;
+
+/// Messaging relay lists of non-contacts older than this are considered stale
+const STALE_RELAY_LIST_AGE: u64 = 30 * 24 * 60 * 60;
+
+/// Kinds of the WoT (via Vertex) search requests and responses
+const WOT_KINDS: [Kind; 3] = [Kind::Custom(5315), Kind::Custom(6315), Kind::Custom(7000)];
+
+/// Kinds stored by Coop, counted one by one in the storage usage
+const STORED_KINDS: [Kind; 12] = [
+    Kind::Metadata,
+    Kind::ContactList,
+    Kind::RelayList,
+    Kind::InboxRelays,
+    Kind::MuteList,
+    Kind::Custom(10044),
+    Kind::Custom(10063),
+    Kind::GiftWrap,
+    Kind::ApplicationSpecificData,
+    WOT_KINDS[0],
+    WOT_KINDS[1],
+    WOT_KINDS[2],
+];
+
+/// Number of events copied at once when compacting the database
+const COMPACT_PAGE: usize = 1000;
+
+/// File marking a complete compacted copy of the database
+const COMPACTED_MARKER: &str = ".complete";
+
+/// File requesting the database to be compacted on the next launch
+const COMPACT_REQUEST: &str = "nostr-compact";
+
+/// Get the path of the local database
+pub fn database_dir() -> PathBuf {
+    config_dir().join("nostr")
+}
+
+/// Get the path of the compacted copy of the local database
+fn compacted_dir() -> PathBuf {
+    config_dir().join("nostr-compacted")
+}
+
+/// Get the path of the profile fetch times
+fn fetch_times_path() -> PathBuf {
+    config_dir().join("profile_fetches")
+}
+
+/// Last time each profile was received from relays, in seconds
+pub type FetchTimes = HashMap<PublicKey, u64>;
+
+/// Number of events and their size for a single kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KindUsage {
+    pub kind: Kind,
+    pub count: usize,
+    /// Size of the events as JSON, in bytes
+    pub size: u64,
+}
+
+/// Local database usage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageUsage {
+    /// Size of the database files on disk, in bytes
+    pub disk_size: u64,
+    /// Usage by kind, largest first
+    pub kinds: Vec<KindUsage>,
+    /// Number of events of other kinds
+    pub others: usize,
+}
+
+/// Pruning policy for non-essential cached events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrunePolicy {
+    /// Old profiles (kind 0) of users outside the contact list
+    StaleProfiles,
+    /// Old messaging relay lists (kind 10050) of users outside the contact list
+    StaleRelayLists,
+    /// WoT search requests and responses
+    WotResponses,
+}
+
+impl PrunePolicy {
+    pub const ALL: [PrunePolicy; 3] = [
+        PrunePolicy::StaleProfiles,
+        PrunePolicy::StaleRelayLists,
+        PrunePolicy::WotResponses,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::StaleProfiles => "Stale profiles of non-contacts",
+            Self::StaleRelayLists => "Stale messaging relay lists of non-contacts",
+            Self::WotResponses => "Web of Trust search results",
+        }
+    }
+}
+
+/// Get the usage of the local database.
+///
+/// Kinds are loaded one at a time, other kinds are only counted.
+pub async fn storage_usage(client: &Client) -> Result<StorageUsage, Error> {
+    let mut kinds: Vec<KindUsage> = vec![];
+
+    for kind in STORED_KINDS.into_iter() {
+        let events = client.database().query(Filter::new().kind(kind)).await?;
+
+        if events.is_empty() {
+            continue;
+        }
+
+        kinds.push(KindUsage {
+            kind,
+            count: events.len(),
+            size: events
+                .iter()
+                .map(|event| event.as_json().len() as u64)
+                .sum(),
+        });
+    }
+
+    kinds.sort_by_key(|usage| std::cmp::Reverse(usage.size));
+
+    let total = client.database().count(Filter::new()).await?;
+    let others = total.saturating_sub(kinds.iter().map(|usage| usage.count).sum());
+
+    let disk_size = std::fs::read_dir(database_dir())?
+        .filter_map(|entry| entry.ok()?.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum();
+
+    Ok(StorageUsage {
+        disk_size,
+        kinds,
+        others,
+    })
+}
+
+/// Read the last time each profile was received from relays
+pub fn read_fetch_times() -> FetchTimes {
+    std::fs::read(fetch_times_path())
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Record the time profiles were received from relays
+pub fn record_fetch_times(times: &FetchTimes) -> Result<(), Error> {
+    let mut all = read_fetch_times();
+    all.extend(times);
+
+    std::fs::write(fetch_times_path(), serde_json::to_vec(&all)?)?;
+
+    Ok(())
+}
+
+/// Get the profiles not fetched for the stale age, except the kept ones.
+///
+/// Profiles without a fetch time are recorded as fetched now, they become stale later.
+fn stale_profiles(
+    events: &[Event],
+    keep: &HashSet<PublicKey>,
+    times: &mut FetchTimes,
+    now: u64,
+) -> Vec<EventId> {
+    events
+        .iter()
+        .filter(|event| !keep.contains(&event.pubkey))
+        .filter(|event| {
+            let fetched_at = *times.entry(event.pubkey).or_insert(now);
+            fetched_at + STALE_PROFILE_AGE < now
+        })
+        .map(|event| event.id)
+        .collect()
+}
+
+/// Delete the events matching a pruning policy, returning the number of deleted events
+pub async fn prune(client: &Client, policy: PrunePolicy) -> Result<usize, Error> {
+    let signer = client.signer().context("Signer not found")?;
+    let public_key = signer.get_public_key().await?;
+
+    // Keep the events of the user and their contacts
+    let mut keep: HashSet<PublicKey> = client
+        .database()
+        .contacts_public_keys(public_key)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    keep.insert(public_key);
+
+    let ids: Vec<EventId> = match policy {
+        PrunePolicy::StaleProfiles => {
+            let filter = Filter::new().kind(Kind::Metadata);
+            let events: Vec<Event> = client.database().query(filter).await?.into_iter().collect();
+
+            let mut times = read_fetch_times();
+            let ids = stale_profiles(&events, &keep, &mut times, Timestamp::now().as_secs());
+
+            // Save the fetch times of the profiles seen for the first time
+            record_fetch_times(&times)?;
+
+            ids
+        }
+        PrunePolicy::StaleRelayLists => {
+            let filter = Filter::new()
+                .kind(Kind::InboxRelays)
+                .until(Timestamp::now() - STALE_RELAY_LIST_AGE);
+
+            client
+                .database()
+                .query(filter)
+                .await?
+                .into_iter()
+                .filter(|event| !keep.contains(&event.pubkey))
+                .map(|event| event.id)
+                .collect()
+        }
+        PrunePolicy::WotResponses => {
+            let filter = Filter::new().kinds(WOT_KINDS);
+
+            client
+                .database()
+                .query(filter)
+                .await?
+                .into_iter()
+                .map(|event| event.id)
+                .collect()
+        }
+    };
+
+    if !ids.is_empty() {
+        client
+            .database()
+            .delete(Filter::new().ids(ids.clone()))
+            .await?;
+    }
+
+    Ok(ids.len())
+}
+
+/// Request the database to be compacted on the next launch.
+///
+/// The database can't be copied while it's in use, writes made after the copy would be lost.
+pub fn request_compaction() -> Result<(), Error> {
+    std::fs::write(config_dir().join(COMPACT_REQUEST), b"")?;
+    Ok(())
+}
+
+/// Compact the database if it was requested, must run before the database is opened.
+///
+/// Returns the number of copied events, `None` if no compaction was requested.
+pub async fn compact_database() -> Result<Option<usize>, Error> {
+    let database = database_dir();
+    let compacted = compacted_dir();
+    let request = config_dir().join(COMPACT_REQUEST);
+
+    // Finish or clean up an interrupted compaction
+    replace_database(&database, &compacted)?;
+
+    if !request.exists() || !database.exists() {
+        return Ok(None);
+    }
+
+    let source = NostrLmdb::open(database.clone()).await?;
+    let copy = NostrLmdb::open(compacted.clone()).await?;
+    let count = copy_events(&source, &copy).await?;
+
+    drop(source);
+    drop(copy);
+    std::fs::write(compacted.join(COMPACTED_MARKER), b"")?;
+
+    replace_database(&database, &compacted)?;
+    std::fs::remove_file(&request)?;
+
+    Ok(Some(count))
+}
+
+/// Copy all events into another database, without the free pages of the source.
+async fn copy_events(source: &NostrLmdb, copy: &NostrLmdb) -> Result<usize, Error> {
+    let mut until: Option<Timestamp> = None;
+    let mut count = 0;
+
+    // Copy the events page by page, newest first
+    loop {
+        let mut filter = Filter::new().limit(COMPACT_PAGE);
+
+        if let Some(until) = until {
+            filter = filter.until(until);
+        }
+
+        let events = source.query(filter).await?;
+        let full = events.len() >= COMPACT_PAGE;
+
+        let Some(oldest) = events.iter().map(|event| event.created_at).min() else {
+            break;
+        };
+
+        for event in events.iter().filter(|event| event.created_at > oldest) {
+            copy.save_event(event).await?;
+            count += 1;
+        }
+
+        // A page may only hold some of the events of its oldest timestamp
+        let filter = Filter::new().since(oldest).until(oldest);
+
+        for event in source.query(filter).await?.iter() {
+            copy.save_event(event).await?;
+            count += 1;
+        }
+
+        if !full || oldest.as_secs() == 0 {
+            break;
+        }
+
+        until = Some(oldest - 1);
+    }
+
+    Ok(count)
+}
+
+/// Replace a database with a complete compacted copy, returns true if it was replaced
+fn replace_database(database: &Path, compacted: &Path) -> Result<bool, Error> {
+    let backup = database.with_extension("old");
+
+    // Restore the database if the last replacement was interrupted
+    if !database.exists() && backup.exists() {
+        std::fs::rename(&backup, database)?;
+    }
+
+    if !compacted.join(COMPACTED_MARKER).exists() {
+        // Remove an interrupted copy
+        if compacted.exists() {
+            std::fs::remove_dir_all(compacted)?;
+        }
+        return Ok(false);
+    }
+
+    if backup.exists() {
+        std::fs::remove_dir_all(&backup)?;
+    }
+
+    if database.exists() {
+        std::fs::rename(database, &backup)?;
+    }
+
+    std::fs::rename(compacted, database)?;
+    std::fs::remove_file(database.join(COMPACTED_MARKER))?;
+
+    if backup.exists() {
+        std::fs::remove_dir_all(&backup)?;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn profile(keys: &Keys) -> Event {
+        EventBuilder::new(Kind::Metadata, "{}")
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_stale_profiles() {
+        let now = 1_000_000_000;
+        let contact = Keys::generate();
+        let stale = Keys::generate();
+        let fresh = Keys::generate();
+        let unknown = Keys::generate();
+
+        let events: Vec<Event> = [&contact, &stale, &fresh, &unknown]
+            .into_iter()
+            .map(profile)
+            .collect();
+
+        let keep = HashSet::from([contact.public_key()]);
+        let mut times = FetchTimes::from([
+            (contact.public_key(), 0),
+            (stale.public_key(), now - STALE_PROFILE_AGE - 1),
+            (fresh.public_key(), now - 60),
+        ]);
+
+        let ids = stale_profiles(&events, &keep, &mut times, now);
+
+        // Contacts are kept, unknown profiles are recorded as fetched now
+        assert_eq!(ids, vec![events[1].id]);
+        assert_eq!(times.get(&unknown.public_key()), Some(&now));
+    }
+
+    #[test]
+    fn test_replace_database() {
+        let root = tempdir().unwrap();
+        let database = root.path().join("nostr");
+        let compacted = root.path().join("nostr-compacted");
+
+        std::fs::create_dir_all(&database).unwrap();
+        std::fs::write(database.join("data.mdb"), "old").unwrap();
+
+        // An incomplete copy is removed
+        std::fs::create_dir_all(&compacted).unwrap();
+        assert!(!replace_database(&database, &compacted).unwrap());
+        assert!(!compacted.exists());
+
+        // A complete copy replaces the database
+        std::fs::create_dir_all(&compacted).unwrap();
+        std::fs::write(compacted.join("data.mdb"), "new").unwrap();
+        std::fs::write(compacted.join(COMPACTED_MARKER), b"").unwrap();
+
+        assert!(replace_database(&database, &compacted).unwrap());
+        assert_eq!(std::fs::read(database.join("data.mdb")).unwrap(), b"new");
+        assert!(!database.join(COMPACTED_MARKER).exists());
+        assert!(!database.with_extension("old").exists());
+
+        // An interrupted replacement restores the backup
+        std::fs::rename(&database, database.with_extension("old")).unwrap();
+        assert!(!replace_database(&database, &compacted).unwrap());
+        assert_eq!(std::fs::read(database.join("data.mdb")).unwrap(), b"new");
+    }
+}
@@ -96,8 +96,28 @@ pub struct ThemeColors {
     pub drop_target_background: Hsla,
     pub cursor: Hsla,
     pub selection: Hsla,
+
+    // Syntax colors, missing from older theme files
+    pub syntax_comment: Hsla,
+    pub syntax_keyword: Hsla,
+    pub syntax_string: Hsla,
+    pub syntax_number: Hsla,
+    pub syntax_function: Hsla,
+    pub syntax_type: Hsla,
+    pub syntax_punctuation: Hsla,
 }
 
+/// Names of the syntax colors, filled with the defaults when missing from a theme file
+pub(crate) const SYNTAX_COLORS: [&str; 7] = [
+    "syntax_comment",
+    "syntax_keyword",
+    "syntax_string",
+    "syntax_number",
+    "syntax_function",
+    "syntax_type",
+    "syntax_punctuation",
+];
+
 /// The default colors for the theme.
 ///
 /// Themes that do not specify all colors are refined off of these defaults.
@@ -106,85 +126,105 @@ impl ThemeColors {
     ///
     /// Themes that do not specify all colors are refined off of these defaults.
     pub fn light() -> Self {
+        Self::light_from(&neutral(), &brand(), &danger(), &warning())
+    }
+
+    /// Returns the light colors built from the given color scales.
+    ///
+    /// This is the same mapping used by [`ThemeColors::light`], which makes it
+    /// possible to derive a complete set of colors from a handful of base colors.
+    pub fn light_from(
+        neutral: &ColorScaleSet,
+        brand: &ColorScaleSet,
+        danger: &ColorScaleSet,
+        warning: &ColorScaleSet,
+    ) -> Self {
         Self {
-            background: neutral().light().step_1(),
-            surface_background: neutral().light().step_2(),
-            elevated_surface_background: neutral().light().step_3(),
-            panel_background: neutral().light().step_1(),
-            overlay: neutral().light_alpha().step_3(),
-            title_bar: neutral().light().step_3(),
-            title_bar_inactive: neutral().light().step_1(),
+            background: neutral.light().step_1(),
+            surface_background: neutral.light().step_2(),
+            elevated_surface_background: neutral.light().step_3(),
+            panel_background: neutral.light().step_1(),
+            overlay: neutral.light_alpha().step_3(),
+            title_bar: neutral.light().step_3(),
+            title_bar_inactive: neutral.light().step_1(),
             window_border: hsl(240.0, 5.9, 78.0),
 
-            border: neutral().light().step_6(),
-            border_variant: neutral().light().step_5(),
-            border_focused: brand().light().step_7(),
-            border_selected: brand().light().step_7(),
+            border: neutral.light().step_6(),
+            border_variant: neutral.light().step_5(),
+            border_focused: brand.light().step_7(),
+            border_selected: brand.light().step_7(),
             border_transparent: gpui::transparent_black(),
-            border_disabled: neutral().light().step_3(),
-            ring: brand().light().step_8(),
-
-            text: neutral().light().step_12(),
-            text_muted: neutral().light().step_11(),
-            text_placeholder: neutral().light().step_10(),
-            text_accent: brand().light().step_9(),
-            text_danger: danger().light().step_9(),
-            text_warning: warning().light().step_9(),
-
-            icon: neutral().light().step_11(),
-            icon_muted: neutral().light().step_10(),
-            icon_accent: brand().light().step_11(),
-
-            element_foreground: brand().light().step_12(),
-            element_background: brand().light().step_9(),
-            element_hover: brand().light_alpha().step_10(),
-            element_active: brand().light().step_10(),
-            element_selected: brand().light().step_11(),
-            element_disabled: brand().light_alpha().step_3(),
-
-            secondary_foreground: brand().light().step_11(),
-            secondary_background: brand().light().step_3(),
-            secondary_hover: brand().light_alpha().step_4(),
-            secondary_active: brand().light().step_5(),
-            secondary_selected: brand().light().step_5(),
-            secondary_disabled: brand().light_alpha().step_3(),
-
-            danger_foreground: danger().light().step_12(),
-            danger_background: danger().light().step_3(),
-            danger_hover: danger().light_alpha().step_4(),
-            danger_active: danger().light().step_5(),
-            danger_selected: danger().light().step_5(),
-            danger_disabled: danger().light_alpha().step_3(),
-
-            warning_foreground: warning().light().step_12(),
-            warning_background: warning().light().step_3(),
-            warning_hover: warning().light_alpha().step_4(),
-            warning_active: warning().light().step_5(),
-            warning_selected: warning().light().step_5(),
-            warning_disabled: warning().light_alpha().step_3(),
+            border_disabled: neutral.light().step_3(),
+            ring: brand.light().step_8(),
+
+            text: neutral.light().step_12(),
+            text_muted: neutral.light().step_11(),
+            text_placeholder: neutral.light().step_10(),
+            text_accent: brand.light().step_11(),
+            text_danger: danger.light().step_9(),
+            text_warning: warning.light().step_11(),
+
+            icon: neutral.light().step_11(),
+            icon_muted: neutral.light().step_10(),
+            icon_accent: brand.light().step_11(),
+
+            element_foreground: brand.light().step_12(),
+            element_background: brand.light().step_9(),
+            element_hover: brand.light_alpha().step_10(),
+            element_active: brand.light().step_10(),
+            element_selected: brand.light().step_11(),
+            element_disabled: brand.light_alpha().step_3(),
+
+            secondary_foreground: brand.light().step_12(),
+            secondary_background: brand.light().step_3(),
+            secondary_hover: brand.light_alpha().step_4(),
+            secondary_active: brand.light().step_5(),
+            secondary_selected: brand.light().step_5(),
+            secondary_disabled: brand.light_alpha().step_3(),
+
+            danger_foreground: danger.light().step_12(),
+            danger_background: danger.light().step_3(),
+            danger_hover: danger.light_alpha().step_4(),
+            danger_active: danger.light().step_5(),
+            danger_selected: danger.light().step_5(),
+            danger_disabled: danger.light_alpha().step_3(),
+
+            warning_foreground: warning.light().step_12(),
+            warning_background: warning.light().step_3(),
+            warning_hover: warning.light_alpha().step_4(),
+            warning_active: warning.light().step_5(),
+            warning_selected: warning.light().step_5(),
+            warning_disabled: warning.light_alpha().step_3(),
 
             ghost_element_background: gpui::transparent_black(),
-            ghost_element_background_alt: neutral().light().step_3(),
-            ghost_element_hover: neutral().light_alpha().step_4(),
-            ghost_element_active: neutral().light().step_5(),
-            ghost_element_selected: neutral().light().step_5(),
-            ghost_element_disabled: neutral().light_alpha().step_2(),
-
-            tab_background: neutral().light().step_3(),
-            tab_foreground: neutral().light().step_11(),
-            tab_hover_background: neutral().light_alpha().step_4(),
-            tab_active_background: neutral().light().step_1(),
-            tab_active_foreground: neutral().light().step_12(),
-
-            scrollbar_thumb_background: neutral().light_alpha().step_3(),
-            scrollbar_thumb_hover_background: neutral().light_alpha().step_4(),
+            ghost_element_background_alt: neutral.light().step_3(),
+            ghost_element_hover: neutral.light_alpha().step_4(),
+            ghost_element_active: neutral.light().step_5(),
+            ghost_element_selected: neutral.light().step_5(),
+            ghost_element_disabled: neutral.light_alpha().step_2(),
+
+            tab_background: neutral.light().step_3(),
+            tab_foreground: neutral.light().step_11(),
+            tab_hover_background: neutral.light_alpha().step_4(),
+            tab_active_background: neutral.light().step_1(),
+            tab_active_foreground: neutral.light().step_12(),
+
+            scrollbar_thumb_background: neutral.light_alpha().step_3(),
+            scrollbar_thumb_hover_background: neutral.light_alpha().step_4(),
             scrollbar_thumb_border: gpui::transparent_black(),
             scrollbar_track_background: gpui::transparent_black(),
             scrollbar_track_border: gpui::transparent_black(),
 
-            drop_target_background: brand().light_alpha().step_2(),
+            drop_target_background: brand.light_alpha().step_2(),
             cursor: hsl(200., 100., 50.),
             selection: hsl(200., 100., 50.).alpha(0.25),
+            syntax_comment: neutral.light().step_10(),
+            syntax_keyword: purple().light().step_11(),
+            syntax_string: green().light().step_11(),
+            syntax_number: orange().light().step_11(),
+            syntax_function: blue().light().step_11(),
+            syntax_type: teal().light().step_11(),
+            syntax_punctuation: neutral.light().step_11(),
         }
     }
 
@@ -192,102 +232,122 @@ impl ThemeColors {
     ///
     /// Themes that do not specify all colors are refined off of these defaults.
     pub fn dark() -> Self {
+        Self::dark_from(&neutral(), &brand(), &danger(), &warning())
+    }
+
+    /// Returns the dark colors built from the given color scales.
+    ///
+    /// This is the same mapping used by [`ThemeColors::dark`], which makes it
+    /// possible to derive a complete set of colors from a handful of base colors.
+    pub fn dark_from(
+        neutral: &ColorScaleSet,
+        brand: &ColorScaleSet,
+        danger: &ColorScaleSet,
+        warning: &ColorScaleSet,
+    ) -> Self {
         Self {
-            background: neutral().dark().step_1(),
-            surface_background: neutral().dark().step_2(),
-            elevated_surface_background: neutral().dark().step_3(),
-            panel_background: neutral().dark().step_1(),
-            overlay: neutral().dark_alpha().step_3(),
-            title_bar: neutral().dark().step_3(),
-            title_bar_inactive: neutral().dark().step_1(),
+            background: neutral.dark().step_1(),
+            surface_background: neutral.dark().step_2(),
+            elevated_surface_background: neutral.dark().step_3(),
+            panel_background: neutral.dark().step_1(),
+            overlay: neutral.dark_alpha().step_3(),
+            title_bar: neutral.dark().step_3(),
+            title_bar_inactive: neutral.dark().step_1(),
             window_border: hsl(240.0, 3.7, 28.0),
 
-            border: neutral().dark().step_6(),
-            border_variant: neutral().dark().step_5(),
-            border_focused: brand().dark().step_7(),
-            border_selected: brand().dark().step_7(),
+            border: neutral.dark().step_6(),
+            border_variant: neutral.dark().step_5(),
+            border_focused: brand.dark().step_7(),
+            border_selected: brand.dark().step_7(),
             border_transparent: gpui::transparent_black(),
-            border_disabled: neutral().dark().step_3(),
-            ring: brand().dark().step_8(),
-
-            text: neutral().dark().step_12(),
-            text_muted: neutral().dark().step_11(),
-            text_placeholder: neutral().dark().step_10(),
-            text_accent: brand().dark().step_9(),
-            text_danger: danger().dark().step_9(),
-            text_warning: warning().dark().step_9(),
-
-            icon: neutral().dark().step_11(),
-            icon_muted: neutral().dark().step_10(),
-            icon_accent: brand().dark().step_11(),
-
-            element_foreground: brand().dark().step_1(),
-            element_background: brand().dark().step_9(),
-            element_hover: brand().dark_alpha().step_10(),
-            element_active: brand().dark().step_10(),
-            element_selected: brand().dark().step_11(),
-            element_disabled: brand().dark_alpha().step_3(),
-
-            secondary_foreground: brand().dark().step_12(),
-            secondary_background: brand().dark().step_3(),
-            secondary_hover: brand().dark_alpha().step_4(),
-            secondary_active: brand().dark().step_5(),
-            secondary_selected: brand().dark().step_5(),
-            secondary_disabled: brand().dark_alpha().step_3(),
-
-            danger_foreground: danger().dark().step_12(),
-            danger_background: danger().dark().step_3(),
-            danger_hover: danger().dark_alpha().step_4(),
-            danger_active: danger().dark().step_5(),
-            danger_selected: danger().dark().step_5(),
-            danger_disabled: danger().dark_alpha().step_3(),
-
-            warning_foreground: warning().dark().step_12(),
-            warning_background: warning().dark().step_3(),
-            warning_hover: warning().dark_alpha().step_4(),
-            warning_active: warning().dark().step_5(),
-            warning_selected: warning().dark().step_5(),
-            warning_disabled: warning().dark_alpha().step_3(),
+            border_disabled: neutral.dark().step_3(),
+            ring: brand.dark().step_8(),
+
+            text: neutral.dark().step_12(),
+            text_muted: neutral.dark().step_11(),
+            text_placeholder: neutral.dark().step_10(),
+            text_accent: brand.dark().step_9(),
+            text_danger: danger.dark().step_9(),
+            text_warning: warning.dark().step_9(),
+
+            icon: neutral.dark().step_11(),
+            icon_muted: neutral.dark().step_10(),
+            icon_accent: brand.dark().step_11(),
+
+            element_foreground: brand.dark().step_1(),
+            element_background: brand.dark().step_9(),
+            element_hover: brand.dark_alpha().step_10(),
+            element_active: brand.dark().step_10(),
+            element_selected: brand.dark().step_11(),
+            element_disabled: brand.dark_alpha().step_3(),
+
+            secondary_foreground: brand.dark().step_12(),
+            secondary_background: brand.dark().step_3(),
+            secondary_hover: brand.dark_alpha().step_4(),
+            secondary_active: brand.dark().step_5(),
+            secondary_selected: brand.dark().step_5(),
+            secondary_disabled: brand.dark_alpha().step_3(),
+
+            danger_foreground: danger.dark().step_12(),
+            danger_background: danger.dark().step_3(),
+            danger_hover: danger.dark_alpha().step_4(),
+            danger_active: danger.dark().step_5(),
+            danger_selected: danger.dark().step_5(),
+            danger_disabled: danger.dark_alpha().step_3(),
+
+            warning_foreground: warning.dark().step_12(),
+            warning_background: warning.dark().step_3(),
+            warning_hover: warning.dark_alpha().step_4(),
+            warning_active: warning.dark().step_5(),
+            warning_selected: warning.dark().step_5(),
+            warning_disabled: warning.dark_alpha().step_3(),
 
             ghost_element_background: gpui::transparent_black(),
-            ghost_element_background_alt: neutral().dark().step_3(),
-            ghost_element_hover: neutral().dark_alpha().step_4(),
-            ghost_element_active: neutral().dark().step_5(),
-            ghost_element_selected: neutral().dark().step_5(),
-            ghost_element_disabled: neutral().dark_alpha().step_2(),
-
-            tab_background: neutral().dark().step_3(),
-            tab_foreground: neutral().dark().step_11(),
-            tab_hover_background: neutral().dark_alpha().step_4(),
-            tab_active_background: neutral().dark().step_1(),
-            tab_active_foreground: neutral().dark().step_12(),
-
-            scrollbar_thumb_background: neutral().dark_alpha().step_3(),
-            scrollbar_thumb_hover_background: neutral().dark_alpha().step_4(),
+            ghost_element_background_alt: neutral.dark().step_3(),
+            ghost_element_hover: neutral.dark_alpha().step_4(),
+            ghost_element_active: neutral.dark().step_5(),
+            ghost_element_selected: neutral.dark().step_5(),
+            ghost_element_disabled: neutral.dark_alpha().step_2(),
+
+            tab_background: neutral.dark().step_3(),
+            tab_foreground: neutral.dark().step_11(),
+            tab_hover_background: neutral.dark_alpha().step_4(),
+            tab_active_background: neutral.dark().step_1(),
+            tab_active_foreground: neutral.dark().step_12(),
+
+            scrollbar_thumb_background: neutral.dark_alpha().step_3(),
+            scrollbar_thumb_hover_background: neutral.dark_alpha().step_4(),
             scrollbar_thumb_border: gpui::transparent_black(),
             scrollbar_track_background: gpui::transparent_black(),
             scrollbar_track_border: gpui::transparent_black(),
 
-            drop_target_background: brand().dark_alpha().step_2(),
+            drop_target_background: brand.dark_alpha().step_2(),
             cursor: hsl(200., 100., 50.),
             selection: hsl(200., 100., 50.).alpha(0.25),
+            syntax_comment: neutral.dark().step_10(),
+            syntax_keyword: purple().dark().step_11(),
+            syntax_string: green().dark().step_11(),
+            syntax_number: orange().dark().step_11(),
+            syntax_function: blue().dark().step_11(),
+            syntax_type: teal().dark().step_11(),
+            syntax_punctuation: neutral.dark().step_11(),
         }
     }
 }
 
-fn neutral() -> ColorScaleSet {
+pub(crate) fn neutral() -> ColorScaleSet {
     gray()
 }
 
-fn brand() -> ColorScaleSet {
+pub(crate) fn brand() -> ColorScaleSet {
     yellow()
 }
 
-fn danger() -> ColorScaleSet {
+pub(crate) fn danger() -> ColorScaleSet {
     tomato()
 }
 
-fn warning() -> ColorScaleSet {
+pub(crate) fn warning() -> ColorScaleSet {
     orange()
 }
 
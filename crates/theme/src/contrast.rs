@@ -0,0 +1,233 @@
+use std::fmt::Display;
+
+use gpui::{Hsla, Rgba};
+
+use crate::{ThemeColors, ThemeFamily, ThemeMode};
+
+/// Minimum contrast ratio for normal text (WCAG 2.1 level AA).
+pub const WCAG_AA: f32 = 4.5;
+
+/// Minimum contrast ratio for large text and UI components (WCAG 2.1 level AA).
+pub const WCAG_AA_LARGE: f32 = 3.0;
+
+/// Minimum contrast ratio for normal text (WCAG 2.1 level AAA).
+pub const WCAG_AAA: f32 = 7.0;
+
+/// Returns the relative luminance of a color, as defined by WCAG 2.1.
+pub fn relative_luminance(color: Hsla) -> f32 {
+    fn channel(value: f32) -> f32 {
+        if value <= 0.03928 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    let rgba = Rgba::from(color);
+
+    0.2126 * channel(rgba.r) + 0.7152 * channel(rgba.g) + 0.0722 * channel(rgba.b)
+}
+
+/// Returns the WCAG contrast ratio between two colors, from 1.0 to 21.0.
+///
+/// A translucent foreground is composited over the background first.
+pub fn contrast_ratio(foreground: Hsla, background: Hsla) -> f32 {
+    let background = background.alpha(1.0);
+    let foreground = composite(foreground, background);
+
+    let a = relative_luminance(foreground);
+    let b = relative_luminance(background);
+
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Composite a translucent color over an opaque one.
+fn composite(color: Hsla, over: Hsla) -> Hsla {
+    let top = Rgba::from(color);
+    let bottom = Rgba::from(over);
+    let alpha = top.a;
+
+    Hsla::from(Rgba {
+        r: top.r * alpha + bottom.r * (1.0 - alpha),
+        g: top.g * alpha + bottom.g * (1.0 - alpha),
+        b: top.b * alpha + bottom.b * (1.0 - alpha),
+        a: 1.0,
+    })
+}
+
+/// A foreground/background pair of [`ThemeColors`] that is drawn together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContrastPair {
+    /// Name of the foreground field
+    pub foreground: &'static str,
+
+    /// Name of the background field
+    pub background: &'static str,
+
+    /// The contrast ratio between the two colors
+    pub ratio: f32,
+
+    /// The minimum required contrast ratio
+    pub required: f32,
+}
+
+impl ContrastPair {
+    /// Returns true if the pair meets the required contrast ratio.
+    pub fn passes(&self) -> bool {
+        self.ratio >= self.required
+    }
+}
+
+impl Display for ContrastPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} on {}: {:.2}:1 (required {:.1}:1)",
+            self.foreground, self.background, self.ratio, self.required
+        )
+    }
+}
+
+macro_rules! contrast_pairs {
+    ($colors:expr, [$(($fg:ident, $bg:ident, $required:expr)),* $(,)?]) => {
+        vec![
+            $(
+                ContrastPair {
+                    foreground: stringify!($fg),
+                    background: stringify!($bg),
+                    ratio: contrast_ratio($colors.$fg, $colors.$bg),
+                    required: $required,
+                },
+            )*
+        ]
+    };
+}
+
+impl ThemeColors {
+    /// Returns all foreground/background pairs used by the UI components.
+    ///
+    /// Body text must meet [`WCAG_AA`], while placeholders, accents and icons
+    /// are held to [`WCAG_AA_LARGE`].
+    pub fn contrast_pairs(&self) -> Vec<ContrastPair> {
+        contrast_pairs!(
+            self,
+            [
+                (text, background, WCAG_AA),
+                (text, surface_background, WCAG_AA),
+                (text, elevated_surface_background, WCAG_AA),
+                (text, panel_background, WCAG_AA),
+                (text_muted, background, WCAG_AA),
+                (text_muted, surface_background, WCAG_AA),
+                (element_foreground, element_background, WCAG_AA),
+                (secondary_foreground, secondary_background, WCAG_AA),
+                (danger_foreground, danger_background, WCAG_AA),
+                (warning_foreground, warning_background, WCAG_AA),
+                (tab_active_foreground, tab_active_background, WCAG_AA),
+                (tab_foreground, tab_background, WCAG_AA),
+                (text_placeholder, background, WCAG_AA_LARGE),
+                (text_accent, background, WCAG_AA_LARGE),
+                (text_danger, background, WCAG_AA_LARGE),
+                (text_warning, background, WCAG_AA_LARGE),
+                (icon, background, WCAG_AA_LARGE),
+                (icon_accent, background, WCAG_AA_LARGE),
+            ]
+        )
+    }
+
+    /// Returns the pairs that don't meet their required contrast ratio.
+    pub fn check_contrast(&self) -> Vec<ContrastPair> {
+        self.contrast_pairs()
+            .into_iter()
+            .filter(|pair| !pair.passes())
+            .collect()
+    }
+}
+
+impl ThemeFamily {
+    /// Returns the failing contrast pairs for both the light and dark colors.
+    pub fn check_contrast(&self) -> Vec<(ThemeMode, ContrastPair)> {
+        let light = self
+            .light()
+            .check_contrast()
+            .into_iter()
+            .map(|pair| (ThemeMode::Light, pair));
+
+        let dark = self
+            .dark()
+            .check_contrast()
+            .into_iter()
+            .map(|pair| (ThemeMode::Dark, pair));
+
+        light.chain(dark).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn bundled_themes() -> Vec<(String, ThemeFamily)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets/themes");
+
+        std::fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                (name, ThemeFamily::from_file(&path).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        let black = gpui::black();
+        let white = gpui::white();
+
+        assert!((contrast_ratio(black, white) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio(white, white) - 1.0).abs() < 0.01);
+        assert_eq!(contrast_ratio(black, white), contrast_ratio(white, black));
+    }
+
+    #[test]
+    fn test_default_theme_contrast() {
+        let failures = ThemeFamily::default().check_contrast();
+        assert!(failures.is_empty(), "{failures:#?}");
+    }
+
+    #[test]
+    fn test_bundled_themes_contrast() {
+        let themes = bundled_themes();
+        assert!(!themes.is_empty());
+
+        let failures: Vec<String> = themes
+            .iter()
+            .flat_map(|(name, theme)| {
+                theme
+                    .check_contrast()
+                    .into_iter()
+                    .map(move |(mode, pair)| format!("{name} ({}): {pair}", mode.name()))
+            })
+            .collect();
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_high_contrast_theme() {
+        let (_, theme) = bundled_themes()
+            .into_iter()
+            .find(|(_, theme)| theme.id == "high-contrast")
+            .expect("high contrast theme is bundled");
+
+        for colors in [theme.light(), theme.dark()] {
+            for pair in colors.contrast_pairs() {
+                assert!(pair.ratio >= WCAG_AAA, "{pair}");
+            }
+        }
+    }
+}
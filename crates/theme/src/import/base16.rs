@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use anyhow::{Context as AnyhowContext, Error};
+use gpui::Hsla;
+
+use super::{Palette, family, map_colors, mode_for, parse_color};
+use crate::{ThemeFamily, ThemeMode};
+
+impl ThemeFamily {
+    /// Convert a Base16 scheme YAML into a theme family.
+    ///
+    /// Both the classic flat format (`scheme`, `base00`...) and the newer
+    /// tinted-theming format (`name`, `variant`, `palette`) are supported.
+    pub fn from_base16(content: &str) -> Result<Self, Error> {
+        let values = parse_yaml(content);
+        let get = |key: &str| -> Option<Hsla> { values.get(key).and_then(|c| parse_color(c)) };
+
+        let background = get("base00").context("Scheme doesn't define base00")?;
+        let foreground = get("base05").context("Scheme doesn't define base05")?;
+        let accent = get("base0D").unwrap_or(foreground);
+
+        let mode = match values.get("variant").map(String::as_str) {
+            Some("light") => ThemeMode::Light,
+            Some("dark") => ThemeMode::Dark,
+            _ => mode_for(background),
+        };
+
+        let palette = Palette {
+            mode,
+            background,
+            foreground,
+            accent,
+            danger: get("base08"),
+            warning: get("base09"),
+        };
+
+        let mut colors = palette.colors();
+
+        map_colors!(colors, get, {
+            "base00" => background, panel_background, title_bar_inactive, tab_active_background;
+            "base01" => surface_background, elevated_surface_background, title_bar, tab_background;
+            "base02" => border, window_border, ghost_element_active, ghost_element_selected;
+            "base03" => text_placeholder, icon_muted;
+            "base04" => text_muted, icon, tab_foreground;
+            "base05" => text, tab_active_foreground, cursor;
+            "base08" => text_danger;
+            "base09" => text_warning;
+            "base0D" => text_accent, icon_accent, border_focused, border_selected, ring;
+        });
+
+        if let Some(selection) = get("base02") {
+            colors.selection = selection.alpha(0.6);
+        }
+
+        let name = values
+            .get("scheme")
+            .or_else(|| values.get("name"))
+            .map(String::as_str)
+            .unwrap_or("Imported Scheme");
+
+        let author = values.get("author").map(String::as_str).unwrap_or_default();
+
+        family(name, author, Some(colors), None)
+    }
+}
+
+/// Parse the flat `key: value` pairs of a Base16 scheme.
+///
+/// Nesting is ignored, which is enough for the `palette` section of the
+/// tinted-theming format.
+fn parse_yaml(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+
+            if line.starts_with('#') {
+                return None;
+            }
+
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim();
+
+            let value = if let Some(quoted) = value.strip_prefix('"') {
+                quoted.split('"').next().unwrap_or_default()
+            } else if let Some(quoted) = value.strip_prefix('\'') {
+                quoted.split('\'').next().unwrap_or_default()
+            } else {
+                value.split(" #").next().unwrap_or_default().trim()
+            };
+
+            if value.is_empty() {
+                return None;
+            }
+
+            Some((key.trim().to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_base16() {
+        let yaml = r##"
+scheme: "Default Dark"
+author: "Chris Kempson (http://chriskempson.com)"
+base00: "181818" # background
+base01: "282828"
+base02: "383838"
+base03: "585858"
+base04: "b8b8b8"
+base05: "d8d8d8"
+base06: "e8e8e8"
+base07: "f8f8f8"
+base08: "ab4642"
+base09: "dc9656"
+base0A: "f7ca88"
+base0B: "a1b56c"
+base0C: "86c1b9"
+base0D: "7cafc2"
+base0E: "ba8baf"
+base0F: "a16946"
+"##;
+
+        let theme = ThemeFamily::from_base16(yaml).unwrap();
+
+        assert_eq!(theme.id, "default-dark");
+        assert_eq!(theme.author, "Chris Kempson (http://chriskempson.com)");
+        assert_eq!(theme.dark.background, parse_color("181818").unwrap());
+        assert_eq!(theme.dark.text_accent, parse_color("7cafc2").unwrap());
+    }
+
+    #[test]
+    fn test_from_base16_tinted() {
+        let yaml = r##"
+system: "base16"
+name: "Sample Light"
+author: "Coop"
+variant: "light"
+palette:
+  base00: "#fafafa"
+  base05: "#383a42"
+  base0D: "#4078f2"
+"##;
+
+        let theme = ThemeFamily::from_base16(yaml).unwrap();
+
+        assert_eq!(theme.name, "Sample Light");
+        assert_eq!(theme.light.text, parse_color("#383a42").unwrap());
+    }
+}
@@ -0,0 +1,298 @@
+use std::path::Path;
+
+use anyhow::{Error, anyhow};
+use gpui::{Hsla, Rgba};
+
+use crate::colors::{danger as default_danger, warning as default_warning};
+use crate::scale::{ColorScaleSet, mix};
+use crate::{ThemeColors, ThemeFamily, ThemeMode};
+
+mod base16;
+mod vscode;
+mod zed;
+
+/// Supported formats for importing third-party themes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeFormat {
+    /// Zed theme family JSON.
+    Zed,
+    /// VS Code color theme JSON (comments and trailing commas are allowed).
+    VsCode,
+    /// Base16 scheme YAML.
+    Base16,
+}
+
+impl ThemeFormat {
+    /// Detect the theme format from the file extension and its content.
+    pub fn detect(path: &Path, content: &str) -> Option<Self> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        if matches!(extension.as_deref(), Some("yaml" | "yml")) {
+            return Some(Self::Base16);
+        }
+
+        let value: serde_json::Value = serde_json::from_str(&strip_jsonc(content)).ok()?;
+
+        if value.get("themes").is_some_and(|themes| themes.is_array()) {
+            Some(Self::Zed)
+        } else if value.get("colors").is_some() || value.get("tokenColors").is_some() {
+            Some(Self::VsCode)
+        } else {
+            None
+        }
+    }
+}
+
+impl ThemeFamily {
+    /// Import a third-party theme file into a theme family.
+    ///
+    /// The format is detected by [`ThemeFormat::detect`].
+    pub fn import<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let format = ThemeFormat::detect(path, &content)
+            .ok_or_else(|| anyhow!("Unsupported theme format: {}", path.display()))?;
+
+        match format {
+            ThemeFormat::Zed => Self::from_zed(&content),
+            ThemeFormat::VsCode => Self::from_vscode(&content),
+            ThemeFormat::Base16 => Self::from_base16(&content),
+        }
+    }
+}
+
+/// The minimal set of colors needed to derive a complete [`ThemeColors`].
+struct Palette {
+    mode: ThemeMode,
+    background: Hsla,
+    foreground: Hsla,
+    accent: Hsla,
+    danger: Option<Hsla>,
+    warning: Option<Hsla>,
+}
+
+impl Palette {
+    /// Derive all theme colors from the palette via [`ColorScaleSet::derive`].
+    fn colors(&self) -> ThemeColors {
+        let bg = self.background;
+        let fg = self.foreground;
+
+        let neutral = ColorScaleSet::derive("Neutral", bg, mix(bg, fg, 0.45), fg);
+        let brand = ColorScaleSet::derive("Brand", bg, self.accent, fg);
+
+        let danger = self
+            .danger
+            .map(|color| ColorScaleSet::derive("Danger", bg, color, fg))
+            .unwrap_or_else(default_danger);
+
+        let warning = self
+            .warning
+            .map(|color| ColorScaleSet::derive("Warning", bg, color, fg))
+            .unwrap_or_else(default_warning);
+
+        let mut colors = match self.mode {
+            ThemeMode::Light => ThemeColors::light_from(&neutral, &brand, &danger, &warning),
+            ThemeMode::Dark => ThemeColors::dark_from(&neutral, &brand, &danger, &warning),
+        };
+
+        colors.window_border = neutral.light().step_7();
+
+        colors
+    }
+}
+
+/// Build a theme family from the imported light and dark colors.
+///
+/// When the source only provides one appearance, it is used for both.
+/// Fails if the name can't be turned into an identifier.
+fn family(
+    name: &str,
+    author: &str,
+    light: Option<ThemeColors>,
+    dark: Option<ThemeColors>,
+) -> Result<ThemeFamily, Error> {
+    let (light, dark) = match (light, dark) {
+        (Some(light), Some(dark)) => (light, dark),
+        (Some(colors), None) | (None, Some(colors)) => (colors, colors),
+        (None, None) => return Err(anyhow!("Theme doesn't contain any colors")),
+    };
+
+    let id = slugify(name);
+
+    if id.is_empty() {
+        return Err(anyhow!(
+            "Theme name \"{name}\" can't be used as an identifier"
+        ));
+    }
+
+    Ok(ThemeFamily {
+        id,
+        name: name.to_owned().into(),
+        author: author.to_owned().into(),
+        url: String::new(),
+        light,
+        dark,
+    })
+}
+
+/// Parse a hex color, with or without the leading `#`.
+fn parse_color(value: &str) -> Option<Hsla> {
+    let value = value.trim();
+
+    if value.is_empty() {
+        return None;
+    }
+
+    let hex = if value.starts_with('#') {
+        value.to_owned()
+    } else {
+        format!("#{value}")
+    };
+
+    Rgba::try_from(hex.as_str()).ok().map(Hsla::from)
+}
+
+/// Guess the appearance of a theme from its background color.
+fn mode_for(background: Hsla) -> ThemeMode {
+    if background.l < 0.5 {
+        ThemeMode::Dark
+    } else {
+        ThemeMode::Light
+    }
+}
+
+/// Convert a theme name into an identifier.
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Remove comments and trailing commas from a JSONC document.
+fn strip_jsonc(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+
+            if c == '\\' {
+                if let Some(next) = chars.next() {
+                    output.push(next);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                output.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        output.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+
+                for next in chars.by_ref() {
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
+                }
+            }
+            ']' | '}' => {
+                let trimmed = output.trim_end().len();
+
+                if output[..trimmed].ends_with(',') {
+                    output.truncate(trimmed - 1);
+                }
+
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+
+    output
+}
+
+/// Assign colors found in the source to theme fields.
+///
+/// Each key is looked up with `$lookup`; when a color is found, it is assigned
+/// to every listed field.
+macro_rules! map_colors {
+    ($colors:expr, $lookup:expr, { $($key:literal => $($field:ident),+;)* }) => {
+        $(
+            if let Some(color) = $lookup($key) {
+                $( $colors.$field = color; )+
+            }
+        )*
+    };
+}
+
+pub(crate) use map_colors;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_jsonc() {
+        let input = r#"{
+            // comment
+            "a": "http://example.com", /* block */
+            "b": [1, 2,],
+        }"#;
+
+        let value: serde_json::Value = serde_json::from_str(&strip_jsonc(input)).unwrap();
+
+        assert_eq!(value["a"], "http://example.com");
+        assert_eq!(value["b"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert!(parse_color("#1e1e2e").is_some());
+        assert!(parse_color("1e1e2e").is_some());
+        assert!(parse_color("#1e1e2eff").is_some());
+        assert!(parse_color("not a color").is_none());
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(
+            slugify("Catppuccin Mocha (Ported)"),
+            "catppuccin-mocha-ported"
+        );
+        assert_eq!(slugify("テーマ"), "テーマ");
+        assert_eq!(slugify("Café Noir"), "café-noir");
+        assert_eq!(slugify("(✨)"), "");
+    }
+
+    #[test]
+    fn test_family_without_id() {
+        let colors = ThemeColors::default();
+
+        assert!(family("Nord", "", Some(colors), None).is_ok());
+        assert!(family("テーマ", "", Some(colors), None).is_ok());
+        assert!(family("✨", "", Some(colors), None).is_err());
+    }
+}
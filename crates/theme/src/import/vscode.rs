@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use anyhow::{Context as AnyhowContext, Error};
+use gpui::Hsla;
+use serde::Deserialize;
+
+use super::{Palette, family, map_colors, mode_for, parse_color, strip_jsonc};
+use crate::{ThemeFamily, ThemeMode};
+
+#[derive(Deserialize)]
+struct VsCodeTheme {
+    name: Option<String>,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    colors: HashMap<String, String>,
+}
+
+impl ThemeFamily {
+    /// Convert a VS Code color theme JSON into a theme family.
+    ///
+    /// VS Code themes only describe a single appearance, so the same colors are
+    /// used for both light and dark mode.
+    pub fn from_vscode(content: &str) -> Result<Self, Error> {
+        let source: VsCodeTheme = serde_json::from_str(&strip_jsonc(content))?;
+        let get =
+            |key: &str| -> Option<Hsla> { source.colors.get(key).and_then(|c| parse_color(c)) };
+
+        let background = get("editor.background")
+            .or_else(|| get("sideBar.background"))
+            .context("Theme doesn't define an editor background color")?;
+
+        let foreground = get("editor.foreground")
+            .or_else(|| get("foreground"))
+            .context("Theme doesn't define a foreground color")?;
+
+        let accent = get("focusBorder")
+            .or_else(|| get("button.background"))
+            .or_else(|| get("textLink.foreground"))
+            .unwrap_or(foreground);
+
+        let mode = match source.kind.as_deref() {
+            Some("light" | "hc-light") => ThemeMode::Light,
+            Some("dark" | "hc-black" | "hc-dark") => ThemeMode::Dark,
+            _ => mode_for(background),
+        };
+
+        let palette = Palette {
+            mode,
+            background,
+            foreground,
+            accent,
+            danger: get("errorForeground").or_else(|| get("editorError.foreground")),
+            warning: get("editorWarning.foreground"),
+        };
+
+        let mut colors = palette.colors();
+
+        map_colors!(colors, get, {
+            "editor.background" => background;
+            "sideBar.background" => surface_background;
+            "editorWidget.background" => elevated_surface_background;
+            "panel.background" => panel_background;
+            "titleBar.activeBackground" => title_bar;
+            "titleBar.inactiveBackground" => title_bar_inactive;
+            "contrastBorder" => window_border;
+            "panel.border" => border;
+            "editorGroup.border" => border_variant;
+            "focusBorder" => border_focused, border_selected, ring;
+            "foreground" => text;
+            "editor.foreground" => text;
+            "descriptionForeground" => text_muted;
+            "input.placeholderForeground" => text_placeholder;
+            "textLink.foreground" => text_accent, icon_accent;
+            "errorForeground" => text_danger;
+            "editorWarning.foreground" => text_warning;
+            "icon.foreground" => icon;
+            "button.background" => element_background;
+            "button.foreground" => element_foreground;
+            "button.hoverBackground" => element_hover;
+            "button.secondaryBackground" => secondary_background;
+            "button.secondaryForeground" => secondary_foreground;
+            "button.secondaryHoverBackground" => secondary_hover;
+            "list.hoverBackground" => ghost_element_hover;
+            "list.activeSelectionBackground" => ghost_element_active, ghost_element_selected;
+            "tab.inactiveBackground" => tab_background;
+            "tab.inactiveForeground" => tab_foreground;
+            "tab.hoverBackground" => tab_hover_background;
+            "tab.activeBackground" => tab_active_background;
+            "tab.activeForeground" => tab_active_foreground;
+            "scrollbarSlider.background" => scrollbar_thumb_background;
+            "scrollbarSlider.hoverBackground" => scrollbar_thumb_hover_background;
+            "editorCursor.foreground" => cursor;
+            "editor.selectionBackground" => selection;
+        });
+
+        let name = source.name.as_deref().unwrap_or("Imported Theme");
+        let author = source.author.as_deref().unwrap_or_default();
+
+        family(name, author, Some(colors), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_vscode() {
+        let json = r##"{
+            // VS Code allows comments in theme files
+            "name": "Sample Light",
+            "type": "light",
+            "colors": {
+                "editor.background": "#ffffff",
+                "editor.foreground": "#333333",
+                "focusBorder": "#0090f1",
+                "editorCursor.foreground": "#000000",
+            },
+            "tokenColors": []
+        }"##;
+
+        let theme = ThemeFamily::from_vscode(json).unwrap();
+
+        assert_eq!(theme.name, "Sample Light");
+        assert_eq!(theme.light.background, parse_color("#ffffff").unwrap());
+        assert_eq!(theme.light.border_focused, parse_color("#0090f1").unwrap());
+        assert_eq!(theme.light.cursor, parse_color("#000000").unwrap());
+    }
+}
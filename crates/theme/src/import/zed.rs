@@ -0,0 +1,174 @@
+use anyhow::{Context as AnyhowContext, Error};
+use gpui::Hsla;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::{Palette, family, map_colors, parse_color, strip_jsonc};
+use crate::{ThemeColors, ThemeFamily, ThemeMode};
+
+#[derive(Deserialize)]
+struct ZedThemeFamily {
+    name: String,
+    #[serde(default)]
+    author: String,
+    themes: Vec<ZedTheme>,
+}
+
+#[derive(Deserialize)]
+struct ZedTheme {
+    appearance: ThemeAppearance,
+    style: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ThemeAppearance {
+    Light,
+    Dark,
+}
+
+impl ThemeFamily {
+    /// Convert a Zed theme family JSON into a theme family.
+    ///
+    /// The first light and the first dark theme of the family are used.
+    pub fn from_zed(content: &str) -> Result<Self, Error> {
+        let source: ZedThemeFamily = serde_json::from_str(&strip_jsonc(content))?;
+
+        let light = source
+            .themes
+            .iter()
+            .find(|theme| matches!(theme.appearance, ThemeAppearance::Light))
+            .map(|theme| colors(&theme.style, ThemeMode::Light))
+            .transpose()?;
+
+        let dark = source
+            .themes
+            .iter()
+            .find(|theme| matches!(theme.appearance, ThemeAppearance::Dark))
+            .map(|theme| colors(&theme.style, ThemeMode::Dark))
+            .transpose()?;
+
+        family(&source.name, &source.author, light, dark)
+    }
+}
+
+fn colors(style: &Map<String, Value>, mode: ThemeMode) -> Result<ThemeColors, Error> {
+    let get = |key: &str| -> Option<Hsla> { style.get(key)?.as_str().and_then(parse_color) };
+
+    // Zed stores the cursor and selection colors per player
+    let player = style
+        .get("players")
+        .and_then(|players| players.as_array())
+        .and_then(|players| players.first());
+
+    let player_color = |key: &str| -> Option<Hsla> {
+        player
+            .and_then(|player| player.get(key))
+            .and_then(|value| value.as_str())
+            .and_then(parse_color)
+    };
+
+    let background = get("background")
+        .or_else(|| get("editor.background"))
+        .context("Theme doesn't define a background color")?;
+
+    let foreground = get("text")
+        .or_else(|| get("editor.foreground"))
+        .context("Theme doesn't define a text color")?;
+
+    let accent = get("text.accent")
+        .or_else(|| player_color("cursor"))
+        .or_else(|| get("border.focused"))
+        .unwrap_or(foreground);
+
+    let palette = Palette {
+        mode,
+        background,
+        foreground,
+        accent,
+        danger: get("error"),
+        warning: get("warning"),
+    };
+
+    let mut colors = palette.colors();
+
+    map_colors!(colors, get, {
+        "background" => background;
+        "surface.background" => surface_background;
+        "elevated_surface.background" => elevated_surface_background;
+        "panel.background" => panel_background;
+        "title_bar.background" => title_bar;
+        "title_bar.inactive_background" => title_bar_inactive;
+        "border" => border, window_border;
+        "border.variant" => border_variant;
+        "border.focused" => border_focused, ring;
+        "border.selected" => border_selected;
+        "border.transparent" => border_transparent;
+        "border.disabled" => border_disabled;
+        "text" => text;
+        "text.muted" => text_muted;
+        "text.placeholder" => text_placeholder;
+        "text.accent" => text_accent;
+        "error" => text_danger;
+        "warning" => text_warning;
+        "icon" => icon;
+        "icon.muted" => icon_muted;
+        "icon.accent" => icon_accent;
+        "ghost_element.background" => ghost_element_background;
+        "ghost_element.hover" => ghost_element_hover;
+        "ghost_element.active" => ghost_element_active;
+        "ghost_element.selected" => ghost_element_selected;
+        "ghost_element.disabled" => ghost_element_disabled;
+        "tab_bar.background" => tab_background;
+        "tab.active_background" => tab_active_background;
+        "scrollbar.thumb.background" => scrollbar_thumb_background;
+        "scrollbar.thumb.hover_background" => scrollbar_thumb_hover_background;
+        "scrollbar.thumb.border" => scrollbar_thumb_border;
+        "scrollbar.track.background" => scrollbar_track_background;
+        "scrollbar.track.border" => scrollbar_track_border;
+        "drop_target.background" => drop_target_background;
+    });
+
+    map_colors!(colors, player_color, {
+        "cursor" => cursor;
+        "selection" => selection;
+    });
+
+    Ok(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_zed() {
+        let json = r##"{
+            "$schema": "https://zed.dev/schema/themes/v0.2.0.json",
+            "name": "Sample",
+            "author": "Coop",
+            "themes": [
+                {
+                    "name": "Sample Dark",
+                    "appearance": "dark",
+                    "style": {
+                        "background": "#1e1e2eff",
+                        "text": "#cdd6f4ff",
+                        "text.accent": "#89b4faff",
+                        "border": "#313244ff",
+                        "error": null,
+                        "players": [{ "cursor": "#f5e0dcff", "selection": "#f5e0dc40" }]
+                    }
+                }
+            ]
+        }"##;
+
+        let theme = ThemeFamily::from_zed(json).unwrap();
+
+        assert_eq!(theme.id, "sample");
+        assert_eq!(theme.dark.border, parse_color("#313244ff").unwrap());
+        assert_eq!(theme.dark.cursor, parse_color("#f5e0dcff").unwrap());
+        // Only a dark theme is provided, so it is used for both appearances
+        assert_eq!(theme.light, theme.dark);
+    }
+}
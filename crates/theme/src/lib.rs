@@ -4,7 +4,9 @@ use std::rc::Rc;
 use gpui::{App, Global, Pixels, SharedString, Window, px};
 
 mod colors;
+mod contrast;
 mod geometry;
+mod import;
 mod notification;
 mod platform_kind;
 mod registry;
@@ -13,7 +15,9 @@ mod scrollbar_mode;
 mod theme;
 
 pub use colors::*;
+pub use contrast::*;
 pub use geometry::*;
+pub use import::*;
 pub use notification::*;
 pub use platform_kind::PlatformKind;
 pub use registry::*;
@@ -36,6 +40,12 @@ pub const TITLEBAR_HEIGHT: Pixels = px(36.0);
 /// Defines workspace tabbar height
 pub const TABBAR_HEIGHT: Pixels = px(28.0);
 
+/// Defines the minimum UI font scale
+pub const MIN_FONT_SCALE: f32 = 0.75;
+
+/// Defines the maximum UI font scale
+pub const MAX_FONT_SCALE: f32 = 2.0;
+
 /// Defines default sidebar width
 pub const SIDEBAR_WIDTH: Pixels = px(240.);
 
@@ -71,9 +81,15 @@ pub struct Theme {
     /// The font family for the application.
     pub font_family: SharedString,
 
+    /// The monospace font family, used for code.
+    pub mono_font_family: SharedString,
+
     /// The root font size for the application, default is 15px.
     pub font_size: Pixels,
 
+    /// The scale applied to the root font size, default is 1.0.
+    pub font_scale: f32,
+
     /// Radius for the general elements.
     pub radius: Pixels,
 
@@ -120,6 +136,21 @@ impl Theme {
         cx.global_mut::<Theme>()
     }
 
+    /// Returns the scaled root font size, used as the window's rem size.
+    pub fn rem_size(&self) -> Pixels {
+        self.font_size * self.font_scale
+    }
+
+    /// Set the UI font scale
+    pub fn set_font_scale(scale: f32, window: Option<&mut Window>, cx: &mut App) {
+        Theme::global_mut(cx).font_scale = scale.clamp(MIN_FONT_SCALE, MAX_FONT_SCALE);
+
+        // Refresh the window if available
+        if let Some(window) = window {
+            window.refresh();
+        }
+    }
+
     /// Returns true if the theme is dark.
     pub fn is_dark(&self) -> bool {
         self.mode.is_dark()
@@ -198,6 +229,12 @@ impl From<ThemeFamily> for Theme {
             _ => ".SystemUIFont",
         };
 
+        let mono_font_family = match platform {
+            PlatformKind::Mac => "Menlo",
+            PlatformKind::Windows => "Consolas",
+            PlatformKind::Linux => "DejaVu Sans Mono",
+        };
+
         // Define the theme colors based on the appearance
         let colors = match mode {
             ThemeMode::Light => family.light(),
@@ -206,7 +243,9 @@ impl From<ThemeFamily> for Theme {
 
         Theme {
             font_size: px(15.),
+            font_scale: 1.0,
             font_family: font_family.into(),
+            mono_font_family: mono_font_family.into(),
             radius: px(6.),
             radius_lg: px(10.),
             shadow: true,
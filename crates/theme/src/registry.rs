@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -7,6 +8,16 @@ use gpui::{App, AppContext, AssetSource, Context, Entity, Global, SharedString};
 
 use crate::ThemeFamily;
 
+/// Prefix of the keys of the user's imported themes
+pub const USER_THEME_PREFIX: &str = "user:";
+
+/// Get the registry key of an imported theme.
+///
+/// The key only holds the theme id, so it resolves to the local copy on every device.
+pub fn user_theme_key(id: &str) -> String {
+    format!("{USER_THEME_PREFIX}{id}")
+}
+
 pub fn init(cx: &mut App) {
     ThemeRegistry::set_global(cx.new(ThemeRegistry::new), cx);
 }
@@ -40,6 +51,7 @@ impl ThemeRegistry {
             for path in paths.into_iter() {
                 match Self::load(&path, asset) {
                     Ok(theme) => {
+                        Self::report_contrast(&theme);
                         themes.insert(path, Rc::new(theme));
                     }
                     Err(e) => {
@@ -58,9 +70,71 @@ impl ThemeRegistry {
         let content = asset.load(path)?.context("Theme not found")?;
 
         // Parse the JSON content into a Theme Family struct
-        let theme: ThemeFamily = serde_json::from_slice(&content)?;
+        ThemeFamily::from_slice(&content)
+    }
+
+    /// Log every foreground/background pair that fails the WCAG contrast check.
+    fn report_contrast(theme: &ThemeFamily) {
+        for (mode, pair) in theme.check_contrast() {
+            log::warn!(
+                "Low contrast in theme {} ({}): {pair}",
+                theme.name,
+                mode.name()
+            );
+        }
+    }
+
+    /// Read all user themes from the given directory.
+    ///
+    /// This reads from the disk, so it should run on a background thread.
+    /// Themes are keyed by their file name, see [`user_theme_key`].
+    pub fn read_dir(dir: &Path) -> Vec<(SharedString, ThemeFamily)> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return vec![];
+        };
+
+        let mut themes = vec![];
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let Some(id) = path.file_stem().map(|stem| stem.to_string_lossy()) else {
+                continue;
+            };
+
+            match ThemeFamily::from_file(&path) {
+                Ok(theme) => {
+                    themes.push((SharedString::from(user_theme_key(&id)), theme));
+                }
+                Err(e) => {
+                    log::error!("Failed to load theme: {}. Error: {e}", path.display());
+                }
+            }
+        }
+
+        themes
+    }
+
+    /// Add the user themes read by [`ThemeRegistry::read_dir`] to the registry.
+    pub fn extend(&mut self, themes: Vec<(SharedString, ThemeFamily)>, cx: &mut Context<Self>) {
+        for (key, theme) in themes.into_iter() {
+            Self::report_contrast(&theme);
+            self.themes.insert(key, Rc::new(theme));
+        }
+
+        cx.notify();
+    }
 
-        Ok(theme)
+    /// Add a theme to the registry.
+    pub fn insert<T>(&mut self, key: T, theme: ThemeFamily, cx: &mut Context<Self>)
+    where
+        T: Into<SharedString>,
+    {
+        Self::report_contrast(&theme);
+        self.themes.insert(key.into(), Rc::new(theme));
+        cx.notify();
     }
 
     /// Returns a reference to the map of themes.
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use gpui::{Hsla, SharedString};
+use gpui::{Hsla, Rgba, SharedString};
 
 /// A collection of colors that are used to style the UI.
 ///
@@ -151,6 +151,35 @@ impl ColorScale {
     pub fn step_12(&self) -> Hsla {
         self.step(ColorScaleStep::TWELVE)
     }
+
+    /// Derive a [`ColorScale`] from a single base color.
+    ///
+    /// Steps 1 to 8 are mixed from `background` towards `base`, step 9 is `base`
+    /// itself, and steps 10 to 12 move towards `foreground` for text usage.
+    pub fn derive(background: Hsla, base: Hsla, foreground: Hsla) -> Self {
+        const BACKGROUND_MIX: [f32; 8] = [0.0, 0.03, 0.08, 0.13, 0.18, 0.25, 0.35, 0.55];
+        const FOREGROUND_MIX: [f32; 3] = [0.1, 0.55, 0.9];
+
+        BACKGROUND_MIX
+            .into_iter()
+            .map(|amount| mix(background, base, amount))
+            .chain(std::iter::once(base))
+            .chain(
+                FOREGROUND_MIX
+                    .into_iter()
+                    .map(|amount| mix(base, foreground, amount)),
+            )
+            .collect()
+    }
+
+    /// Derive a transparent [`ColorScale`] from a single base color.
+    pub fn derive_alpha(base: Hsla) -> Self {
+        const ALPHA: [f32; 12] = [
+            0.01, 0.02, 0.06, 0.09, 0.12, 0.15, 0.19, 0.27, 0.45, 0.49, 0.61, 0.87,
+        ];
+
+        ALPHA.into_iter().map(|alpha| base.alpha(alpha)).collect()
+    }
 }
 
 pub struct ColorScales {
@@ -259,6 +288,22 @@ impl ColorScaleSet {
         }
     }
 
+    /// Derive a [`ColorScaleSet`] from a single base color.
+    ///
+    /// Imported themes only describe a single appearance, so the light and dark
+    /// scales are identical.
+    pub fn derive(
+        name: impl Into<SharedString>,
+        background: Hsla,
+        base: Hsla,
+        foreground: Hsla,
+    ) -> Self {
+        let scale = || ColorScale::derive(background, base, foreground);
+        let alpha = || ColorScale::derive_alpha(base);
+
+        Self::new(name, scale(), alpha(), scale(), alpha())
+    }
+
     pub fn name(&self) -> &SharedString {
         &self.name
     }
@@ -279,3 +324,19 @@ impl ColorScaleSet {
         &self.dark_alpha
     }
 }
+
+/// Linearly mix two colors in RGB space.
+///
+/// `amount` is the weight of `to`, from 0.0 (only `from`) to 1.0 (only `to`).
+pub fn mix(from: Hsla, to: Hsla, amount: f32) -> Hsla {
+    let amount = amount.clamp(0.0, 1.0);
+    let from = Rgba::from(from);
+    let to = Rgba::from(to);
+
+    Hsla::from(Rgba {
+        r: from.r + (to.r - from.r) * amount,
+        g: from.g + (to.g - from.g) * amount,
+        b: from.b + (to.b - from.b) * amount,
+        a: from.a + (to.a - from.a) * amount,
+    })
+}
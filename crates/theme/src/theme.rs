@@ -5,6 +5,7 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::ThemeColors;
+use crate::colors::SYNTAX_COLORS;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Eq, Hash, Deserialize, Serialize)]
 pub enum ThemeMode {
@@ -119,9 +120,33 @@ impl ThemeFamily {
     /// ```
     pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
         let json_data = std::fs::read(path)?;
-        let theme_family = serde_json::from_slice(&json_data)?;
 
-        Ok(theme_family)
+        Self::from_slice(&json_data)
+    }
+
+    /// Parse a theme family from JSON.
+    ///
+    /// The syntax colors missing from older theme files are filled with the defaults.
+    pub fn from_slice(json: &[u8]) -> anyhow::Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_slice(json)?;
+
+        for (mode, defaults) in [
+            ("light", ThemeColors::light()),
+            ("dark", ThemeColors::dark()),
+        ] {
+            let Some(colors) = value.get_mut(mode).and_then(|v| v.as_object_mut()) else {
+                continue;
+            };
+            let defaults = serde_json::to_value(defaults)?;
+
+            for name in SYNTAX_COLORS {
+                if !colors.contains_key(name) {
+                    colors.insert(name.to_string(), defaults[name].clone());
+                }
+            }
+        }
+
+        Ok(serde_json::from_value(value)?)
     }
 
     /// Load a theme family from a JSON file in the assets/themes directory.
@@ -329,10 +354,26 @@ mod tests {
         assert_eq!(theme.id, "test-theme");
         assert_eq!(theme.name, "Test Theme");
 
+        // Missing syntax colors fall back to the defaults
+        let defaults = ThemeColors::dark();
+        assert_eq!(theme.dark.syntax_keyword, defaults.syntax_keyword);
+
         // Clean up
         dir.close().unwrap();
     }
 
+    #[test]
+    fn test_from_slice_keeps_syntax_colors() {
+        let mut theme = ThemeFamily::default();
+        theme.dark.syntax_comment = gpui::transparent_black();
+
+        // Colors set by the theme are kept, even when transparent
+        let json = serde_json::to_vec(&theme).unwrap();
+        let parsed = ThemeFamily::from_slice(&json).unwrap();
+        assert_eq!(parsed.dark.syntax_comment, gpui::transparent_black());
+        assert_eq!(parsed, theme);
+    }
+
     #[test]
     fn test_from_file_nonexistent() {
         // Test that loading a non-existent file returns an error
@@ -20,6 +20,7 @@ impl<T: IconNamed> From<T> for Icon {
 
 #[derive(IntoElement, Clone)]
 pub enum IconName {
+    Archive,
     ArrowLeft,
     ArrowRight,
     Boom,
@@ -39,6 +40,7 @@ pub enum IconName {
     Ellipsis,
     Emoji,
     Eye,
+    Folder,
     Input,
     Info,
     Invite,
@@ -72,6 +74,7 @@ pub enum IconName {
     PanelBottom,
     PanelBottomOpen,
     PaperPlaneFill,
+    Pin,
     Warning,
     WindowClose,
     WindowMaximize,
@@ -92,6 +95,7 @@ impl IconName {
 impl IconNamed for IconName {
     fn path(self) -> SharedString {
         match self {
+            Self::Archive => "icons/archive.svg",
             Self::ArrowLeft => "icons/arrow-left.svg",
             Self::ArrowRight => "icons/arrow-right.svg",
             Self::Boom => "icons/boom.svg",
@@ -111,6 +115,7 @@ impl IconNamed for IconName {
             Self::Ellipsis => "icons/ellipsis.svg",
             Self::Emoji => "icons/emoji.svg",
             Self::Eye => "icons/eye.svg",
+            Self::Folder => "icons/folder.svg",
             Self::Input => "icons/input.svg",
             Self::Info => "icons/info.svg",
             Self::Invite => "icons/invite.svg",
@@ -144,6 +149,7 @@ impl IconNamed for IconName {
             Self::PanelBottom => "icons/panel-bottom.svg",
             Self::PanelBottomOpen => "icons/panel-bottom-open.svg",
             Self::PaperPlaneFill => "icons/paper-plane-fill.svg",
+            Self::Pin => "icons/pin.svg",
             Self::Warning => "icons/warning.svg",
             Self::WindowClose => "icons/window-close.svg",
             Self::WindowMaximize => "icons/window-maximize.svg",
@@ -595,6 +595,43 @@ impl InputState {
         self.disabled = was_disabled;
     }
 
+    /// Replace text in the given range (UTF-8 byte offsets).
+    ///
+    /// And the cursor will be moved to the end of replaced text.
+    pub fn replace_range<T>(
+        &mut self,
+        range: Range<usize>,
+        text: T,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) where
+        T: Into<SharedString>,
+    {
+        let was_disabled = self.disabled;
+        self.disabled = false;
+        let text: SharedString = text.into();
+        let start = range.start.min(self.text.len());
+        let end = range.end.clamp(start, self.text.len());
+        let range_utf16 = self.range_to_utf16(&(start..end));
+        self.replace_text_in_range_silent(Some(range_utf16), &text, window, cx);
+        self.selected_range = (self.selected_range.end..self.selected_range.end).into();
+        self.disabled = was_disabled;
+    }
+
+    /// Return the selected range (UTF-8 byte offsets).
+    pub fn selected_range(&self) -> Range<usize> {
+        self.selected_range.into()
+    }
+
+    /// Select the given range (UTF-8 byte offsets).
+    pub fn set_selected_range(&mut self, range: Range<usize>, cx: &mut Context<Self>) {
+        let start = range.start.min(self.text.len());
+        let end = range.end.clamp(start, self.text.len());
+        self.selection_reversed = false;
+        self.selected_range = (start..end).into();
+        cx.notify();
+    }
+
     fn replace_text<T>(&mut self, text: T, window: &mut Window, cx: &mut Context<Self>)
     where
         T: Into<SharedString>,
@@ -254,7 +254,7 @@ impl Root {
 
 impl Render for Root {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        let rem_size = cx.theme().font_size;
+        let rem_size = cx.theme().rem_size();
         let font_family = cx.theme().font_family.clone();
         let decorations = window.window_decorations();
 